@@ -20,6 +20,12 @@ extern "C" {
 /// Opaque handle to the Flow engine
 typedef struct FlowHandle FlowHandle;
 
+/// Callback for results delivered on a background thread
+/// @param success Whether `result` holds a value
+/// @param result Result string, valid only for the duration of the call
+/// @param context The context pointer passed when registering the callback
+typedef void (*FlowResultCallback)(bool success, const char* result, void* context);
+
 // ============ Lifecycle ============
 
 /// Initialize the Flow engine
@@ -40,14 +46,66 @@ bool flow_start_recording(FlowHandle* handle);
 
 /// Stop audio recording and get the duration
 /// @param handle Engine handle
-/// @return Duration in milliseconds, or 0 on failure
+/// @return Duration in milliseconds, or 0 on failure or if the recording was too short to keep
 uint64_t flow_stop_recording(FlowHandle* handle);
 
+/// Check whether the last stopped recording was cut off at the maximum duration
+/// @param handle Engine handle
+/// @return true if the capture stopped itself before flow_stop_recording
+bool flow_was_recording_truncated(FlowHandle* handle);
+
+/// Get stats for the last stopped recording as JSON
+/// {"duration_ms", "peak", "likely_no_signal", "truncated", "clip_count"}
+/// likely_no_signal is true when every sample was near zero (muted or wrong microphone)
+/// clip_count is the number of runs of full-scale samples (too loud: move back or lower the gain)
+/// @param handle Engine handle
+/// @return JSON string (caller must free with flow_free_string), or NULL if nothing recorded yet
+char* flow_get_last_capture_stats(FlowHandle* handle);
+
+/// Check microphone permission without prompting
+/// Without permission recordings contain only silence, so check before recording
+/// @return 0 = authorized, 1 = denied, 2 = not determined
+uint8_t flow_microphone_permission_status(void);
+
+/// Prompt for microphone permission if needed and wait for the answer (blocks)
+/// @param handle Engine handle
+/// @return 0 = authorized, 1 = denied, 2 = not determined (prompt timed out)
+uint8_t flow_request_microphone_permission(FlowHandle* handle);
+
 /// Check if currently recording
 /// @param handle Engine handle
 /// @return true if recording
 bool flow_is_recording(FlowHandle* handle);
 
+/// Take the device error that interrupted the recording (e.g. the mic was unplugged)
+/// @param handle Engine handle
+/// @return Error message (caller must free with flow_free_string), or NULL if none
+char* flow_take_audio_error(FlowHandle* handle);
+
+/// Record the user speaking and save the input gain that levels their speech (blocking)
+/// Recordings started afterwards use the saved gain
+/// @param handle Engine handle
+/// @param duration_ms Length of the sample to record (at least the minimum recording length, 300ms by default)
+/// @return The new gain multiplier, or 0.0 on failure (e.g. nothing was said)
+float flow_calibrate_gain(FlowHandle* handle, uint64_t duration_ms);
+
+/// List the attached input devices
+/// @param handle Engine handle
+/// @return JSON array [{name, is_default}] (caller must free with flow_free_string), or NULL on failure
+char* flow_list_input_devices(FlowHandle* handle);
+
+/// Watch for input devices being added or removed, or the default changing
+/// The callback runs on a background thread with the new flow_list_input_devices JSON.
+/// Replaces any earlier watcher.
+/// @param handle Engine handle
+/// @param callback Called with each new device list
+/// @param context Passed back to the callback; must be usable from any thread
+void flow_watch_input_devices(FlowHandle* handle, FlowResultCallback callback, void* context);
+
+/// Stop watching input devices; no callbacks fire after this returns
+/// @param handle Engine handle
+void flow_stop_watching_input_devices(FlowHandle* handle);
+
 /// Get current audio level (RMS amplitude) from the recording
 /// @param handle Engine handle
 /// @return Value between 0.0 and 1.0, or 0.0 if not recording
@@ -58,15 +116,37 @@ float flow_get_audio_level(FlowHandle* handle);
 /// Transcribe the recorded audio and process it
 /// @param handle Engine handle
 /// @param app_name Name of the current app (for mode selection), or NULL
-/// @return Processed text (caller must free with flow_free_string), or NULL on failure
+/// @return Processed text (caller must free with flow_free_string), "" if no speech was detected, or NULL on failure
 char* flow_transcribe(FlowHandle* handle, const char* app_name);
 
 /// Retry the last transcription using cached audio
 /// @param handle Engine handle
 /// @param app_name Name of the current app (for mode selection), or NULL
-/// @return Processed text (caller must free with flow_free_string), or NULL on failure
+/// @return Processed text (caller must free with flow_free_string), "" if no speech was detected, or NULL on failure
 char* flow_retry_last_transcription(FlowHandle* handle, const char* app_name);
 
+/// Abort the transcription currently in flight (call from another thread, e.g. on Escape)
+/// The blocked transcribe call returns NULL with last error "Request cancelled"; audio is kept for retry
+/// @param handle Engine handle
+/// @return true if a request was cancelled
+bool flow_cancel_transcription(FlowHandle* handle);
+
+// ============ Dictation ============
+
+/// Record, classify, transcribe, and format in one blocking call (run off the main thread)
+/// Uses the Messages contact when Messages is frontmost
+/// Posts a notification with the result when the "notify_on_dictation" setting is "true"
+/// Checks output for formal contacts against the "profanity_action" setting ("off", "warn", "soften")
+/// @param handle Engine handle
+/// @param silence_timeout_ms End recording after this much quiet following speech, or 0 to wait for flow_stop_dictation
+/// @return JSON {raw, adapted, contact, category, mode, usage, warnings} (caller must free with flow_free_string), or NULL on failure
+char* flow_run_dictation(FlowHandle* handle, uint64_t silence_timeout_ms);
+
+/// End the recording of a flow_run_dictation call running on another thread
+/// @param handle Engine handle
+/// @return true if a dictation was recording
+bool flow_stop_dictation(FlowHandle* handle);
+
 // ============ Shortcuts ============
 
 /// Add a voice shortcut
@@ -105,6 +185,67 @@ bool flow_set_app_mode(FlowHandle* handle, const char* app_name, uint8_t mode);
 /// @return Writing mode (0-3)
 uint8_t flow_get_app_mode(FlowHandle* handle, const char* app_name);
 
+/// Pin a writing mode to a contact, overriding their category default
+/// @param handle Engine handle
+/// @param contact_name Contact name (matched case- and emoji-insensitively)
+/// @param mode Writing mode (0-3)
+/// @return true on success
+bool flow_set_contact_mode(FlowHandle* handle, const char* contact_name, uint8_t mode);
+
+/// Remove a contact's pinned writing mode
+/// @param handle Engine handle
+/// @param contact_name Contact name
+/// @return true if a pinned mode was removed
+bool flow_clear_contact_mode(FlowHandle* handle, const char* contact_name);
+
+/// Replace a writing mode's built-in prompt with a template
+/// Variables: {contact_name}, {category}, {length}, {language}, {mode}; {{ and }} are literal braces
+/// @param handle Engine handle
+/// @param mode Writing mode (0-3)
+/// @param template Prompt template
+/// @return true on success; false if invalid (see flow_get_last_error)
+bool flow_set_mode_prompt_template(FlowHandle* handle, uint8_t mode, const char* template_text);
+
+/// Remove a writing mode's prompt template so its built-in prompt applies again
+/// @param handle Engine handle
+/// @param mode Writing mode (0-3)
+/// @return true if a template was removed
+bool flow_clear_mode_prompt_template(FlowHandle* handle, uint8_t mode);
+
+/// Get a contact's emoji usage as JSON
+/// {"dictations": n, "with_emoji": n, "pinned": "add"|null, "learned": "avoid"|null}
+/// @param handle Engine handle
+/// @param contact_name Contact name
+/// @return JSON string (caller must free with flow_free_string), or NULL on error
+char* flow_get_contact_emoji_usage(FlowHandle* handle, const char* contact_name);
+
+/// Pin an emoji policy to a contact, overriding the learned one
+/// @param handle Engine handle
+/// @param contact_name Contact name
+/// @param policy 0 = avoid, 1 = preserve, 2 = add
+/// @return true on success
+bool flow_set_contact_emoji_policy(FlowHandle* handle, const char* contact_name, uint8_t policy);
+
+/// Remove a contact's pinned emoji policy so the learned one applies again
+/// @param handle Engine handle
+/// @param contact_name Contact name
+/// @return true if a pinned policy was removed
+bool flow_clear_contact_emoji_policy(FlowHandle* handle, const char* contact_name);
+
+/// Preview every writing mode on a sample sentence, offline
+/// The rule-based approximation, not what the model will write
+/// @param sample Sample sentence
+/// @return JSON object mapping mode to text, e.g. {"formal": "...", "casual": "..."}
+///         (caller must free with flow_free_string), or NULL if sample is invalid
+char* flow_preview_modes(const char* sample);
+
+/// Get the most recent contact classification decisions, newest first
+/// Only filled in while the log_classifications setting is "true"
+/// @param handle Engine handle
+/// @param limit Maximum number of entries
+/// @return JSON array (caller must free with flow_free_string), or NULL on error
+char* flow_get_classification_log(FlowHandle* handle, uint32_t limit);
+
 // ============ Learning ============
 
 /// Report a user edit to learn from
@@ -165,6 +306,11 @@ void flow_free_string(char* s);
 /// @return true if configured
 bool flow_is_configured(FlowHandle* handle);
 
+/// Send a tiny request to the configured providers to confirm the key and model work (blocking)
+/// @param handle Engine handle
+/// @return true if both providers answered; false with the reason in flow_get_last_error
+bool flow_health_check(FlowHandle* handle);
+
 // ============ App Tracking ============
 
 /// Set the currently active app
@@ -205,6 +351,12 @@ uint8_t flow_get_style_suggestion(FlowHandle* handle);
 /// @return JSON string (caller must free with flow_free_string)
 char* flow_get_stats_json(FlowHandle* handle);
 
+/// Get completion token usage per provider/model as JSON
+/// @param handle Engine handle
+/// @param since_unix_secs Start of the window (unix seconds)
+/// @return JSON array (caller must free with flow_free_string), or NULL on error
+char* flow_get_usage_summary_json(FlowHandle* handle, int64_t since_unix_secs);
+
 /// Get recent transcriptions as JSON
 /// @param handle Engine handle
 /// @param limit Maximum number of transcriptions to return
@@ -220,7 +372,7 @@ char* flow_get_shortcuts_json(FlowHandle* handle);
 
 /// Switch completion provider (loads API key from database)
 /// @param handle Engine handle
-/// @param provider 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
+/// @param provider 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = None (raw transcription), 4 = Rules (offline)
 /// @return true on success
 bool flow_switch_completion_provider(FlowHandle* handle, uint8_t provider);
 
@@ -233,9 +385,15 @@ bool flow_set_completion_provider(FlowHandle* handle, uint8_t provider, const ch
 
 /// Get current completion provider
 /// @param handle Engine handle
-/// @return 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 255 = Unknown
+/// @return 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = None, 4 = Rules, 255 = Unknown
 uint8_t flow_get_completion_provider(FlowHandle* handle);
 
+/// Set the model used by the current completion provider (saved per provider)
+/// @param handle Engine handle
+/// @param model Model identifier, or NULL/empty to use the provider default
+/// @return true on success
+bool flow_set_completion_model(FlowHandle* handle, const char* model);
+
 /// Get API key for a specific provider in masked form (e.g., "sk-••••••••")
 /// @param handle Engine handle
 /// @param provider 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
@@ -287,6 +445,16 @@ uint8_t flow_get_cloud_transcription_provider(FlowHandle* handle);
 /// @return Error string (caller must free with flow_free_string) or NULL if none
 char* flow_get_last_error(FlowHandle* handle);
 
+/// Get the last macOS permission a detector found denied, and clear it
+/// @param handle Engine handle
+/// @return 0 = none, 1 = Accessibility, 2 = Automation, 3 = Contacts
+uint8_t flow_take_missing_permission(FlowHandle* handle);
+
+/// Get the System Settings deep link for a permission
+/// @param permission 1 = Accessibility, 2 = Automation, 3 = Contacts
+/// @return URL string (caller must free with flow_free_string) or NULL if unknown
+char* flow_permission_settings_url(uint8_t permission);
+
 #ifdef __cplusplus
 }
 #endif
@@ -3,21 +3,46 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{Device, Sample, SampleFormat, SizedSample, Stream, StreamConfig};
 use parking_lot::Mutex;
+use serde::Serialize;
+use std::path::Path;
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
 
-use crate::AudioData;
 use crate::error::{Error, Result};
+use crate::{AudioClip, AudioData, PcmFormat};
 
 /// Audio capture configuration
 #[derive(Debug, Clone)]
 pub struct AudioCaptureConfig {
     /// Sample rate in Hz (default: 16000 for speech recognition)
+    /// 0 opens the device in its default format; see [`AudioCapture::negotiated_config`]
     pub sample_rate: u32,
     /// Number of channels (default: 1 for mono)
     pub channels: u16,
-    /// Buffer size in samples
+    /// Buffer size in samples (also the interval for duration callbacks)
     pub buffer_size: usize,
+    /// RMS level (0.0-1.0) that moves an armed capture into recording
+    pub speech_threshold: f32,
+    /// Stop buffering once this much audio is captured (guards against stuck push-to-talk)
+    /// Off by default. Hitting the limit moves the capture to Idle and flags the clip
+    /// `truncated` (see [`AudioCapture::was_truncated`]), but leaves the input device
+    /// open: call `stop()` as usual to release it and get the clip.
+    pub max_duration_ms: Option<u64>,
+    /// Fail `stop()` with `Error::TooShort` for shorter recordings, so an accidental
+    /// push-to-talk tap never reaches a provider (0 to accept any length)
+    pub min_duration_ms: u64,
+    /// Fail `stop()` with `Error::NoSignal` instead of returning a clip with no input
+    pub strict_signal_check: bool,
+    /// Encoding of the captured PCM (default: 16-bit for Whisper compatibility)
+    pub sample_format: PcmFormat,
+    /// Multiplier applied to input before buffering; see [`AudioCapture::calibrate`]
+    pub gain: f32,
+    /// Consecutive full-scale samples that count as one clipping event
+    pub clip_run_threshold: usize,
 }
 
 impl Default for AudioCaptureConfig {
@@ -26,6 +51,13 @@ impl Default for AudioCaptureConfig {
             sample_rate: 16000,
             channels: 1,
             buffer_size: 4096,
+            speech_threshold: 0.02,
+            max_duration_ms: None,
+            min_duration_ms: DEFAULT_MIN_DURATION_MS,
+            strict_signal_check: false,
+            sample_format: PcmFormat::Pcm16,
+            gain: 1.0,
+            clip_run_threshold: DEFAULT_CLIP_RUN,
         }
     }
 }
@@ -34,8 +66,130 @@ impl Default for AudioCaptureConfig {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CaptureState {
     Idle,
+    /// Stream is running but nothing is buffered until speech is detected
+    Armed,
     Recording,
     Paused,
+    /// The input device failed (e.g. it was unplugged); see [`AudioCapture::take_error`]
+    Errored,
+}
+
+/// Callback fired on every capture state transition
+/// Runs on a notification thread of its own, in transition order, so transitions made
+/// on the audio thread (Armed -> Recording) never wait on it
+pub type StateCallback = Box<dyn Fn(CaptureState) + Send + 'static>;
+
+/// Callback receiving the buffered duration in milliseconds
+/// Invoked from the audio thread each time another `buffer_size` samples arrive
+pub type DurationCallback = Box<dyn Fn(u64) + Send + 'static>;
+
+/// Callback receiving the number of clipping events so far in the recording
+/// Invoked from the audio thread whenever a new event is detected
+pub type ClippingCallback = Box<dyn Fn(u64) + Send + 'static>;
+
+/// Capture state shared with the audio thread, notifying the callbacks on change
+struct SharedState {
+    state: Mutex<CaptureState>,
+    /// Queue to the thread running the state callback, if one is set
+    notifier: Mutex<Option<mpsc::Sender<CaptureState>>>,
+    duration_callback: Mutex<Option<DurationCallback>>,
+    clipping_callback: Mutex<Option<ClippingCallback>>,
+    /// Clipping events detected since the stream opened
+    clip_events: AtomicU64,
+    /// Set when `max_duration_ms` cut the recording short
+    truncated: AtomicBool,
+    /// Last stream error reported by the device
+    error: Mutex<Option<String>>,
+}
+
+impl SharedState {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(CaptureState::Idle),
+            notifier: Mutex::new(None),
+            duration_callback: Mutex::new(None),
+            clipping_callback: Mutex::new(None),
+            clip_events: AtomicU64::new(0),
+            truncated: AtomicBool::new(false),
+            error: Mutex::new(None),
+        }
+    }
+
+    fn get(&self) -> CaptureState {
+        *self.state.lock()
+    }
+
+    fn set(&self, next: CaptureState) {
+        let prev = std::mem::replace(&mut *self.state.lock(), next);
+        if prev != next {
+            self.notify(next);
+        }
+    }
+
+    /// Transition only if currently in `expected`; returns whether it happened
+    fn set_if(&self, expected: CaptureState, next: CaptureState) -> bool {
+        {
+            let mut state = self.state.lock();
+            if *state != expected {
+                return false;
+            }
+            *state = next;
+        }
+        if expected != next {
+            self.notify(next);
+        }
+        true
+    }
+
+    /// Record a device error and move to `Errored`
+    fn fail(&self, message: String) {
+        *self.error.lock() = Some(message);
+        self.set(CaptureState::Errored);
+    }
+
+    /// Run `callback` for each transition on a new notification thread, or stop
+    /// notifying; a replaced thread exits once it has delivered what was queued
+    fn set_callback(&self, callback: Option<StateCallback>) {
+        let notifier = callback.map(|callback| {
+            let (tx, rx) = mpsc::channel::<CaptureState>();
+            std::thread::spawn(move || {
+                for state in rx {
+                    callback(state);
+                }
+            });
+            tx
+        });
+        *self.notifier.lock() = notifier;
+    }
+
+    /// Queue `state` for the callback; never blocks on it
+    fn notify(&self, state: CaptureState) {
+        if let Some(notifier) = self.notifier.lock().as_ref() {
+            let _ = notifier.send(state);
+        }
+    }
+
+    /// Count `events` new clipping events and tell the callback
+    fn report_clipping(&self, events: usize) {
+        if events == 0 {
+            return;
+        }
+        let total = self.clip_events.fetch_add(events as u64, Ordering::SeqCst) + events as u64;
+        if let Some(callback) = self.clipping_callback.lock().as_ref() {
+            callback(total);
+        }
+    }
+
+    /// Report the buffered duration if the buffer just crossed a `interval` boundary
+    fn report_progress(&self, before: usize, after: usize, interval: usize, sample_rate: u32) {
+        let interval = interval.max(1);
+        if after / interval == before / interval {
+            return;
+        }
+        if let Some(callback) = self.duration_callback.lock().as_ref() {
+            callback(after as u64 * 1000 / sample_rate.max(1) as u64);
+        }
+    }
 }
 
 /// Handles audio capture from the default input device
@@ -45,9 +199,10 @@ pub struct AudioCapture {
     stream_config: StreamConfig,
     input_channels: u16,
     sample_format: SampleFormat,
-    state: Arc<Mutex<CaptureState>>,
+    state: Arc<SharedState>,
     buffer: Arc<Mutex<Vec<f32>>>,
     stream: Option<Stream>,
+    last_stats: Option<CaptureStats>,
 }
 
 impl AudioCapture {
@@ -58,12 +213,44 @@ impl AudioCapture {
 
     /// Create a new AudioCapture with custom configuration
     pub fn with_config(config: AudioCaptureConfig) -> Result<Self> {
-        let host = cpal::default_host();
-
-        let device = host
+        let device = cpal::default_host()
             .default_input_device()
             .ok_or_else(|| Error::Audio("No input device available".to_string()))?;
+        Self::with_device(device, config)
+    }
+
+    /// Capture what the system plays (the other side of a call) instead of the mic
+    ///
+    /// There's no loopback input built into macOS, so this records from a loopback
+    /// driver such as BlackHole (on Linux, a PulseAudio monitor source), picked by
+    /// its default name. The driver is an ordinary input device, so no permission
+    /// beyond the microphone's is involved. Audio comes out as the same
+    /// [`AudioClip`] as mic capture. To record from an aggregate device that
+    /// combines the driver with the speakers, or a renamed one, pass its name to
+    /// [`with_device_name`](Self::with_device_name).
+    pub fn system_audio() -> Result<Self> {
+        Self::system_audio_with_config(AudioCaptureConfig::default())
+    }
 
+    /// [`system_audio`](Self::system_audio) with custom configuration
+    pub fn system_audio_with_config(config: AudioCaptureConfig) -> Result<Self> {
+        let device = find_input_device(is_loopback_device)?.ok_or_else(|| {
+            Error::Audio(
+                "No system audio device found, install a loopback driver such as BlackHole"
+                    .to_string(),
+            )
+        })?;
+        Self::with_device(device, config)
+    }
+
+    /// Capture from the input device called `name` (as [`list_input_devices`] reports it)
+    pub fn with_device_name(name: &str, config: AudioCaptureConfig) -> Result<Self> {
+        let device = find_input_device(|device_name| device_name == name)?
+            .ok_or_else(|| Error::Audio(format!("No input device named '{name}'")))?;
+        Self::with_device(device, config)
+    }
+
+    fn with_device(device: Device, config: AudioCaptureConfig) -> Result<Self> {
         // note: device.name() is deprecated in cpal 0.17+, but works
         #[allow(deprecated)]
         let device_name = device.name().unwrap_or_else(|_| "Unknown".to_string());
@@ -79,8 +266,25 @@ impl AudioCapture {
         }
 
         let (supported_config, input_channels, sample_format, sample_rate) =
-            select_supported_config(&supported_configs, config.sample_rate, config.channels)
-                .ok_or_else(|| Error::Audio("No supported input config found".to_string()))?;
+            if config.sample_rate == 0 {
+                // pro-audio interfaces can reject anything but their native format
+                let native = device.default_input_config().map_err(|e| {
+                    Error::Audio(format!("Failed to get default input config: {e}"))
+                })?;
+                let (channels, format, rate) = (
+                    native.channels(),
+                    native.sample_format(),
+                    native.sample_rate(),
+                );
+                info!(
+                    "Using device default format: {} Hz, {} channels, {:?}",
+                    rate, channels, format
+                );
+                (native, channels, format, rate)
+            } else {
+                select_supported_config(&supported_configs, config.sample_rate, config.channels)
+                    .ok_or_else(|| Error::Audio("No supported input config found".to_string()))?
+            };
 
         let stream_config = supported_config.config();
 
@@ -99,25 +303,113 @@ impl AudioCapture {
             stream_config,
             input_channels,
             sample_format,
-            state: Arc::new(Mutex::new(CaptureState::Idle)),
+            state: Arc::new(SharedState::new()),
             buffer: Arc::new(Mutex::new(Vec::new())),
             stream: None,
+            last_stats: None,
         })
     }
 
     /// Start recording audio
+    /// If the capture is armed, recording begins immediately on the existing stream
     pub fn start(&mut self) -> Result<()> {
-        if *self.state.lock() == CaptureState::Recording {
-            return Ok(());
+        match self.state.get() {
+            CaptureState::Recording => return Ok(()),
+            CaptureState::Armed if self.stream.is_some() => {
+                self.state.set(CaptureState::Recording);
+                info!("Audio capture started (from armed)");
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        self.open_stream()?;
+        self.state.set(CaptureState::Recording);
+
+        info!("Audio capture started");
+        Ok(())
+    }
+
+    /// Start the stream without buffering; recording begins on the first chunk
+    /// whose level reaches `speech_threshold`
+    pub fn arm(&mut self) -> Result<()> {
+        match self.state.get() {
+            CaptureState::Armed | CaptureState::Recording => return Ok(()),
+            _ => {}
         }
 
+        self.open_stream()?;
+        self.state.set(CaptureState::Armed);
+
+        info!("Audio capture armed");
+        Ok(())
+    }
+
+    /// Register a callback fired on every state transition (replaces any previous one)
+    pub fn set_state_callback(&mut self, callback: impl Fn(CaptureState) + Send + 'static) {
+        self.state.set_callback(Some(Box::new(callback)));
+    }
+
+    /// Remove the state transition callback
+    pub fn clear_state_callback(&mut self) {
+        self.state.set_callback(None);
+    }
+
+    /// Register a callback receiving the buffered duration (ms) every `buffer_size` samples
+    pub fn set_duration_callback(&mut self, callback: impl Fn(u64) + Send + 'static) {
+        *self.state.duration_callback.lock() = Some(Box::new(callback));
+    }
+
+    /// Remove the duration callback
+    pub fn clear_duration_callback(&mut self) {
+        *self.state.duration_callback.lock() = None;
+    }
+
+    /// Register a callback fired when the input clips, so the UI can suggest moving back
+    /// from the mic or lowering the gain (replaces any previous one)
+    pub fn set_clipping_callback(&mut self, callback: impl Fn(u64) + Send + 'static) {
+        *self.state.clipping_callback.lock() = Some(Box::new(callback));
+    }
+
+    /// Remove the clipping callback
+    pub fn clear_clipping_callback(&mut self) {
+        *self.state.clipping_callback.lock() = None;
+    }
+
+    /// Clipping events detected so far in the current recording
+    pub fn clip_count(&self) -> u64 {
+        self.state.clip_events.load(Ordering::SeqCst)
+    }
+
+    /// Whether the last recording hit `max_duration_ms` and stopped buffering on its own
+    /// The device stays open until `stop()`, which still has to be called
+    pub fn was_truncated(&self) -> bool {
+        self.state.truncated.load(Ordering::SeqCst)
+    }
+
+    /// Take the device error that moved the capture to `Errored`, if any
+    pub fn take_error(&mut self) -> Option<String> {
+        self.state.error.lock().take()
+    }
+
+    /// Build and play a fresh input stream with an empty buffer
+    fn open_stream(&mut self) -> Result<()> {
+        // a new buffer rather than a cleared one: a callback from a stream that's still
+        // tearing down writes to the old one and can't leak into this session
+        self.buffer = Arc::new(Mutex::new(Vec::new()));
         let buffer = Arc::clone(&self.buffer);
         let state = Arc::clone(&self.state);
 
-        // clear buffer
-        buffer.lock().clear();
+        self.state.truncated.store(false, Ordering::SeqCst);
+        self.state.clip_events.store(0, Ordering::SeqCst);
+        self.state.error.lock().take();
 
-        let err_fn = |err| error!("Audio stream error: {}", err);
+        // a failed device never recovers on the same stream, so stop buffering and report it
+        let err_state = Arc::clone(&self.state);
+        let err_fn = move |err: cpal::StreamError| {
+            error!("Audio stream error: {}", err);
+            err_state.fail(err.to_string());
+        };
 
         let stream = match self.sample_format {
             SampleFormat::F32 => self.build_stream::<f32>(buffer, state, err_fn)?,
@@ -145,55 +437,153 @@ impl AudioCapture {
             .map_err(|e| Error::Audio(format!("Failed to start stream: {e}")))?;
 
         self.stream = Some(stream);
-        *self.state.lock() = CaptureState::Recording;
-
-        info!("Audio capture started");
         Ok(())
     }
 
-    /// Stop recording and return the captured audio data
-    pub fn stop(&mut self) -> Result<AudioData> {
-        *self.state.lock() = CaptureState::Idle;
+    /// Stop recording and return the captured audio with its format
+    /// The clip is flagged `truncated` if the capture hit `max_duration_ms`.
+    /// Fails with `Error::Audio` if the device errored mid-recording; the error
+    /// stays available from `take_error`. Fails with `Error::TooShort` (discarding
+    /// the audio) if less than `min_duration_ms` was captured.
+    pub fn stop(&mut self) -> Result<AudioClip> {
+        let errored = self.state.get() == CaptureState::Errored;
+        self.state.set(CaptureState::Idle);
 
         // drop the stream to stop recording
         self.stream = None;
 
+        if errored {
+            self.buffer.lock().clear();
+            let reason = self.state.error.lock().clone().unwrap_or_default();
+            return Err(Error::Audio(format!(
+                "Input device disconnected: {}",
+                reason
+            )));
+        }
+
         let samples = std::mem::take(&mut *self.buffer.lock());
-        let audio_data = self.samples_to_pcm(&samples);
+        let duration_ms = (samples.len() as u64 * 1000) / self.config.sample_rate.max(1) as u64;
+        self.check_min_duration(duration_ms)?;
+        let audio_data = encode_samples(&samples, self.config.sample_format);
 
         info!("Audio capture stopped, {} bytes captured", audio_data.len());
-        Ok(audio_data)
+        let mut clip = AudioClip::new(audio_data, self.config.sample_rate, self.config.channels)
+            .with_format(self.config.sample_format);
+        clip.truncated = self.was_truncated();
+
+        let stats = CaptureStats::from_clip(&clip, self.clip_count());
+        self.last_stats = Some(stats);
+        if stats.likely_no_signal {
+            warn!(
+                "Captured {}ms with no signal (peak {:.4}), check the input device",
+                stats.duration_ms, stats.peak
+            );
+            if self.config.strict_signal_check {
+                return Err(Error::NoSignal(stats.peak));
+            }
+        }
+
+        Ok(clip)
+    }
+
+    /// Discard the current recording and start a new one in a single call
+    ///
+    /// The old stream is dropped before the new one opens, and the new one records
+    /// into a fresh buffer, so audio from the previous session can't bleed in even if
+    /// its stream is still delivering a last chunk. Use this instead of `stop()` then
+    /// `start()` for rapid push-to-talk.
+    pub fn restart(&mut self) -> Result<()> {
+        self.state.set(CaptureState::Idle);
+        self.stream = None;
+
+        self.open_stream()?;
+        self.state.set(CaptureState::Recording);
+
+        info!("Audio capture restarted");
+        Ok(())
+    }
+
+    /// Stats for the clip returned by the last `stop()`
+    pub fn last_stats(&self) -> Option<CaptureStats> {
+        self.last_stats
+    }
+
+    /// Record for `duration_ms` while the user speaks and return the gain that brings
+    /// their speech peaks to about -6 dBFS
+    ///
+    /// Blocks for the whole sample. Fails if a recording is already running, if
+    /// `duration_ms` is under `min_duration_ms` (the sample would be discarded as too
+    /// short), or if the sample holds no speech (see [`recommended_gain`]).
+    pub fn calibrate(&mut self, duration_ms: u64) -> Result<f32> {
+        if self.state.get() != CaptureState::Idle {
+            return Err(Error::Audio(
+                "Can't calibrate while a recording is running".to_string(),
+            ));
+        }
+        if duration_ms < self.config.min_duration_ms {
+            return Err(Error::Audio(format!(
+                "Calibration needs at least {}ms of audio, got {}ms",
+                self.config.min_duration_ms, duration_ms
+            )));
+        }
+
+        self.start()?;
+        std::thread::sleep(std::time::Duration::from_millis(duration_ms));
+        let clip = self.stop()?;
+
+        let gain = recommended_gain(&clip_to_f32_mono(&clip), clip.sample_rate, self.config.gain)?;
+        info!("Calibrated input gain: {:.2}", gain);
+        Ok(gain)
     }
 
     /// Stop recording without draining the buffer
     pub fn stop_stream(&mut self) -> Result<()> {
-        *self.state.lock() = CaptureState::Idle;
+        self.state.set(CaptureState::Idle);
         self.stream = None;
         info!("Audio capture stopped (buffer retained)");
         Ok(())
     }
 
-    /// Drain buffered audio into PCM data without touching the stream
+    /// Drain buffered audio into PCM data (in the configured sample format) without touching the stream
     pub fn take_buffered_audio(&mut self) -> AudioData {
         let samples = std::mem::take(&mut *self.buffer.lock());
-        self.samples_to_pcm(&samples)
+        encode_samples(&samples, self.config.sample_format)
+    }
+
+    /// Encoding of the PCM this capture produces
+    pub fn sample_format(&self) -> PcmFormat {
+        self.config.sample_format
     }
 
     /// Pause recording (keeps stream alive but stops buffering)
     pub fn pause(&mut self) {
-        *self.state.lock() = CaptureState::Paused;
+        // keep Errored visible until the caller stops
+        if self.state.get() == CaptureState::Errored {
+            return;
+        }
+        self.state.set(CaptureState::Paused);
         debug!("Audio capture paused");
     }
 
     /// Resume recording after pause
     pub fn resume(&mut self) {
-        *self.state.lock() = CaptureState::Recording;
-        debug!("Audio capture resumed");
+        if self
+            .state
+            .set_if(CaptureState::Paused, CaptureState::Recording)
+        {
+            debug!("Audio capture resumed");
+        }
     }
 
     /// Get current capture state
     pub fn state(&self) -> CaptureState {
-        *self.state.lock()
+        self.state.get()
+    }
+
+    /// `Error::TooShort` if `duration_ms` is under the configured `min_duration_ms`
+    /// For callers that drain the buffer themselves instead of calling `stop()`.
+    pub fn check_min_duration(&self, duration_ms: u64) -> Result<()> {
+        check_min_duration(duration_ms, self.config.min_duration_ms)
     }
 
     /// Get current buffer duration in milliseconds
@@ -202,6 +592,16 @@ impl AudioCapture {
         (samples as u64 * 1000) / (self.config.sample_rate as u64 * self.config.channels as u64)
     }
 
+    /// Stream parameters cpal actually opened: (sample rate, input channels, sample format)
+    /// Captured audio is downmixed to mono at this rate.
+    pub fn negotiated_config(&self) -> (u32, u16, SampleFormat) {
+        (
+            self.stream_config.sample_rate,
+            self.input_channels,
+            self.sample_format,
+        )
+    }
+
     /// Current capture sample rate
     pub fn sample_rate(&self) -> u32 {
         self.config.sample_rate
@@ -210,28 +610,25 @@ impl AudioCapture {
     /// Get current audio level (RMS amplitude) from the last 50ms of audio
     /// Returns a value between 0.0 and 1.0
     pub fn current_audio_level(&self) -> f32 {
-        let buffer = self.buffer.lock();
-        if buffer.is_empty() {
-            return 0.0;
-        }
-
-        // Calculate how many samples represent 50ms
-        let samples_per_50ms = (self.config.sample_rate as usize / 20).max(1);
-        let start_idx = buffer.len().saturating_sub(samples_per_50ms);
-        let recent_samples = &buffer[start_idx..];
-
-        // Calculate RMS (root mean square) for perceived loudness
-        let sum_squares: f32 = recent_samples.iter().map(|&s| s * s).sum();
-        let rms = (sum_squares / recent_samples.len() as f32).sqrt();
+        // RMS (root mean square) for perceived loudness
+        let level = tail_rms(&self.buffer.lock(), self.config.sample_rate, 50);
 
         // Amplify a bit for visual effect (typical speech is quite quiet)
-        (rms * 3.0).min(1.0)
+        (level * 3.0).min(1.0)
+    }
+
+    /// Whether the last `window_ms` of buffered audio has an RMS below `threshold`
+    /// (e.g. [`SILENCE_RMS_THRESHOLD`]). An empty buffer counts as silent.
+    /// Only holds the buffer lock for one pass over the window, so it's fine to poll
+    /// from UI code for "speak now" prompts.
+    pub fn is_silence(&self, window_ms: u64, threshold: f32) -> bool {
+        tail_rms(&self.buffer.lock(), self.config.sample_rate, window_ms) < threshold
     }
 
     fn build_stream<T>(
         &self,
         buffer: Arc<Mutex<Vec<f32>>>,
-        state: Arc<Mutex<CaptureState>>,
+        state: Arc<SharedState>,
         err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
     ) -> Result<Stream>
     where
@@ -240,27 +637,66 @@ impl AudioCapture {
     {
         let channels = self.input_channels as usize;
         let stream_config = self.stream_config.clone();
+        let speech_threshold = self.config.speech_threshold;
+        let gain = self.config.gain;
+        let mut clip_detector = ClipDetector::new(self.config.clip_run_threshold);
+        let progress_interval = self.config.buffer_size;
+        let sample_rate = self.config.sample_rate;
+        let max_samples = self
+            .config
+            .max_duration_ms
+            .map(|ms| (ms * sample_rate as u64 / 1000) as usize);
 
         self.device
             .build_input_stream(
                 &stream_config,
                 move |data: &[T], _: &cpal::InputCallbackInfo| {
-                    if *state.lock() != CaptureState::Recording {
+                    let current = state.get();
+                    if current != CaptureState::Recording && current != CaptureState::Armed {
                         return;
                     }
 
-                    if channels == 1 {
-                        buffer
-                            .lock()
-                            .extend(data.iter().map(|sample| sample.to_sample::<f32>()));
-                    } else {
-                        let mut buf = buffer.lock();
-                        for frame in data.chunks_exact(channels) {
-                            let mut sum = 0.0f32;
-                            for sample in frame {
-                                sum += sample.to_sample::<f32>();
+                    let mono = || {
+                        to_mono_f32(data, channels).map(|sample| {
+                            if gain == 1.0 {
+                                sample
+                            } else {
+                                (sample * gain).clamp(-1.0, 1.0)
                             }
-                            buf.push(sum / channels as f32);
+                        })
+                    };
+
+                    // armed: wait for the first chunk loud enough to count as speech
+                    if current == CaptureState::Armed
+                        && (rms_of(mono()) < speech_threshold
+                            || !state.set_if(CaptureState::Armed, CaptureState::Recording))
+                    {
+                        return;
+                    }
+
+                    // converted straight into the buffer: no allocation on the audio thread
+                    // beyond the buffer's own growth
+                    let (before, after, hit_limit, clip_events) = {
+                        let mut buf = buffer.lock();
+                        let before = buf.len();
+                        buf.extend(mono());
+                        let clip_events = clip_detector.feed(&buf[before..]);
+                        let hit_limit = max_samples.is_some_and(|max| buf.len() >= max);
+                        if let Some(max) = max_samples {
+                            buf.truncate(max);
+                        }
+                        (before, buf.len(), hit_limit, clip_events)
+                    };
+                    state.report_clipping(clip_events);
+                    // outside the buffer lock so the callback can't stall other readers
+                    state.report_progress(before, after, progress_interval, sample_rate);
+
+                    // stop buffering; a stream can't be dropped from its own callback, so it
+                    // stays open (returning early above) until the caller's stop()
+                    if hit_limit {
+                        state.truncated.store(true, Ordering::SeqCst);
+                        if state.set_if(CaptureState::Recording, CaptureState::Idle) {
+                            warn!("Recording hit max duration, stopped capture");
                         }
                     }
                 },
@@ -269,28 +705,641 @@ impl AudioCapture {
             )
             .map_err(|e| Error::Audio(format!("Failed to build stream: {e}")))
     }
+}
+
+impl Drop for AudioCapture {
+    fn drop(&mut self) {
+        // set directly: no callback while tearing down
+        *self.state.state.lock() = CaptureState::Idle;
+        self.stream = None;
+    }
+}
+
+/// An input device the host reports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InputDevice {
+    pub name: String,
+    /// The system default input, which `AudioCapture` records from
+    pub is_default: bool,
+}
+
+/// Input devices currently attached, in host order
+pub fn list_input_devices() -> Result<Vec<InputDevice>> {
+    let host = cpal::default_host();
+    // note: device.name() is deprecated in cpal 0.17+, but works
+    #[allow(deprecated)]
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+    let devices = host
+        .input_devices()
+        .map_err(|e| Error::Audio(format!("Failed to list input devices: {e}")))?;
+
+    #[allow(deprecated)]
+    Ok(devices
+        .filter_map(|device| device.name().ok())
+        .map(|name| InputDevice {
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            name,
+        })
+        .collect())
+}
+
+/// First input device whose name satisfies `matches`, in host order
+fn find_input_device(matches: impl Fn(&str) -> bool) -> Result<Option<Device>> {
+    let devices = cpal::default_host()
+        .input_devices()
+        .map_err(|e| Error::Audio(format!("Failed to list input devices: {e}")))?;
+    // note: device.name() is deprecated in cpal 0.17+, but works
+    #[allow(deprecated)]
+    Ok(devices
+        .into_iter()
+        .find(|device| device.name().is_ok_and(|name| matches(&name))))
+}
+
+/// How often `watch_devices` polls the host; cpal has no device-change events
+pub const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Background thread reporting input device changes; stops when dropped
+pub struct DeviceWatcher {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for DeviceWatcher {
+    fn drop(&mut self) {
+        // dropping the sender wakes the thread out of its wait
+        self.stop = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Call `callback` with the full device list whenever a device is added or
+/// removed, or the default changes (e.g. AirPods connect)
+///
+/// The current list is not reported on start; call [`list_input_devices`] for it.
+pub fn watch_devices(callback: impl Fn(Vec<InputDevice>) + Send + 'static) -> DeviceWatcher {
+    watch_devices_with(DEVICE_POLL_INTERVAL, list_input_devices, callback)
+}
+
+/// `watch_devices` with the poll interval and device source supplied
+fn watch_devices_with(
+    interval: Duration,
+    mut list: impl FnMut() -> Result<Vec<InputDevice>> + Send + 'static,
+    callback: impl Fn(Vec<InputDevice>) + Send + 'static,
+) -> DeviceWatcher {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let thread = std::thread::spawn(move || {
+        let mut known = list().unwrap_or_default();
+        while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+            match list() {
+                Ok(devices) if devices != known => {
+                    debug!("Input devices changed: {} attached", devices.len());
+                    known = devices.clone();
+                    callback(devices);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Polling input devices failed: {}", e),
+            }
+        }
+    });
+    DeviceWatcher {
+        stop: Some(stop),
+        thread: Some(thread),
+    }
+}
+
+/// Whether the app may record from the microphone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionStatus {
+    Authorized,
+    /// Denied by the user or restricted by policy
+    Denied,
+    /// The user hasn't been asked yet
+    NotDetermined,
+}
+
+/// Default names of the drivers that expose system output as an input device
+const LOOPBACK_DEVICE_NAMES: &[&str] = &[
+    "blackhole",
+    "loopback audio",
+    "soundflower",
+    "zoomaudiodevice",
+    "monitor of",
+];
+
+/// Whether an input device named `name` carries system audio rather than a microphone
+fn is_loopback_device(name: &str) -> bool {
+    let name = name.to_lowercase();
+    LOOPBACK_DEVICE_NAMES
+        .iter()
+        .any(|loopback| name.contains(loopback))
+}
+
+/// How long `request_microphone_permission` waits for the user to answer the prompt
+#[cfg(target_os = "macos")]
+const PERMISSION_PROMPT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Check microphone permission without prompting
+/// Without permission macOS delivers silence instead of failing, so check before `start()`
+pub fn microphone_permission_status() -> PermissionStatus {
+    #[cfg(target_os = "macos")]
+    {
+        // AVAuthorizationStatus: 0 = not determined, 1 = restricted, 2 = denied, 3 = authorized
+        match av_capture::audio_authorization_status() {
+            0 => PermissionStatus::NotDetermined,
+            3 => PermissionStatus::Authorized,
+            _ => PermissionStatus::Denied,
+        }
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        PermissionStatus::Authorized
+    }
+}
+
+/// Prompt for microphone access if the user hasn't been asked, and wait for the answer
+/// Returns `NotDetermined` if the prompt goes unanswered for a minute
+pub async fn request_microphone_permission() -> PermissionStatus {
+    let status = microphone_permission_status();
+    if status != PermissionStatus::NotDetermined {
+        return status;
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let answer = av_capture::request_audio_access();
+        match tokio::time::timeout(PERMISSION_PROMPT_TIMEOUT, answer).await {
+            Ok(Ok(granted)) => info!("Microphone permission granted: {}", granted),
+            Ok(Err(_)) => warn!("Microphone permission request ended without an answer"),
+            Err(_) => return PermissionStatus::NotDetermined,
+        }
+        microphone_permission_status()
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        status
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod av_capture {
+    use futures::channel::oneshot;
+    use std::ffi::{c_char, c_void};
+
+    type Id = *mut c_void;
+    type Sel = *mut c_void;
+
+    #[link(name = "AVFoundation", kind = "framework")]
+    unsafe extern "C" {
+        static AVMediaTypeAudio: Id;
+    }
+
+    #[link(name = "System", kind = "dylib")]
+    unsafe extern "C" {
+        static _NSConcreteStackBlock: c_void;
+    }
+
+    #[link(name = "objc")]
+    unsafe extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn objc_msgSend();
+    }
+
+    /// `[AVCaptureDevice authorizationStatusForMediaType:AVMediaTypeAudio]`
+    pub(super) fn audio_authorization_status() -> isize {
+        unsafe {
+            let class = objc_getClass(c"AVCaptureDevice".as_ptr());
+            if class.is_null() {
+                return 0;
+            }
+            let selector = sel_registerName(c"authorizationStatusForMediaType:".as_ptr());
+            let send = std::mem::transmute::<
+                unsafe extern "C" fn(),
+                unsafe extern "C" fn(Id, Sel, Id) -> isize,
+            >(objc_msgSend);
+            send(class, selector, AVMediaTypeAudio)
+        }
+    }
+
+    #[repr(C)]
+    struct BlockDescriptor {
+        reserved: usize,
+        size: usize,
+    }
 
-    /// Convert f32 samples to 16-bit PCM bytes
-    fn samples_to_pcm(&self, samples: &[f32]) -> AudioData {
-        samples
+    /// Block literal for the `^(BOOL granted)` completion handler, capturing the reply
+    #[repr(C)]
+    struct AccessBlock {
+        isa: *const c_void,
+        flags: i32,
+        reserved: i32,
+        invoke: unsafe extern "C" fn(*mut AccessBlock, i8),
+        descriptor: *const BlockDescriptor,
+        reply: *mut oneshot::Sender<bool>,
+    }
+
+    static ACCESS_BLOCK_DESCRIPTOR: BlockDescriptor = BlockDescriptor {
+        reserved: 0,
+        size: std::mem::size_of::<AccessBlock>(),
+    };
+
+    /// Called once, on a queue of AVFoundation's, with the user's answer
+    unsafe extern "C" fn access_answered(block: *mut AccessBlock, granted: i8) {
+        let reply = unsafe { Box::from_raw((*block).reply) };
+        let _ = reply.send(granted != 0);
+    }
+
+    /// `[AVCaptureDevice requestAccessForMediaType:AVMediaTypeAudio completionHandler:]`
+    /// Shows the prompt; the receiver gets whether access was granted
+    pub(super) fn request_audio_access() -> oneshot::Receiver<bool> {
+        let (tx, rx) = oneshot::channel();
+        unsafe {
+            let class = objc_getClass(c"AVCaptureDevice".as_ptr());
+            if class.is_null() {
+                return rx;
+            }
+            let mut block = AccessBlock {
+                isa: &raw const _NSConcreteStackBlock,
+                flags: 0,
+                reserved: 0,
+                invoke: access_answered,
+                descriptor: &ACCESS_BLOCK_DESCRIPTOR,
+                reply: Box::into_raw(Box::new(tx)),
+            };
+            let selector =
+                sel_registerName(c"requestAccessForMediaType:completionHandler:".as_ptr());
+            let send = std::mem::transmute::<
+                unsafe extern "C" fn(),
+                unsafe extern "C" fn(Id, Sel, Id, *mut AccessBlock),
+            >(objc_msgSend);
+            // the handler runs later, so AVFoundation copies the block (reply pointer
+            // included) to the heap before this returns
+            send(class, selector, AVMediaTypeAudio, &mut block);
+        }
+        rx
+    }
+}
+
+/// Peak level below which a whole clip counts as no input at all (about -60 dBFS)
+/// Far below room noise, so only muted or virtual devices produce it
+pub const NO_SIGNAL_PEAK_THRESHOLD: f32 = 0.001;
+
+/// Summary of a finished capture, so the UI can warn about bad input
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CaptureStats {
+    pub duration_ms: u64,
+    /// Highest absolute sample level (0.0-1.0)
+    pub peak: f32,
+    /// Audio was captured but every sample is near zero (muted or wrong mic)
+    pub likely_no_signal: bool,
+    /// Capture hit `max_duration_ms` and stopped on its own
+    pub truncated: bool,
+    /// Runs of full-scale samples; any at all means the input was too loud
+    pub clip_count: u64,
+}
+
+impl CaptureStats {
+    /// Stats for `clip`, with `clip_count` taken from the capture's live counter
+    /// ([`AudioCapture::clip_count`]) so they agree with what the clipping callback reported
+    pub fn from_clip(clip: &AudioClip, clip_count: u64) -> Self {
+        let samples = decode_samples(&clip.data, clip.format);
+        let peak = samples
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+
+        Self {
+            duration_ms: clip.duration_ms(),
+            peak,
+            likely_no_signal: !clip.is_empty() && peak < NO_SIGNAL_PEAK_THRESHOLD,
+            truncated: clip.truncated,
+            clip_count,
+        }
+    }
+}
+
+/// Level at or above which a sample is at full scale (an i16 max decodes just below 1.0)
+pub const CLIP_LEVEL: f32 = 0.999;
+
+/// Default [`AudioCaptureConfig::min_duration_ms`]; shorter than any real utterance
+pub const DEFAULT_MIN_DURATION_MS: u64 = 300;
+
+fn check_min_duration(duration_ms: u64, min_ms: u64) -> Result<()> {
+    if duration_ms < min_ms {
+        info!(
+            "Discarding {}ms recording, shorter than {}ms",
+            duration_ms, min_ms
+        );
+        return Err(Error::TooShort {
+            duration_ms,
+            min_ms,
+        });
+    }
+    Ok(())
+}
+
+/// Default [`AudioCaptureConfig::clip_run_threshold`]; a lone full-scale sample can be
+/// a legitimate peak, a run of them is the converter flattening the wave
+pub const DEFAULT_CLIP_RUN: usize = 3;
+
+/// Counts clipping events across consecutive chunks of samples
+#[derive(Debug, Clone)]
+struct ClipDetector {
+    min_run: usize,
+    run: usize,
+}
+
+impl ClipDetector {
+    fn new(min_run: usize) -> Self {
+        Self {
+            min_run: min_run.max(1),
+            run: 0,
+        }
+    }
+
+    /// New clipping events in `samples`; a run continuing from the last chunk counts once
+    fn feed(&mut self, samples: &[f32]) -> usize {
+        let mut events = 0;
+        for sample in samples {
+            if sample.abs() >= CLIP_LEVEL {
+                self.run += 1;
+                if self.run == self.min_run {
+                    events += 1;
+                }
+            } else {
+                self.run = 0;
+            }
+        }
+        events
+    }
+}
+
+/// Convert f32 samples to 16-bit little-endian PCM bytes
+/// Rounds half to even; negatives scale by 32768 so -1.0 reaches i16::MIN
+pub fn samples_to_pcm(samples: &[f32]) -> AudioData {
+    samples
+        .iter()
+        .flat_map(|&sample| {
+            let clamped = sample.clamp(-1.0, 1.0);
+            let scale = if clamped < 0.0 { 32768.0 } else { 32767.0 };
+            let pcm = (clamped * scale).round_ties_even() as i16;
+            pcm.to_le_bytes()
+        })
+        .collect()
+}
+
+/// Convert 16-bit little-endian PCM bytes back to f32 samples (inverse of [`samples_to_pcm`])
+/// Channels are left interleaved; a trailing odd byte is ignored
+pub fn pcm_to_f32(data: &[u8]) -> Vec<f32> {
+    data.chunks_exact(2)
+        .map(|bytes| {
+            let pcm = i16::from_le_bytes([bytes[0], bytes[1]]) as f32;
+            if pcm < 0.0 {
+                pcm / 32768.0
+            } else {
+                pcm / 32767.0
+            }
+        })
+        .collect()
+}
+
+/// Encode f32 samples as little-endian PCM in `format`
+pub fn encode_samples(samples: &[f32], format: PcmFormat) -> AudioData {
+    match format {
+        PcmFormat::Pcm16 => samples_to_pcm(samples),
+        PcmFormat::Pcm24 => samples
             .iter()
             .flat_map(|&sample| {
-                // clamp and convert to i16
-                let clamped = sample.clamp(-1.0, 1.0);
-                let pcm = (clamped * 32767.0) as i16;
-                pcm.to_le_bytes()
+                let value = (sample.clamp(-1.0, 1.0) * 8_388_607.0).round_ties_even() as i32;
+                let bytes = value.to_le_bytes();
+                [bytes[0], bytes[1], bytes[2]]
             })
-            .collect()
+            .collect(),
+        PcmFormat::Float32 => samples
+            .iter()
+            .flat_map(|&sample| sample.clamp(-1.0, 1.0).to_le_bytes())
+            .collect(),
     }
 }
 
-impl Drop for AudioCapture {
-    fn drop(&mut self) {
-        *self.state.lock() = CaptureState::Idle;
-        self.stream = None;
+/// Decode little-endian PCM in `format` back to f32 samples (inverse of [`encode_samples`])
+/// Channels are left interleaved; a trailing partial sample is ignored
+pub fn decode_samples(data: &[u8], format: PcmFormat) -> Vec<f32> {
+    match format {
+        PcmFormat::Pcm16 => pcm_to_f32(data),
+        PcmFormat::Pcm24 => data
+            .chunks_exact(3)
+            // shift into the top bytes so the sign extends, then back down
+            .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_607.0)
+            .collect(),
+        PcmFormat::Float32 => data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    }
+}
+
+/// Decode a clip to mono f32 samples, whatever its format and channel count
+pub fn clip_to_f32_mono(clip: &AudioClip) -> Vec<f32> {
+    downmix(decode_samples(&clip.data, clip.format), clip.channels)
+}
+
+/// Convert interleaved 16-bit PCM to mono f32 by averaging each frame
+pub fn pcm_to_f32_mono(data: &[u8], channels: u16) -> Vec<f32> {
+    downmix(pcm_to_f32(data), channels)
+}
+
+/// Linearly interpolate mono samples from `from_rate` to `to_rate`
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate == 0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let step = from_rate as f64 / to_rate as f64;
+    let output_len = (samples.len() as u64 * to_rate as u64 / from_rate as u64) as usize;
+    (0..output_len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let idx = pos as usize;
+            let frac = (pos - idx as f64) as f32;
+            match (samples.get(idx), samples.get(idx + 1)) {
+                (Some(&a), Some(&b)) => a + (b - a) * frac,
+                (Some(&a), None) => a,
+                _ => 0.0,
+            }
+        })
+        .collect()
+}
+
+/// Compressed formats `read_audio_file` hands to afconvert with `audio-decode`
+const DECODED_EXTENSIONS: &[&str] = &["m4a", "aac", "mp3", "caf", "aif", "aiff", "mp4"];
+
+/// Load a recording from disk in its own rate, channel count, and encoding
+///
+/// WAV is read natively. The formats in `DECODED_EXTENSIONS` need the
+/// `audio-decode` feature on macOS; anything else is an `Error::Audio`.
+pub fn read_audio_file(path: &Path) -> Result<AudioClip> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+
+    if extension == "wav" || extension == "wave" {
+        return AudioClip::from_wav(&std::fs::read(path)?);
+    }
+    if !DECODED_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(Error::Audio(format!(
+            "Unsupported audio file: {}",
+            path.display()
+        )));
+    }
+    decode_with_afconvert(path)
+}
+
+#[cfg(all(feature = "audio-decode", target_os = "macos"))]
+fn decode_with_afconvert(path: &Path) -> Result<AudioClip> {
+    let wav_path = std::env::temp_dir().join(format!("flow-decode-{}.wav", uuid::Uuid::new_v4()));
+    let output = std::process::Command::new("/usr/bin/afconvert")
+        .args(["-f", "WAVE", "-d", "LEI16"])
+        .arg(path)
+        .arg(&wav_path)
+        .output()?;
+    let decoded = if output.status.success() {
+        std::fs::read(&wav_path)
+            .map_err(Error::from)
+            .and_then(|bytes| AudioClip::from_wav(&bytes))
+    } else {
+        Err(Error::Audio(format!(
+            "afconvert failed on {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    };
+    let _ = std::fs::remove_file(&wav_path);
+    decoded
+}
+
+#[cfg(not(all(feature = "audio-decode", target_os = "macos")))]
+fn decode_with_afconvert(path: &Path) -> Result<AudioClip> {
+    Err(Error::Audio(format!(
+        "Decoding {} needs the audio-decode feature on macOS; convert it to WAV first",
+        path.display()
+    )))
+}
+
+fn downmix(samples: Vec<f32>, channels: u16) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    if channels == 1 {
+        return samples;
+    }
+
+    samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Speech peak level `recommended_gain` aims for (about -6 dBFS)
+pub const CALIBRATION_TARGET_PEAK: f32 = 0.5;
+
+/// Range `recommended_gain` clamps to; beyond it the mic or its placement is the problem
+pub const MIN_GAIN: f32 = 0.25;
+pub const MAX_GAIN: f32 = 8.0;
+
+/// 50ms windows at least this loud (RMS) count as speech during calibration
+const CALIBRATION_SPEECH_RMS: f32 = 0.01;
+
+/// Gain that brings the speech in `samples` to `CALIBRATION_TARGET_PEAK`
+///
+/// `current_gain` is the gain the samples were recorded with. The speech peak is
+/// the 95th percentile of the per-window peaks of speech windows, so a single
+/// click or bump doesn't skew it. Fails when no window is loud enough to be speech.
+pub fn recommended_gain(samples: &[f32], sample_rate: u32, current_gain: f32) -> Result<f32> {
+    let window = (sample_rate as usize / 20).max(1);
+    let mut peaks: Vec<f32> = samples
+        .chunks(window)
+        .filter(|chunk| rms(chunk) >= CALIBRATION_SPEECH_RMS)
+        .map(|chunk| chunk.iter().fold(0.0f32, |peak, s| peak.max(s.abs())))
+        .collect();
+    if peaks.is_empty() {
+        return Err(Error::Audio(
+            "No speech heard during calibration, speak at your normal volume and try again"
+                .to_string(),
+        ));
+    }
+
+    peaks.sort_by(f32::total_cmp);
+    let speech_peak = peaks[(peaks.len() - 1) * 95 / 100];
+    let gain = current_gain * CALIBRATION_TARGET_PEAK / speech_peak;
+    Ok(gain.clamp(MIN_GAIN, MAX_GAIN))
+}
+
+/// RMS level below which a clip counts as silence (about -46 dBFS)
+pub const SILENCE_RMS_THRESHOLD: f32 = 0.005;
+
+/// Whether PCM audio in `format` is silent: empty, or no 50ms window louder than `threshold` RMS
+pub fn is_silent(data: &[u8], format: PcmFormat, sample_rate: u32, threshold: f32) -> bool {
+    let samples = decode_samples(data, format);
+    let window = (sample_rate as usize / 20).max(1);
+    samples.chunks(window).all(|chunk| rms(chunk) < threshold)
+}
+
+/// Root mean square amplitude of the samples
+fn rms(samples: &[f32]) -> f32 {
+    rms_of(samples.iter().copied())
+}
+
+/// RMS of `samples` in a single pass, without collecting them
+fn rms_of(samples: impl Iterator<Item = f32>) -> f32 {
+    let (count, sum_squares) =
+        samples.fold((0usize, 0.0f32), |(count, sum), s| (count + 1, sum + s * s));
+    if count == 0 {
+        return 0.0;
     }
+    (sum_squares / count as f32).sqrt()
 }
 
+/// RMS of the last `window_ms` of mono `samples` (all of them when shorter)
+fn tail_rms(samples: &[f32], sample_rate: u32, window_ms: u64) -> f32 {
+    let window = (u64::from(sample_rate) * window_ms / 1000).max(1) as usize;
+    rms(&samples[samples.len().saturating_sub(window)..])
+}
+
+/// Sine wave at half full scale, for deterministic tests and benches
+#[cfg(any(test, feature = "test-util"))]
+pub fn generate_tone(freq_hz: f32, duration_ms: u64, sample_rate: u32) -> Vec<f32> {
+    let len = (duration_ms * sample_rate as u64 / 1000) as usize;
+    let step = std::f32::consts::TAU * freq_hz / sample_rate as f32;
+    (0..len).map(|i| 0.5 * (step * i as f32).sin()).collect()
+}
+
+/// Digital silence of the given length
+#[cfg(any(test, feature = "test-util"))]
+pub fn generate_silence(duration_ms: u64, sample_rate: u32) -> Vec<f32> {
+    vec![0.0; (duration_ms * sample_rate as u64 / 1000) as usize]
+}
+
+/// Convert interleaved device samples of any format to mono f32 in [-1, 1]
+/// Integer formats are scaled from their full range (unsigned ones are centered
+/// first), so an I16 or U16 mic records at the same level as an F32 one.
+fn to_mono_f32<T>(data: &[T], channels: usize) -> impl Iterator<Item = f32> + '_
+where
+    T: Sample,
+    f32: cpal::FromSample<T>,
+{
+    let channels = channels.max(1);
+    data.chunks_exact(channels).map(move |frame| {
+        let sum: f32 = frame.iter().map(|sample| sample.to_sample::<f32>()).sum();
+        sum / channels as f32
+    })
+}
+
+/// Pick the input config closest to what was asked for
+/// F32 is preferred; devices without it fall back to I16, then U16, then the rest,
+/// all converted to f32 by [`to_mono_f32`] as they arrive.
 fn select_supported_config(
     ranges: &[cpal::SupportedStreamConfigRange],
     preferred_rate: u32,
@@ -373,26 +1422,179 @@ fn choose_sample_rate(range: cpal::SupportedStreamConfigRange, preferred_rate: u
 mod tests {
     use super::*;
 
+    #[test]
+    #[cfg(not(target_os = "macos"))]
+    fn test_microphone_permission_off_macos() {
+        assert_eq!(microphone_permission_status(), PermissionStatus::Authorized);
+        let status = futures::executor::block_on(request_microphone_permission());
+        assert_eq!(status, PermissionStatus::Authorized);
+    }
+
+    #[test]
+    fn test_is_loopback_device() {
+        assert!(is_loopback_device("BlackHole 2ch"));
+        assert!(is_loopback_device("Loopback Audio"));
+        assert!(is_loopback_device(
+            "Monitor of Built-in Audio Analog Stereo"
+        ));
+        assert!(!is_loopback_device("MacBook Pro Microphone"));
+        assert!(!is_loopback_device("AirPods"));
+        // aggregates can be anything; those are picked by name
+        assert!(!is_loopback_device("Aggregate Device"));
+    }
+
+    #[test]
+    fn test_watch_devices_reports_changes() {
+        let device = |name: &str, is_default| InputDevice {
+            name: name.to_string(),
+            is_default,
+        };
+        let mut polls = vec![
+            vec![device("MacBook Pro Microphone", true)],
+            vec![device("MacBook Pro Microphone", true)],
+            vec![
+                device("MacBook Pro Microphone", false),
+                device("AirPods", true),
+            ],
+        ]
+        .into_iter();
+        let last = Arc::new(Mutex::new(None));
+        let seen = Arc::clone(&last);
+        let (tx, rx) = mpsc::channel();
+        let watcher = watch_devices_with(
+            Duration::from_millis(1),
+            move || {
+                Ok(polls
+                    .next()
+                    .unwrap_or_else(|| vec![device("AirPods", true)]))
+            },
+            move |devices| {
+                *seen.lock() = Some(devices.len());
+                let _ = tx.send(devices);
+            },
+        );
+
+        let first = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(first.len(), 2);
+        assert!(first[1].is_default);
+        let second = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(second, vec![device("AirPods", true)]);
+
+        // dropping joins the thread; no callbacks after that
+        drop(watcher);
+        assert_eq!(*last.lock(), Some(1));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_to_mono_f32_from_each_format() {
+        let close = |actual: Vec<f32>, expected: &[f32]| {
+            assert_eq!(actual.len(), expected.len());
+            for (a, e) in actual.iter().zip(expected) {
+                assert!((a - e).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+            }
+        };
+
+        close(to_mono_f32(&[0.5f32, -0.25], 1).collect(), &[0.5, -0.25]);
+        close(
+            to_mono_f32(&[0i16, i16::MIN, 16384], 1).collect(),
+            &[0.0, -1.0, 0.5],
+        );
+        // unsigned formats are centered on their midpoint
+        close(
+            to_mono_f32(&[32768u16, 0, 49152], 1).collect(),
+            &[0.0, -1.0, 0.5],
+        );
+        close(to_mono_f32(&[0i32, i32::MIN], 1).collect(), &[0.0, -1.0]);
+        close(to_mono_f32(&[128u8, 0], 1).collect(), &[0.0, -1.0]);
+        close(to_mono_f32(&[0.25f64], 1).collect(), &[0.25]);
+
+        // stereo frames are averaged; a trailing partial frame is dropped
+        close(
+            to_mono_f32(&[16384i16, 0, -16384, -16384, 5], 2).collect(),
+            &[0.25, -0.5],
+        );
+    }
+
+    #[test]
+    fn test_select_config_without_f32() {
+        let range = |channels, format| {
+            cpal::SupportedStreamConfigRange::new(
+                channels,
+                8000,
+                48000,
+                cpal::SupportedBufferSize::Unknown,
+                format,
+            )
+        };
+
+        let ranges = [range(1, SampleFormat::U16), range(2, SampleFormat::I16)];
+        let (_, channels, format, rate) = select_supported_config(&ranges, 16000, 1).unwrap();
+        assert_eq!((channels, format, rate), (2, SampleFormat::I16, 16000));
+
+        let ranges = [range(1, SampleFormat::U16)];
+        let (_, _, format, _) = select_supported_config(&ranges, 16000, 1).unwrap();
+        assert_eq!(format, SampleFormat::U16);
+
+        let ranges = [range(1, SampleFormat::I16), range(1, SampleFormat::F32)];
+        let (_, _, format, _) = select_supported_config(&ranges, 16000, 1).unwrap();
+        assert_eq!(format, SampleFormat::F32);
+        assert!(select_supported_config(&[], 16000, 1).is_none());
+    }
+
     #[test]
     fn test_default_config() {
         let config = AudioCaptureConfig::default();
         assert_eq!(config.sample_rate, 16000);
         assert_eq!(config.channels, 1);
+        assert_eq!(config.max_duration_ms, None);
+        assert_eq!(config.min_duration_ms, DEFAULT_MIN_DURATION_MS);
+    }
+
+    #[test]
+    fn test_check_min_duration() {
+        assert!(matches!(
+            check_min_duration(50, 300),
+            Err(Error::TooShort {
+                duration_ms: 50,
+                min_ms: 300
+            })
+        ));
+        // the threshold itself is long enough
+        assert!(check_min_duration(300, 300).is_ok());
+        assert!(check_min_duration(0, 0).is_ok());
+    }
+
+    #[test]
+    fn test_recommended_gain() {
+        let loud = generate_tone(440.0, 1000, 16000);
+        let gain = recommended_gain(&loud, 16000, 1.0).unwrap();
+        assert!((gain - 1.0).abs() < 0.01);
+
+        let quiet: Vec<f32> = loud.iter().map(|s| s * 0.2).collect();
+        let gain = recommended_gain(&quiet, 16000, 1.0).unwrap();
+        assert!((gain - 5.0).abs() < 0.05);
+        // recorded with 1.5x already applied: the raw mic needs 1.5x more
+        let gain = recommended_gain(&quiet, 16000, 1.5).unwrap();
+        assert!((gain - 7.5).abs() < 0.1);
+
+        // barely-there speech is capped rather than amplified to noise
+        let faint: Vec<f32> = loud.iter().map(|s| s * 0.04).collect();
+        assert_eq!(recommended_gain(&faint, 16000, 1.0).unwrap(), MAX_GAIN);
+
+        // the user didn't speak
+        let silence = generate_silence(1000, 16000);
+        assert!(matches!(
+            recommended_gain(&silence, 16000, 1.0),
+            Err(Error::Audio(_))
+        ));
     }
 
     #[test]
     fn test_samples_to_pcm() {
         // this test doesn't need audio hardware, just validates PCM conversion logic
-        // test conversion manually
         let samples = [0.0f32, 0.5, -0.5, 1.0, -1.0];
-        let pcm: Vec<u8> = samples
-            .iter()
-            .flat_map(|&sample| {
-                let clamped = sample.clamp(-1.0, 1.0);
-                let pcm = (clamped * 32767.0) as i16;
-                pcm.to_le_bytes()
-            })
-            .collect();
+        let pcm = samples_to_pcm(&samples);
 
         // 5 samples * 2 bytes each = 10 bytes
         assert_eq!(pcm.len(), 10);
@@ -400,12 +1602,392 @@ mod tests {
         // check silence (0.0 -> 0)
         assert_eq!(i16::from_le_bytes([pcm[0], pcm[1]]), 0);
 
-        // check 0.5 -> ~16383
-        let half_pos = i16::from_le_bytes([pcm[2], pcm[3]]);
-        assert!((half_pos - 16383).abs() < 2);
+        // 0.5 * 32767 = 16383.5 rounds to the even 16384; -0.5 scales by 32768
+        assert_eq!(i16::from_le_bytes([pcm[2], pcm[3]]), 16384);
+        assert_eq!(i16::from_le_bytes([pcm[4], pcm[5]]), -16384);
+
+        // full scale uses the whole i16 range without overflowing
+        assert_eq!(i16::from_le_bytes([pcm[6], pcm[7]]), 32767);
+        assert_eq!(i16::from_le_bytes([pcm[8], pcm[9]]), -32768);
+
+        // rounded, not truncated toward zero
+        let pcm = samples_to_pcm(&[0.9 / 32767.0, -0.9 / 32768.0, 2.0, -2.0]);
+        let values: Vec<i16> = pcm
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(values, vec![1, -1, 32767, -32768]);
+    }
+
+    #[test]
+    fn test_state_callback_fires_on_transitions() {
+        let state = SharedState::new();
+        let (tx, rx) = mpsc::channel();
+        state.set_callback(Some(Box::new(move |s| {
+            let _ = tx.send(s);
+        })));
+
+        state.set(CaptureState::Armed);
+        // same state again is not a transition
+        state.set(CaptureState::Armed);
+        assert!(!state.set_if(CaptureState::Paused, CaptureState::Recording));
+        assert!(state.set_if(CaptureState::Armed, CaptureState::Recording));
+        state.set(CaptureState::Idle);
+
+        // removing the callback lets the thread drain the queue and hang up
+        state.set_callback(None);
+        assert_eq!(
+            rx.iter().collect::<Vec<_>>(),
+            vec![
+                CaptureState::Armed,
+                CaptureState::Recording,
+                CaptureState::Idle
+            ]
+        );
+    }
+
+    #[test]
+    fn test_device_error_moves_to_errored() {
+        let state = SharedState::new();
+        let (tx, rx) = mpsc::channel();
+        state.set_callback(Some(Box::new(move |s| {
+            let _ = tx.send(s);
+        })));
+
+        state.set(CaptureState::Recording);
+        state.fail("device not available".to_string());
+
+        assert_eq!(state.get(), CaptureState::Errored);
+        assert_eq!(state.error.lock().as_deref(), Some("device not available"));
+        state.set_callback(None);
+        assert_eq!(
+            rx.iter().collect::<Vec<_>>(),
+            vec![CaptureState::Recording, CaptureState::Errored]
+        );
+        // resume can't revive a dead stream
+        assert!(!state.set_if(CaptureState::Paused, CaptureState::Recording));
+    }
+
+    #[test]
+    fn test_rms() {
+        assert_eq!(rms(&[]), 0.0);
+        assert_eq!(rms(&[0.0; 8]), 0.0);
+        assert!((rms(&[0.5, -0.5, 0.5, -0.5]) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_tail_rms_reads_recent_window() {
+        // 100ms of speech-level audio followed by 50ms of silence at 1kHz
+        let mut samples = vec![0.5; 100];
+        samples.extend([0.0; 50]);
+        assert_eq!(tail_rms(&samples, 1000, 50), 0.0);
+        assert!(tail_rms(&samples, 1000, 100) > SILENCE_RMS_THRESHOLD);
+        // a window longer than the buffer covers all of it
+        assert!((tail_rms(&samples[..100], 1000, 500) - 0.5).abs() < 1e-6);
+        assert_eq!(tail_rms(&[], 16000, 50), 0.0);
+    }
+
+    #[test]
+    fn test_pcm_to_f32_roundtrip() {
+        let samples = [0.0f32, 0.25, -0.25, 0.5, -0.5, 0.999, -0.999, 1.0, -1.0];
+        let restored = pcm_to_f32(&samples_to_pcm(&samples));
+
+        assert_eq!(restored.len(), samples.len());
+        // rounding loses at most half a quantization step
+        for (original, restored) in samples.iter().zip(&restored) {
+            assert!((original - restored).abs() <= 0.5 / 32767.0);
+        }
+        assert_eq!(restored[7], 1.0);
+        assert_eq!(restored[8], -1.0);
+    }
+
+    #[test]
+    fn test_pcm_to_f32_mono() {
+        // stereo frames: (0.5, -0.5) and (1.0, 0.0)
+        let pcm = samples_to_pcm(&[0.5, -0.5, 1.0, 0.0]);
+        let mono = pcm_to_f32_mono(&pcm, 2);
+
+        assert_eq!(mono.len(), 2);
+        assert!(mono[0].abs() < 1e-4);
+        assert!((mono[1] - 0.5).abs() < 1e-4);
+
+        assert_eq!(pcm_to_f32_mono(&pcm, 1).len(), 4);
+    }
+
+    #[test]
+    fn test_audio_clip_duration() {
+        // 1 second of 16kHz mono
+        let clip = AudioClip::mono(samples_to_pcm(&[0.0; 16000]), 16000);
+        assert_eq!(clip.duration_ms(), 1000);
+
+        // same bytes read as stereo are half as long
+        let stereo = AudioClip::new(clip.data.clone(), 16000, 2);
+        assert_eq!(stereo.duration_ms(), 500);
+
+        let bytes: AudioData = clip.into();
+        assert_eq!(bytes.len(), 32000);
+    }
+
+    #[test]
+    fn test_audio_clip_append() {
+        let mut first = AudioClip::mono(samples_to_pcm(&[0.5; 1600]), 16000);
+        // a stray half sample at the end must not shift the next segment
+        first.data.push(0xFF);
+        let second = AudioClip::mono(samples_to_pcm(&[-0.5; 1600]), 16000);
+
+        first.append(&second).unwrap();
+        assert_eq!(first.duration_ms(), 200);
+        let samples = pcm_to_f32(&first.data);
+        assert!((samples[1599] - 0.5).abs() < 0.001);
+        assert!((samples[1600] + 0.5).abs() < 0.001);
+
+        let stereo = AudioClip::new(second.data.clone(), 16000, 2);
+        assert!(first.append(&stereo).is_err());
+        let resampled = AudioClip::mono(second.data.clone(), 44100);
+        assert!(first.append(&resampled).is_err());
+
+        let joined = AudioClip::concat(&[second.clone(), second.clone(), second])
+            .unwrap()
+            .unwrap();
+        assert_eq!(joined.duration_ms(), 300);
+        assert!(AudioClip::concat(&[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_audio_clip_to_wav() {
+        // 1 second of silence at 16kHz mono
+        let wav = AudioClip::mono(vec![0u8; 32000], 16000).to_wav();
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(&wav[12..16], b"fmt ");
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(wav.len(), 44 + 32000);
+
+        let wav = AudioClip::mono(vec![0u8; 64000], 16000)
+            .with_format(PcmFormat::Float32)
+            .to_wav();
+        assert_eq!(u16::from_le_bytes([wav[20], wav[21]]), 3); // IEEE float
+        assert_eq!(
+            u32::from_le_bytes([wav[28], wav[29], wav[30], wav[31]]),
+            64000
+        ); // byte rate
+        assert_eq!(u16::from_le_bytes([wav[34], wav[35]]), 32); // bits per sample
+    }
+
+    #[test]
+    fn test_from_wav_extensible() {
+        // rewrite the fmt chunk the way DAWs and recorders write 24-bit and float files
+        let extensible = |clip: &AudioClip, subformat: u16| {
+            let plain = clip.to_wav();
+            let mut wav = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+            wav.extend_from_slice(&40u32.to_le_bytes());
+            wav.extend_from_slice(&0xFFFEu16.to_le_bytes());
+            wav.extend_from_slice(&plain[22..36]);
+            wav.extend_from_slice(&22u16.to_le_bytes()); // extension size
+            wav.extend_from_slice(&plain[34..36]); // valid bits
+            wav.extend_from_slice(&3u32.to_le_bytes()); // front left and right
+            wav.extend_from_slice(&subformat.to_le_bytes());
+            wav.extend_from_slice(&[
+                0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+            ]);
+            wav.extend_from_slice(&plain[36..]);
+            wav
+        };
+
+        let pcm24 =
+            AudioClip::new(vec![0, 0, 0x40, 0, 0, 0xC0], 48000, 2).with_format(PcmFormat::Pcm24);
+        assert_eq!(AudioClip::from_wav(&extensible(&pcm24, 1)).unwrap(), pcm24);
+
+        let float = AudioClip::new(0.5f32.to_le_bytes().repeat(2), 44100, 2)
+            .with_format(PcmFormat::Float32);
+        assert_eq!(AudioClip::from_wav(&extensible(&float, 3)).unwrap(), float);
+
+        // ADPCM behind the extensible header is still unsupported
+        assert!(AudioClip::from_wav(&extensible(&pcm24, 2)).is_err());
+    }
+
+    #[test]
+    fn test_resample_preserves_tone() {
+        let tone = generate_tone(440.0, 500, 48000);
+        let resampled = resample(&tone, 48000, 16000);
+        assert_eq!(resampled.len(), 8000);
+
+        // a tone well below Nyquist keeps its level and frequency
+        let expected = generate_tone(440.0, 500, 16000);
+        let max_error = resampled
+            .iter()
+            .zip(&expected)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0f32, f32::max);
+        assert!(max_error < 1e-3, "max error {max_error}");
+    }
+
+    #[test]
+    fn test_is_silent() {
+        let silence = samples_to_pcm(&[0.0; 16000]);
+        assert!(is_silent(
+            &silence,
+            PcmFormat::Pcm16,
+            16000,
+            SILENCE_RMS_THRESHOLD
+        ));
+        assert!(is_silent(
+            &[],
+            PcmFormat::Pcm16,
+            16000,
+            SILENCE_RMS_THRESHOLD
+        ));
+
+        // low hiss stays silent, a short burst of speech-level audio does not
+        let mut samples: Vec<f32> = (0..16000)
+            .map(|i| if i % 2 == 0 { 0.001 } else { -0.001 })
+            .collect();
+        assert!(is_silent(
+            &samples_to_pcm(&samples),
+            PcmFormat::Pcm16,
+            16000,
+            SILENCE_RMS_THRESHOLD
+        ));
+        for sample in &mut samples[8000..8800] {
+            *sample = 0.3;
+        }
+        assert!(!is_silent(
+            &samples_to_pcm(&samples),
+            PcmFormat::Pcm16,
+            16000,
+            SILENCE_RMS_THRESHOLD
+        ));
+    }
+
+    #[test]
+    fn test_generated_audio() {
+        let tone = generate_tone(440.0, 250, 16000);
+        assert_eq!(tone.len(), 4000);
+        // RMS of a sine is amplitude / sqrt(2)
+        assert!((rms(&tone) - 0.5 / 2f32.sqrt()).abs() < 1e-3);
+        assert_eq!(tone, generate_tone(440.0, 250, 16000));
+
+        let silence = generate_silence(250, 16000);
+        assert_eq!(silence.len(), 4000);
+
+        let mut clip = silence.clone();
+        clip.extend(&tone);
+        assert!(is_silent(
+            &samples_to_pcm(&silence),
+            PcmFormat::Pcm16,
+            16000,
+            SILENCE_RMS_THRESHOLD
+        ));
+        assert!(!is_silent(
+            &samples_to_pcm(&clip),
+            PcmFormat::Pcm16,
+            16000,
+            SILENCE_RMS_THRESHOLD
+        ));
+    }
+
+    #[test]
+    fn test_duration_callback_fires_on_interval() {
+        let state = SharedState::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        *state.duration_callback.lock() = Some(Box::new(move |ms| sink.lock().push(ms)));
+
+        // 1600-sample interval at 16kHz = every 100ms
+        state.report_progress(0, 1000, 1600, 16000);
+        state.report_progress(1000, 2000, 1600, 16000);
+        state.report_progress(2000, 3000, 1600, 16000);
+        state.report_progress(3000, 3300, 1600, 16000);
+
+        assert_eq!(*seen.lock(), vec![125, 206]);
+    }
+
+    #[test]
+    fn test_capture_stats_no_signal() {
+        let muted = AudioClip::mono(samples_to_pcm(&[0.0; 1600]), 16000);
+        let stats = CaptureStats::from_clip(&muted, 0);
+        assert!(stats.likely_no_signal);
+        assert_eq!(stats.peak, 0.0);
+        assert_eq!(stats.duration_ms, 100);
+
+        // quiet room noise is still a signal
+        let quiet: Vec<f32> = (0..1600)
+            .map(|i| if i % 2 == 0 { 0.002 } else { -0.002 })
+            .collect();
+        let stats = CaptureStats::from_clip(&AudioClip::mono(samples_to_pcm(&quiet), 16000), 0);
+        assert!(!stats.likely_no_signal);
+        assert!(stats.peak > NO_SIGNAL_PEAK_THRESHOLD);
+
+        // nothing captured is not a wrong-mic problem
+        let empty = AudioClip::mono(Vec::new(), 16000);
+        assert!(!CaptureStats::from_clip(&empty, 0).likely_no_signal);
+    }
+
+    #[test]
+    fn test_clipping_detection() {
+        let mut samples = vec![0.3; 100];
+        // a lone full-scale peak is fine
+        samples[10] = 1.0;
+        // two flattened runs
+        samples[20..26].fill(1.0);
+        samples[50..54].fill(-1.0);
+        assert_eq!(ClipDetector::new(DEFAULT_CLIP_RUN).feed(&samples), 2);
+
+        // a run split across callback chunks counts once
+        let mut detector = ClipDetector::new(3);
+        assert_eq!(detector.feed(&[0.2, 1.0, 1.0]), 0);
+        assert_eq!(detector.feed(&[1.0, 1.0, 0.1]), 1);
+        assert_eq!(detector.feed(&[-1.0, -1.0, -1.0]), 1);
+
+        let state = SharedState::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        *state.clipping_callback.lock() = Some(Box::new(move |total| {
+            let _ = tx.send(total);
+        }));
+        state.report_clipping(0);
+        state.report_clipping(2);
+        state.report_clipping(1);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_encode_decode_formats() {
+        let samples = [0.5, -0.5, 1.0, -1.0, 0.0];
+
+        for format in [PcmFormat::Pcm16, PcmFormat::Pcm24, PcmFormat::Float32] {
+            let data = encode_samples(&samples, format);
+            assert_eq!(data.len(), samples.len() * format.bytes_per_sample());
+
+            let restored = decode_samples(&data, format);
+            for (original, restored) in samples.iter().zip(&restored) {
+                assert!((original - restored).abs() < 0.0001, "{:?}", format);
+            }
+        }
+
+        // 24-bit rounds to the nearest step like 16-bit, rather than toward zero
+        let data = encode_samples(&[0.6 / 8_388_607.0, -0.6 / 8_388_607.0], PcmFormat::Pcm24);
+        assert_eq!(data, [1, 0, 0, 0xff, 0xff, 0xff]);
+
+        // silence detection decodes the clip's own format
+        let quiet = encode_samples(&[0.001; 16000], PcmFormat::Pcm24);
+        let loud = encode_samples(&[0.3; 16000], PcmFormat::Pcm24);
+        assert!(is_silent(
+            &quiet,
+            PcmFormat::Pcm24,
+            16000,
+            SILENCE_RMS_THRESHOLD
+        ));
+        assert!(!is_silent(
+            &loud,
+            PcmFormat::Pcm24,
+            16000,
+            SILENCE_RMS_THRESHOLD
+        ));
 
-        // check -0.5 -> ~-16383
-        let half_neg = i16::from_le_bytes([pcm[4], pcm[5]]);
-        assert!((half_neg + 16383).abs() < 2);
+        // duration follows the sample width
+        let clip = AudioClip::mono(encode_samples(&[0.0; 16000], PcmFormat::Pcm24), 16000)
+            .with_format(PcmFormat::Pcm24);
+        assert_eq!(clip.duration_ms(), 1000);
+        assert_eq!(clip_to_f32_mono(&clip).len(), 16000);
     }
 }
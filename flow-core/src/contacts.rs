@@ -1,11 +1,13 @@
 //! Contact categorization engine for context-aware transcription
 
-use crate::types::{Contact, ContactCategory};
+use crate::types::{Contact, ContactCategory, WritingMode};
 use aho_corasick::AhoCorasick;
+use chrono::{DateTime, Local, NaiveTime, Utc};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use unicode_normalization::UnicodeNormalization;
 
 /// Input for contact classification
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,24 +15,255 @@ pub struct ContactInput {
     pub name: String,
     #[serde(default)]
     pub organization: String,
+    /// Relationship label from Contacts.app ("spouse", "mother", "friend")
+    /// When it names a known relationship it decides the category outright.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relationship: Option<String>,
 }
 
-/// Result of contact classification
+impl ContactInput {
+    /// Name folded for matching: NFC, emoji stripped, whitespace collapsed, lowercased
+    /// The original `name` is kept untouched for display
+    pub fn normalized_name(&self) -> String {
+        normalize_name(&self.name)
+    }
+}
+
+/// Fold a contact name into its matching key (see [`ContactInput::normalized_name`])
+pub fn normalize_name(name: &str) -> String {
+    let stripped: String = fix_mojibake(name)
+        .nfc()
+        .filter(|c| !is_emoji_char(*c))
+        .collect();
+    stripped
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Repair UTF-8 text that was decoded as Latin-1/Windows-1252 ("â¤ï¸ Alex" -> "❤️ Alex")
+/// Text that isn't double-encoded (plain ASCII, real accents, real emoji) is returned unchanged
+pub fn fix_mojibake(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_string();
+    }
+
+    // map each char back to the byte it was decoded from; give up on anything else
+    let bytes: Option<Vec<u8>> = text.chars().map(cp1252_byte).collect();
+    match bytes.map(String::from_utf8) {
+        Some(Ok(repaired)) => repaired,
+        _ => text.to_string(),
+    }
+}
+
+/// The Windows-1252 byte that decodes to `c`, if any (Latin-1 for unassigned 0x80-0x9F)
+fn cp1252_byte(c: char) -> Option<u8> {
+    let byte = match c as u32 {
+        0x00..=0xFF => c as u32 as u8,
+        0x20AC => 0x80,
+        0x201A => 0x82,
+        0x0192 => 0x83,
+        0x201E => 0x84,
+        0x2026 => 0x85,
+        0x2020 => 0x86,
+        0x2021 => 0x87,
+        0x02C6 => 0x88,
+        0x2030 => 0x89,
+        0x0160 => 0x8A,
+        0x2039 => 0x8B,
+        0x0152 => 0x8C,
+        0x017D => 0x8E,
+        0x2018 => 0x91,
+        0x2019 => 0x92,
+        0x201C => 0x93,
+        0x201D => 0x94,
+        0x2022 => 0x95,
+        0x2013 => 0x96,
+        0x2014 => 0x97,
+        0x02DC => 0x98,
+        0x2122 => 0x99,
+        0x0161 => 0x9A,
+        0x203A => 0x9B,
+        0x0153 => 0x9C,
+        0x017E => 0x9E,
+        0x0178 => 0x9F,
+        _ => return None,
+    };
+    Some(byte)
+}
+
+/// Emoji, pictographs, and the joiners/selectors that glue them together
+pub(crate) fn is_emoji_char(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF // pictographs, emoticons, hearts, skin tones
+            | 0x2600..=0x27BF // misc symbols and dingbats (❤, ❣)
+            | 0x2B00..=0x2BFF // arrows and stars
+            | 0xFE0E..=0xFE0F // variation selectors
+            | 0x200D // zero-width joiner
+    )
+}
+
+/// Category plus the rule that produced it, for debugging classification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassificationDetail {
+    pub category: ContactCategory,
+    /// Which rule fired and the token that matched it
+    pub reason: String,
+    /// How reliable the rule that fired is (0.0-1.0)
+    #[serde(default)]
+    pub confidence: f32,
+    /// Confidence was below the classifier's minimum, so `category` was forced to FormalNeutral
+    #[serde(default)]
+    pub fallback_applied: bool,
+    /// A work contact messaged outside the user's work hours (see [`ContactClassifier::classify_with_context`])
+    #[serde(default)]
+    pub after_hours: bool,
+}
+
+impl ClassificationDetail {
+    fn new(category: ContactCategory, confidence: f32, reason: impl Into<String>) -> Self {
+        Self {
+            category,
+            reason: reason.into(),
+            confidence,
+            fallback_applied: false,
+            after_hours: false,
+        }
+    }
+
+    /// Writing mode for this contact: the category's, or Casual for work contacts after hours
+    pub fn suggested_writing_mode(&self) -> WritingMode {
+        if self.after_hours {
+            WritingMode::Casual
+        } else {
+            self.category.suggested_writing_mode()
+        }
+    }
+}
+
+/// One contact's classification at a point in time, as kept in the classification log
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassificationDecision {
+    pub contact_name: String,
+    #[serde(flatten)]
+    pub detail: ClassificationDetail,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ClassificationDecision {
+    /// A decision about `contact_name` made now
+    pub fn new(contact_name: impl Into<String>, detail: ClassificationDetail) -> Self {
+        Self {
+            contact_name: contact_name.into(),
+            detail,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// The user's working hours, in local time
+/// `start` is inclusive and `end` exclusive; a window like 22:00-06:00 wraps past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl WorkHours {
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end }
+    }
+
+    /// Parse "HH:MM-HH:MM", e.g. "09:00-17:30"
+    pub fn parse(window: &str) -> Option<Self> {
+        let (start, end) = window.split_once('-')?;
+        let time = |s: &str| NaiveTime::parse_from_str(s.trim(), "%H:%M").ok();
+        Some(Self::new(time(start)?, time(end)?))
+    }
+
+    /// Whether `time` falls within the window
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Optional signals about the message being written, beyond the contact itself
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClassificationContext {
+    /// The user's local time of day
+    pub local_time: Option<NaiveTime>,
+}
+
+impl ClassificationContext {
+    /// Context for a message being written now
+    pub fn now() -> Self {
+        Self {
+            local_time: Some(Local::now().time()),
+        }
+    }
+}
+
+/// Version of the `classify_batch_json` schema; bumped on breaking changes
+pub const CLASSIFICATION_SCHEMA_VERSION: u32 = 1;
+
+/// One contact's classification as the FFI sees it
+/// `category` and `mode` serialize as snake_case strings ("close_family", "very_casual")
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ClassificationResult {
     pub name: String,
     pub category: ContactCategory,
+    /// Writing mode the category suggests
+    pub mode: WritingMode,
+    /// How reliable the rule that fired is (0.0-1.0)
+    pub confidence: f32,
+    /// Which rule fired and the token that matched it
+    pub reason: String,
+}
+
+/// Versioned envelope for batch classification: `{"version": 1, "results": [...]}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassificationBatch {
+    pub version: u32,
+    pub results: Vec<ClassificationResult>,
+}
+
+/// Confidence of each classification rule, strongest signals first
+mod confidence {
+    pub const RELATIONSHIP: f32 = 0.98;
+    pub const PARTNER_EMOJI: f32 = 0.95;
+    pub const PARTNER_KEYWORD: f32 = 0.85;
+    pub const FAMILY_KEYWORD: f32 = 0.9;
+    pub const ORGANIZATION: f32 = 0.9;
+    pub const PROFESSIONAL_KEYWORD: f32 = 0.85;
+    pub const PROFESSIONAL_SUFFIX: f32 = 0.8;
+    pub const EMAIL_DOMAIN: f32 = 0.7;
+    pub const CASUAL_EMOJI: f32 = 0.7;
+    pub const CASUAL_NICKNAME: f32 = 0.5;
+    pub const DEFAULT: f32 = 0.3;
 }
 
 /// Contact classification engine with rule-based heuristics
 pub struct ContactClassifier {
     /// Pattern matchers for efficient keyword detection
     partner_patterns: AhoCorasick,
-    family_patterns: AhoCorasick,
+    /// Family titles, matched against whole words of the name
+    family_titles: HashSet<&'static str>,
     professional_patterns: AhoCorasick,
     professional_suffixes: AhoCorasick,
     casual_emojis: Vec<char>,
     partner_emojis: Vec<char>,
+    /// Below this confidence, fall back to FormalNeutral
+    min_confidence: f32,
+    /// The user's own organizations (normalized); contacts there are Coworkers
+    known_employers: HashSet<String>,
+    /// Outside these, work contacts get Casual when the time is known
+    work_hours: Option<WorkHours>,
 
     /// In-memory contact cache
     contacts: Arc<RwLock<HashMap<String, Contact>>>,
@@ -57,19 +290,33 @@ impl ContactClassifier {
             "boo",
         ];
 
-        // Family titles (case-insensitive)
-        let family_keywords = vec![
+        // Family titles (whole words, case-insensitive); "ICE Mom", "Mom-in-law",
+        // "Grandma's", and "step-dad"/"stepdad" all match through their title word
+        let family_titles = [
             "mom",
-            "dad",
+            "mommy",
+            "momma",
+            "mum",
+            "mummy",
             "mama",
-            "papa",
             "mother",
+            "dad",
+            "daddy",
+            "papa",
             "father",
             "grandma",
             "grandpa",
             "grandmother",
             "grandfather",
+            "granny",
+            "gran",
+            "grandad",
+            "granddad",
+            "nana",
+            "nan",
             "aunt",
+            "auntie",
+            "aunty",
             "uncle",
             "sister",
             "brother",
@@ -78,14 +325,6 @@ impl ContactClassifier {
             "cousin",
             "nephew",
             "niece",
-            "ice mom",
-            "ice dad",
-            "ice mama",
-            "ice papa",
-            "ice aunt",
-            "ice uncle",
-            "ice grandmother",
-            "ice grandfather",
         ];
 
         // Professional titles (case-insensitive)
@@ -133,107 +372,399 @@ impl ContactClassifier {
 
         Self {
             partner_patterns: AhoCorasick::new(partner_keywords).unwrap(),
-            family_patterns: AhoCorasick::new(family_keywords).unwrap(),
+            family_titles: family_titles.into_iter().collect(),
             professional_patterns: AhoCorasick::new(professional_keywords).unwrap(),
             professional_suffixes: AhoCorasick::new(professional_suffixes).unwrap(),
             casual_emojis,
             partner_emojis,
+            min_confidence: 0.0,
+            known_employers: HashSet::new(),
+            work_hours: None,
             contacts: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
+    /// Organizations the user works for; contacts whose organization matches are Coworkers
+    /// rather than Professional. Matching ignores case, emoji, and extra whitespace.
+    /// An entry can also be an email domain ("acme.io") for addresses saved as names
+    pub fn with_known_employers<I, S>(mut self, employers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.known_employers.extend(
+            employers
+                .into_iter()
+                .map(|employer| normalize_name(employer.as_ref()))
+                .filter(|employer| !employer.is_empty()),
+        );
+        self
+    }
+
+    /// Force FormalNeutral when the matching rule's confidence is below `min_confidence`
+    /// Too formal is a safer mistake than too casual with someone important
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Self {
+        self.min_confidence = min_confidence.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Relax work contacts to Casual outside `work_hours` (see [`Self::classify_with_context`])
+    pub fn with_work_hours(mut self, work_hours: WorkHours) -> Self {
+        self.work_hours = Some(work_hours);
+        self
+    }
+
     /// Classify a single contact using strict ordering heuristics
-    /// CRITICAL: Partner detection has ABSOLUTE HIGHEST PRIORITY and overrides everything
+    /// A recognized `relationship` label wins, and a name that is an email address goes by
+    /// its organization or domain; otherwise Partner detection has ABSOLUTE HIGHEST PRIORITY
+    /// and overrides everything
     pub fn classify(&self, input: &ContactInput) -> ContactCategory {
-        let name_lower = input.name.to_lowercase();
-        let name_trimmed = input.name.trim();
+        self.classify_with_reason(input).category
+    }
+
+    /// Classify a single contact and report which rule fired
+    /// Applies the minimum-confidence fallback; see `fallback_applied` on the result
+    pub fn classify_with_reason(&self, input: &ContactInput) -> ClassificationDetail {
+        let mut detail = self.match_rules(input);
+        if detail.confidence < self.min_confidence
+            && detail.category != ContactCategory::FormalNeutral
+        {
+            detail.reason = format!(
+                "{} (confidence {:.2} below {:.2}, using FormalNeutral)",
+                detail.reason, detail.confidence, self.min_confidence
+            );
+            detail.category = ContactCategory::FormalNeutral;
+            detail.fallback_applied = true;
+        }
+        detail
+    }
+
+    /// Classify a single contact, taking the time of day into account
+    /// With work hours set and `context.local_time` outside them, a Professional or
+    /// Coworker contact is marked `after_hours` and suggests Casual. Without either,
+    /// this is the same as [`Self::classify_with_reason`].
+    pub fn classify_with_context(
+        &self,
+        input: &ContactInput,
+        context: &ClassificationContext,
+    ) -> ClassificationDetail {
+        let mut detail = self.classify_with_reason(input);
+        if let (Some(hours), Some(time)) = (self.work_hours, context.local_time)
+            && matches!(
+                detail.category,
+                ContactCategory::Professional | ContactCategory::Coworker
+            )
+            && !hours.contains(time)
+        {
+            detail.reason = format!(
+                "{} (after hours at {}, using Casual)",
+                detail.reason,
+                time.format("%H:%M")
+            );
+            detail.after_hours = true;
+        }
+        detail
+    }
+
+    /// Run the rules in priority order and return the first that fires
+    fn match_rules(&self, input: &ContactInput) -> ClassificationDetail {
+        // Keywords match against the folded name; emoji and casing signals need the original
+        let name_lower = input.normalized_name();
+        let name_repaired = fix_mojibake(&input.name);
+        let name_trimmed = name_repaired.trim();
+
+        // RULE 0: The relationship the user recorded in Contacts beats any guess from the name
+        if let Some(label) = input.relationship.as_deref()
+            && let Some(category) = self.relationship_category(label)
+        {
+            return ClassificationDetail::new(
+                category,
+                confidence::RELATIONSHIP,
+                format!("relationship \"{}\"", clean_relationship_label(label)),
+            );
+        }
+
+        // Messages keys some conversations on an email address; its domain says who they work
+        // for, and keywords would only match inside it ("honeywell", "booking")
+        if let Some(domain) = email_domain(name_trimmed) {
+            return self
+                .match_organization(input)
+                .unwrap_or_else(|| self.match_email_domain(&domain));
+        }
 
         // RULE 1: Partner detection (romantic emojis + terms of endearment)
         // HIGHEST PRIORITY - overrides organization field and all other indicators
-        if self.has_partner_emoji(name_trimmed) || self.partner_patterns.is_match(&name_lower) {
-            return ContactCategory::Partner;
+        if let Some(emoji) = self.find_emoji(name_trimmed, &self.partner_emojis) {
+            return ClassificationDetail::new(
+                ContactCategory::Partner,
+                confidence::PARTNER_EMOJI,
+                format!("partner emoji '{}'", emoji),
+            );
+        }
+        if let Some(keyword) = find_pattern(&self.partner_patterns, &name_lower) {
+            return ClassificationDetail::new(
+                ContactCategory::Partner,
+                confidence::PARTNER_KEYWORD,
+                format!("partner keyword \"{}\"", keyword),
+            );
         }
 
         // RULE 2: Close Family detection (familial titles + ICE)
-        if self.family_patterns.is_match(&name_lower) {
-            return ContactCategory::CloseFamily;
+        if let Some(keyword) = self.find_family_title(&name_lower) {
+            return ClassificationDetail::new(
+                ContactCategory::CloseFamily,
+                confidence::FAMILY_KEYWORD,
+                format!("family keyword \"{}\"", keyword),
+            );
         }
 
         // RULE 3: Professional detection (organization OR professional titles/credentials)
-        if !input.organization.is_empty() {
-            return ContactCategory::Professional;
+        if let Some(detail) = self.match_organization(input) {
+            return detail;
         }
 
-        if self.professional_patterns.is_match(&name_lower)
-            || self.has_professional_suffix(&name_lower)
-        {
-            return ContactCategory::Professional;
+        if let Some(keyword) = find_pattern(&self.professional_patterns, &name_lower) {
+            return ClassificationDetail::new(
+                ContactCategory::Professional,
+                confidence::PROFESSIONAL_KEYWORD,
+                format!("professional keyword \"{}\"", keyword.trim()),
+            );
+        }
+
+        if let Some(suffix) = self.find_professional_suffix(&name_lower) {
+            return ClassificationDetail::new(
+                ContactCategory::Professional,
+                confidence::PROFESSIONAL_SUFFIX,
+                format!("professional suffix \"{}\"", suffix),
+            );
         }
 
         // RULE 4: Casual / Peer detection (casual emojis + informal formatting)
-        if self.has_casual_emoji(name_trimmed) || self.is_casual_nickname(name_trimmed) {
-            return ContactCategory::CasualPeer;
+        if let Some(emoji) = self.find_emoji(name_trimmed, &self.casual_emojis) {
+            return ClassificationDetail::new(
+                ContactCategory::CasualPeer,
+                confidence::CASUAL_EMOJI,
+                format!("casual emoji '{}'", emoji),
+            );
+        }
+        if let Some(reason) = self.casual_nickname_reason(name_trimmed) {
+            return ClassificationDetail::new(
+                ContactCategory::CasualPeer,
+                confidence::CASUAL_NICKNAME,
+                reason,
+            );
         }
 
         // RULE 5: Formal / Neutral (default fallback)
-        ContactCategory::FormalNeutral
+        ClassificationDetail::new(
+            ContactCategory::FormalNeutral,
+            confidence::DEFAULT,
+            "no rule matched (default)",
+        )
     }
 
     /// Classify multiple contacts and return JSON mapping
+    /// Names that normalize to the same key are deduplicated; the first spelling wins
     pub fn classify_batch(&self, inputs: &[ContactInput]) -> HashMap<String, ContactCategory> {
-        inputs
-            .iter()
-            .map(|input| (input.name.clone(), self.classify(input)))
+        self.classify_deduped(inputs)
+            .into_iter()
+            .map(|(input, detail)| (input.name.clone(), detail.category))
             .collect()
     }
 
-    /// Classify batch and return JSON-serializable result
+    /// Classify a batch into a [`ClassificationBatch`], results in input order
+    pub fn classify_batch_results(&self, inputs: &[ContactInput]) -> ClassificationBatch {
+        let results = self
+            .classify_deduped(inputs)
+            .into_iter()
+            .map(|(input, detail)| ClassificationResult {
+                name: input.name.clone(),
+                category: detail.category,
+                mode: detail.category.suggested_writing_mode(),
+                confidence: detail.confidence,
+                reason: detail.reason,
+            })
+            .collect();
+        ClassificationBatch {
+            version: CLASSIFICATION_SCHEMA_VERSION,
+            results,
+        }
+    }
+
+    /// Classify a batch and return the versioned JSON from `classify_batch_results`
     pub fn classify_batch_json(&self, inputs: &[ContactInput]) -> String {
-        let result = self.classify_batch(inputs);
+        let batch = self.classify_batch_results(inputs);
+        serde_json::to_string(&batch).unwrap_or_else(|_| {
+            format!(
+                r#"{{"version":{},"results":[]}}"#,
+                CLASSIFICATION_SCHEMA_VERSION
+            )
+        })
+    }
+
+    /// Classify batch and return JSON including the reason for each category
+    /// Output format: {"ContactName": {"category": "...", "reason": "..."}, ...}
+    pub fn classify_batch_json_verbose(&self, inputs: &[ContactInput]) -> String {
+        let result: HashMap<String, ClassificationDetail> = self
+            .classify_deduped(inputs)
+            .into_iter()
+            .map(|(input, detail)| (input.name.clone(), detail))
+            .collect();
         serde_json::to_string(&result).unwrap_or_else(|_| "{}".to_string())
     }
 
-    /// Check if name contains romantic emoji
-    fn has_partner_emoji(&self, name: &str) -> bool {
-        name.chars().any(|c| self.partner_emojis.contains(&c))
+    /// Classify each input, one result per normalized name, in order of first appearance
+    /// Spellings of one name can carry different signals ("Mom" and "Mom ❤️"), so the
+    /// spelling whose rule is most confident wins; on a tie the first one seen is kept.
+    fn classify_deduped<'a>(
+        &self,
+        inputs: &'a [ContactInput],
+    ) -> Vec<(&'a ContactInput, ClassificationDetail)> {
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut results: Vec<(&ContactInput, ClassificationDetail)> = Vec::new();
+        for input in inputs {
+            let detail = self.classify_with_reason(input);
+            match positions.get(&input.normalized_name()) {
+                Some(&i) if detail.confidence > results[i].1.confidence => {
+                    results[i] = (input, detail);
+                }
+                Some(_) => {}
+                None => {
+                    positions.insert(input.normalized_name(), results.len());
+                    results.push((input, detail));
+                }
+            }
+        }
+        results
+    }
+
+    /// Find the first emoji from `emojis` present in the name
+    fn find_emoji(&self, name: &str, emojis: &[char]) -> Option<char> {
+        name.chars().find(|c| emojis.contains(c))
     }
 
-    /// Check if name contains casual emoji (non-romantic)
-    fn has_casual_emoji(&self, name: &str) -> bool {
-        name.chars().any(|c| self.casual_emojis.contains(&c))
+    /// Category for a Contacts relationship label, if it names one we know
+    /// Family labels reuse the family titles, so "Mother-in-law" and "stepbrother" count.
+    fn relationship_category(&self, label: &str) -> Option<ContactCategory> {
+        let label = clean_relationship_label(label);
+        let category = match label.as_str() {
+            "spouse" | "partner" | "husband" | "wife" | "boyfriend" | "girlfriend" | "fiance"
+            | "fiancé" | "fiancee" | "fiancée" | "domestic partner" => ContactCategory::Partner,
+            "parent" | "child" | "son" | "daughter" | "sibling" | "grandparent" | "grandchild"
+            | "grandson" | "granddaughter" => ContactCategory::CloseFamily,
+            "friend" => ContactCategory::CasualPeer,
+            "manager" | "assistant" => ContactCategory::Professional,
+            "coworker" | "co-worker" | "colleague" => ContactCategory::Coworker,
+            _ if self.find_family_title(&label).is_some() => ContactCategory::CloseFamily,
+            _ => return None,
+        };
+        Some(category)
     }
 
-    /// Check if name ends with professional credential suffix
-    fn has_professional_suffix(&self, name_lower: &str) -> bool {
+    /// First word of `name` that is a family title, optionally with a "step" prefix
+    /// Words split on anything non-alphanumeric, so "Tom" never matches "mom"
+    fn find_family_title<'a>(&self, name: &'a str) -> Option<&'a str> {
+        name.split(|c: char| !c.is_alphanumeric()).find(|&word| {
+            let title = word
+                .strip_prefix("step")
+                .filter(|rest| !rest.is_empty())
+                .unwrap_or(word);
+            self.family_titles.contains(title)
+        })
+    }
+
+    /// The user's own organization marks a coworker, any other an outside contact
+    fn match_organization(&self, input: &ContactInput) -> Option<ClassificationDetail> {
+        if input.organization.is_empty() {
+            return None;
+        }
+        if self
+            .known_employers
+            .contains(&normalize_name(&input.organization))
+        {
+            return Some(ClassificationDetail::new(
+                ContactCategory::Coworker,
+                confidence::ORGANIZATION,
+                format!("known employer \"{}\"", input.organization),
+            ));
+        }
+        Some(ClassificationDetail::new(
+            ContactCategory::Professional,
+            confidence::ORGANIZATION,
+            format!("organization \"{}\"", input.organization),
+        ))
+    }
+
+    /// Classify a contact saved as an email address by its `domain`
+    fn match_email_domain(&self, domain: &str) -> ClassificationDetail {
+        if self.is_known_employer_domain(domain) {
+            return ClassificationDetail::new(
+                ContactCategory::Coworker,
+                confidence::EMAIL_DOMAIN,
+                format!("known employer email domain \"{}\"", domain),
+            );
+        }
+        if PERSONAL_EMAIL_DOMAINS.contains(&domain) {
+            // a lowercase address isn't a nickname, so skip the casual rules
+            return ClassificationDetail::new(
+                ContactCategory::FormalNeutral,
+                confidence::DEFAULT,
+                format!("personal email domain \"{}\"", domain),
+            );
+        }
+        ClassificationDetail::new(
+            ContactCategory::Professional,
+            confidence::EMAIL_DOMAIN,
+            format!("email domain \"{}\"", domain),
+        )
+    }
+
+    /// Whether an email `domain` belongs to one of the user's employers
+    /// An employer given as a domain ("acme.io") matches it and its subdomains; a name
+    /// matches only as the whole organization label, ignoring spaces and punctuation
+    /// ("Acme Labs" ~ "acmelabs.com" or "acme-labs.co.uk", but not "acme.com").
+    fn is_known_employer_domain(&self, domain: &str) -> bool {
+        let squash = |s: &str| -> String { s.chars().filter(|c| c.is_alphanumeric()).collect() };
+        let label = squash(organization_label(domain));
+        self.known_employers.iter().any(|employer| {
+            employer == domain
+                || domain.ends_with(&format!(".{}", employer))
+                || (!label.is_empty() && squash(employer) == label)
+        })
+    }
+
+    /// Find a professional credential suffix at the end of the name
+    fn find_professional_suffix<'a>(&self, name_lower: &'a str) -> Option<&'a str> {
         // Look for ", MD" or " PhD" patterns
         let words: Vec<&str> = name_lower.split_whitespace().collect();
         if let Some(last) = words.last() {
             let cleaned = last.trim_matches(|c: char| !c.is_alphanumeric());
-            if self.professional_suffixes.is_match(cleaned) {
-                return true;
+            if let Some(suffix) = find_pattern(&self.professional_suffixes, cleaned) {
+                return Some(suffix);
             }
         }
 
         // Check after comma (e.g., "Smith, MD")
         if let Some(after_comma) = name_lower.split(',').nth(1) {
             let trimmed = after_comma.trim();
-            if self.professional_suffixes.is_match(trimmed) {
-                return true;
+            if let Some(suffix) = find_pattern(&self.professional_suffixes, trimmed) {
+                return Some(suffix);
             }
         }
 
-        false
+        None
     }
 
-    /// Check if name looks like casual nickname
-    fn is_casual_nickname(&self, name: &str) -> bool {
+    /// Explain why the name looks like a casual nickname, if it does
+    fn casual_nickname_reason(&self, name: &str) -> Option<String> {
         // Check for informal descriptors first
         let name_lower = name.to_lowercase();
         let informal_descriptors = ["from gym", "roommate", "lol", "haha", "buddy", "pal"];
-        let has_informal_descriptor = informal_descriptors.iter().any(|d| name_lower.contains(d));
-
-        if has_informal_descriptor {
-            return true;
+        if let Some(descriptor) = informal_descriptors
+            .iter()
+            .find(|d| name_lower.contains(*d))
+        {
+            return Some(format!("informal descriptor \"{}\"", descriptor));
         }
 
         // Check if name is all lowercase (original string, not lowercased)
@@ -241,19 +772,19 @@ impl ContactClassifier {
         let has_letters = name.chars().any(|c| c.is_alphabetic());
         let all_lowercase = has_letters && name.chars().all(|c| !c.is_uppercase());
 
-        all_lowercase
+        all_lowercase.then(|| "all-lowercase nickname".to_string())
     }
 
     /// Store or update contact in cache
     pub fn upsert_contact(&self, contact: Contact) {
         let mut contacts = self.contacts.write();
-        contacts.insert(contact.name.clone(), contact);
+        contacts.insert(normalize_name(&contact.name), contact);
     }
 
     /// Get contact by name
     pub fn get_contact(&self, name: &str) -> Option<Contact> {
         let contacts = self.contacts.read();
-        contacts.get(name).cloned()
+        contacts.get(&normalize_name(name)).cloned()
     }
 
     /// Get or create contact from input
@@ -275,7 +806,7 @@ impl ContactClassifier {
     /// Record interaction with contact
     pub fn record_interaction(&self, name: &str) {
         let mut contacts = self.contacts.write();
-        if let Some(contact) = contacts.get_mut(name) {
+        if let Some(contact) = contacts.get_mut(&normalize_name(name)) {
             contact.record_interaction();
         }
     }
@@ -290,6 +821,74 @@ impl ContactClassifier {
     }
 }
 
+/// Consumer mail providers; an address there says nothing about work
+const PERSONAL_EMAIL_DOMAINS: &[&str] = &[
+    "aol.com",
+    "fastmail.com",
+    "gmail.com",
+    "gmx.com",
+    "gmx.de",
+    "googlemail.com",
+    "hey.com",
+    "hotmail.com",
+    "icloud.com",
+    "live.com",
+    "mac.com",
+    "mail.com",
+    "me.com",
+    "msn.com",
+    "outlook.com",
+    "proton.me",
+    "protonmail.com",
+    "qq.com",
+    "yahoo.com",
+    "yandex.ru",
+];
+
+/// Lowercased domain of `name` if the whole name is an email address ("mailto:" allowed)
+fn email_domain(name: &str) -> Option<String> {
+    let name = name.trim();
+    let address = name.strip_prefix("mailto:").unwrap_or(name);
+    let (local, domain) = address.split_once('@')?;
+    let labels_ok = domain.split('.').count() >= 2
+        && domain.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        });
+    let local_ok = !local.is_empty() && !local.contains(char::is_whitespace);
+    (local_ok && labels_ok).then(|| domain.to_lowercase())
+}
+
+/// The label naming the organization: "acme" for "acme.com", "mail.acme.com", and "acme.co.uk"
+fn organization_label(domain: &str) -> &str {
+    let labels: Vec<&str> = domain.split('.').collect();
+    let n = labels.len();
+    // second-level registries like co.uk and com.au push the name one label left
+    let second_level = n >= 3 && matches!(labels[n - 2], "co" | "com" | "ac" | "org" | "net");
+    if second_level {
+        labels[n - 3]
+    } else {
+        labels[n - 2]
+    }
+}
+
+/// Lowercase a Contacts label and unwrap Apple's built-in form ("_$!<Spouse>!$_" -> "spouse")
+fn clean_relationship_label(label: &str) -> String {
+    let label = label.trim();
+    label
+        .strip_prefix("_$!<")
+        .and_then(|rest| rest.strip_suffix(">!$_"))
+        .unwrap_or(label)
+        .trim()
+        .to_lowercase()
+}
+
+/// Return the first substring of `haystack` matched by `patterns`
+fn find_pattern<'a>(patterns: &AhoCorasick, haystack: &'a str) -> Option<&'a str> {
+    patterns
+        .find(haystack)
+        .map(|m| &haystack[m.start()..m.end()])
+}
+
 impl Default for ContactClassifier {
     fn default() -> Self {
         Self::new()
@@ -308,18 +907,22 @@ mod tests {
             ContactInput {
                 name: "Bae".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "❤️ Alex".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "My Love".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "Hubby 💍".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
         ];
 
@@ -342,18 +945,22 @@ mod tests {
             ContactInput {
                 name: "Bae".to_string(),
                 organization: "Acme Corp".to_string(),
+                relationship: None,
             },
             ContactInput {
                 name: "❤️ Alex".to_string(),
                 organization: "Tech Inc".to_string(),
+                relationship: None,
             },
             ContactInput {
                 name: "My Love".to_string(),
                 organization: "Business LLC".to_string(),
+                relationship: None,
             },
             ContactInput {
                 name: "Hubby 💍".to_string(),
                 organization: "Company XYZ".to_string(),
+                relationship: None,
             },
         ];
 
@@ -376,18 +983,22 @@ mod tests {
             ContactInput {
                 name: "Mom".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "Dad".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "ICE Mom".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "Grandma".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
         ];
 
@@ -401,6 +1012,85 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_relationship_overrides_name() {
+        let classifier = ContactClassifier::new();
+        let contact = |name: &str, relationship: &str| ContactInput {
+            name: name.to_string(),
+            organization: String::new(),
+            relationship: Some(relationship.to_string()),
+        };
+
+        // nothing in the name says partner, the Contacts card does
+        let detail = classifier.classify_with_reason(&contact("Alex Chen", "_$!<Spouse>!$_"));
+        assert_eq!(detail.category, ContactCategory::Partner);
+        assert_eq!(detail.reason, "relationship \"spouse\"");
+
+        // and it beats what the name suggests
+        assert_eq!(
+            classifier.classify(&contact("Dr. Jane Chen", "mother")),
+            ContactCategory::CloseFamily
+        );
+        assert_eq!(
+            classifier.classify(&contact("Sam", "Father-in-law")),
+            ContactCategory::CloseFamily
+        );
+        assert_eq!(
+            classifier.classify(&contact("Pat 🔥", "colleague")),
+            ContactCategory::Coworker
+        );
+
+        // labels we don't know fall back to the usual rules
+        assert_eq!(
+            classifier.classify(&contact("Mom", "_$!<Other>!$_")),
+            ContactCategory::CloseFamily
+        );
+    }
+
+    #[test]
+    fn test_family_title_variants() {
+        let classifier = ContactClassifier::new();
+
+        for name in [
+            "mom-in-law",
+            "Mother-in-Law Jane",
+            "step-dad",
+            "Stepmum",
+            "Grandma's",
+            "my mum",
+            "Nan",
+            "Auntie Carol",
+            "Brother (Sam)",
+        ] {
+            let input = ContactInput {
+                name: name.to_string(),
+                organization: String::new(),
+                relationship: None,
+            };
+            assert_eq!(
+                classifier.classify(&input),
+                ContactCategory::CloseFamily,
+                "Failed for: {}",
+                name
+            );
+        }
+
+        // titles only count as whole words
+        for name in ["Tom", "Broderick", "Nancy", "Stephanie", "Dadiani"] {
+            let input = ContactInput {
+                name: name.to_string(),
+                organization: String::new(),
+                relationship: None,
+            };
+            assert_ne!(
+                classifier.classify(&input),
+                ContactCategory::CloseFamily,
+                "Matched: {}",
+                name
+            );
+        }
+    }
+
     #[test]
     fn test_professional_classification() {
         let classifier = ContactClassifier::new();
@@ -409,6 +1099,7 @@ mod tests {
         let case1 = ContactInput {
             name: "Sarah".to_string(),
             organization: "Acme Inc".to_string(),
+            relationship: None,
         };
         assert_eq!(classifier.classify(&case1), ContactCategory::Professional);
 
@@ -417,18 +1108,22 @@ mod tests {
             ContactInput {
                 name: "Dr. Smith".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "Prof. Johnson".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "John Smith, MD".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "Jane Doe PhD".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
         ];
 
@@ -450,14 +1145,17 @@ mod tests {
             ContactInput {
                 name: "dave from gym".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "Mike 🍺".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "alex lol".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
         ];
 
@@ -479,14 +1177,17 @@ mod tests {
             ContactInput {
                 name: "John Smith".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "Uber Driver".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "Plumber".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
         ];
 
@@ -508,22 +1209,27 @@ mod tests {
             ContactInput {
                 name: "Mom".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "❤️ Alex".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "Sarah".to_string(),
                 organization: "Acme Inc".to_string(),
+                relationship: None,
             },
             ContactInput {
                 name: "dave from gym".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "John Smith".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
         ];
 
@@ -550,20 +1256,389 @@ mod tests {
             ContactInput {
                 name: "Mom".to_string(),
                 organization: String::new(),
+                relationship: None,
             },
             ContactInput {
                 name: "Sarah Work".to_string(),
                 organization: "Acme Inc".to_string(),
+                relationship: None,
             },
         ];
 
         let json = classifier.classify_batch_json(&inputs);
-        let parsed: HashMap<String, ContactCategory> = serde_json::from_str(&json).unwrap();
+        let parsed: ClassificationBatch = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.version, CLASSIFICATION_SCHEMA_VERSION);
+        assert_eq!(parsed.results.len(), 2);
+
+        let mom = &parsed.results[0];
+        assert_eq!(mom.name, "Mom");
+        assert_eq!(mom.category, ContactCategory::CloseFamily);
+        assert_eq!(mom.mode, WritingMode::Casual);
+        assert!(mom.confidence > 0.0);
+        assert_eq!(mom.reason, "family keyword \"mom\"");
+        assert_eq!(parsed.results[1].category, ContactCategory::Professional);
+
+        // stable lowercase strings, not Debug names
+        let raw: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(raw["version"], 1);
+        assert_eq!(raw["results"][0]["category"], "close_family");
+        assert_eq!(raw["results"][0]["mode"], "casual");
+        assert_eq!(raw["results"][1]["mode"], "formal");
+    }
+
+    #[test]
+    fn test_known_employer_is_coworker() {
+        let classifier = ContactClassifier::new().with_known_employers(["Acme Corp", " "]);
+
+        let coworker = ContactInput {
+            name: "Priya Shah".to_string(),
+            organization: "ACME corp".to_string(),
+            relationship: None,
+        };
+        let vendor = ContactInput {
+            name: "Sam Lee".to_string(),
+            organization: "Globex".to_string(),
+            relationship: None,
+        };
+
+        assert_eq!(classifier.classify(&coworker), ContactCategory::Coworker);
+        assert_eq!(
+            ContactCategory::Coworker.suggested_writing_mode(),
+            WritingMode::Casual
+        );
+        assert_eq!(classifier.classify(&vendor), ContactCategory::Professional);
+
+        // without known employers every organization is Professional
+        assert_eq!(
+            ContactClassifier::new().classify(&coworker),
+            ContactCategory::Professional
+        );
+    }
+
+    #[test]
+    fn test_min_confidence_fallback() {
+        let nickname = ContactInput {
+            name: "jake from gym".to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+        let mom = ContactInput {
+            name: "Mom".to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+
+        let lenient = ContactClassifier::new();
+        let detail = lenient.classify_with_reason(&nickname);
+        assert_eq!(detail.category, ContactCategory::CasualPeer);
+        assert!(!detail.fallback_applied);
+
+        let strict = ContactClassifier::new().with_min_confidence(0.7);
+        let detail = strict.classify_with_reason(&nickname);
+        assert_eq!(detail.category, ContactCategory::FormalNeutral);
+        assert!(detail.fallback_applied);
+        assert!(detail.reason.contains("below"));
+        assert_eq!(strict.classify(&nickname), ContactCategory::FormalNeutral);
+
+        // strong signals are unaffected
+        let detail = strict.classify_with_reason(&mom);
+        assert_eq!(detail.category, ContactCategory::CloseFamily);
+        assert!(!detail.fallback_applied);
+
+        // the default category is never marked as a fallback
+        let stranger = ContactInput {
+            name: "John Smith".to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+        assert!(!strict.classify_with_reason(&stranger).fallback_applied);
+    }
+
+    #[test]
+    fn test_verbose_json_includes_reason() {
+        let classifier = ContactClassifier::new();
+
+        let inputs = vec![
+            ContactInput {
+                name: "Mom".to_string(),
+                organization: String::new(),
+                relationship: None,
+            },
+            ContactInput {
+                name: "Dr. Patel".to_string(),
+                organization: String::new(),
+                relationship: None,
+            },
+            ContactInput {
+                name: "John Smith".to_string(),
+                organization: String::new(),
+                relationship: None,
+            },
+        ];
+
+        let json = classifier.classify_batch_json_verbose(&inputs);
+        let parsed: HashMap<String, ClassificationDetail> = serde_json::from_str(&json).unwrap();
+
+        let mom = &parsed["Mom"];
+        assert_eq!(mom.category, ContactCategory::CloseFamily);
+        assert_eq!(mom.reason, "family keyword \"mom\"");
+
+        let doctor = &parsed["Dr. Patel"];
+        assert_eq!(doctor.category, ContactCategory::Professional);
+        assert!(doctor.reason.contains("dr."));
+
+        assert_eq!(
+            parsed["John Smith"].category,
+            ContactCategory::FormalNeutral
+        );
+    }
+
+    #[test]
+    fn test_normalized_name() {
+        let input = |name: &str| ContactInput {
+            name: name.to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+
+        assert_eq!(input("Mom").normalized_name(), "mom");
+        assert_eq!(input("  mom ").normalized_name(), "mom");
+        // Heart with variation selector, as Contacts exports it
+        assert_eq!(input("Mom \u{2764}\u{FE0F}").normalized_name(), "mom");
+        assert_eq!(input("Jake 🔥🔥").normalized_name(), "jake");
+        // Decomposed accent (e + combining acute) folds to the precomposed form
+        assert_eq!(
+            input("Jose\u{301}").normalized_name(),
+            input("Jos\u{e9}").normalized_name()
+        );
+        assert_eq!(
+            input("Sarah   from  gym").normalized_name(),
+            "sarah from gym"
+        );
+        // ZWJ family emoji leaves nothing behind
+        assert_eq!(
+            input("Fam \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}").normalized_name(),
+            "fam"
+        );
+    }
+
+    #[test]
+    fn test_batch_dedupes_normalized_names() {
+        let classifier = ContactClassifier::new();
+
+        let inputs = vec![
+            ContactInput {
+                name: "Mom".to_string(),
+                organization: String::new(),
+                relationship: None,
+            },
+            ContactInput {
+                name: "mom".to_string(),
+                organization: String::new(),
+                relationship: None,
+            },
+            ContactInput {
+                name: " Mom ".to_string(),
+                organization: String::new(),
+                relationship: None,
+            },
+        ];
 
-        assert_eq!(parsed.get("Mom"), Some(&ContactCategory::CloseFamily));
+        let result = classifier.classify_batch(&inputs);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result.get("Mom"), Some(&ContactCategory::CloseFamily));
+    }
+
+    #[test]
+    fn test_batch_keeps_strongest_duplicate() {
+        let classifier = ContactClassifier::new();
+        let input = |name: &str| ContactInput {
+            name: name.to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+
+        // the emoji on the second spelling still counts
+        let result = classifier.classify_batch(&[input("Alex"), input("Alex ❤️")]);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result.get("Alex ❤️"), Some(&ContactCategory::Partner));
+
+        // mojibake-looking spellings from Messages fold into the same contact
+        let heart = "Alex \u{e2}\u{9d}\u{a4}\u{ef}\u{b8}\u{8f}";
+        let beer = "mike \u{f0}\u{178}\u{8d}\u{ba}";
+        let batch = classifier.classify_batch_results(&[
+            input("alex"),
+            input(heart),
+            input("Mike"),
+            input(beer),
+        ]);
+        let summary: Vec<(&str, ContactCategory)> = batch
+            .results
+            .iter()
+            .map(|r| (r.name.as_str(), r.category))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (heart, ContactCategory::Partner),
+                (beer, ContactCategory::CasualPeer)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_emoji_still_signals_after_normalization() {
+        let classifier = ContactClassifier::new();
+
+        let partner = ContactInput {
+            name: "Alex \u{2764}\u{FE0F}".to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+        assert_eq!(partner.normalized_name(), "alex");
+        assert_eq!(classifier.classify(&partner), ContactCategory::Partner);
+
+        let casual = ContactInput {
+            name: "Jake 🔥".to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+        assert_eq!(classifier.classify(&casual), ContactCategory::CasualPeer);
+    }
+
+    #[test]
+    fn test_contact_cache_uses_normalized_name() {
+        let classifier = ContactClassifier::new();
+        let input = ContactInput {
+            name: "Mom ❤️".to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+        classifier.upsert_contact(classifier.get_or_create_contact(&input));
+
+        classifier.record_interaction("mom");
+        let contact = classifier.get_contact("  MOM ").unwrap();
+        assert_eq!(contact.name, "Mom ❤️");
+        assert_eq!(contact.frequency, 1);
+    }
+
+    #[test]
+    fn test_fix_mojibake() {
+        // "❤️ Alex" and "Mike 🍺" read back as Windows-1252
+        let heart = "\u{e2}\u{9d}\u{a4}\u{ef}\u{b8}\u{8f} Alex";
+        let beer = "Mike \u{f0}\u{178}\u{8d}\u{ba}";
+        assert_eq!(fix_mojibake(heart), "❤️ Alex");
+        assert_eq!(fix_mojibake(beer), "Mike 🍺");
+
+        // already-correct names are left alone
+        assert_eq!(fix_mojibake("José"), "José");
+        assert_eq!(fix_mojibake("Mom ❤️"), "Mom ❤️");
+        assert_eq!(fix_mojibake("John Smith"), "John Smith");
+
+        let classifier = ContactClassifier::new();
+        let input = ContactInput {
+            name: beer.to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+        assert_eq!(classifier.classify(&input), ContactCategory::CasualPeer);
+        assert_eq!(input.normalized_name(), "mike");
+    }
+
+    #[test]
+    fn test_classify_with_context_work_hours() {
+        let at = |h, m| ClassificationContext {
+            local_time: NaiveTime::from_hms_opt(h, m, 0),
+        };
+        let doctor = ContactInput {
+            name: "Dr. Smith".to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+        let hours = WorkHours::parse("09:00-17:00").unwrap();
+        let classifier = ContactClassifier::new().with_work_hours(hours);
+
+        // start is inside the window, end is outside
+        for (time, after_hours) in [
+            (at(8, 59), true),
+            (at(9, 0), false),
+            (at(16, 59), false),
+            (at(17, 0), true),
+        ] {
+            let detail = classifier.classify_with_context(&doctor, &time);
+            assert_eq!(detail.category, ContactCategory::Professional);
+            assert_eq!(detail.after_hours, after_hours, "{:?}", time);
+        }
+        let evening = classifier.classify_with_context(&doctor, &at(20, 0));
+        assert_eq!(evening.suggested_writing_mode(), WritingMode::Casual);
+        assert!(evening.reason.contains("after hours at 20:00"));
+
+        // personal contacts, no time, or no configured hours: unchanged
+        let mom = ContactInput {
+            name: "Mom".to_string(),
+            organization: String::new(),
+            relationship: None,
+        };
+        assert!(
+            !classifier
+                .classify_with_context(&mom, &at(20, 0))
+                .after_hours
+        );
+        let detail = classifier.classify_with_context(&doctor, &ClassificationContext::default());
+        assert_eq!(detail.suggested_writing_mode(), WritingMode::Formal);
+        assert!(
+            !ContactClassifier::new()
+                .classify_with_context(&doctor, &at(20, 0))
+                .after_hours
+        );
+
+        // an overnight shift wraps past midnight
+        let night = WorkHours::parse("22:00 - 06:00").unwrap();
+        assert!(night.contains(NaiveTime::from_hms_opt(23, 30, 0).unwrap()));
+        assert!(night.contains(NaiveTime::from_hms_opt(0, 0, 0).unwrap()));
+        assert!(!night.contains(NaiveTime::from_hms_opt(6, 0, 0).unwrap()));
+        assert!(WorkHours::parse("9-5").is_none());
+    }
+
+    #[test]
+    fn test_email_as_name() {
+        let classifier = ContactClassifier::new().with_known_employers(["Acme Labs", "acme.io"]);
+        let classify = |name: &str| {
+            classifier.classify_with_reason(&ContactInput {
+                name: name.to_string(),
+                organization: String::new(),
+                relationship: None,
+            })
+        };
+
+        let cases = [
+            ("jane@stripe.com", ContactCategory::Professional),
+            ("J.Doe@Law-Firm.co.uk", ContactCategory::Professional),
+            ("jane@acmelabs.com", ContactCategory::Coworker),
+            ("ops@mail.acme-labs.co.uk", ContactCategory::Coworker),
+            ("ops@mail.acme.io", ContactCategory::Coworker),
+            // sharing a word with an employer isn't enough
+            ("mailto:jane@acme.com", ContactCategory::Professional),
+            ("jane.doe@gmail.com", ContactCategory::FormalNeutral),
+            ("JaneDoe@iCloud.com", ContactCategory::FormalNeutral),
+            // keywords inside an address don't count
+            ("mom@icloud.com", ContactCategory::FormalNeutral),
+            ("jane@honeywell.com", ContactCategory::Professional),
+            ("booking@acme.com", ContactCategory::Professional),
+            ("lovelace@corp.com", ContactCategory::Professional),
+            ("drsmith@gmail.com", ContactCategory::FormalNeutral),
+            // not an address, just an @ in a name
+            ("dave @ the gym", ContactCategory::CasualPeer),
+        ];
+        for (name, category) in cases {
+            assert_eq!(classify(name).category, category, "Failed for: {}", name);
+        }
         assert_eq!(
-            parsed.get("Sarah Work"),
-            Some(&ContactCategory::Professional)
+            classify("jane@stripe.com").reason,
+            "email domain \"stripe.com\""
         );
+        assert!(classify("jane.doe@gmail.com").reason.contains("personal"));
+        assert_eq!(organization_label("acme.com"), "acme");
+        assert_eq!(email_domain("jane@localhost"), None);
     }
 }
@@ -0,0 +1,515 @@
+//! End-to-end dictation: capture -> contact -> classify -> transcribe -> adapt
+//!
+//! `run_dictation` is the high-level entry point the FFI layer wraps. Each stage
+//! is injectable: audio comes from an [`AudioSource`], the recipient from a
+//! [`ContactSource`], transcription/completion from the usual providers, and the
+//! result optionally goes to an [`OutputSink`], so tests can run the whole flow
+//! without a mic, Messages.app, or network.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::audio::{AudioCapture, AudioCaptureConfig};
+use crate::contacts::ContactInput;
+use crate::error::{Error, Result};
+use crate::linter::LintWarning;
+use crate::macos_messages::{MessagesDetector, contact_relationship};
+use crate::notify::post_notification;
+use crate::output::OutputSink;
+use crate::pipeline::AdaptivePipeline;
+use crate::providers::{TokenUsage, TranscriptionProvider, TranscriptionRequest};
+use crate::storage::Storage;
+use crate::types::{AudioClip, ContactCategory, WritingMode};
+
+/// How often the microphone source checks for stop and trailing silence
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Produces the audio for one dictation
+#[async_trait]
+pub trait AudioSource: Send + Sync {
+    /// Record until the utterance is over and return the clip
+    async fn record(&self) -> Result<AudioClip>;
+}
+
+/// Finds who the dictation is addressed to
+pub trait ContactSource: Send + Sync {
+    /// The current recipient, or `None` when there isn't one
+    fn active_contact(&self) -> Result<Option<ContactInput>>;
+}
+
+/// Records from the default input device
+///
+/// Stops when `stop` is cancelled, when the capture hits its max duration, or
+/// (with `with_silence_timeout`) once speech has been followed by that much quiet.
+pub struct MicrophoneSource {
+    config: AudioCaptureConfig,
+    stop: CancellationToken,
+    silence_timeout: Option<Duration>,
+}
+
+impl MicrophoneSource {
+    pub fn new(stop: CancellationToken) -> Self {
+        Self {
+            config: AudioCaptureConfig::default(),
+            stop,
+            silence_timeout: None,
+        }
+    }
+
+    pub fn with_config(mut self, config: AudioCaptureConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// End the recording after this much silence following speech
+    pub fn with_silence_timeout(mut self, timeout: Duration) -> Self {
+        self.silence_timeout = Some(timeout);
+        self
+    }
+}
+
+#[async_trait]
+impl AudioSource for MicrophoneSource {
+    async fn record(&self) -> Result<AudioClip> {
+        let config = self.config.clone();
+        let stop = self.stop.clone();
+        let silence_timeout = self.silence_timeout;
+
+        // cpal streams aren't Send, so the capture lives entirely on a blocking thread
+        tokio::task::spawn_blocking(move || {
+            let threshold = config.speech_threshold;
+            let mut capture = AudioCapture::with_config(config)?;
+            capture.start()?;
+
+            let mut heard_speech = false;
+            let mut quiet_since: Option<Instant> = None;
+            while !stop.is_cancelled() && !capture.was_truncated() {
+                if let Some(timeout) = silence_timeout {
+                    if capture.current_audio_level() >= threshold {
+                        heard_speech = true;
+                        quiet_since = None;
+                    } else if heard_speech
+                        && quiet_since.get_or_insert_with(Instant::now).elapsed() >= timeout
+                    {
+                        debug!("Trailing silence reached, ending dictation capture");
+                        break;
+                    }
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+
+            capture.stop()
+        })
+        .await
+        .map_err(|e| Error::Audio(format!("Capture thread failed: {}", e)))?
+    }
+}
+
+/// Reads the open conversation in Messages.app
+#[derive(Default)]
+pub struct MessagesContactSource {
+    detector: MessagesDetector,
+}
+
+impl MessagesContactSource {
+    /// Read the conversation through `detector`, e.g. one with a longer timeout
+    pub fn new(detector: MessagesDetector) -> Self {
+        Self { detector }
+    }
+}
+
+impl ContactSource for MessagesContactSource {
+    fn active_contact(&self) -> Result<Option<ContactInput>> {
+        let Some(name) = self.detector.get_active_contact()? else {
+            return Ok(None);
+        };
+        // the relationship only sharpens classification, so a failed lookup isn't fatal
+        let relationship = contact_relationship(&name).unwrap_or_else(|e| {
+            warn!("Failed to read contact relationship: {}", e);
+            None
+        });
+        Ok(Some(ContactInput {
+            name,
+            organization: String::new(),
+            relationship,
+        }))
+    }
+}
+
+/// Falls back to the last detected contact when `inner` finds none
+///
+/// Every contact `inner` reports is remembered in storage; an empty or failed
+/// read reuses it while it's fresh (see [`Storage::get_last_contact`]), which
+/// rides out flaky Accessibility reads and focus changes between dictations.
+pub struct CachedContactSource {
+    inner: Box<dyn ContactSource>,
+    storage: Arc<Storage>,
+}
+
+impl CachedContactSource {
+    pub fn new(inner: Box<dyn ContactSource>, storage: Arc<Storage>) -> Self {
+        Self { inner, storage }
+    }
+
+    fn cached(&self) -> Result<Option<ContactInput>> {
+        Ok(self.storage.get_last_contact()?.map(|name| {
+            debug!("Reusing recently detected contact");
+            ContactInput {
+                name,
+                organization: String::new(),
+                relationship: None,
+            }
+        }))
+    }
+}
+
+impl ContactSource for CachedContactSource {
+    fn active_contact(&self) -> Result<Option<ContactInput>> {
+        match self.inner.active_contact() {
+            Ok(Some(contact)) => {
+                if let Err(e) = self.storage.set_last_contact(&contact.name) {
+                    warn!("Failed to remember contact: {}", e);
+                }
+                Ok(Some(contact))
+            }
+            Ok(None) => self.cached(),
+            Err(e @ Error::PermissionDenied { .. }) => Err(e),
+            Err(e) => {
+                debug!("Contact lookup failed, trying the last contact: {}", e);
+                self.cached()
+            }
+        }
+    }
+}
+
+/// Always reports the same recipient (or none)
+pub struct FixedContactSource(pub Option<ContactInput>);
+
+impl ContactSource for FixedContactSource {
+    fn active_contact(&self) -> Result<Option<ContactInput>> {
+        Ok(self.0.clone())
+    }
+}
+
+/// The stages `run_dictation` runs
+pub struct DictationConfig {
+    audio: Box<dyn AudioSource>,
+    contact: Box<dyn ContactSource>,
+    transcription: Arc<dyn TranscriptionProvider>,
+    pipeline: AdaptivePipeline,
+    language: Option<String>,
+    vocabulary: Vec<String>,
+    notify: bool,
+    output: Option<Box<dyn OutputSink>>,
+}
+
+impl DictationConfig {
+    pub fn new(
+        audio: Box<dyn AudioSource>,
+        contact: Box<dyn ContactSource>,
+        transcription: Arc<dyn TranscriptionProvider>,
+        pipeline: AdaptivePipeline,
+    ) -> Self {
+        Self {
+            audio,
+            contact,
+            transcription,
+            pipeline,
+            language: None,
+            vocabulary: Vec::new(),
+            notify: false,
+            output: None,
+        }
+    }
+
+    /// Language hint passed to the transcription provider
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Terms to bias transcription toward
+    pub fn with_vocabulary<I, S>(mut self, terms: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.vocabulary.extend(terms.into_iter().map(Into::into));
+        self
+    }
+
+    /// Post a notification with the formatted text when the dictation finishes
+    pub fn with_notification(mut self, notify: bool) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    /// Deliver the formatted text through `output` (paste buffer, typing, ...)
+    pub fn with_output(mut self, output: Box<dyn OutputSink>) -> Self {
+        self.output = Some(output);
+        self
+    }
+}
+
+/// Everything one dictation produced
+#[derive(Debug, Clone, Serialize)]
+pub struct DictationResult {
+    /// Transcription before formatting
+    pub raw: String,
+    /// Text formatted for the recipient
+    pub adapted: String,
+    /// Recipient name, if one was detected
+    pub contact: Option<String>,
+    pub category: ContactCategory,
+    pub mode: WritingMode,
+    /// Completion token usage, when the provider reports it
+    pub usage: Option<TokenUsage>,
+    /// Profanity found in `adapted` for a formal contact
+    pub warnings: Vec<LintWarning>,
+    /// The output sink took the text; false without one or when delivery failed
+    pub delivered: bool,
+}
+
+impl DictationResult {
+    /// Notification title and body: who it was formatted for, then the text
+    pub fn notification(&self) -> (String, String) {
+        let title = match &self.contact {
+            Some(contact) => format!("{} · {}", contact, self.mode),
+            None => format!("Dictation · {}", self.mode),
+        };
+        (title, self.adapted.clone())
+    }
+}
+
+/// Record, detect the recipient, transcribe, and format for them
+///
+/// The contact is read before recording starts, so focus changes while the
+/// user speaks don't redirect the text; a failed lookup is treated as no contact.
+pub async fn run_dictation(config: &DictationConfig) -> Result<DictationResult> {
+    let contact = match config.contact.active_contact() {
+        Ok(contact) => contact,
+        Err(e) => {
+            warn!("Contact lookup failed, dictating without one: {}", e);
+            None
+        }
+    };
+
+    let clip = config.audio.record().await?;
+    if clip.data.is_empty() {
+        return Err(Error::EmptyTranscription);
+    }
+
+    let mut request = TranscriptionRequest::from_clip(clip).with_vocabulary(&config.vocabulary);
+    if let Some(language) = &config.language {
+        request = request.with_language(language.clone());
+    }
+    let transcription = config.transcription.transcribe(request).await?;
+    let raw = transcription.text.trim().to_string();
+
+    let input = contact.unwrap_or(ContactInput {
+        name: String::new(),
+        organization: String::new(),
+        relationship: None,
+    });
+    let contact_name = (!input.name.is_empty()).then(|| input.name.clone());
+    let adaptation = config.pipeline.adapt_detailed(&raw, input).await?;
+    info!(
+        "Dictation finished: {} chars raw, {} chars adapted",
+        raw.len(),
+        adaptation.text.len()
+    );
+
+    let mut result = DictationResult {
+        raw,
+        adapted: adaptation.text,
+        contact: contact_name,
+        category: adaptation.category,
+        mode: adaptation.mode,
+        usage: adaptation.usage,
+        warnings: adaptation.warnings,
+        delivered: false,
+    };
+    // the text is in the result either way, so a failed delivery doesn't fail the dictation
+    if let Some(output) = &config.output {
+        match output.deliver(&result.adapted) {
+            Ok(()) => result.delivered = true,
+            Err(e) => warn!("Failed to deliver dictation output: {}", e),
+        }
+    }
+    if config.notify {
+        let (title, body) = result.notification();
+        if let Err(e) = post_notification(&title, &body) {
+            warn!("Failed to post dictation notification: {}", e);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{
+        CompletionProvider, CompletionRequest, CompletionResponse, FinishReason, TranscriptFormat,
+        TranscriptionResponse,
+    };
+
+    struct FixedAudio(Vec<u8>);
+
+    #[async_trait]
+    impl AudioSource for FixedAudio {
+        async fn record(&self) -> Result<AudioClip> {
+            Ok(AudioClip::mono(self.0.clone(), 16000))
+        }
+    }
+
+    struct FixedTranscription(&'static str);
+
+    #[async_trait]
+    impl TranscriptionProvider for FixedTranscription {
+        fn name(&self) -> &'static str {
+            "Fixed"
+        }
+
+        fn model_name(&self) -> &str {
+            "fixed"
+        }
+
+        async fn transcribe(
+            &self,
+            _request: TranscriptionRequest,
+        ) -> Result<TranscriptionResponse> {
+            Ok(TranscriptionResponse {
+                text: self.0.to_string(),
+                confidence: None,
+                language: None,
+                duration_ms: 10,
+                segments: None,
+                completed_text: None,
+                format: TranscriptFormat::Plain,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    struct UppercaseCompletion;
+
+    #[async_trait]
+    impl CompletionProvider for UppercaseCompletion {
+        fn name(&self) -> &'static str {
+            "Uppercase"
+        }
+
+        fn model_name(&self) -> &str {
+            "upper"
+        }
+
+        async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+            Ok(CompletionResponse {
+                text: request.text.to_uppercase(),
+                usage: Some(TokenUsage {
+                    prompt_tokens: 10,
+                    completion_tokens: 2,
+                    total_tokens: 12,
+                }),
+                model: None,
+                finish_reason: FinishReason::Stop,
+                prompt_truncated: false,
+                estimated_cost_usd: None,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    fn config(audio: Vec<u8>, contact: Option<&'static str>) -> DictationConfig {
+        DictationConfig::new(
+            Box::new(FixedAudio(audio)),
+            Box::new(FixedContactSource(contact.map(|name| ContactInput {
+                name: name.to_string(),
+                organization: String::new(),
+                relationship: None,
+            }))),
+            Arc::new(FixedTranscription(" running late ")),
+            AdaptivePipeline::new(Arc::new(UppercaseCompletion)),
+        )
+    }
+
+    #[test]
+    fn test_run_dictation_with_mocks() {
+        let result =
+            futures::executor::block_on(run_dictation(&config(vec![0; 320], Some("Mom")))).unwrap();
+        assert_eq!(result.raw, "running late");
+        assert_eq!(result.adapted, "RUNNING LATE");
+        assert_eq!(result.contact.as_deref(), Some("Mom"));
+        assert_eq!(result.category, ContactCategory::CloseFamily);
+        assert_eq!(result.mode, WritingMode::Casual);
+        assert_eq!(result.usage.clone().unwrap().total_tokens, 12);
+        assert_eq!(
+            result.notification(),
+            ("Mom · casual".to_string(), "RUNNING LATE".to_string())
+        );
+
+        let result =
+            futures::executor::block_on(run_dictation(&config(vec![0; 320], None))).unwrap();
+        assert_eq!(result.contact, None);
+        assert_eq!(result.category, ContactCategory::FormalNeutral);
+        assert!(!result.delivered);
+
+        let err =
+            futures::executor::block_on(run_dictation(&config(Vec::new(), None))).unwrap_err();
+        assert!(matches!(err, Error::EmptyTranscription));
+    }
+
+    struct RecordingSink(Arc<parking_lot::Mutex<Vec<String>>>);
+
+    impl OutputSink for RecordingSink {
+        fn deliver(&self, text: &str) -> Result<()> {
+            self.0.lock().push(text.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run_dictation_delivers_output() {
+        let delivered = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let config = config(vec![0; 320], Some("Mom"))
+            .with_output(Box::new(RecordingSink(Arc::clone(&delivered))));
+        let result = futures::executor::block_on(run_dictation(&config)).unwrap();
+        assert!(result.delivered);
+        assert_eq!(*delivered.lock(), vec!["RUNNING LATE".to_string()]);
+    }
+
+    #[test]
+    fn test_cached_contact_source() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let live = CachedContactSource::new(
+            Box::new(FixedContactSource(Some(ContactInput {
+                name: "Mom".to_string(),
+                organization: String::new(),
+                relationship: None,
+            }))),
+            Arc::clone(&storage),
+        );
+        assert_eq!(live.active_contact().unwrap().unwrap().name, "Mom");
+
+        // detection drops out: the contact just seen is reused
+        let lost =
+            CachedContactSource::new(Box::new(FixedContactSource(None)), Arc::clone(&storage));
+        assert_eq!(lost.active_contact().unwrap().unwrap().name, "Mom");
+
+        storage
+            .set_setting(crate::storage::SETTING_LAST_CONTACT_TTL_SECS, "0")
+            .unwrap();
+        assert!(lost.active_contact().unwrap().is_none());
+    }
+}
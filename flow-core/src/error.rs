@@ -1,5 +1,8 @@
 //! Error types for Flow
 
+use std::fmt;
+use std::io;
+
 use thiserror::Error;
 
 /// Result type alias using FlowWhispr's Error type
@@ -17,6 +20,20 @@ pub enum Error {
     #[error("Completion failed: {0}")]
     Completion(String),
 
+    /// A provider API answered with an error (or none at all, when `status` is `None`)
+    #[error("Provider error: {message}")]
+    ProviderError {
+        status: Option<u16>,
+        message: String,
+    },
+
+    /// Uploading audio to a provider's file store failed (before any transcription ran)
+    #[error("Upload failed: {message}")]
+    Upload {
+        status: Option<u16>,
+        message: String,
+    },
+
     #[error("Storage error: {0}")]
     Storage(#[from] rusqlite::Error),
 
@@ -26,6 +43,41 @@ pub enum Error {
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
+    #[error("No speech detected")]
+    EmptyTranscription,
+
+    /// A provider's safety filter refused the input or withheld its output
+    #[error(
+        "The model refused to respond: {reason}{}",
+        .category.as_ref().map(|c| format!(" ({c})")).unwrap_or_default()
+    )]
+    ContentBlocked {
+        /// The provider's block or finish reason, e.g. "SAFETY"
+        reason: String,
+        /// Harm category that triggered the block, when reported
+        category: Option<String>,
+    },
+
+    #[error("Request cancelled")]
+    Cancelled,
+
+    #[error("Stream error: {0}")]
+    Stream(#[from] crate::providers::StreamError),
+
+    #[error("No audio signal detected (peak {0:.4}), check your microphone")]
+    NoSignal(f32),
+
+    /// The recording ended before `AudioCaptureConfig::min_duration_ms`, e.g. an accidental tap
+    #[error("Recording too short ({duration_ms}ms, minimum {min_ms}ms)")]
+    TooShort { duration_ms: u64, min_ms: u64 },
+
+    #[error("{what} permission denied, grant it in System Settings")]
+    PermissionDenied { what: Permission },
+
+    /// A query to another app didn't answer in time
+    #[error("{0} timed out")]
+    Timeout(String),
+
     #[error("Invalid configuration: {0}")]
     Config(String),
 
@@ -38,3 +90,130 @@ pub enum Error {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
+
+impl Error {
+    /// Provider error for a non-success HTTP response
+    pub fn provider(status: reqwest::StatusCode, message: impl Into<String>) -> Self {
+        Error::ProviderError {
+            status: Some(status.as_u16()),
+            message: message.into(),
+        }
+    }
+
+    /// HTTP status the provider answered with, if any
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::ProviderError { status, .. } | Error::Upload { status, .. } => *status,
+            Error::Network(e) => e.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+
+    /// Whether the same request could succeed if sent again
+    /// Timeouts, 408, 429, 5xx, dropped connections, and transient I/O failures are;
+    /// 4xx and I/O errors like a missing or unreadable file are not
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::ProviderError { status, .. } | Error::Upload { status, .. } => {
+                status.is_none_or(is_retryable_status)
+            }
+            Error::Network(_) => self.status().is_none_or(is_retryable_status),
+            Error::Stream(crate::providers::StreamError::Disconnected { .. }) => true,
+            Error::Io(e) => matches!(
+                e.kind(),
+                io::ErrorKind::TimedOut
+                    | io::ErrorKind::Interrupted
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::WouldBlock
+            ),
+            _ => false,
+        }
+    }
+
+    /// Whether the provider rejected the credentials (401/403)
+    pub fn is_auth_error(&self) -> bool {
+        matches!(self.status(), Some(401 | 403))
+    }
+}
+
+fn is_retryable_status(status: u16) -> bool {
+    status == 408 || status == 429 || status >= 500
+}
+
+/// macOS privacy permissions the engine depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Reading window titles through System Events
+    Accessibility,
+    /// Sending Apple events to other apps (Messages, System Events)
+    Automation,
+    /// Reading the address book
+    Contacts,
+}
+
+impl Permission {
+    /// Deep link to the System Settings pane that grants this permission
+    pub fn settings_url(self) -> &'static str {
+        match self {
+            Permission::Accessibility => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility"
+            }
+            Permission::Automation => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_Automation"
+            }
+            Permission::Contacts => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_Contacts"
+            }
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Permission::Accessibility => "Accessibility",
+            Permission::Automation => "Automation",
+            Permission::Contacts => "Contacts",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_classification() {
+        let status = |status| Error::ProviderError {
+            status: Some(status),
+            message: String::new(),
+        };
+        assert!(status(429).is_retryable());
+        assert!(status(503).is_retryable());
+        assert!(!status(400).is_retryable());
+        assert!(!status(401).is_retryable());
+        assert!(status(401).is_auth_error());
+        assert!(status(403).is_auth_error());
+        assert!(!status(429).is_auth_error());
+
+        let no_response = Error::ProviderError {
+            status: None,
+            message: String::new(),
+        };
+        assert!(no_response.is_retryable());
+        assert!(!Error::Cancelled.is_retryable());
+        assert!(!Error::Completion("no choices".to_string()).is_retryable());
+    }
+
+    #[test]
+    fn test_only_transient_io_errors_retry() {
+        let io_error = |kind| Error::Io(io::Error::from(kind));
+        assert!(io_error(io::ErrorKind::TimedOut).is_retryable());
+        assert!(io_error(io::ErrorKind::ConnectionReset).is_retryable());
+        assert!(io_error(io::ErrorKind::Interrupted).is_retryable());
+        assert!(!io_error(io::ErrorKind::NotFound).is_retryable());
+        assert!(!io_error(io::ErrorKind::PermissionDenied).is_retryable());
+    }
+}
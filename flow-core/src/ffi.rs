@@ -12,31 +12,58 @@ use std::path::PathBuf;
 use std::ptr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use parking_lot::Mutex;
 use serde::Serialize;
 use tokio::runtime::Runtime;
-use tracing::{debug, error};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
 
 use crate::apps::AppTracker;
-use crate::audio::{AudioCapture, CaptureState};
-use crate::contacts::{ContactClassifier, ContactInput};
+use crate::audio::{
+    AudioCapture, AudioCaptureConfig, CaptureState, CaptureStats, DEFAULT_MIN_DURATION_MS,
+    DeviceWatcher, PermissionStatus, SILENCE_RMS_THRESHOLD, list_input_devices,
+    microphone_permission_status, request_microphone_permission, watch_devices,
+};
+use crate::contacts::{ClassificationContext, ContactClassifier, ContactInput, WorkHours};
+use crate::dictation::{
+    CachedContactSource, ContactSource, DictationConfig, FixedContactSource, MessagesContactSource,
+    MicrophoneSource, run_dictation,
+};
+use crate::error::{Error, Permission};
+use crate::fillers::{FillerLevel, remove_fillers};
 use crate::learning::LearningEngine;
-use crate::macos_messages::MessagesDetector;
+use crate::linter::{LintAction, OutputLinter};
+use crate::macos_messages::{MessagesDetector, contact_relationship};
 use crate::modes::{StyleLearner, WritingMode, WritingModeEngine};
+use crate::pipeline::AdaptivePipeline;
+use crate::prompt_template::PromptTemplate;
 use crate::providers::{
     Base10TranscriptionProvider, CompletionProvider, GeminiCompletionProvider,
     GeminiTranscriptionProvider, LocalWhisperTranscriptionProvider, OpenAICompletionProvider,
-    OpenAITranscriptionProvider, OpenRouterCompletionProvider, TranscriptionCompletionParams,
-    TranscriptionProvider, TranscriptionRequest, WhisperModel,
+    OpenAITranscriptionProvider, OpenRouterCompletionProvider, PassthroughCompletionProvider,
+    ProviderRegistry, RateLimiters, RuleBasedAdapter, TranscriptionCompletionParams,
+    TranscriptionProvider, TranscriptionRequest, WhisperModel, build_from_storage_with,
+    default_http_client,
 };
 use crate::shortcuts::ShortcutsEngine;
 use crate::storage::{
-    SETTING_CLOUD_TRANSCRIPTION_PROVIDER, SETTING_COMPLETION_PROVIDER, SETTING_GEMINI_API_KEY,
-    SETTING_LOCAL_WHISPER_MODEL, SETTING_OPENAI_API_KEY, SETTING_OPENROUTER_API_KEY,
-    SETTING_USE_LOCAL_TRANSCRIPTION, Storage,
+    AudioCacheConfig, DEFAULT_AUDIO_CACHE_MAX_MB, SETTING_AUDIO_CACHE_MAX_MB,
+    SETTING_CLOUD_TRANSCRIPTION_PROVIDER, SETTING_COMPLETION_PROVIDER, SETTING_FILLER_LEVEL,
+    SETTING_INPUT_GAIN, SETTING_KNOWN_EMPLOYERS, SETTING_LOCAL_WHISPER_MODEL,
+    SETTING_LOG_CLASSIFICATIONS, SETTING_MIN_RECORDING_MS, SETTING_NOTIFY_ON_DICTATION,
+    SETTING_PROFANITY_ACTION, SETTING_PROFANITY_WORDS, SETTING_SAVE_DICTATION_AUDIO,
+    SETTING_SYSTEM_PROMPT_PREFIX, SETTING_SYSTEM_PROMPT_SUFFIX, SETTING_USE_LOCAL_TRANSCRIPTION,
+    SETTING_WORK_HOURS, Storage, completion_model_setting,
 };
-use crate::types::{Shortcut, Transcription, TranscriptionHistoryEntry, TranscriptionStatus};
+use crate::types::{
+    AudioClip, EmojiPolicy, PcmFormat, Shortcut, Transcription, TranscriptionHistoryEntry,
+    TranscriptionStatus,
+};
+
+/// Frequent contacts passed to transcription as vocabulary hints
+const VOCABULARY_CONTACT_LIMIT: usize = 20;
 
 /// Log with timestamp
 macro_rules! log_with_time {
@@ -51,25 +78,37 @@ macro_rules! log_with_time {
 /// Opaque handle to the Flow engine
 pub struct FlowHandle {
     runtime: Runtime,
-    storage: Storage,
+    storage: Arc<Storage>,
     audio: Mutex<Option<AudioCapture>>,
     last_audio: Mutex<Option<crate::AudioData>>,
     last_audio_sample_rate: Mutex<Option<u32>>,
     last_error: Mutex<Option<String>>,
     transcription: Arc<dyn TranscriptionProvider>,
     completion: Arc<dyn CompletionProvider>,
+    /// Kept across completion provider rebuilds so a new model doesn't reset the rate limit
+    rate_limiters: RateLimiters,
     shortcuts: ShortcutsEngine,
     learning: LearningEngine,
     modes: Mutex<WritingModeEngine>,
     app_tracker: AppTracker,
     style_learner: Mutex<StyleLearner>,
     is_model_loading: Arc<AtomicBool>,
-    contact_classifier: ContactClassifier,
+    contact_classifier: Arc<ContactClassifier>,
     /// Captured contact name at recording start (for Messages.app context)
     captured_contact: Mutex<Option<String>>,
     /// Temporary storage for audio between stop and transcribe (ensures mic is fully released)
     pending_audio: Mutex<Option<crate::AudioData>>,
     pending_sample_rate: Mutex<Option<u32>>,
+    /// Signal and truncation stats for the last stopped recording
+    last_capture_stats: Mutex<Option<CaptureStats>>,
+    /// Cancels the provider request currently in flight, if any
+    in_flight: Mutex<Option<CancellationToken>>,
+    /// Last permission a detector reported as denied, for the "grant permission" flow
+    missing_permission: Mutex<Option<Permission>>,
+    /// Ends the recording of the flow_run_dictation call in progress, if any
+    dictation_stop: Mutex<Option<CancellationToken>>,
+    /// Input device watcher started by flow_watch_input_devices
+    device_watcher: Mutex<Option<DeviceWatcher>>,
 }
 
 #[derive(Serialize)]
@@ -114,21 +153,9 @@ fn estimate_duration_ms(bytes: usize, sample_rate: u32) -> u64 {
 
 fn load_persisted_configuration(handle: &mut FlowHandle) {
     // Load all API keys
-    let openai_key = handle
-        .storage
-        .get_setting(SETTING_OPENAI_API_KEY)
-        .ok()
-        .flatten();
-    let gemini_key = handle
-        .storage
-        .get_setting(SETTING_GEMINI_API_KEY)
-        .ok()
-        .flatten();
-    let openrouter_key = handle
-        .storage
-        .get_setting(SETTING_OPENROUTER_API_KEY)
-        .ok()
-        .flatten();
+    let openai_key = handle.storage.get_api_key("openai").ok().flatten();
+    let gemini_key = handle.storage.get_api_key("gemini").ok().flatten();
+    let openrouter_key = handle.storage.get_api_key("openrouter").ok().flatten();
 
     // Load saved provider preferences
     let saved_completion_provider = handle
@@ -180,20 +207,26 @@ fn load_persisted_configuration(handle: &mut FlowHandle) {
     tracing::info!("  Use local transcription: {}", use_local_transcription);
 
     // Initialize completion provider based on saved preference
-    match saved_completion_provider.as_deref() {
-        Some("gemini") => {
-            debug!("Restoring Gemini completion provider from database");
-            handle.completion = Arc::new(GeminiCompletionProvider::new(gemini_key.clone()));
-        }
-        Some("openrouter") => {
-            debug!("Restoring OpenRouter completion provider from database");
-            handle.completion = Arc::new(OpenRouterCompletionProvider::new(openrouter_key));
+    handle.completion = match build_from_storage_with(
+        &handle.storage,
+        &ProviderRegistry::new(),
+        &handle.rate_limiters,
+    ) {
+        Ok(provider) => {
+            debug!(
+                "Restoring {} completion provider from database",
+                provider.name()
+            );
+            Arc::from(provider)
         }
-        _ => {
-            debug!("Restoring OpenAI completion provider from database");
-            handle.completion = Arc::new(OpenAICompletionProvider::new(openai_key.clone()));
+        Err(e) => {
+            debug!(
+                "Saved completion provider unavailable ({}), using OpenAI",
+                e
+            );
+            Arc::new(OpenAICompletionProvider::new(openai_key.clone()))
         }
-    }
+    };
 
     // Initialize transcription provider separately
     if use_local_transcription {
@@ -262,33 +295,71 @@ pub extern "C" fn flow_init(db_path: *const c_char) -> *mut FlowHandle {
         }
     };
 
+    let save_audio = storage
+        .get_setting(SETTING_SAVE_DICTATION_AUDIO)
+        .ok()
+        .flatten()
+        .is_some_and(|value| value == "true");
+    if save_audio && let Some(parent) = db_path.parent() {
+        let max_mb = storage
+            .get_setting(SETTING_AUDIO_CACHE_MAX_MB)
+            .ok()
+            .flatten()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_AUDIO_CACHE_MAX_MB);
+        storage.set_audio_cache(Some(AudioCacheConfig {
+            dir: parent.join("audio"),
+            max_bytes: max_mb * 1024 * 1024,
+        }));
+    }
+
     let shortcuts =
         ShortcutsEngine::from_storage(&storage).unwrap_or_else(|_| ShortcutsEngine::new());
     let learning = LearningEngine::from_storage(&storage).unwrap_or_else(|_| LearningEngine::new());
     let modes = WritingModeEngine::new(WritingMode::Casual);
     let app_tracker = AppTracker::new();
     let style_learner = StyleLearner::new();
-    let contact_classifier = ContactClassifier::new();
+    let known_employers = storage
+        .get_setting(SETTING_KNOWN_EMPLOYERS)
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    let mut contact_classifier =
+        ContactClassifier::new().with_known_employers(known_employers.split(','));
+    if let Some(hours) = storage
+        .get_setting(SETTING_WORK_HOURS)
+        .ok()
+        .flatten()
+        .and_then(|window| WorkHours::parse(&window))
+    {
+        contact_classifier = contact_classifier.with_work_hours(hours);
+    }
 
     let mut handle = FlowHandle {
         runtime,
-        storage,
+        storage: Arc::new(storage),
         audio: Mutex::new(None),
         last_audio: Mutex::new(None),
         last_audio_sample_rate: Mutex::new(None),
         last_error: Mutex::new(None),
         transcription: Arc::new(OpenAITranscriptionProvider::new(None)),
         completion: Arc::new(OpenAICompletionProvider::new(None)),
+        rate_limiters: RateLimiters::new(),
         shortcuts,
         learning,
         modes: Mutex::new(modes),
         app_tracker,
         style_learner: Mutex::new(style_learner),
         is_model_loading: Arc::new(AtomicBool::new(false)),
-        contact_classifier,
+        contact_classifier: Arc::new(contact_classifier),
         captured_contact: Mutex::new(None),
         pending_audio: Mutex::new(None),
         pending_sample_rate: Mutex::new(None),
+        last_capture_stats: Mutex::new(None),
+        in_flight: Mutex::new(None),
+        missing_permission: Mutex::new(None),
+        dictation_stop: Mutex::new(None),
+        device_watcher: Mutex::new(None),
     };
 
     load_persisted_configuration(&mut handle);
@@ -373,16 +444,27 @@ pub extern "C" fn flow_start_recording(handle: *mut FlowHandle) -> bool {
         .unwrap_or(false);
 
     if is_messages {
-        match MessagesDetector::get_active_contact() {
+        match MessagesDetector::new().get_active_contact() {
             Ok(Some(contact_name)) => {
                 debug!(
                     "Captured Messages contact at recording start: {}",
                     contact_name
                 );
+                if let Err(e) = handle.storage.set_last_contact(&contact_name) {
+                    warn!("Failed to remember contact: {}", e);
+                }
                 *handle.captured_contact.lock() = Some(contact_name);
             }
             Ok(None) => {
                 debug!("Messages active but no conversation detected at recording start");
+                *handle.captured_contact.lock() = handle.storage.get_last_contact().ok().flatten();
+            }
+            Err(Error::PermissionDenied { what }) => {
+                warn!(
+                    "{} permission denied, can't read the Messages contact",
+                    what
+                );
+                *handle.missing_permission.lock() = Some(what);
                 *handle.captured_contact.lock() = None;
             }
             Err(e) => {
@@ -390,7 +472,7 @@ pub extern "C" fn flow_start_recording(handle: *mut FlowHandle) -> bool {
                     "Failed to capture Messages contact at recording start: {}",
                     e
                 );
-                *handle.captured_contact.lock() = None;
+                *handle.captured_contact.lock() = handle.storage.get_last_contact().ok().flatten();
             }
         }
     } else {
@@ -401,7 +483,7 @@ pub extern "C" fn flow_start_recording(handle: *mut FlowHandle) -> bool {
 
     // create new audio capture if needed
     if audio_lock.is_none() {
-        match AudioCapture::new() {
+        match AudioCapture::with_config(capture_config(handle)) {
             Ok(capture) => *audio_lock = Some(capture),
             Err(e) => {
                 let message = format!("Failed to create audio capture: {e}");
@@ -432,7 +514,8 @@ pub extern "C" fn flow_start_recording(handle: *mut FlowHandle) -> bool {
 }
 
 /// Stop audio recording and get the duration
-/// Returns duration in milliseconds, or 0 on failure
+/// Returns duration in milliseconds, or 0 on failure (including a recording shorter
+/// than the minimum duration, which is discarded; see flow_get_last_error)
 /// This function extracts audio data and fully releases the microphone device
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_stop_recording(handle: *mut FlowHandle) -> u64 {
@@ -446,11 +529,36 @@ pub extern "C" fn flow_stop_recording(handle: *mut FlowHandle) -> u64 {
 
         match capture.stop_stream() {
             Ok(_) => {
+                // An accidental tap: discard it rather than pay to transcribe nothing
+                if let Err(e) = capture.check_min_duration(duration) {
+                    drop(capture);
+                    *handle.pending_audio.lock() = None;
+                    *handle.pending_sample_rate.lock() = None;
+                    set_last_error(handle, e.to_string());
+                    return 0;
+                }
+
                 // Extract and cache audio data before dropping AudioCapture
                 let sample_rate = capture.sample_rate();
-                let audio_data = capture.take_buffered_audio();
+                let mut clip = AudioClip::mono(capture.take_buffered_audio(), sample_rate);
+                clip.truncated = capture.was_truncated();
+
+                let stats = CaptureStats::from_clip(&clip, capture.clip_count());
+                if stats.likely_no_signal {
+                    warn!(
+                        "Recording has no signal (peak {:.4}), check the microphone",
+                        stats.peak
+                    );
+                }
+                if stats.clip_count > 0 {
+                    warn!(
+                        "Recording clipped {} times, input is too loud",
+                        stats.clip_count
+                    );
+                }
+                *handle.last_capture_stats.lock() = Some(stats);
 
-                *handle.pending_audio.lock() = Some(audio_data);
+                *handle.pending_audio.lock() = Some(clip.data);
                 *handle.pending_sample_rate.lock() = Some(sample_rate);
 
                 // AudioCapture is dropped here - CPAL device fully released
@@ -475,6 +583,55 @@ pub extern "C" fn flow_stop_recording(handle: *mut FlowHandle) -> u64 {
     }
 }
 
+/// Check whether the last stopped recording was cut off at the maximum duration
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_was_recording_truncated(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    handle
+        .last_capture_stats
+        .lock()
+        .is_some_and(|stats| stats.truncated)
+}
+
+/// Get stats for the last stopped recording as JSON
+/// {"duration_ms", "peak", "likely_no_signal", "truncated", "clip_count"}; null if nothing was recorded yet
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_last_capture_stats(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    let Some(stats) = *handle.last_capture_stats.lock() else {
+        return ptr::null_mut();
+    };
+
+    match CString::new(serde_json::to_string(&stats).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+fn permission_status_code(status: PermissionStatus) -> u8 {
+    match status {
+        PermissionStatus::Authorized => 0,
+        PermissionStatus::Denied => 1,
+        PermissionStatus::NotDetermined => 2,
+    }
+}
+
+/// Check microphone permission without prompting
+/// Returns 0 = authorized, 1 = denied, 2 = not determined
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_microphone_permission_status() -> u8 {
+    permission_status_code(microphone_permission_status())
+}
+
+/// Prompt for microphone permission if needed and block until the user answers
+/// Returns 0 = authorized, 1 = denied, 2 = not determined (prompt timed out)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_request_microphone_permission(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
+    permission_status_code(handle.runtime.block_on(request_microphone_permission()))
+}
+
 /// Check if currently recording
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_is_recording(handle: *mut FlowHandle) -> bool {
@@ -488,6 +645,136 @@ pub extern "C" fn flow_is_recording(handle: *mut FlowHandle) -> bool {
     }
 }
 
+/// Capture settings with the calibrated input gain applied
+fn capture_config(handle: &FlowHandle) -> AudioCaptureConfig {
+    let gain = handle
+        .storage
+        .get_setting(SETTING_INPUT_GAIN)
+        .ok()
+        .flatten()
+        .and_then(|value| value.parse::<f32>().ok())
+        .filter(|gain| gain.is_finite() && *gain > 0.0)
+        .unwrap_or(1.0);
+    let min_duration_ms = handle
+        .storage
+        .get_setting(SETTING_MIN_RECORDING_MS)
+        .ok()
+        .flatten()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_MIN_DURATION_MS);
+    AudioCaptureConfig {
+        gain,
+        min_duration_ms,
+        ..AudioCaptureConfig::default()
+    }
+}
+
+/// Record `duration_ms` of the user speaking and save the input gain that levels it
+/// Blocks for the whole sample. Returns the new gain, or 0.0 on error (e.g. nothing
+/// was said, the sample is shorter than the minimum recording, or a recording is running)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_calibrate_gain(handle: *mut FlowHandle, duration_ms: u64) -> f32 {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    if handle.audio.lock().is_some() {
+        set_last_error(handle, "Can't calibrate while a recording is running");
+        return 0.0;
+    }
+
+    let result = AudioCapture::with_config(capture_config(handle))
+        .and_then(|mut capture| capture.calibrate(duration_ms))
+        .and_then(|gain| {
+            handle
+                .storage
+                .set_setting(SETTING_INPUT_GAIN, &gain.to_string())?;
+            Ok(gain)
+        });
+    match result {
+        Ok(gain) => gain,
+        Err(e) => {
+            set_last_error(handle, format!("Calibration failed: {}", e));
+            0.0
+        }
+    }
+}
+
+/// List input devices as JSON: [{"name": "...", "is_default": bool}, ...]
+/// Returns null on error; caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_list_input_devices(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    match list_input_devices().and_then(|devices| Ok(serde_json::to_string(&devices)?)) {
+        Ok(json) => match CString::new(json) {
+            Ok(cstr) => cstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        Err(e) => {
+            set_last_error(handle, format!("Failed to list input devices: {}", e));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Caller-owned context pointer handed back to a callback on another thread
+struct CallbackContext(*mut c_void);
+
+// Safety: the caller of flow_watch_input_devices promises the context may be used from any thread
+unsafe impl Send for CallbackContext {}
+
+/// Call `callback` with the flow_list_input_devices JSON whenever an input device is
+/// added or removed or the default changes. Runs on a background thread and replaces
+/// any earlier watcher. `result` is only valid during the call.
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_watch_input_devices(
+    handle: *mut FlowHandle,
+    callback: ResultCallback,
+    context: *mut c_void,
+) {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    let context = CallbackContext(context);
+    let watcher = watch_devices(move |devices| {
+        let context = &context;
+        match serde_json::to_string(&devices)
+            .ok()
+            .and_then(|json| CString::new(json).ok())
+        {
+            Some(json) => callback(true, json.as_ptr(), context.0),
+            None => callback(false, ptr::null(), context.0),
+        }
+    });
+    // the old watcher's thread is joined here, so it never fires after this returns
+    *handle.device_watcher.lock() = Some(watcher);
+}
+
+/// Stop the watcher started by flow_watch_input_devices; no callbacks fire after this returns
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_stop_watching_input_devices(handle: *mut FlowHandle) {
+    let handle = unsafe { &*handle };
+    let watcher = handle.device_watcher.lock().take();
+    drop(watcher);
+}
+
+/// Take the device error that interrupted the recording (e.g. the mic was unplugged)
+/// Returns null if there was none; caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_take_audio_error(handle: *mut FlowHandle) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    let error = handle
+        .audio
+        .lock()
+        .as_mut()
+        .and_then(|capture| capture.take_error());
+    match error.map(CString::new) {
+        Some(Ok(cstr)) => cstr.into_raw(),
+        _ => ptr::null_mut(),
+    }
+}
+
 /// Get current audio level (RMS amplitude) from the recording
 /// Returns a value between 0.0 and 1.0, or 0.0 if not recording
 #[unsafe(no_mangle)]
@@ -526,16 +813,28 @@ fn transcribe_with_audio(
                 debug!("Using captured Messages contact: {}", contact_name);
 
                 // Classify the contact
+                let relationship = contact_relationship(&contact_name).unwrap_or_else(|e| {
+                    warn!("Failed to read contact relationship: {}", e);
+                    None
+                });
                 let input = ContactInput {
                     name: contact_name.clone(),
                     organization: String::new(),
+                    relationship,
                 };
-                let category = handle.contact_classifier.classify(&input);
-                let contact_mode = category.suggested_writing_mode();
+                let detail = handle
+                    .contact_classifier
+                    .classify_with_context(&input, &ClassificationContext::now());
+                let contact_mode = handle
+                    .storage
+                    .get_contact_mode(&contact_name)
+                    .ok()
+                    .flatten()
+                    .unwrap_or_else(|| detail.suggested_writing_mode());
 
                 debug!(
                     "Contact '{}' classified as {:?}, using mode {:?}",
-                    contact_name, category, contact_mode
+                    contact_name, detail.category, contact_mode
                 );
 
                 // Record the interaction
@@ -569,12 +868,7 @@ fn transcribe_with_audio(
         .unwrap_or(false);
 
     // Build mode string for worker
-    let mode_str = match mode {
-        WritingMode::Formal => "formal",
-        WritingMode::Casual => "casual",
-        WritingMode::VeryCasual => "very_casual",
-        WritingMode::Excited => "excited",
-    };
+    let mode_str = mode.as_str();
 
     // For cloud transcription (auto mode), worker handles everything
     let completion_params = if !use_local_transcription {
@@ -589,17 +883,65 @@ fn transcribe_with_audio(
         None
     };
 
-    // Perform transcription
+    // Silent recordings would come back empty (or hallucinated); skip the provider call.
+    // capture_config keeps the default 16-bit encoding.
+    if crate::audio::is_silent(
+        &audio_data,
+        PcmFormat::Pcm16,
+        sample_rate,
+        SILENCE_RMS_THRESHOLD,
+    ) {
+        debug!("Recording is silent, skipping transcription");
+        return Err(Error::EmptyTranscription);
+    }
+
+    // Bias recognition toward the current recipient and frequently messaged contacts
+    let mut vocabulary: Vec<String> = handle.captured_contact.lock().iter().cloned().collect();
+    if let Ok(contacts) = handle
+        .storage
+        .get_frequent_contacts(VOCABULARY_CONTACT_LIMIT)
+    {
+        vocabulary.extend(contacts.into_iter().map(|c| c.name));
+    }
+
+    // Keep a copy for the audio cache before the request takes ownership
+    let cached_clip = handle
+        .storage
+        .audio_cache_enabled()
+        .then(|| AudioClip::mono(audio_data.clone(), sample_rate));
+
+    // Perform transcription (flow_cancel_transcription aborts it from another thread)
+    let cancel = CancellationToken::new();
+    *handle.in_flight.lock() = Some(cancel.clone());
     let transcription = handle.runtime.block_on(async {
-        let mut request = TranscriptionRequest::new(audio_data, sample_rate);
+        let mut request =
+            TranscriptionRequest::new(audio_data, sample_rate).with_vocabulary(vocabulary);
         if let Some(params) = completion_params {
             request = request.with_completion(params);
         }
-        transcription_provider.transcribe(request).await
-    })?;
+        transcription_provider
+            .transcribe_cancellable(request, &cancel)
+            .await
+    });
+    *handle.in_flight.lock() = None;
+    let transcription = transcription?;
+
+    // Nothing to format - don't let completion rewrite an empty string into something
+    if transcription.is_empty() {
+        debug!("Transcription came back empty");
+        return Err(Error::EmptyTranscription);
+    }
 
-    // Process shortcuts and corrections on raw transcription
-    let (text_with_shortcuts, triggered) = handle.shortcuts.process(&transcription.text);
+    // Strip fillers, then process shortcuts and corrections on the raw transcription
+    let filler_level = handle
+        .storage
+        .get_setting(SETTING_FILLER_LEVEL)
+        .ok()
+        .flatten()
+        .map(|value| FillerLevel::from_setting(&value))
+        .unwrap_or_default();
+    let cleaned = remove_fillers(&transcription.text, filler_level);
+    let (text_with_shortcuts, triggered) = handle.shortcuts.process(&cleaned);
     let (text_with_corrections, _applied) = handle.learning.apply_corrections(&text_with_shortcuts);
 
     // Use worker completion if available, otherwise use corrected transcription
@@ -643,13 +985,18 @@ fn transcribe_with_audio(
     if let Err(e) = handle.storage.save_history_entry(&history) {
         error!("Failed to save transcription history: {}", e);
     }
+    if let Some(clip) = cached_clip
+        && let Err(e) = handle.storage.save_dictation_audio(&history.id, &clip)
+    {
+        warn!("Failed to save dictation audio: {}", e);
+    }
 
     Ok(processed_text)
 }
 
 /// Transcribe the recorded audio and process it
 /// Returns the processed text (caller must free with flow_free_string)
-/// Returns null on failure
+/// Returns an empty string when no speech was detected, null on failure
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_transcribe(handle: *mut FlowHandle, app_name: *const c_char) -> *mut c_char {
     let handle = unsafe { &*handle };
@@ -705,6 +1052,22 @@ pub extern "C" fn flow_transcribe(handle: *mut FlowHandle, app_name: *const c_ch
                 Err(_) => ptr::null_mut(),
             }
         }
+        Err(Error::EmptyTranscription) => {
+            // Not a failure: return an empty string the caller can detect
+            clear_last_error(handle);
+            *handle.last_audio.lock() = None;
+            *handle.last_audio_sample_rate.lock() = None;
+            match CString::new("") {
+                Ok(cstr) => cstr.into_raw(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+        Err(Error::Cancelled) => {
+            // User aborted: not a failure worth recording, and the audio stays retryable
+            debug!("Transcription cancelled");
+            set_last_error(handle, Error::Cancelled.to_string());
+            ptr::null_mut()
+        }
         Err(e) => {
             let message = format!("Transcription failed: {e}");
             error!("{message}");
@@ -720,7 +1083,8 @@ pub extern "C" fn flow_transcribe(handle: *mut FlowHandle, app_name: *const c_ch
 }
 
 /// Retry the last transcription using cached audio
-/// Returns processed text (caller must free with flow_free_string), or null on failure
+/// Returns processed text (caller must free with flow_free_string), an empty string
+/// when no speech was detected, or null on failure
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_retry_last_transcription(
     handle: *mut FlowHandle,
@@ -761,6 +1125,22 @@ pub extern "C" fn flow_retry_last_transcription(
                 Err(_) => ptr::null_mut(),
             }
         }
+        Err(Error::EmptyTranscription) => {
+            // Not a failure: return an empty string the caller can detect
+            clear_last_error(handle);
+            *handle.last_audio.lock() = None;
+            *handle.last_audio_sample_rate.lock() = None;
+            match CString::new("") {
+                Ok(cstr) => cstr.into_raw(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+        Err(Error::Cancelled) => {
+            // User aborted: not a failure worth recording, and the audio stays retryable
+            debug!("Transcription cancelled");
+            set_last_error(handle, Error::Cancelled.to_string());
+            ptr::null_mut()
+        }
         Err(e) => {
             let message = format!("Transcription failed: {e}");
             error!("{message}");
@@ -775,6 +1155,150 @@ pub extern "C" fn flow_retry_last_transcription(
     }
 }
 
+/// Abort the transcription currently in flight (e.g. the user pressed Escape)
+/// The blocked flow_transcribe / flow_retry_last_transcription call returns null
+/// with last error "Request cancelled"; the audio is kept for a retry
+/// Returns true if a request was cancelled
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_cancel_transcription(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    match handle.in_flight.lock().take() {
+        Some(cancel) => {
+            cancel.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+// ============ Dictation ============
+
+/// Record, classify, transcribe, and format in one blocking call (run off the main thread)
+/// Records from the default mic until flow_stop_dictation, or until `silence_timeout_ms`
+/// of quiet follows speech when non-zero. The Messages contact is used when Messages is frontmost
+/// Posts a notification with the result when the notify_on_dictation setting is "true"
+/// Profanity in output for formal contacts is flagged or softened per profanity_action
+/// Returns DictationResult JSON, or null on error; caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_run_dictation(
+    handle: *mut FlowHandle,
+    silence_timeout_ms: u64,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    let is_messages = handle.app_tracker.current_app().is_some_and(|ctx| {
+        ctx.app_name.to_lowercase().contains("messages")
+            || ctx.bundle_id.as_deref() == Some("com.apple.MobileSMS")
+    });
+    let contact: Box<dyn ContactSource> = if is_messages {
+        Box::new(CachedContactSource::new(
+            Box::new(MessagesContactSource::default()),
+            Arc::clone(&handle.storage),
+        ))
+    } else {
+        Box::new(FixedContactSource(None))
+    };
+
+    let stop = CancellationToken::new();
+    *handle.dictation_stop.lock() = Some(stop.clone());
+    let mut microphone = MicrophoneSource::new(stop).with_config(capture_config(handle));
+    if silence_timeout_ms > 0 {
+        microphone = microphone.with_silence_timeout(Duration::from_millis(silence_timeout_ms));
+    }
+
+    let filler_level = handle
+        .storage
+        .get_setting(SETTING_FILLER_LEVEL)
+        .ok()
+        .flatten()
+        .map(|value| FillerLevel::from_setting(&value))
+        .unwrap_or_default();
+    let log_classifications = handle
+        .storage
+        .get_setting(SETTING_LOG_CLASSIFICATIONS)
+        .ok()
+        .flatten()
+        .is_some_and(|value| value == "true");
+    let pipeline = AdaptivePipeline::new(Arc::clone(&handle.completion))
+        .with_shared_classifier(Arc::clone(&handle.contact_classifier))
+        .with_storage(Arc::clone(&handle.storage))
+        .with_classification_logging(log_classifications)
+        .with_filler_level(filler_level)
+        .with_linter(output_linter(handle))
+        .with_system_affixes(
+            non_empty_setting(handle, SETTING_SYSTEM_PROMPT_PREFIX),
+            non_empty_setting(handle, SETTING_SYSTEM_PROMPT_SUFFIX),
+        );
+    let notify = handle
+        .storage
+        .get_setting(SETTING_NOTIFY_ON_DICTATION)
+        .ok()
+        .flatten()
+        .is_some_and(|value| value == "true");
+    let config = DictationConfig::new(
+        Box::new(microphone),
+        contact,
+        Arc::clone(&handle.transcription),
+        pipeline,
+    )
+    .with_notification(notify);
+
+    let result = handle.runtime.block_on(run_dictation(&config));
+    handle.dictation_stop.lock().take();
+
+    match result.and_then(|result| Ok(serde_json::to_string(&result)?)) {
+        Ok(json) => match CString::new(json) {
+            Ok(cstr) => cstr.into_raw(),
+            Err(_) => {
+                set_last_error(handle, "Invalid dictation result");
+                ptr::null_mut()
+            }
+        },
+        Err(e) => {
+            set_last_error(handle, format!("Dictation failed: {}", e));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// A setting's value, treating blank as unset
+fn non_empty_setting(handle: &FlowHandle, key: &str) -> Option<String> {
+    handle
+        .storage
+        .get_setting(key)
+        .ok()
+        .flatten()
+        .filter(|value| !value.trim().is_empty())
+}
+
+/// Profanity linter configured from settings
+fn output_linter(handle: &FlowHandle) -> OutputLinter {
+    let setting = |key| handle.storage.get_setting(key).ok().flatten();
+    let action = setting(SETTING_PROFANITY_ACTION)
+        .map(|value| LintAction::from_setting(&value))
+        .unwrap_or_default();
+    let linter = OutputLinter::new(action);
+    match setting(SETTING_PROFANITY_WORDS).filter(|words| !words.trim().is_empty()) {
+        Some(words) => linter.with_word_list(&words),
+        None => linter,
+    }
+}
+
+/// End the recording of a flow_run_dictation call running on another thread
+/// Returns true if a dictation was recording
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_stop_dictation(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    match handle.dictation_stop.lock().take() {
+        Some(stop) => {
+            stop.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
 // ============ Shortcuts ============
 
 /// Add a voice shortcut
@@ -796,111 +1320,358 @@ pub extern "C" fn flow_add_shortcut(
         Err(_) => return false,
     };
 
-    let replacement_str = match unsafe { CStr::from_ptr(replacement) }.to_str() {
-        Ok(s) => s.to_string(),
-        Err(_) => return false,
+    let replacement_str = match unsafe { CStr::from_ptr(replacement) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return false,
+    };
+
+    let shortcut = Shortcut::new(trigger_str, replacement_str);
+
+    if let Err(e) = handle.storage.save_shortcut(&shortcut) {
+        error!("Failed to save shortcut: {}", e);
+        return false;
+    }
+
+    handle.shortcuts.add_shortcut(shortcut);
+    true
+}
+
+/// Remove a voice shortcut
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_remove_shortcut(handle: *mut FlowHandle, trigger: *const c_char) -> bool {
+    if trigger.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*handle };
+
+    let trigger_str = match unsafe { CStr::from_ptr(trigger) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    handle.shortcuts.remove_shortcut(trigger_str);
+    true
+}
+
+/// Get the number of shortcuts
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_shortcut_count(handle: *mut FlowHandle) -> usize {
+    let handle = unsafe { &*handle };
+    handle.shortcuts.count()
+}
+
+// ============ Writing Modes ============
+
+/// Set the writing mode for an app
+/// mode: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_app_mode(
+    handle: *mut FlowHandle,
+    app_name: *const c_char,
+    mode: u8,
+) -> bool {
+    if app_name.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*handle };
+
+    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    let writing_mode = match mode {
+        0 => WritingMode::Formal,
+        1 => WritingMode::Casual,
+        2 => WritingMode::VeryCasual,
+        3 => WritingMode::Excited,
+        _ => return false,
+    };
+
+    let mut modes = handle.modes.lock();
+    if let Err(e) = modes.set_mode_with_storage(app, writing_mode, &handle.storage) {
+        error!("Failed to save app mode: {}", e);
+        return false;
+    }
+
+    true
+}
+
+/// Get the writing mode for an app
+/// Returns: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_app_mode(handle: *mut FlowHandle, app_name: *const c_char) -> u8 {
+    if app_name.is_null() {
+        return 1; // default to casual
+    }
+
+    let handle = unsafe { &*handle };
+
+    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return 1,
+    };
+
+    let mut modes = handle.modes.lock();
+    let mode = modes.get_mode_with_storage(app, &handle.storage);
+
+    match mode {
+        WritingMode::Formal => 0,
+        WritingMode::Casual => 1,
+        WritingMode::VeryCasual => 2,
+        WritingMode::Excited => 3,
+    }
+}
+
+/// Pin a writing mode to a contact, overriding their category default
+/// mode: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+/// Returns true on success
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_contact_mode(
+    handle: *mut FlowHandle,
+    contact_name: *const c_char,
+    mode: u8,
+) -> bool {
+    if contact_name.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*handle };
+
+    let name = match unsafe { CStr::from_ptr(contact_name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    let writing_mode = match mode {
+        0 => WritingMode::Formal,
+        1 => WritingMode::Casual,
+        2 => WritingMode::VeryCasual,
+        3 => WritingMode::Excited,
+        _ => return false,
+    };
+
+    if let Err(e) = handle.storage.set_contact_mode(name, writing_mode) {
+        error!("Failed to save contact mode: {}", e);
+        return false;
+    }
+
+    true
+}
+
+/// Remove a contact's pinned writing mode
+/// Returns true if a pinned mode was removed
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_clear_contact_mode(
+    handle: *mut FlowHandle,
+    contact_name: *const c_char,
+) -> bool {
+    if contact_name.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*handle };
+
+    let name = match unsafe { CStr::from_ptr(contact_name) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    match handle.storage.clear_contact_mode(name) {
+        Ok(removed) => removed,
+        Err(e) => {
+            error!("Failed to clear contact mode: {}", e);
+            false
+        }
+    }
+}
+
+/// Replace a writing mode's built-in prompt with a template, e.g.
+/// "Rewrite for {contact_name}, a {category} contact, keeping it {length}."
+/// mode: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+/// Returns false if the template is invalid; the reason is in flow_get_last_error
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_mode_prompt_template(
+    handle: *mut FlowHandle,
+    mode: u8,
+    template: *const c_char,
+) -> bool {
+    if template.is_null() {
+        return false;
+    }
+
+    let handle = unsafe { &*handle };
+
+    let source = match unsafe { CStr::from_ptr(template) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    let Some(writing_mode) = WritingMode::all().get(mode as usize).copied() else {
+        return false;
     };
 
-    let shortcut = Shortcut::new(trigger_str, replacement_str);
+    let template = match PromptTemplate::parse(source) {
+        Ok(template) => template,
+        Err(e) => {
+            set_last_error(handle, e.to_string());
+            return false;
+        }
+    };
 
-    if let Err(e) = handle.storage.save_shortcut(&shortcut) {
-        error!("Failed to save shortcut: {}", e);
+    if let Err(e) = handle.storage.set_prompt_template(writing_mode, &template) {
+        error!("Failed to save prompt template: {}", e);
+        set_last_error(handle, e.to_string());
         return false;
     }
 
-    handle.shortcuts.add_shortcut(shortcut);
+    clear_last_error(handle);
     true
 }
 
-/// Remove a voice shortcut
-/// Returns true on success
+/// Remove a writing mode's prompt template so its built-in prompt applies again
+/// Returns true if a template was removed
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_remove_shortcut(handle: *mut FlowHandle, trigger: *const c_char) -> bool {
-    if trigger.is_null() {
+pub extern "C" fn flow_clear_mode_prompt_template(handle: *mut FlowHandle, mode: u8) -> bool {
+    let handle = unsafe { &*handle };
+
+    let Some(writing_mode) = WritingMode::all().get(mode as usize).copied() else {
         return false;
+    };
+
+    match handle.storage.clear_prompt_template(writing_mode) {
+        Ok(removed) => removed,
+        Err(e) => {
+            error!("Failed to clear prompt template: {}", e);
+            false
+        }
+    }
+}
+
+/// Get a contact's emoji usage as JSON:
+/// {"dictations": n, "with_emoji": n, "pinned": "add"|null, "learned": "avoid"|null}
+/// "pinned" is the user's override; without one, "learned" is what's applied.
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_contact_emoji_usage(
+    handle: *mut FlowHandle,
+    contact_name: *const c_char,
+) -> *mut c_char {
+    if contact_name.is_null() {
+        return ptr::null_mut();
     }
 
     let handle = unsafe { &*handle };
 
-    let trigger_str = match unsafe { CStr::from_ptr(trigger) }.to_str() {
+    let name = match unsafe { CStr::from_ptr(contact_name) }.to_str() {
         Ok(s) => s,
-        Err(_) => return false,
+        Err(_) => return ptr::null_mut(),
     };
 
-    handle.shortcuts.remove_shortcut(trigger_str);
-    true
-}
+    let usage = match handle.storage.get_emoji_usage(name) {
+        Ok(usage) => usage,
+        Err(e) => {
+            error!("Failed to read emoji usage: {}", e);
+            return ptr::null_mut();
+        }
+    };
 
-/// Get the number of shortcuts
-#[unsafe(no_mangle)]
-pub extern "C" fn flow_shortcut_count(handle: *mut FlowHandle) -> usize {
-    let handle = unsafe { &*handle };
-    handle.shortcuts.count()
+    let json = serde_json::json!({
+        "dictations": usage.dictations,
+        "with_emoji": usage.with_emoji,
+        "pinned": usage.pinned,
+        "learned": usage.learned(),
+    });
+    CString::new(json.to_string()).unwrap().into_raw()
 }
 
-// ============ Writing Modes ============
-
-/// Set the writing mode for an app
-/// mode: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+/// Pin an emoji policy to a contact, overriding the one learned from their history
+/// policy: 0 = Avoid, 1 = Preserve, 2 = Add
 /// Returns true on success
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_set_app_mode(
+pub extern "C" fn flow_set_contact_emoji_policy(
     handle: *mut FlowHandle,
-    app_name: *const c_char,
-    mode: u8,
+    contact_name: *const c_char,
+    policy: u8,
 ) -> bool {
-    if app_name.is_null() {
+    if contact_name.is_null() {
         return false;
     }
 
     let handle = unsafe { &*handle };
 
-    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+    let name = match unsafe { CStr::from_ptr(contact_name) }.to_str() {
         Ok(s) => s,
         Err(_) => return false,
     };
 
-    let writing_mode = match mode {
-        0 => WritingMode::Formal,
-        1 => WritingMode::Casual,
-        2 => WritingMode::VeryCasual,
-        3 => WritingMode::Excited,
+    let policy = match policy {
+        0 => EmojiPolicy::Avoid,
+        1 => EmojiPolicy::Preserve,
+        2 => EmojiPolicy::Add,
         _ => return false,
     };
 
-    let mut modes = handle.modes.lock();
-    if let Err(e) = modes.set_mode_with_storage(app, writing_mode, &handle.storage) {
-        error!("Failed to save app mode: {}", e);
+    if let Err(e) = handle.storage.set_emoji_policy(name, policy) {
+        error!("Failed to save emoji policy: {}", e);
         return false;
     }
 
     true
 }
 
-/// Get the writing mode for an app
-/// Returns: 0 = Formal, 1 = Casual, 2 = VeryCasual, 3 = Excited
+/// Remove a contact's pinned emoji policy so the learned one applies again
+/// Returns true if a pinned policy was removed
 #[unsafe(no_mangle)]
-pub extern "C" fn flow_get_app_mode(handle: *mut FlowHandle, app_name: *const c_char) -> u8 {
-    if app_name.is_null() {
-        return 1; // default to casual
+pub extern "C" fn flow_clear_contact_emoji_policy(
+    handle: *mut FlowHandle,
+    contact_name: *const c_char,
+) -> bool {
+    if contact_name.is_null() {
+        return false;
     }
 
     let handle = unsafe { &*handle };
 
-    let app = match unsafe { CStr::from_ptr(app_name) }.to_str() {
+    let name = match unsafe { CStr::from_ptr(contact_name) }.to_str() {
         Ok(s) => s,
-        Err(_) => return 1,
+        Err(_) => return false,
     };
 
-    let mut modes = handle.modes.lock();
-    let mode = modes.get_mode_with_storage(app, &handle.storage);
+    match handle.storage.clear_emoji_policy(name) {
+        Ok(removed) => removed,
+        Err(e) => {
+            error!("Failed to clear emoji policy: {}", e);
+            false
+        }
+    }
+}
 
-    match mode {
-        WritingMode::Formal => 0,
-        WritingMode::Casual => 1,
-        WritingMode::VeryCasual => 2,
-        WritingMode::Excited => 3,
+/// Preview every writing mode on a sample sentence, offline
+/// Returns JSON object mapping mode to text, e.g. {"formal": "...", "casual": "...", ...}
+/// The rule-based approximation, not what the model will write; null if sample is invalid.
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_preview_modes(sample: *const c_char) -> *mut c_char {
+    if sample.is_null() {
+        return ptr::null_mut();
     }
+    let sample = match unsafe { CStr::from_ptr(sample) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let previews: serde_json::Map<String, serde_json::Value> = WritingMode::all()
+        .iter()
+        .map(|mode| (mode.to_string(), mode.example_transform(sample).into()))
+        .collect();
+
+    let json = serde_json::to_string(&previews).unwrap_or_else(|_| "{}".to_string());
+    CString::new(json).unwrap().into_raw()
 }
 
 // ============ Learning ============
@@ -1098,7 +1869,10 @@ pub extern "C" fn flow_validate_corrections(
         }
     };
 
-    let results = match runtime.block_on(crate::providers::validate_corrections(pairs)) {
+    let results = match runtime.block_on(crate::providers::validate_corrections(
+        default_http_client().as_ref(),
+        pairs,
+    )) {
         Ok(r) => r,
         Err(e) => {
             error!("Validation failed: {}", e);
@@ -1159,6 +1933,28 @@ pub extern "C" fn flow_is_configured(handle: *mut FlowHandle) -> bool {
     handle.transcription.is_configured() && handle.completion.is_configured()
 }
 
+/// Send a tiny request to the transcription and completion providers to confirm they work
+/// Returns false with the reason in flow_get_last_error (bad key, unknown model, ...)
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_health_check(handle: *mut FlowHandle) -> bool {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    if let Err(e) = handle.runtime.block_on(handle.transcription.health_check()) {
+        set_last_error(handle, format!("Transcription check failed: {}", e));
+        return false;
+    }
+
+    // Base10 ("Auto (Cloud)") completes inside the transcription call
+    if handle.transcription.name() != "Auto (Cloud)"
+        && let Err(e) = handle.runtime.block_on(handle.completion.health_check())
+    {
+        set_last_error(handle, format!("Completion check failed: {}", e));
+        return false;
+    }
+    true
+}
+
 // ============ App Tracking ============
 
 /// Set the currently active app (call from Swift when app switches)
@@ -1314,6 +2110,31 @@ pub extern "C" fn flow_get_stats_json(handle: *mut FlowHandle) -> *mut c_char {
     }
 }
 
+/// Get completion token usage per provider/model since a unix timestamp (seconds) as JSON
+/// [{"provider", "model", "prompt_tokens", "completion_tokens", "total_tokens", "calls"}]
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_usage_summary_json(
+    handle: *mut FlowHandle,
+    since_unix_secs: i64,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    let since = chrono::DateTime::from_timestamp(since_unix_secs, 0).unwrap_or_default();
+
+    let summary = match handle.storage.get_usage_summary(since) {
+        Ok(summary) => summary,
+        Err(e) => {
+            error!("Failed to load usage summary: {}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    match CString::new(serde_json::to_string(&summary).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
 /// Get recent transcriptions as JSON (caller must free with flow_free_string)
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_get_recent_transcriptions_json(
@@ -1374,16 +2195,38 @@ pub extern "C" fn flow_get_last_error(handle: *mut FlowHandle) -> *mut c_char {
 // ============ Provider Configuration ============
 
 /// Switch completion provider (loads API key from database)
-/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
+/// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = None (raw transcription, no key needed),
+/// 4 = Rules (offline rule-based formatting, no key needed)
 /// Returns true if provider was switched successfully
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_switch_completion_provider(handle: *mut FlowHandle, provider: u8) -> bool {
     let handle = unsafe { &mut *handle };
 
-    let (setting_key, provider_name) = match provider {
-        0 => (SETTING_OPENAI_API_KEY, "openai"),
-        1 => (SETTING_GEMINI_API_KEY, "gemini"),
-        2 => (SETTING_OPENROUTER_API_KEY, "openrouter"),
+    if provider == 3 || provider == 4 {
+        let name = if provider == 3 { "none" } else { "rules" };
+        if let Err(e) = handle
+            .storage
+            .set_setting(SETTING_COMPLETION_PROVIDER, name)
+        {
+            let message = format!("Failed to save completion provider: {e}");
+            error!("{message}");
+            set_last_error(handle, message);
+            return false;
+        }
+        handle.completion = if provider == 3 {
+            Arc::new(PassthroughCompletionProvider::new())
+        } else {
+            Arc::new(RuleBasedAdapter::new())
+        };
+        debug!("Switched completion provider to {}", name);
+        clear_last_error(handle);
+        return true;
+    }
+
+    let provider_name = match provider {
+        0 => "openai",
+        1 => "gemini",
+        2 => "openrouter",
         _ => {
             set_last_error(handle, "Invalid provider");
             return false;
@@ -1391,9 +2234,9 @@ pub extern "C" fn flow_switch_completion_provider(handle: *mut FlowHandle, provi
     };
 
     // Load the API key from the database
-    let api_key = match handle.storage.get_setting(setting_key) {
-        Ok(Some(key)) if !key.is_empty() => key,
-        Ok(Some(_)) | Ok(None) => {
+    let api_key = match handle.storage.get_api_key(provider_name) {
+        Ok(Some(key)) => key,
+        Ok(None) => {
             let message = format!("No API key configured for {}", provider_name);
             error!("{message}");
             set_last_error(handle, message);
@@ -1444,6 +2287,61 @@ pub extern "C" fn flow_switch_completion_provider(handle: *mut FlowHandle, provi
     true
 }
 
+/// Set the model used by the current completion provider (saved per provider)
+/// model: model identifier, or null/empty to use the provider default
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_set_completion_model(handle: *mut FlowHandle, model: *const c_char) -> bool {
+    let handle = unsafe { &mut *handle };
+    clear_last_error(handle);
+
+    let model = if model.is_null() {
+        String::new()
+    } else {
+        match unsafe { CStr::from_ptr(model) }.to_str() {
+            Ok(s) => s.trim().to_string(),
+            Err(_) => {
+                set_last_error(handle, "Invalid UTF-8 in model");
+                return false;
+            }
+        }
+    };
+
+    let provider_name = handle
+        .storage
+        .get_setting(SETTING_COMPLETION_PROVIDER)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "openai".to_string());
+
+    if let Err(e) = handle
+        .storage
+        .set_setting(&completion_model_setting(&provider_name), &model)
+    {
+        let message = format!("Failed to save completion model: {e}");
+        error!("{message}");
+        set_last_error(handle, message);
+        return false;
+    }
+
+    match build_from_storage_with(
+        &handle.storage,
+        &ProviderRegistry::new(),
+        &handle.rate_limiters,
+    ) {
+        Ok(provider) => {
+            handle.completion = Arc::from(provider);
+            debug!("Set completion model for {} to {:?}", provider_name, model);
+            true
+        }
+        Err(e) => {
+            let message = format!("Failed to rebuild completion provider: {e}");
+            error!("{message}");
+            set_last_error(handle, message);
+            false
+        }
+    }
+}
+
 /// Set completion provider with API key (saves both)
 /// provider: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter
 /// api_key: The API key for the provider
@@ -1466,7 +2364,7 @@ pub extern "C" fn flow_set_completion_provider(
 
     match provider {
         0 => {
-            if let Err(e) = handle.storage.set_setting(SETTING_OPENAI_API_KEY, &key) {
+            if let Err(e) = handle.storage.set_api_key("openai", &key) {
                 let message = format!("Failed to save OpenAI API key: {e}");
                 error!("{message}");
                 set_last_error(handle, message);
@@ -1486,7 +2384,7 @@ pub extern "C" fn flow_set_completion_provider(
             debug!("Set completion provider to OpenAI");
         }
         1 => {
-            if let Err(e) = handle.storage.set_setting(SETTING_GEMINI_API_KEY, &key) {
+            if let Err(e) = handle.storage.set_api_key("gemini", &key) {
                 let message = format!("Failed to save Gemini API key: {e}");
                 error!("{message}");
                 set_last_error(handle, message);
@@ -1506,7 +2404,7 @@ pub extern "C" fn flow_set_completion_provider(
             debug!("Set completion provider to Gemini");
         }
         2 => {
-            if let Err(e) = handle.storage.set_setting(SETTING_OPENROUTER_API_KEY, &key) {
+            if let Err(e) = handle.storage.set_api_key("openrouter", &key) {
                 let message = format!("Failed to save OpenRouter API key: {e}");
                 error!("{message}");
                 set_last_error(handle, message);
@@ -1532,7 +2430,7 @@ pub extern "C" fn flow_set_completion_provider(
 }
 
 /// Get the current completion provider name
-/// Returns: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 255 = Unknown
+/// Returns: 0 = OpenAI, 1 = Gemini, 2 = OpenRouter, 3 = None, 4 = Rules, 255 = Unknown
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_get_completion_provider(handle: *mut FlowHandle) -> u8 {
     let handle = unsafe { &*handle };
@@ -1541,6 +2439,8 @@ pub extern "C" fn flow_get_completion_provider(handle: *mut FlowHandle) -> u8 {
         "OpenAI GPT" => 0,
         "Gemini" => 1,
         "OpenRouter" => 2,
+        "Passthrough" => 3,
+        "Rule-based" => 4,
         _ => 255,
     }
 }
@@ -1572,14 +2472,14 @@ fn mask_api_key(key: &str) -> String {
 pub extern "C" fn flow_get_api_key(handle: *mut FlowHandle, provider: u8) -> *mut c_char {
     let handle = unsafe { &*handle };
 
-    let setting_key = match provider {
-        0 => SETTING_OPENAI_API_KEY,
-        1 => SETTING_GEMINI_API_KEY,
-        2 => SETTING_OPENROUTER_API_KEY,
+    let provider_name = match provider {
+        0 => "openai",
+        1 => "gemini",
+        2 => "openrouter",
         _ => return ptr::null_mut(),
     };
 
-    match handle.storage.get_setting(setting_key) {
+    match handle.storage.get_api_key(provider_name) {
         Ok(Some(key)) => {
             let masked = mask_api_key(&key);
             CString::new(masked).unwrap().into_raw()
@@ -1698,7 +2598,7 @@ pub extern "C" fn flow_set_transcription_mode(
 
         match cloud_provider.as_str() {
             "openai" => {
-                if let Ok(Some(key)) = handle.storage.get_setting(SETTING_OPENAI_API_KEY) {
+                if let Ok(Some(key)) = handle.storage.get_api_key("openai") {
                     handle.transcription = Arc::new(OpenAITranscriptionProvider::new(Some(key)));
                     debug!("Enabled OpenAI remote transcription");
                 } else {
@@ -1852,7 +2752,7 @@ pub extern "C" fn flow_get_active_messages_contact(handle: *mut FlowHandle) -> *
     let handle = unsafe { &*handle };
     clear_last_error(handle);
 
-    match MessagesDetector::get_active_contact() {
+    match MessagesDetector::new().get_active_contact() {
         Ok(Some(name)) => match CString::new(name) {
             Ok(cstr) => cstr.into_raw(),
             Err(_) => {
@@ -1862,14 +2762,48 @@ pub extern "C" fn flow_get_active_messages_contact(handle: *mut FlowHandle) -> *
         },
         Ok(None) => ptr::null_mut(),
         Err(e) => {
+            if let Error::PermissionDenied { what } = e {
+                *handle.missing_permission.lock() = Some(what);
+            }
             set_last_error(handle, format!("Failed to get active contact: {}", e));
             ptr::null_mut()
         }
     }
 }
 
-/// Classify a contact given name and organization
-/// Returns JSON string with category
+/// Get the last permission a detector found denied and clear it
+/// Returns 0 = none, 1 = Accessibility, 2 = Automation, 3 = Contacts
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_take_missing_permission(handle: *mut FlowHandle) -> u8 {
+    let handle = unsafe { &*handle };
+    match handle.missing_permission.lock().take() {
+        None => 0,
+        Some(Permission::Accessibility) => 1,
+        Some(Permission::Automation) => 2,
+        Some(Permission::Contacts) => 3,
+    }
+}
+
+/// Get the System Settings deep link for a permission (1-3, as above)
+/// Returns null for unknown values; caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_permission_settings_url(permission: u8) -> *mut c_char {
+    let permission = match permission {
+        1 => Permission::Accessibility,
+        2 => Permission::Automation,
+        3 => Permission::Contacts,
+        _ => return ptr::null_mut(),
+    };
+
+    match CString::new(permission.settings_url()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Classify a contact given name and organization, at the current local time
+/// Returns JSON: {"name": "...", "category": "...", "mode": "...", "after_hours": false}
+/// A work contact outside the configured work hours is after_hours with mode "casual"
 /// Caller must free with flow_free_string
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_classify_contact(
@@ -1908,13 +2842,18 @@ pub extern "C" fn flow_classify_contact(
     let input = ContactInput {
         name: name_str.to_string(),
         organization: org_str,
+        relationship: None,
     };
 
-    let category = handle.contact_classifier.classify(&input);
+    let detail = handle
+        .contact_classifier
+        .classify_with_context(&input, &ClassificationContext::now());
 
     let result = serde_json::json!({
         "name": name_str,
-        "category": category,
+        "category": detail.category,
+        "mode": detail.suggested_writing_mode(),
+        "after_hours": detail.after_hours,
     });
 
     match CString::new(result.to_string()) {
@@ -1928,7 +2867,9 @@ pub extern "C" fn flow_classify_contact(
 
 /// Classify multiple contacts from JSON array
 /// Input format: [{"name": "...", "organization": "..."}]
-/// Output format: {"ContactName": "category", ...}
+/// Output format (schema version 1, in input order):
+/// {"version": 1, "results": [{"name", "category", "mode", "confidence", "reason"}, ...]}
+/// category and mode are snake_case strings, e.g. "close_family", "very_casual"
 /// Caller must free with flow_free_string
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_classify_contacts_batch(
@@ -1971,6 +2912,53 @@ pub extern "C" fn flow_classify_contacts_batch(
     }
 }
 
+/// Classify multiple contacts from JSON array, including why each category was chosen
+/// Input format: [{"name": "...", "organization": "..."}]
+/// Output format: {"ContactName": {"category": "...", "reason": "..."}, ...}
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_classify_contacts_batch_verbose(
+    handle: *mut FlowHandle,
+    contacts_json: *const c_char,
+) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    let json_str = unsafe {
+        if contacts_json.is_null() {
+            set_last_error(handle, "JSON cannot be null");
+            return ptr::null_mut();
+        }
+        match CStr::from_ptr(contacts_json).to_str() {
+            Ok(s) => s,
+            Err(_) => {
+                set_last_error(handle, "Invalid UTF-8 in JSON");
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let inputs: Vec<ContactInput> = match serde_json::from_str(json_str) {
+        Ok(i) => i,
+        Err(e) => {
+            set_last_error(handle, format!("Invalid JSON: {}", e));
+            return ptr::null_mut();
+        }
+    };
+
+    let result_json = handle
+        .contact_classifier
+        .classify_batch_json_verbose(&inputs);
+
+    match CString::new(result_json) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => {
+            set_last_error(handle, "Failed to create result string");
+            ptr::null_mut()
+        }
+    }
+}
+
 /// Record interaction with a contact (updates frequency)
 #[unsafe(no_mangle)]
 pub extern "C" fn flow_record_contact_interaction(handle: *mut FlowHandle, name: *const c_char) {
@@ -2023,6 +3011,33 @@ pub extern "C" fn flow_get_frequent_contacts(handle: *mut FlowHandle, limit: u32
     }
 }
 
+/// Get the most recent classification decisions as a JSON array, newest first
+/// Returns: [{"contact_name": "...", "category": "professional", "confidence": 0.9,
+/// "reason": "...", "fallback_applied": false, "after_hours": false, "timestamp": "..."}, ...]
+/// Empty unless the log_classifications setting is "true".
+/// Caller must free with flow_free_string
+#[unsafe(no_mangle)]
+pub extern "C" fn flow_get_classification_log(handle: *mut FlowHandle, limit: u32) -> *mut c_char {
+    let handle = unsafe { &*handle };
+    clear_last_error(handle);
+
+    let decisions = match handle.storage.get_classification_log(limit as usize) {
+        Ok(decisions) => decisions,
+        Err(e) => {
+            set_last_error(handle, format!("Failed to read classification log: {}", e));
+            return ptr::null_mut();
+        }
+    };
+
+    match CString::new(serde_json::to_string(&decisions).unwrap_or_default()) {
+        Ok(cstr) => cstr.into_raw(),
+        Err(_) => {
+            set_last_error(handle, "Failed to serialize classification log");
+            ptr::null_mut()
+        }
+    }
+}
+
 /// Get suggested writing mode for a contact category
 /// Returns: 0=Formal, 1=Casual, 2=VeryCasual, 3=Excited
 #[unsafe(no_mangle)]
@@ -2041,6 +3056,7 @@ pub extern "C" fn flow_get_writing_mode_for_category(
         2 => ContactCategory::CasualPeer,
         3 => ContactCategory::Partner,
         4 => ContactCategory::FormalNeutral,
+        5 => ContactCategory::Coworker,
         _ => ContactCategory::FormalNeutral,
     };
 
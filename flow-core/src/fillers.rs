@@ -0,0 +1,330 @@
+//! Filler word and stutter removal for raw transcriptions
+//!
+//! Whisper transcribes hesitations faithfully ("um", "uh", "I I think"). This
+//! cleans them up before shortcuts, corrections, and completion run.
+//! Example (aggressive): "Um, so I I think, like, we should go" -> "So I think we should go"
+
+use serde::{Deserialize, Serialize};
+
+/// How much to strip
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FillerLevel {
+    /// Leave the text untouched
+    #[default]
+    Off,
+    /// Only collapse repeated words and stutters ("I I", "th-the")
+    Light,
+    /// Also drop hesitations ("um", "uh") and comma-delimited discourse fillers (", like,")
+    Aggressive,
+}
+
+impl FillerLevel {
+    /// Parse a stored setting value; unknown values are treated as Off
+    pub fn from_setting(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "light" => FillerLevel::Light,
+            "aggressive" => FillerLevel::Aggressive,
+            _ => FillerLevel::Off,
+        }
+    }
+}
+
+/// Word lists used by [`remove_fillers_with`]; all entries lowercase
+#[derive(Debug, Clone)]
+pub struct FillerWords {
+    /// Removed anywhere in aggressive mode
+    pub hesitations: Vec<String>,
+    /// Removed in aggressive mode only when followed by a comma ("like," "you know,")
+    pub discourse: Vec<String>,
+    /// Words that legitimately repeat ("that that", "had had") and are never collapsed
+    pub keep_repeated: Vec<String>,
+}
+
+impl Default for FillerWords {
+    fn default() -> Self {
+        let owned = |words: &[&str]| words.iter().map(|w| w.to_string()).collect();
+        Self {
+            hesitations: owned(&["um", "umm", "uh", "uhh", "uhm", "er", "erm", "ah", "hmm"]),
+            discourse: owned(&[
+                "like",
+                "you know",
+                "i mean",
+                "basically",
+                "literally",
+                "sort of",
+                "kind of",
+            ]),
+            keep_repeated: owned(&["that", "had", "is", "do", "bye"]),
+        }
+    }
+}
+
+/// Hyphenated forms like "re-read" are words, not stutters
+const WORD_PREFIXES: &[&str] = &[
+    "re", "co", "pre", "de", "un", "non", "ex", "sub", "bi", "tri", "mid", "mis", "anti", "semi",
+    "self", "post",
+];
+
+/// Remove fillers with the default word lists
+pub fn remove_fillers(text: &str, level: FillerLevel) -> String {
+    remove_fillers_with(text, level, &FillerWords::default())
+}
+
+/// Remove fillers using custom word lists
+pub fn remove_fillers_with(text: &str, level: FillerLevel, words: &FillerWords) -> String {
+    if level == FillerLevel::Off {
+        return text.to_string();
+    }
+
+    let tokens: Vec<Token> = text.split_whitespace().map(Token::parse).collect();
+    let mut kept: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut capitalize_next = false;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let token = &tokens[i];
+        let at_sentence_start = kept.last().is_none_or(|t| t.ends_sentence());
+
+        let dropped = if level == FillerLevel::Aggressive {
+            if words.hesitations.iter().any(|w| token.is(w)) {
+                1
+            } else {
+                discourse_len(&tokens[i..], &words.discourse)
+            }
+        } else {
+            0
+        };
+
+        if dropped > 0 {
+            let trail = &tokens[i + dropped - 1].trail;
+            absorb_punctuation(&mut kept, trail);
+            capitalize_next |= at_sentence_start || trail.ends_with(['.', '?', '!']);
+            i += dropped;
+            continue;
+        }
+
+        // "I I think" -> "I think": the earlier copy goes if nothing separates them
+        let repeated = tokens.get(i + 1).is_some_and(|next| {
+            token.trail.is_empty()
+                && next.lead.is_empty()
+                && next.core.eq_ignore_ascii_case(&token.core)
+                && !token.core.is_empty()
+                && !words.keep_repeated.iter().any(|w| token.is(w))
+        });
+        if repeated {
+            capitalize_next |= at_sentence_start && starts_uppercase(&token.core);
+            i += 1;
+            continue;
+        }
+
+        let mut token = token.clone();
+        token.core = collapse_stutter(&token.core);
+        if capitalize_next && kept.last().is_none_or(|t| t.ends_sentence()) {
+            token.core = capitalize(&token.core);
+        }
+        capitalize_next = false;
+        kept.push(token);
+        i += 1;
+    }
+
+    kept.iter().map(Token::render).collect::<Vec<_>>().join(" ")
+}
+
+/// A whitespace-separated word split into leading punctuation, the word, and trailing punctuation
+#[derive(Debug, Clone)]
+struct Token {
+    lead: String,
+    core: String,
+    trail: String,
+}
+
+impl Token {
+    fn parse(raw: &str) -> Self {
+        let is_word = |c: char| c.is_alphanumeric();
+        let start = raw.find(is_word).unwrap_or(raw.len());
+        let end = raw.rfind(is_word).map_or(start, |i| {
+            i + raw[i..].chars().next().map_or(0, char::len_utf8)
+        });
+        Self {
+            lead: raw[..start].to_string(),
+            core: raw[start..end].to_string(),
+            trail: raw[end..].to_string(),
+        }
+    }
+
+    fn is(&self, word: &str) -> bool {
+        self.core.eq_ignore_ascii_case(word)
+    }
+
+    fn ends_sentence(&self) -> bool {
+        self.trail.ends_with(['.', '?', '!'])
+    }
+
+    fn render(&self) -> String {
+        format!("{}{}{}", self.lead, self.core, self.trail)
+    }
+}
+
+/// Number of tokens in a comma-terminated discourse filler starting at `tokens[0]`
+fn discourse_len(tokens: &[Token], discourse: &[String]) -> usize {
+    for phrase in discourse {
+        let words: Vec<&str> = phrase.split_whitespace().collect();
+        if words.is_empty() || tokens.len() < words.len() {
+            continue;
+        }
+        let candidate = &tokens[..words.len()];
+        let last = candidate.len() - 1;
+        let matches = candidate
+            .iter()
+            .zip(&words)
+            .enumerate()
+            .all(|(j, (token, word))| {
+                token.is(word)
+                    && (j == 0 || token.lead.is_empty())
+                    && (j == last || token.trail.is_empty())
+            });
+        if matches && candidate[0].lead.is_empty() && candidate[last].trail == "," {
+            return words.len();
+        }
+    }
+    0
+}
+
+/// Keep sentence punctuation from a removed filler and drop a comma it leaves dangling
+fn absorb_punctuation(kept: &mut [Token], removed_trail: &str) {
+    let Some(previous) = kept.last_mut() else {
+        return;
+    };
+    let terminal = removed_trail.trim_start_matches(',');
+    if !terminal.is_empty() {
+        // "go, um." -> "go."
+        if previous.trail.ends_with(',') {
+            previous.trail.pop();
+        }
+        if !previous.ends_sentence() {
+            previous.trail.push_str(terminal);
+        }
+    } else if removed_trail == "," && previous.trail == "," {
+        // "we should, um, go" -> "we should go"
+        previous.trail.clear();
+    }
+}
+
+/// "th-the" -> "the", "w-w-what" -> "what"; real hyphenated words are left alone
+fn collapse_stutter(word: &str) -> String {
+    let parts: Vec<&str> = word.split('-').collect();
+    let Some((last, fragments)) = parts.split_last() else {
+        return word.to_string();
+    };
+    if fragments.is_empty() {
+        return word.to_string();
+    }
+
+    let last_lower = last.to_lowercase();
+    let is_stutter = fragments.iter().all(|fragment| {
+        let fragment = fragment.to_lowercase();
+        !fragment.is_empty()
+            && last_lower.starts_with(&fragment)
+            && (fragment == last_lower || !WORD_PREFIXES.contains(&fragment.as_str()))
+    });
+
+    if is_stutter {
+        // keep the capital from "I-I" or "Th-the"
+        if starts_uppercase(fragments[0]) {
+            capitalize(last)
+        } else {
+            last.to_string()
+        }
+    } else {
+        word.to_string()
+    }
+}
+
+fn starts_uppercase(word: &str) -> bool {
+    word.chars().next().is_some_and(char::is_uppercase)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_off_is_identity() {
+        let text = "Um, so I I think, like, we should go";
+        assert_eq!(remove_fillers(text, FillerLevel::Off), text);
+    }
+
+    #[test]
+    fn test_light_collapses_repeats_and_stutters() {
+        assert_eq!(
+            remove_fillers(
+                "I I think th-the meeting is at w-w-what time",
+                FillerLevel::Light
+            ),
+            "I think the meeting is at what time"
+        );
+        // hesitations survive light mode
+        assert_eq!(
+            remove_fillers("um I think so", FillerLevel::Light),
+            "um I think so"
+        );
+        // legitimate repeats and hyphenated words are kept
+        assert_eq!(
+            remove_fillers(
+                "I know that that is a well-known re-read",
+                FillerLevel::Light
+            ),
+            "I know that that is a well-known re-read"
+        );
+    }
+
+    #[test]
+    fn test_aggressive_removes_fillers() {
+        assert_eq!(
+            remove_fillers(
+                "Um, so I I think, like, we should go",
+                FillerLevel::Aggressive
+            ),
+            "So I think we should go"
+        );
+        assert_eq!(
+            remove_fillers(
+                "I mean, it's fine. Uh, see you there uh.",
+                FillerLevel::Aggressive
+            ),
+            "It's fine. See you there."
+        );
+        // "like" as a verb stays
+        assert_eq!(
+            remove_fillers("I like it, um, a lot", FillerLevel::Aggressive),
+            "I like it a lot"
+        );
+    }
+
+    #[test]
+    fn test_custom_word_lists() {
+        let words = FillerWords {
+            hesitations: vec!["okay".to_string()],
+            discourse: Vec::new(),
+            keep_repeated: Vec::new(),
+        };
+        assert_eq!(
+            remove_fillers_with("okay um let's start", FillerLevel::Aggressive, &words),
+            "Um let's start"
+        );
+        assert_eq!(
+            FillerLevel::from_setting("Aggressive"),
+            FillerLevel::Aggressive
+        );
+        assert_eq!(FillerLevel::from_setting("bogus"), FillerLevel::Off);
+    }
+}
@@ -0,0 +1,146 @@
+//! Frontmost app detection for choosing a writing mode outside Messages
+//!
+//! When there's no contact to classify (Mail, Notes, a doc), the mode comes from
+//! the frontmost app instead: a per-app override, then a per-category override,
+//! both stored in `Storage`, then the category's built-in default.
+
+use tracing::debug;
+
+use crate::apps::AppRegistry;
+use crate::error::Result;
+use crate::macos_messages::{decode_output, run_osascript, succeeded};
+use crate::storage::Storage;
+use crate::types::{AppCategory, WritingMode};
+
+/// Reads the frontmost app and maps it to a writing mode
+pub struct HostEventDetector {
+    registry: AppRegistry,
+}
+
+impl Default for HostEventDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostEventDetector {
+    pub fn new() -> Self {
+        Self {
+            registry: AppRegistry::new(),
+        }
+    }
+
+    /// Use a registry with custom app mappings
+    pub fn with_registry(registry: AppRegistry) -> Self {
+        Self { registry }
+    }
+
+    /// Get the bundle ID of the frontmost application using AppleScript
+    ///
+    /// Returns:
+    /// - Ok(Some(bundle_id)) if a frontmost app was found
+    /// - Ok(None) if System Events gave no answer
+    /// - Err(PermissionDenied) if Automation or Accessibility access is missing
+    /// - Err if AppleScript execution fails
+    pub fn detect_frontmost_app() -> Result<Option<String>> {
+        let script = r#"
+            tell application "System Events"
+                set frontApp to first application process whose frontmost is true
+                return bundle identifier of frontApp
+            end tell
+        "#;
+
+        let output = run_osascript(script)?;
+
+        if !succeeded(&output)? {
+            return Ok(None);
+        }
+
+        let bundle_id = decode_output(&output.stdout).trim().to_string();
+        if bundle_id.is_empty() || bundle_id == "missing value" {
+            return Ok(None);
+        }
+
+        Ok(Some(bundle_id))
+    }
+
+    /// Categorize an app from its bundle ID alone
+    pub fn category_for(&self, bundle_id: &str) -> AppCategory {
+        // "com.apple.Notes" -> "notes" for the name-based fallback
+        let app_name = bundle_id.rsplit('.').next().unwrap_or(bundle_id);
+        self.registry.categorize(app_name, Some(bundle_id))
+    }
+
+    /// Writing mode for an app: app override, then category override, then category default
+    pub fn mode_for(&self, bundle_id: &str, storage: Option<&Storage>) -> WritingMode {
+        let category = self.category_for(bundle_id);
+
+        let stored = storage.and_then(|storage| {
+            storage
+                .get_app_mode(bundle_id)
+                .ok()
+                .flatten()
+                .or_else(|| storage.get_category_mode(category).ok().flatten())
+        });
+        let mode = stored.unwrap_or_else(|| self.registry.suggested_mode(category));
+        debug!(
+            "Frontmost app {} ({:?}) -> mode {:?}",
+            bundle_id, category, mode
+        );
+        mode
+    }
+
+    /// Detect the frontmost app and pick its mode; None if it couldn't be detected
+    pub fn detect_mode(&self, storage: Option<&Storage>) -> Result<Option<WritingMode>> {
+        Ok(Self::detect_frontmost_app()?.map(|bundle_id| self.mode_for(&bundle_id, storage)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_for_uses_overrides() {
+        let detector = HostEventDetector::new();
+        assert_eq!(detector.category_for("com.apple.mail"), AppCategory::Email);
+        assert_eq!(
+            detector.category_for("com.apple.Notes"),
+            AppCategory::Documents
+        );
+        assert_eq!(
+            detector.mode_for("com.apple.mail", None),
+            WritingMode::Formal
+        );
+
+        let storage = Storage::in_memory().unwrap();
+        storage
+            .set_category_mode(AppCategory::Documents, WritingMode::Casual)
+            .unwrap();
+        assert_eq!(
+            detector.mode_for("com.apple.Notes", Some(&storage)),
+            WritingMode::Casual
+        );
+
+        // a per-app override beats the category
+        storage
+            .save_app_mode("com.apple.Notes", WritingMode::VeryCasual)
+            .unwrap();
+        assert_eq!(
+            detector.mode_for("com.apple.Notes", Some(&storage)),
+            WritingMode::VeryCasual
+        );
+        assert_eq!(
+            detector.mode_for("com.apple.mail", Some(&storage)),
+            WritingMode::Formal
+        );
+    }
+
+    #[test]
+    #[ignore] // Only run on macOS
+    fn test_detect_frontmost_app() {
+        let result = HostEventDetector::detect_frontmost_app();
+        println!("Frontmost app: {:?}", result);
+        assert!(result.is_ok());
+    }
+}
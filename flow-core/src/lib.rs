@@ -6,30 +6,49 @@
 pub mod apps;
 pub mod audio;
 pub mod contacts;
+pub mod dictation;
 pub mod error;
 pub mod ffi;
+pub mod fillers;
+pub mod host;
 pub mod learning;
+pub mod linter;
 pub mod macos_messages;
 pub mod metrics;
 pub mod modes;
+pub mod notify;
+pub mod output;
+pub mod pipeline;
+pub mod prompt_template;
 pub mod providers;
+pub mod redaction;
+pub mod script;
 pub mod shortcuts;
 pub mod storage;
+pub mod streaming_pipeline;
 pub mod types;
 pub mod voice_commands;
 pub mod whisper_models;
 
-pub use error::{Error, Result};
+pub use error::{Error, Permission, Result};
 pub use types::*;
 
 /// Re-export the main engine components for convenience
 pub use apps::{AppRegistry, AppTracker};
 pub use audio::AudioCapture;
 pub use contacts::ContactClassifier;
+pub use dictation::{DictationConfig, DictationResult, run_dictation};
+pub use host::HostEventDetector;
 pub use learning::LearningEngine;
-pub use macos_messages::MessagesDetector;
+pub use linter::OutputLinter;
+pub use macos_messages::{DEFAULT_MESSAGES_TIMEOUT, MessagesDetector};
 pub use metrics::{MetricsCollector, SessionStats, UserStats};
 pub use modes::WritingModeEngine;
+pub use output::{ClipboardSink, OutputSink, TypeSink};
+pub use pipeline::AdaptivePipeline;
+pub use prompt_template::PromptTemplate;
 pub use providers::{CompletionProvider, TranscriptionProvider};
+pub use redaction::Redactor;
 pub use shortcuts::ShortcutsEngine;
-pub use storage::Storage;
+pub use storage::{EmojiUsage, MIN_EMOJI_SAMPLES, RankedContact, Storage};
+pub use streaming_pipeline::StreamingPipeline;
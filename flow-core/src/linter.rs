@@ -0,0 +1,288 @@
+//! Profanity check on formatted output before it is inserted
+//!
+//! A message to a boss shouldn't go out with "damn" in it just because the user
+//! said it. For formal contact categories the linter either flags profane words
+//! or softens them; casual recipients are never touched.
+//! Example (soften, Professional): "That damn report is late" -> "That darn report is late"
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::ContactCategory;
+
+/// What to do with profanity in output for a formal contact
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintAction {
+    /// Don't check
+    #[default]
+    Off,
+    /// Leave the text alone and report each word found
+    Warn,
+    /// Replace each word with its milder form (or drop it) and report it
+    Soften,
+}
+
+impl LintAction {
+    /// Parse a stored setting value; unknown values are treated as Off
+    pub fn from_setting(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "warn" => LintAction::Warn,
+            "soften" => LintAction::Soften,
+            _ => LintAction::Off,
+        }
+    }
+}
+
+/// A profane word found in output meant for a formal contact
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintWarning {
+    /// The word as it appeared in the output
+    pub word: String,
+    pub category: ContactCategory,
+    /// What replaced it when softened; `None` when only flagged
+    pub replacement: Option<String>,
+}
+
+/// Output text after linting plus what was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintOutcome {
+    pub text: String,
+    pub warnings: Vec<LintWarning>,
+}
+
+/// Words checked by default, with the milder form used when softening
+/// An empty replacement drops the word.
+const DEFAULT_WORDS: &[(&str, &str)] = &[
+    ("damn", "darn"),
+    ("dammit", "darn it"),
+    ("goddamn", "darn"),
+    ("hell", "heck"),
+    ("shit", "shoot"),
+    ("shitty", "bad"),
+    ("bullshit", "nonsense"),
+    ("crap", "stuff"),
+    ("crappy", "bad"),
+    ("fuck", ""),
+    ("fucking", ""),
+    ("fuckin", ""),
+    ("effing", ""),
+    ("wtf", "what"),
+    ("pissed", "annoyed"),
+    ("ass", ""),
+    ("asshole", "jerk"),
+    ("bitch", ""),
+];
+
+/// Checks formatted output for profanity when the recipient is formal
+#[derive(Debug, Clone)]
+pub struct OutputLinter {
+    action: LintAction,
+    /// Lowercase word and its softened form ("" to drop it)
+    words: Vec<(String, String)>,
+    categories: Vec<ContactCategory>,
+}
+
+impl OutputLinter {
+    /// Linter with the default word list for Professional, FormalNeutral, and Coworker contacts
+    pub fn new(action: LintAction) -> Self {
+        Self {
+            action,
+            words: DEFAULT_WORDS
+                .iter()
+                .map(|(word, soft)| (word.to_string(), soft.to_string()))
+                .collect(),
+            categories: vec![
+                ContactCategory::Professional,
+                ContactCategory::FormalNeutral,
+                ContactCategory::Coworker,
+            ],
+        }
+    }
+
+    /// Replace the word list with a setting value: comma-separated words, each
+    /// optionally `word=replacement` (no replacement drops the word when softening)
+    pub fn with_word_list(mut self, list: &str) -> Self {
+        self.words = list
+            .split(',')
+            .filter_map(|entry| {
+                let (word, soft) = entry.split_once('=').unwrap_or((entry, ""));
+                let word = word.trim().to_lowercase();
+                (!word.is_empty()).then(|| (word, soft.trim().to_string()))
+            })
+            .collect();
+        self
+    }
+
+    /// Contact categories the linter applies to
+    pub fn with_categories(mut self, categories: Vec<ContactCategory>) -> Self {
+        self.categories = categories;
+        self
+    }
+
+    /// Whether output for `category` is checked
+    pub fn applies_to(&self, category: ContactCategory) -> bool {
+        self.action != LintAction::Off && self.categories.contains(&category)
+    }
+
+    /// Check `text` formatted for a `category` contact
+    pub fn lint(&self, text: &str, category: ContactCategory) -> LintOutcome {
+        if !self.applies_to(category) {
+            return LintOutcome {
+                text: text.to_string(),
+                warnings: Vec::new(),
+            };
+        }
+
+        let mut warnings = Vec::new();
+        let mut kept: Vec<String> = Vec::new();
+        let mut capitalize_next = false;
+        for raw in text.split_whitespace() {
+            let (lead, core, trail) = split_word(raw);
+            let Some((_, soft)) = self
+                .words
+                .iter()
+                .find(|(w, _)| core.eq_ignore_ascii_case(w))
+            else {
+                if std::mem::take(&mut capitalize_next) {
+                    kept.push(format!("{}{}{}", lead, match_case("A", core), trail));
+                } else {
+                    kept.push(raw.to_string());
+                }
+                continue;
+            };
+
+            if self.action == LintAction::Warn {
+                warnings.push(LintWarning {
+                    word: core.to_string(),
+                    category,
+                    replacement: None,
+                });
+                kept.push(raw.to_string());
+                continue;
+            }
+
+            warnings.push(LintWarning {
+                word: core.to_string(),
+                category,
+                replacement: Some(soft.clone()),
+            });
+            if soft.is_empty() {
+                // "Fuck, it broke" -> "It broke"
+                capitalize_next |= kept
+                    .last()
+                    .is_none_or(|previous| previous.ends_with(['.', '?', '!']));
+                // keep sentence punctuation from the dropped word: "it's late, damn." -> "it's late."
+                if let Some(previous) = kept.last_mut()
+                    && !trail.is_empty()
+                {
+                    let previous_trail = previous.len()
+                        - previous
+                            .trim_end_matches(|c: char| !c.is_alphanumeric())
+                            .len();
+                    previous.truncate(previous.len() - previous_trail);
+                    previous.push_str(trail);
+                }
+            } else {
+                let case = if std::mem::take(&mut capitalize_next) {
+                    "A"
+                } else {
+                    core
+                };
+                kept.push(format!("{}{}{}", lead, match_case(case, soft), trail));
+            }
+        }
+
+        LintOutcome {
+            text: kept.join(" "),
+            warnings,
+        }
+    }
+}
+
+impl Default for OutputLinter {
+    fn default() -> Self {
+        Self::new(LintAction::Off)
+    }
+}
+
+/// Split a whitespace-separated word into leading punctuation, the word, and trailing punctuation
+fn split_word(raw: &str) -> (&str, &str, &str) {
+    let start = raw.find(char::is_alphanumeric).unwrap_or(raw.len());
+    let end = raw[start..]
+        .rfind(char::is_alphanumeric)
+        .map_or(start, |i| {
+            start + i + raw[start + i..].chars().next().map_or(0, char::len_utf8)
+        });
+    (&raw[..start], &raw[start..end], &raw[end..])
+}
+
+/// Give `replacement` the capitalization of `original` ("Damn" -> "Darn", "DAMN" -> "DARN")
+fn match_case(original: &str, replacement: &str) -> String {
+    let letters: Vec<char> = original.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        return replacement.to_uppercase();
+    }
+    if original.chars().next().is_some_and(char::is_uppercase) {
+        let mut chars = replacement.chars();
+        return match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        };
+    }
+    replacement.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soften_for_formal_contacts() {
+        let linter = OutputLinter::new(LintAction::Soften);
+        let outcome = linter.lint(
+            "Damn, the report is late. What the hell happened, fucking again?",
+            ContactCategory::Professional,
+        );
+        assert_eq!(
+            outcome.text,
+            "Darn, the report is late. What the heck happened, again?"
+        );
+        assert_eq!(outcome.warnings.len(), 3);
+        assert_eq!(outcome.warnings[0].word, "Damn");
+        assert_eq!(outcome.warnings[0].replacement.as_deref(), Some("darn"));
+
+        let outcome = linter.lint("Fuck, it broke again.", ContactCategory::FormalNeutral);
+        assert_eq!(outcome.text, "It broke again.");
+
+        // casual recipients are left alone
+        let outcome = linter.lint("damn that was fun", ContactCategory::CasualPeer);
+        assert_eq!(outcome.text, "damn that was fun");
+        assert!(outcome.warnings.is_empty());
+
+        // "hello" and "assess" aren't profanity
+        let outcome = linter.lint("Hello, please assess it", ContactCategory::Professional);
+        assert!(outcome.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_warn_and_custom_words() {
+        let linter = OutputLinter::new(LintAction::Warn).with_word_list("frick, heck=gosh");
+        let outcome = linter.lint("Frick, that damn build", ContactCategory::Coworker);
+        assert_eq!(outcome.text, "Frick, that damn build");
+        assert_eq!(
+            outcome.warnings,
+            vec![LintWarning {
+                word: "Frick".to_string(),
+                category: ContactCategory::Coworker,
+                replacement: None,
+            }]
+        );
+
+        let linter = linter.with_categories(vec![ContactCategory::Partner]);
+        assert!(linter.applies_to(ContactCategory::Partner));
+        assert!(!linter.applies_to(ContactCategory::Professional));
+        assert!(!OutputLinter::default().applies_to(ContactCategory::Professional));
+        assert_eq!(LintAction::from_setting(" Soften "), LintAction::Soften);
+        assert_eq!(LintAction::from_setting("bogus"), LintAction::Off);
+    }
+}
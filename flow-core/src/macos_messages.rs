@@ -1,19 +1,54 @@
-//! macOS Messages.app integration for contact detection
+//! macOS Messages.app integration for contact detection, plus the relationship
+//! the user recorded for that contact in Contacts.app
 
-use crate::error::{Error, Result};
-use std::process::Command;
+use crate::contacts::fix_mojibake;
+use crate::error::{Error, Permission, Result};
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How long a Messages query may take before it's abandoned
+pub const DEFAULT_MESSAGES_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How often a running osascript is checked against its deadline
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 /// Detect the active contact name from Messages.app window title
-pub struct MessagesDetector;
+///
+/// On a busy system System Events can take seconds to answer, so every query is
+/// abandoned after `timeout` rather than stalling dictation.
+#[derive(Debug, Clone, Copy)]
+pub struct MessagesDetector {
+    timeout: Duration,
+}
 
 impl MessagesDetector {
+    pub fn new() -> Self {
+        Self {
+            timeout: DEFAULT_MESSAGES_TIMEOUT,
+        }
+    }
+
+    /// Set how long each AppleScript query may run
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
     /// Get the active Messages window contact name using AppleScript
     ///
     /// Returns:
     /// - Ok(Some(name)) if Messages is open and has a window
     /// - Ok(None) if Messages is not running or no window exists
+    /// - Err(PermissionDenied) if Automation or Accessibility access is missing
+    /// - Err(Timeout) if the query didn't answer within the detector's timeout
     /// - Err if AppleScript execution fails
-    pub fn get_active_contact() -> Result<Option<String>> {
+    pub fn get_active_contact(&self) -> Result<Option<String>> {
         let script = r#"
             tell application "System Events"
                 tell application process "Messages"
@@ -24,18 +59,14 @@ impl MessagesDetector {
             end tell
         "#;
 
-        let output = Command::new("osascript")
-            .arg("-e")
-            .arg(script)
-            .output()
-            .map_err(|e| Error::Io(e))?;
+        let output = run_osascript_with_timeout(script, self.timeout)?;
 
-        if !output.status.success() {
+        if !succeeded(&output)? {
             // Messages not running or no window
             return Ok(None);
         }
 
-        let window_title = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let window_title = decode_output(&output.stdout).trim().to_string();
 
         if window_title.is_empty() {
             return Ok(None);
@@ -60,7 +91,7 @@ impl MessagesDetector {
     }
 
     /// Check if Messages.app is currently running
-    pub fn is_messages_running() -> Result<bool> {
+    pub fn is_messages_running(&self) -> Result<bool> {
         let script = r#"
             tell application "System Events"
                 if exists (processes where name is "Messages") then
@@ -71,13 +102,9 @@ impl MessagesDetector {
             end tell
         "#;
 
-        let output = Command::new("osascript")
-            .arg("-e")
-            .arg(script)
-            .output()
-            .map_err(|e| Error::Io(e))?;
+        let output = run_osascript_with_timeout(script, self.timeout)?;
 
-        if !output.status.success() {
+        if !succeeded(&output)? {
             return Ok(false);
         }
 
@@ -90,7 +117,7 @@ impl MessagesDetector {
 
     /// Get all open conversation window titles
     /// Returns vector of contact names from all open Messages windows
-    pub fn get_all_conversations() -> Result<Vec<String>> {
+    pub fn get_all_conversations(&self) -> Result<Vec<String>> {
         let script = r#"
             tell application "System Events"
                 tell application process "Messages"
@@ -103,17 +130,13 @@ impl MessagesDetector {
             end tell
         "#;
 
-        let output = Command::new("osascript")
-            .arg("-e")
-            .arg(script)
-            .output()
-            .map_err(|e| Error::Io(e))?;
+        let output = run_osascript_with_timeout(script, self.timeout)?;
 
-        if !output.status.success() {
+        if !succeeded(&output)? {
             return Ok(Vec::new());
         }
 
-        let result = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let result = decode_output(&output.stdout).trim().to_string();
 
         if result.is_empty() {
             return Ok(Vec::new());
@@ -130,6 +153,163 @@ impl MessagesDetector {
     }
 }
 
+impl Default for MessagesDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Relationship of `name` to the user, as recorded in Contacts.app
+///
+/// Looks for `name` among the related names on the user's own card ("Alex Chen",
+/// labelled spouse), falling back to the nickname on `name`'s card ("Mom"). Labels
+/// come back raw, e.g. "_$!<Spouse>!$_"; the classifier normalizes them.
+/// Returns `Ok(None)` when there's no card, no match, or Contacts isn't available.
+pub fn contact_relationship(name: &str) -> Result<Option<String>> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Ok(None);
+    }
+
+    let script = format!(
+        r#"
+            set contactName to "{}"
+            tell application "Contacts"
+                set myCard to my card
+                if myCard is not missing value then
+                    repeat with relatedName in related names of myCard
+                        if value of relatedName is contactName then return label of relatedName
+                    end repeat
+                end if
+                set matches to (people whose name is contactName)
+                if (count of matches) > 0 then
+                    set contactNickname to nickname of item 1 of matches
+                    if contactNickname is not missing value then return contactNickname
+                end if
+            end tell
+            return ""
+        "#,
+        escape_applescript(name)
+    );
+
+    let output = run_osascript(&script)?;
+    if !succeeded(&output)? {
+        return Ok(None);
+    }
+
+    let relationship = decode_output(&output.stdout).trim().to_string();
+    Ok((!relationship.is_empty()).then_some(relationship))
+}
+
+/// Quote text for an AppleScript string literal
+pub(crate) fn escape_applescript(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Run an AppleScript with UTF-8 output
+///
+/// Launched from the app bundle there's no locale in the environment, so osascript
+/// falls back to a legacy encoding and emoji in window titles come back garbled.
+pub(crate) fn run_osascript(script: &str) -> Result<Output> {
+    Command::new("osascript")
+        .arg("-e")
+        .arg(script)
+        .env("LANG", "en_US.UTF-8")
+        .env("LC_ALL", "en_US.UTF-8")
+        .output()
+        .map_err(Error::Io)
+}
+
+/// Like [`run_osascript`], but kills osascript and fails with `Error::Timeout`
+/// once it has run for `timeout`
+pub(crate) fn run_osascript_with_timeout(script: &str, timeout: Duration) -> Result<Output> {
+    let mut command = Command::new("osascript");
+    command
+        .arg("-e")
+        .arg(script)
+        .env("LANG", "en_US.UTF-8")
+        .env("LC_ALL", "en_US.UTF-8");
+    output_with_timeout(command, timeout)
+}
+
+/// Run `command` to completion, or kill it once `timeout` has passed
+fn output_with_timeout(mut command: Command, timeout: Duration) -> Result<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Error::Io)?;
+    // drain the pipes as the child writes so a full pipe can't block it past the deadline
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Error::Timeout(format!(
+                "AppleScript query after {}ms",
+                timeout.as_millis()
+            )));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Whether osascript succeeded; a denied permission is an error rather than `false`
+///
+/// Other failures (Messages not running, no window) report `Ok(false)` so callers
+/// can treat them as "nothing to detect".
+pub(crate) fn succeeded(output: &Output) -> Result<bool> {
+    if output.status.success() {
+        return Ok(true);
+    }
+    match denied_permission(&String::from_utf8_lossy(&output.stderr)) {
+        Some(what) => Err(Error::PermissionDenied { what }),
+        None => Ok(false),
+    }
+}
+
+/// Recognize the privacy errors osascript reports on stderr
+fn denied_permission(stderr: &str) -> Option<Permission> {
+    // -1743: errAEEventNotPermitted, Automation access for the target app was refused
+    if stderr.contains("-1743") || stderr.contains("Not authorized to send Apple events") {
+        return Some(Permission::Automation);
+    }
+    // -1719 / -25211: System Events was refused assistive (Accessibility) access
+    if stderr.contains("-1719") || stderr.contains("-25211") || stderr.contains("assistive access")
+    {
+        return Some(Permission::Accessibility);
+    }
+    None
+}
+
+/// Decode osascript stdout, repairing text that was double-encoded anyway
+pub(crate) fn decode_output(stdout: &[u8]) -> String {
+    fix_mojibake(&String::from_utf8_lossy(stdout))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,10 +323,60 @@ mod tests {
         assert_eq!(MessagesDetector::normalize_window_title("Mom"), "Mom");
     }
 
+    #[test]
+    fn test_decode_output_repairs_mojibake() {
+        // "Mike 🍺\n" as UTF-8, garbled through Windows-1252, re-encoded as UTF-8
+        let garbled = "Mike \u{f0}\u{178}\u{8d}\u{ba}\n";
+        assert_eq!(decode_output(garbled.as_bytes()).trim(), "Mike 🍺");
+        assert_eq!(decode_output("Mike 🍺\n".as_bytes()).trim(), "Mike 🍺");
+    }
+
+    #[test]
+    fn test_denied_permission() {
+        assert_eq!(
+            denied_permission(
+                "execution error: Not authorized to send Apple events to System Events. (-1743)"
+            ),
+            Some(Permission::Automation)
+        );
+        assert_eq!(
+            denied_permission(
+                "execution error: System Events got an error: osascript is not allowed assistive access. (-1719)"
+            ),
+            Some(Permission::Accessibility)
+        );
+        // Messages not running is not a permission problem
+        assert_eq!(
+            denied_permission(
+                "execution error: System Events got an error: Can't get application process \"Messages\". (-1728)"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_output_with_timeout() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo Mom");
+        let output = output_with_timeout(command, Duration::from_secs(5)).unwrap();
+        assert!(output.status.success());
+        assert_eq!(decode_output(&output.stdout).trim(), "Mom");
+
+        // a hung query is abandoned instead of blocking
+        let mut command = Command::new("sleep");
+        command.arg("5");
+        let started = Instant::now();
+        let err = output_with_timeout(command, Duration::from_millis(50))
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::Timeout(_)));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
     #[test]
     #[ignore] // Only run on macOS with Messages.app
     fn test_get_active_contact() {
-        let result = MessagesDetector::get_active_contact();
+        let result = MessagesDetector::new().get_active_contact();
         println!("Active contact: {:?}", result);
         assert!(result.is_ok());
     }
@@ -154,7 +384,7 @@ mod tests {
     #[test]
     #[ignore] // Only run on macOS
     fn test_is_messages_running() {
-        let result = MessagesDetector::is_messages_running();
+        let result = MessagesDetector::new().is_messages_running();
         println!("Messages running: {:?}", result);
         assert!(result.is_ok());
     }
@@ -334,6 +334,27 @@ mod tests {
     use super::*;
     use crate::types::AppCategory;
 
+    #[test]
+    fn test_writing_mode_string_roundtrip() {
+        for mode in WritingMode::all() {
+            assert_eq!(mode.to_string().parse::<WritingMode>().unwrap(), *mode);
+            // storage historically wrote the Debug form
+            assert_eq!(format!("{:?}", mode).parse::<WritingMode>().unwrap(), *mode);
+        }
+        assert_eq!(WritingMode::VeryCasual.to_string(), "very_casual");
+        assert_eq!(
+            "Very Casual".parse::<WritingMode>().unwrap(),
+            WritingMode::VeryCasual
+        );
+        assert_eq!(
+            "FORMAL".parse::<WritingMode>().unwrap(),
+            WritingMode::Formal
+        );
+
+        let err = "shouty".parse::<WritingMode>().unwrap_err();
+        assert!(err.to_string().contains("shouty"));
+    }
+
     #[test]
     fn test_mode_suggestions() {
         assert_eq!(
@@ -350,6 +371,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_contextual_prompt() {
+        use crate::types::ContactCategory;
+
+        let prompt = WritingMode::Casual.contextual_prompt("Mom", ContactCategory::CloseFamily);
+        assert!(prompt.contains("Mom"));
+        assert!(prompt.contains("close family member"));
+        assert!(prompt.contains(WritingMode::Casual.prompt_modifier()));
+
+        let prompt = WritingMode::Excited.contextual_prompt("Alex", ContactCategory::Partner);
+        assert!(prompt.contains("romantic partner"));
+        assert!(prompt.contains("\"Alex\""));
+
+        let prompt = WritingMode::Formal.contextual_prompt("  ", ContactCategory::Professional);
+        assert!(prompt.starts_with("The user is messaging a professional contact."));
+    }
+
     #[test]
     fn test_style_analysis() {
         assert_eq!(
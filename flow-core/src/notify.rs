@@ -0,0 +1,64 @@
+//! Notification Center alerts for dictations that finish in the background
+//!
+//! Posted through AppleScript's `display notification`, which needs no app
+//! bundle or entitlement. Elsewhere `post_notification` does nothing.
+
+use crate::error::Result;
+
+/// Longest body shown; Notification Center truncates long alerts anyway
+const MAX_BODY_CHARS: usize = 240;
+
+/// Show a notification with `title` and `body`
+pub fn post_notification(title: &str, body: &str) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        use crate::error::Error;
+        use crate::macos_messages::{escape_applescript, run_osascript, succeeded};
+
+        let script = format!(
+            "display notification \"{}\" with title \"{}\"",
+            escape_applescript(&shorten(body)),
+            escape_applescript(title)
+        );
+        let output = run_osascript(&script)?;
+        if !succeeded(&output)? {
+            return Err(Error::Io(std::io::Error::other(format!(
+                "Posting notification failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))));
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (title, body);
+        Ok(())
+    }
+}
+
+/// Cut `text` to `MAX_BODY_CHARS`, ending with an ellipsis when shortened
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= MAX_BODY_CHARS {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(MAX_BODY_CHARS - 1).collect();
+    short.push('…');
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macos_messages::escape_applescript;
+
+    #[test]
+    fn test_escape_and_shorten() {
+        assert_eq!(escape_applescript(r#"say "hi" \o/"#), r#"say \"hi\" \\o/"#);
+        assert_eq!(shorten("running late"), "running late");
+
+        let long = "a".repeat(500);
+        let short = shorten(&long);
+        assert_eq!(short.chars().count(), MAX_BODY_CHARS);
+        assert!(short.ends_with('…'));
+    }
+}
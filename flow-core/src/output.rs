@@ -0,0 +1,241 @@
+//! Delivering the formatted text to the app the user is dictating into
+//!
+//! An [`OutputSink`] is the last step of a dictation: [`ClipboardSink`] puts the
+//! text on the pasteboard for the user (or the host app) to paste, [`TypeSink`]
+//! types it into the focused field as synthetic keystrokes. Both are macOS only;
+//! elsewhere they fail with an unsupported error.
+
+use crate::error::{Error, Result};
+
+/// Most UTF-16 units macOS accepts in one synthetic key event
+const MAX_EVENT_UNITS: usize = 20;
+
+/// Pause between synthetic key events so slow text fields don't drop input
+#[cfg(target_os = "macos")]
+const KEY_EVENT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(4);
+
+/// Where a finished dictation's text goes
+pub trait OutputSink: Send + Sync {
+    fn deliver(&self, text: &str) -> Result<()>;
+}
+
+/// Copies the text to the general pasteboard
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClipboardSink;
+
+impl OutputSink for ClipboardSink {
+    fn deliver(&self, text: &str) -> Result<()> {
+        #[cfg(target_os = "macos")]
+        {
+            use std::io::Write;
+            use std::process::{Command, Stdio};
+
+            // pbcopy reads its input in the locale's encoding; force UTF-8 for emoji
+            let mut child = Command::new("pbcopy")
+                .env("LANG", "en_US.UTF-8")
+                .env("LC_ALL", "en_US.UTF-8")
+                .stdin(Stdio::piped())
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(text.as_bytes())?;
+            }
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(Error::Io(std::io::Error::other(format!(
+                    "pbcopy exited with {}",
+                    status
+                ))));
+            }
+            Ok(())
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = text;
+            Err(unsupported("Clipboard output"))
+        }
+    }
+}
+
+/// Types the text into the focused app with synthetic key events
+///
+/// Each event carries a run of Unicode text rather than key codes, so any
+/// keyboard layout, accents, and emoji type correctly. Runs never split a
+/// character (or an emoji sequence) between events. Posting key events needs
+/// Accessibility access; without it macOS drops them, so that's checked first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TypeSink;
+
+impl OutputSink for TypeSink {
+    fn deliver(&self, text: &str) -> Result<()> {
+        #[cfg(target_os = "macos")]
+        {
+            if !key_events::is_trusted() {
+                return Err(Error::PermissionDenied {
+                    what: crate::error::Permission::Accessibility,
+                });
+            }
+            for run in typing_runs(text) {
+                key_events::type_units(&run)?;
+                std::thread::sleep(KEY_EVENT_INTERVAL);
+            }
+            Ok(())
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = text;
+            Err(unsupported("Typed output"))
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn unsupported(what: &str) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("{} is only supported on macOS", what),
+    ))
+}
+
+/// Split `text` into UTF-16 runs of at most `MAX_EVENT_UNITS`, one per key event
+///
+/// Breaks only between user-perceived characters: surrogate pairs, combining
+/// accents, skin tones, and ZWJ emoji sequences stay in one run. A single
+/// sequence longer than the limit gets a run of its own.
+fn typing_runs(text: &str) -> Vec<Vec<u16>> {
+    let mut clusters: Vec<Vec<u16>> = Vec::new();
+    let mut previous: Option<char> = None;
+    for c in text.chars() {
+        if !previous.is_some_and(|p| joins(p, c)) {
+            clusters.push(Vec::new());
+        }
+        if let Some(cluster) = clusters.last_mut() {
+            let mut units = [0u16; 2];
+            cluster.extend_from_slice(c.encode_utf16(&mut units));
+        }
+        previous = Some(c);
+    }
+
+    let mut runs: Vec<Vec<u16>> = Vec::new();
+    for cluster in clusters {
+        match runs.last_mut() {
+            Some(run) if run.len() + cluster.len() <= MAX_EVENT_UNITS => run.extend(cluster),
+            _ => runs.push(cluster),
+        }
+    }
+    runs
+}
+
+/// Whether `c` belongs to the same visible character as `previous`
+fn joins(previous: char, c: char) -> bool {
+    const ZWJ: char = '\u{200D}';
+    previous == ZWJ
+        || c == ZWJ
+        || matches!(c,
+            '\u{0300}'..='\u{036F}'      // combining accents
+            | '\u{20D0}'..='\u{20FF}'    // combining marks for symbols, incl. the keycap
+            | '\u{FE00}'..='\u{FE0F}'    // variation selectors (emoji vs text style)
+            | '\u{1F3FB}'..='\u{1F3FF}'  // skin tones
+            | '\u{E0020}'..='\u{E007F}'  // tag sequences (subdivision flags)
+        )
+        || (is_regional_indicator(previous) && is_regional_indicator(c))
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+#[cfg(target_os = "macos")]
+mod key_events {
+    use std::ffi::c_void;
+
+    use crate::error::{Error, Result};
+
+    type CGEventRef = *mut c_void;
+
+    /// kCGHIDEventTap: post as if typed on the keyboard
+    const HID_EVENT_TAP: u32 = 0;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    unsafe extern "C" {
+        fn CGEventCreateKeyboardEvent(
+            source: *const c_void,
+            keycode: u16,
+            key_down: bool,
+        ) -> CGEventRef;
+        fn CGEventKeyboardSetUnicodeString(event: CGEventRef, length: usize, string: *const u16);
+        fn CGEventPost(tap: u32, event: CGEventRef);
+    }
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    unsafe extern "C" {
+        fn AXIsProcessTrusted() -> bool;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    unsafe extern "C" {
+        fn CFRelease(cf: *const c_void);
+    }
+
+    pub(super) fn is_trusted() -> bool {
+        unsafe { AXIsProcessTrusted() }
+    }
+
+    /// Post a key down and up carrying `units` as their text
+    pub(super) fn type_units(units: &[u16]) -> Result<()> {
+        for key_down in [true, false] {
+            unsafe {
+                let event = CGEventCreateKeyboardEvent(std::ptr::null(), 0, key_down);
+                if event.is_null() {
+                    return Err(Error::Io(std::io::Error::other(
+                        "Failed to create key event",
+                    )));
+                }
+                CGEventKeyboardSetUnicodeString(event, units.len(), units.as_ptr());
+                CGEventPost(HID_EVENT_TAP, event);
+                CFRelease(event);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runs(text: &str) -> Vec<String> {
+        typing_runs(text)
+            .iter()
+            .map(|run| String::from_utf16(run).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_typing_runs_keep_characters_whole() {
+        assert_eq!(runs("running late"), vec!["running late"]);
+        assert!(runs("").is_empty());
+
+        let long = "a".repeat(45);
+        let split = runs(&long);
+        assert_eq!(split.len(), 3);
+        assert!(
+            typing_runs(&long)
+                .iter()
+                .all(|run| run.len() <= MAX_EVENT_UNITS)
+        );
+        assert_eq!(split.concat(), long);
+
+        // 19 letters then a surrogate pair: the emoji moves to the next event whole
+        let text = format!("{}🍺", "a".repeat(19));
+        assert_eq!(runs(&text), vec!["a".repeat(19), "🍺".to_string()]);
+
+        // a family emoji (8 UTF-16 units) and a flag are never cut apart
+        let family = "👨‍👩‍👧";
+        let text = format!("{}{}🇨🇦", "b".repeat(15), family);
+        assert_eq!(runs(&text), vec!["b".repeat(15), format!("{}🇨🇦", family)]);
+
+        // a combining accent stays with its letter
+        let text = format!("{}e\u{301}", "c".repeat(19));
+        assert_eq!(runs(&text), vec!["c".repeat(19), "e\u{301}".to_string()]);
+    }
+}
@@ -0,0 +1,1067 @@
+//! Text-only adaptive pipeline: contact classification -> writing mode -> completion
+//!
+//! Runs the same formatting path as a Messages dictation without the mic or
+//! Messages.app, so the adaptive behaviour can be exercised from tests and CI.
+//! A writing mode pinned to the contact in storage wins over the category default.
+//! Text outside the Latin script keeps its language, and an unpinned mode that only
+//! makes sense in English is softened (see [`Script::adjust_mode`]).
+//!
+//! Contact frequency counts completed dictations, not classifications: `plan` never
+//! touches it, and `adapt` records one interaction per contact per debounce window so
+//! a UI that re-runs detection while the user dictates doesn't inflate the count.
+//!
+//! `trace` replays a saved clip through transcription and the same path, recording
+//! every intermediate so a bad adaptation can be pinned to the stage that caused it.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{StreamExt, TryStreamExt};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::contacts::{
+    ClassificationContext, ClassificationDecision, ClassificationDetail, ContactClassifier,
+    ContactInput, normalize_name,
+};
+use crate::error::{Error, Result};
+use crate::fillers::{FillerLevel, remove_fillers};
+use crate::linter::{LintWarning, OutputLinter};
+use crate::providers::{
+    CompletionProvider, CompletionRequest, CompletionResponse, FinishReason, LengthHint,
+    TokenUsage, TranscriptionProvider, TranscriptionRequest,
+};
+use crate::redaction::Redactor;
+use crate::script::{Script, detect_script};
+use crate::storage::Storage;
+use crate::types::{AudioClip, ContactCategory, WritingMode};
+
+/// Continuations requested by default before a truncated reply is returned as-is
+pub const DEFAULT_MAX_CONTINUATIONS: usize = 2;
+
+/// Dictations to the same contact closer together than this count once
+pub const DEFAULT_FREQUENCY_DEBOUNCE: Duration = Duration::from_secs(5);
+
+/// Completions `adapt_many` keeps in flight at once by default
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+
+/// Formats raw text for a recipient using an injected completion provider
+pub struct AdaptivePipeline {
+    classifier: Arc<ContactClassifier>,
+    completion: Arc<dyn CompletionProvider>,
+    storage: Option<Arc<Storage>>,
+    filler_level: FillerLevel,
+    linter: OutputLinter,
+    system_prefix: Option<String>,
+    system_suffix: Option<String>,
+    first_person: bool,
+    max_continuations: usize,
+    frequency_debounce: Duration,
+    max_in_flight: usize,
+    /// Keep each classification in storage's classification log
+    log_classifications: bool,
+    /// Normalized name and time of the last interaction counted
+    last_recorded: Mutex<Option<(String, Instant)>>,
+}
+
+impl AdaptivePipeline {
+    pub fn new(completion: Arc<dyn CompletionProvider>) -> Self {
+        Self {
+            classifier: Arc::new(ContactClassifier::new()),
+            completion,
+            storage: None,
+            filler_level: FillerLevel::Off,
+            linter: OutputLinter::default(),
+            system_prefix: None,
+            system_suffix: None,
+            first_person: true,
+            max_continuations: DEFAULT_MAX_CONTINUATIONS,
+            frequency_debounce: DEFAULT_FREQUENCY_DEBOUNCE,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            log_classifications: false,
+            last_recorded: Mutex::new(None),
+        }
+    }
+
+    /// Window in which repeat dictations to one contact count as one (zero to count all)
+    pub fn with_frequency_debounce(mut self, window: Duration) -> Self {
+        self.frequency_debounce = window;
+        self
+    }
+
+    /// How many times a reply cut off by the token limit is continued (0 to never)
+    pub fn with_max_continuations(mut self, max: usize) -> Self {
+        self.max_continuations = max;
+        self
+    }
+
+    /// Most completions `adapt_many` runs at once, to stay under provider rate limits
+    pub fn with_max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = max;
+        self
+    }
+
+    /// Strip filler words from the raw text before formatting it
+    pub fn with_filler_level(mut self, level: FillerLevel) -> Self {
+        self.filler_level = level;
+        self
+    }
+
+    /// Check the formatted text for profanity before it is returned
+    pub fn with_linter(mut self, linter: OutputLinter) -> Self {
+        self.linter = linter;
+        self
+    }
+
+    /// Global instructions framing every mode's system prompt
+    /// See [`CompletionRequest::resolve_system_prompt`] for the order they're applied in.
+    pub fn with_system_affixes(mut self, prefix: Option<String>, suffix: Option<String>) -> Self {
+        self.system_prefix = prefix;
+        self.system_suffix = suffix;
+        self
+    }
+
+    /// Whether the text is the user's own message (the default), so the model keeps it
+    /// in the first person; turn off when formatting someone else's words
+    pub fn with_first_person(mut self, first_person: bool) -> Self {
+        self.first_person = first_person;
+        self
+    }
+
+    /// Consult per-contact pinned modes in `storage` and persist contact frequency there
+    pub fn with_storage(mut self, storage: Arc<Storage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Record why each contact was classified as it was, in storage's classification log
+    /// Off by default; has no effect without storage.
+    pub fn with_classification_logging(mut self, enabled: bool) -> Self {
+        self.log_classifications = enabled;
+        self
+    }
+
+    /// Use an existing classifier (e.g. one with cached contacts)
+    pub fn with_classifier(mut self, classifier: ContactClassifier) -> Self {
+        self.classifier = Arc::new(classifier);
+        self
+    }
+
+    /// Share a classifier with other owners so interactions recorded here are seen there
+    pub fn with_shared_classifier(mut self, classifier: Arc<ContactClassifier>) -> Self {
+        self.classifier = classifier;
+        self
+    }
+
+    /// Build the completion request `adapt` would send, without calling the provider
+    /// A contact with an empty name is classified but not named in the request.
+    /// Very casual messages and friends get an SMS-length target. The temperature
+    /// follows the mode (see [`WritingMode::suggested_temperature`]). Work contacts
+    /// get Casual outside the classifier's work hours. With storage,
+    /// the contact's pinned or learned emoji policy constrains the output. A prompt
+    /// template stored for the mode is applied later, by `adapt` and `trace`.
+    pub fn plan(&self, raw_text: &str, contact: &ContactInput) -> CompletionRequest {
+        let detail = self
+            .classifier
+            .classify_with_context(contact, &ClassificationContext::now());
+        let category = detail.category;
+        let pinned = self
+            .storage
+            .as_ref()
+            .and_then(|storage| storage.get_contact_mode(&contact.name).ok().flatten());
+        let script = detect_script(raw_text);
+        let mode = pinned.unwrap_or_else(|| {
+            let mode = detail.suggested_writing_mode();
+            script.map_or(mode, |script| script.adjust_mode(mode))
+        });
+        debug!(
+            "Contact '{}' classified as {:?}, using mode {:?}",
+            contact.name, category, mode
+        );
+
+        let mut request = CompletionRequest::new(raw_text)
+            .with_mode(mode)
+            .with_temperature(mode.suggested_temperature());
+        if self.first_person {
+            request = request.with_first_person();
+        }
+        if let Some(script) = script.filter(|&script| script != Script::Latin) {
+            request = request.with_script(script);
+        }
+        if let Some(prefix) = &self.system_prefix {
+            request = request.with_system_prefix(prefix.clone());
+        }
+        if let Some(suffix) = &self.system_suffix {
+            request = request.with_system_suffix(suffix.clone());
+        }
+        if let Some(length) = LengthHint::for_recipient(mode, Some(category)) {
+            request = request.with_target_length(length);
+        }
+        if contact.name.is_empty() {
+            return request;
+        }
+        let emoji_policy = self.storage.as_ref().and_then(|storage| {
+            storage
+                .get_emoji_usage(&contact.name)
+                .ok()
+                .and_then(|usage| usage.policy())
+        });
+        if let Some(policy) = emoji_policy {
+            request = request.with_constraints(policy.constraints());
+        }
+        request.with_contact(contact.name.clone(), category)
+    }
+
+    /// Classify the contact, pick its writing mode, and format `raw_text` for them
+    pub async fn adapt(&self, raw_text: &str, contact: ContactInput) -> Result<String> {
+        Ok(self.adapt_detailed(raw_text, contact).await?.text)
+    }
+
+    /// Like `adapt`, but also report the category, mode, and token usage
+    pub async fn adapt_detailed(
+        &self,
+        raw_text: &str,
+        contact: ContactInput,
+    ) -> Result<Adaptation> {
+        let raw_text = remove_fillers(raw_text, self.filler_level);
+        let (adaptation, model) = self.draft(&raw_text, &contact).await?;
+        if !contact.name.is_empty() {
+            self.record_dictation(&contact.name, adaptation.category);
+            if self.log_classifications
+                && let Some(storage) = &self.storage
+            {
+                let decision = ClassificationDecision::new(
+                    contact.name.clone(),
+                    self.classifier
+                        .classify_with_context(&contact, &ClassificationContext::now()),
+                );
+                if let Err(e) = storage.log_classification(&decision) {
+                    warn!("Failed to log classification: {}", e);
+                }
+            }
+            if let Some(storage) = &self.storage
+                && let Err(e) = storage.record_emoji_usage(&contact.name, &raw_text)
+            {
+                warn!("Failed to record emoji usage: {}", e);
+            }
+        }
+
+        if let (Some(storage), Some(usage)) = (&self.storage, &adaptation.usage) {
+            let model = model
+                .as_deref()
+                .unwrap_or_else(|| self.completion.model_name());
+            if let Err(e) = storage.record_usage(self.completion.provider_name(), model, usage) {
+                warn!("Failed to record completion usage: {}", e);
+            }
+        }
+
+        Ok(adaptation)
+    }
+
+    /// Plan, complete, and lint `raw_text` (fillers already removed) without recording
+    /// anything; also returns the model the provider reported
+    async fn draft(
+        &self,
+        raw_text: &str,
+        contact: &ContactInput,
+    ) -> Result<(Adaptation, Option<String>)> {
+        if raw_text.trim().is_empty() {
+            return Err(Error::EmptyTranscription);
+        }
+
+        let request = self.apply_prompt_template(self.plan(raw_text, contact))?;
+        let mode = request.mode;
+        let category = request
+            .contact
+            .as_ref()
+            .map_or_else(|| self.classifier.classify(contact), |c| c.category);
+        let response = self.complete(&request).await?;
+
+        let lint = self.linter.lint(&response.text, category);
+        if !lint.warnings.is_empty() {
+            warn!(
+                "Profanity in output for {:?} contact: {} word(s)",
+                category,
+                lint.warnings.len()
+            );
+        }
+
+        let adaptation = Adaptation {
+            text: lint.text,
+            category,
+            mode,
+            usage: response.usage,
+            warnings: lint.warnings,
+        };
+        Ok((adaptation, response.model))
+    }
+
+    /// Replay `clip` through `transcription` and the adaptation path, recording each stage
+    ///
+    /// Nothing is persisted: contact frequency and token usage are left alone. A failed
+    /// transcription is returned as the error; a failed completion is kept in the trace
+    /// so the stages before it can still be inspected.
+    pub async fn trace(
+        &self,
+        transcription: &dyn TranscriptionProvider,
+        clip: AudioClip,
+        contact: ContactInput,
+    ) -> Result<PipelineTrace> {
+        let transcript = transcription
+            .transcribe(TranscriptionRequest::from_clip(clip))
+            .await?
+            .text;
+        let cleaned = remove_fillers(&transcript, self.filler_level);
+        let classification = self
+            .classifier
+            .classify_with_context(&contact, &ClassificationContext::now());
+        let request = self.apply_prompt_template(self.plan(&cleaned, &contact))?;
+        let prompt = request.resolve_system_prompt(|| request.style_instruction());
+
+        let mut trace = PipelineTrace {
+            transcript,
+            cleaned,
+            contact: contact.name.clone(),
+            relationship: contact.relationship.clone(),
+            classification,
+            mode: request.mode,
+            prompt,
+            output: None,
+            usage: None,
+            warnings: Vec::new(),
+            error: None,
+        };
+        if trace.cleaned.trim().is_empty() {
+            trace.error = Some(Error::EmptyTranscription.to_string());
+            return Ok(trace.redacted());
+        }
+
+        match self.complete(&request).await {
+            Ok(response) => {
+                let lint = self
+                    .linter
+                    .lint(&response.text, trace.classification.category);
+                trace.output = Some(lint.text);
+                trace.usage = response.usage;
+                trace.warnings = lint.warnings;
+            }
+            Err(e) => trace.error = Some(e.to_string()),
+        }
+        Ok(trace.redacted())
+    }
+
+    /// Swap in the prompt template stored for the request's mode, if there is one
+    fn apply_prompt_template(&self, request: CompletionRequest) -> Result<CompletionRequest> {
+        let Some(storage) = &self.storage else {
+            return Ok(request);
+        };
+        match storage.get_prompt_template(request.mode)? {
+            Some(template) => request.with_prompt_template(&template),
+            None => Ok(request),
+        }
+    }
+
+    /// Run `request`, continuing replies cut off by the token limit
+    async fn complete(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        if !self.completion.is_configured() {
+            return Err(Error::ProviderNotConfigured(format!(
+                "{} is not configured",
+                self.completion.name()
+            )));
+        }
+
+        let mut response = self.completion.complete(request.clone()).await?;
+        for _ in 0..self.max_continuations {
+            if !response.is_truncated() {
+                break;
+            }
+            debug!("Completion hit the token limit, continuing");
+            response = self
+                .completion
+                .continue_completion(&response, request)
+                .await?;
+        }
+        // tell the user the model refused instead of inserting nothing
+        if response.finish_reason == FinishReason::ContentFilter && response.text.trim().is_empty()
+        {
+            return Err(Error::ContentBlocked {
+                reason: "content_filter".to_string(),
+                category: None,
+            });
+        }
+        Ok(response)
+    }
+
+    /// Format `raw_text` for each of `contacts`, e.g. to preview one message across recipients
+    ///
+    /// Runs up to `max_in_flight` completions concurrently and returns the adaptations in
+    /// the order of `contacts`. The first failure fails the whole batch. Previews aren't
+    /// dictations: contact frequency, emoji usage, token usage and the classification
+    /// log are left alone.
+    pub async fn adapt_many(
+        &self,
+        raw_text: &str,
+        contacts: &[ContactInput],
+    ) -> Result<Vec<Adaptation>> {
+        let raw_text = remove_fillers(raw_text, self.filler_level);
+        futures::stream::iter(contacts)
+            .map(|contact| async {
+                let (adaptation, _) = self.draft(&raw_text, contact).await?;
+                Ok::<_, Error>(adaptation)
+            })
+            .buffered(self.max_in_flight.max(1))
+            .try_collect()
+            .await
+    }
+
+    /// Count a completed dictation to `name`, once per debounce window
+    fn record_dictation(&self, name: &str, category: ContactCategory) {
+        let key = normalize_name(name);
+        {
+            let mut last = self.last_recorded.lock();
+            if let Some((last_key, at)) = last.as_ref()
+                && *last_key == key
+                && at.elapsed() < self.frequency_debounce
+            {
+                debug!("Skipping repeat interaction with '{}'", name);
+                return;
+            }
+            *last = Some((key, Instant::now()));
+        }
+
+        self.classifier.record_interaction(name);
+        if let Some(storage) = &self.storage
+            && let Err(e) = storage.increment_frequency(name, category)
+        {
+            warn!("Failed to record contact frequency: {}", e);
+        }
+    }
+}
+
+/// Formatted text plus the decisions that produced it
+#[derive(Debug, Clone)]
+pub struct Adaptation {
+    pub text: String,
+    pub category: ContactCategory,
+    pub mode: WritingMode,
+    pub usage: Option<TokenUsage>,
+    /// Profanity the linter found (and possibly softened) in `text`
+    pub warnings: Vec<LintWarning>,
+}
+
+/// Every intermediate of one `trace` run, for debugging a bad adaptation
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineTrace {
+    /// Text the transcription provider returned
+    pub transcript: String,
+    /// Transcript after filler removal, as sent for formatting
+    pub cleaned: String,
+    pub contact: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relationship: Option<String>,
+    /// Category, the rule that picked it, and its confidence
+    pub classification: ClassificationDetail,
+    pub mode: WritingMode,
+    /// System prompt framing the formatting instruction; providers wrap it in their own
+    pub prompt: String,
+    pub output: Option<String>,
+    pub usage: Option<TokenUsage>,
+    pub warnings: Vec<LintWarning>,
+    /// Why formatting failed, if it did
+    pub error: Option<String>,
+}
+
+impl PipelineTrace {
+    /// Pretty-printed JSON for bug reports
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Mask anything that looks like an API key, e.g. pasted into a system prefix
+    fn redacted(mut self) -> Self {
+        let redactor = Redactor::api_keys();
+        let mask = |text: &mut String| *text = redactor.redact(text).text;
+        mask(&mut self.transcript);
+        mask(&mut self.cleaned);
+        mask(&mut self.prompt);
+        if let Some(output) = self.output.as_mut() {
+            mask(output);
+        }
+        if let Some(error) = self.error.as_mut() {
+            mask(error);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contacts::WorkHours;
+    use crate::prompt_template::PromptTemplate;
+    use crate::providers::{FIRST_PERSON_INSTRUCTION, TranscriptFormat, TranscriptionResponse};
+    use async_trait::async_trait;
+
+    /// Transcribes every clip as the same text
+    struct FixedTranscription(&'static str);
+
+    #[async_trait]
+    impl TranscriptionProvider for FixedTranscription {
+        fn name(&self) -> &'static str {
+            "Fixed"
+        }
+
+        fn model_name(&self) -> &str {
+            "fixed"
+        }
+
+        async fn transcribe(
+            &self,
+            _request: TranscriptionRequest,
+        ) -> Result<TranscriptionResponse> {
+            Ok(TranscriptionResponse {
+                text: self.0.to_string(),
+                confidence: None,
+                language: None,
+                duration_ms: 10,
+                segments: None,
+                completed_text: None,
+                format: TranscriptFormat::Plain,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    /// Echoes the mode and recipient it was asked to format for
+    struct EchoProvider;
+
+    #[async_trait]
+    impl CompletionProvider for EchoProvider {
+        fn name(&self) -> &'static str {
+            "Echo"
+        }
+
+        fn model_name(&self) -> &str {
+            "echo"
+        }
+
+        async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+            let contact = request.contact.map(|c| c.name).unwrap_or_default();
+            Ok(CompletionResponse {
+                text: format!("{:?}|{}|{}", request.mode, contact, request.text),
+                usage: None,
+                model: None,
+                finish_reason: FinishReason::Stop,
+                prompt_truncated: false,
+                estimated_cost_usd: None,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    /// Rewrites "I" into "They" unless told the text is first person, like a model
+    /// that mistakes the dictation for something to report on
+    struct PerspectiveFlipper;
+
+    #[async_trait]
+    impl CompletionProvider for PerspectiveFlipper {
+        fn name(&self) -> &'static str {
+            "Flipper"
+        }
+
+        fn model_name(&self) -> &str {
+            "flip"
+        }
+
+        async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+            let text = if request
+                .style_instruction()
+                .contains(FIRST_PERSON_INSTRUCTION)
+            {
+                request.text.replace("gonna", "going to")
+            } else {
+                request
+                    .text
+                    .replace("I'm gonna", "They will")
+                    .replace("I ", "They ")
+            };
+            Ok(CompletionResponse {
+                text,
+                usage: None,
+                model: None,
+                finish_reason: FinishReason::Stop,
+                prompt_truncated: false,
+                estimated_cost_usd: None,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    /// Stops at the token limit on the first call, finishes on the continuation
+    struct CutOffProvider;
+
+    #[async_trait]
+    impl CompletionProvider for CutOffProvider {
+        fn name(&self) -> &'static str {
+            "CutOff"
+        }
+
+        fn model_name(&self) -> &str {
+            "cutoff"
+        }
+
+        async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+            let (text, finish_reason) = match request.messages.last() {
+                None => ("Running late, will be", FinishReason::Length),
+                Some(previous) => {
+                    assert_eq!(previous.role, crate::providers::ChatRole::Assistant);
+                    assert_eq!(previous.content, "Running late, will be");
+                    ("there in 10.", FinishReason::Stop)
+                }
+            };
+            Ok(CompletionResponse {
+                text: text.to_string(),
+                usage: Some(TokenUsage {
+                    prompt_tokens: 10,
+                    completion_tokens: 5,
+                    total_tokens: 15,
+                }),
+                model: None,
+                finish_reason,
+                prompt_truncated: false,
+                estimated_cost_usd: None,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    fn contact(name: &str) -> ContactInput {
+        ContactInput {
+            name: name.to_string(),
+            organization: String::new(),
+            relationship: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_uses_contact_mode() {
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider));
+
+        let request = pipeline.plan("running late", &contact("Mom"));
+        assert_eq!(request.mode, WritingMode::Casual);
+        assert_eq!(
+            request.contact.unwrap().category,
+            ContactCategory::CloseFamily
+        );
+
+        let request = pipeline.plan("running late", &contact("Dr. Smith"));
+        assert_eq!(request.mode, WritingMode::Formal);
+        assert_eq!(request.target_length, None);
+    }
+
+    #[test]
+    fn test_plan_temperature_follows_mode() {
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider));
+        let request = pipeline.plan("running late", &contact("Dr. Smith"));
+        assert_eq!(request.temperature, Some(0.2));
+        let request = pipeline.plan("running late", &contact("babe ❤️"));
+        assert_eq!(request.mode, WritingMode::Excited);
+        assert_eq!(request.temperature, Some(0.8));
+
+        assert_eq!(ContactCategory::Partner.suggested_temperature(), 0.8);
+        assert_eq!(ContactCategory::Professional.suggested_temperature(), 0.2);
+        assert!(
+            WritingMode::all()
+                .windows(2)
+                .all(|pair| pair[0].suggested_temperature() < pair[1].suggested_temperature())
+        );
+    }
+
+    #[test]
+    fn test_plan_keeps_texts_short() {
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider));
+
+        let request = pipeline.plan("be there in 5", &contact("Jake 🍺"));
+        assert_eq!(request.target_length, Some(LengthHint::Sms));
+        assert_eq!(
+            request.effective_max_tokens(),
+            Some(LengthHint::Sms.max_tokens())
+        );
+        assert!(
+            request
+                .style_instruction()
+                .ends_with(LengthHint::Sms.instruction())
+        );
+
+        // an explicit cap wins over the length's
+        let request = request.with_max_tokens(20);
+        assert_eq!(request.effective_max_tokens(), Some(20));
+    }
+
+    #[test]
+    fn test_plan_keeps_non_latin_language() {
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider));
+
+        let request = pipeline.plan("буду через 5 минут", &contact("Jake 🍺"));
+        assert_eq!(request.script, Some(Script::Cyrillic));
+        assert_eq!(request.mode, WritingMode::Casual);
+        assert!(
+            request
+                .style_instruction()
+                .contains("written in Cyrillic script")
+        );
+
+        let request = pipeline.plan("be there in 5", &contact("Jake 🍺"));
+        assert_eq!(request.script, None);
+        assert_eq!(request.mode, WritingMode::VeryCasual);
+
+        // a mode the user pinned is kept as-is
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        storage
+            .set_contact_mode("Jake 🍺", WritingMode::VeryCasual)
+            .unwrap();
+        let pipeline = pipeline.with_storage(storage);
+        let request = pipeline.plan("буду через 5 минут", &contact("Jake 🍺"));
+        assert_eq!(request.mode, WritingMode::VeryCasual);
+        assert_eq!(request.script, Some(Script::Cyrillic));
+    }
+
+    #[test]
+    fn test_formal_rewrite_keeps_first_person() {
+        let pipeline = AdaptivePipeline::new(Arc::new(PerspectiveFlipper));
+        let raw = "I'm gonna be late, I will call when I land";
+
+        let request = pipeline.plan(raw, &contact("Dr. Smith"));
+        assert_eq!(request.mode, WritingMode::Formal);
+        assert!(request.first_person);
+
+        let output =
+            futures::executor::block_on(pipeline.adapt(raw, contact("Dr. Smith"))).unwrap();
+        assert!(output.split_whitespace().any(|word| word == "I"));
+        assert!(!output.contains("They"));
+
+        // opting out leaves the perspective to the model
+        let pipeline = pipeline.with_first_person(false);
+        let output =
+            futures::executor::block_on(pipeline.adapt(raw, contact("Dr. Smith"))).unwrap();
+        assert!(output.starts_with("They"));
+    }
+
+    #[test]
+    fn test_continues_truncated_reply() {
+        let pipeline = AdaptivePipeline::new(Arc::new(CutOffProvider));
+        let adaptation =
+            futures::executor::block_on(pipeline.adapt_detailed("running late", contact("Mom")))
+                .unwrap();
+        assert_eq!(adaptation.text, "Running late, will be there in 10.");
+        let usage = adaptation.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 20);
+        assert_eq!(usage.total_tokens, 30);
+
+        // continuation off: the cut-off text comes back as-is
+        let pipeline = pipeline.with_max_continuations(0);
+        let output =
+            futures::executor::block_on(pipeline.adapt("running late", contact("Mom"))).unwrap();
+        assert_eq!(output, "Running late, will be");
+    }
+
+    #[test]
+    fn test_plan_prefers_pinned_mode() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        storage
+            .set_contact_mode("Brother", WritingMode::VeryCasual)
+            .unwrap();
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider)).with_storage(storage);
+
+        let request = pipeline.plan("running late", &contact("Brother"));
+        assert_eq!(request.mode, WritingMode::VeryCasual);
+        assert_eq!(
+            request.contact.unwrap().category,
+            ContactCategory::CloseFamily
+        );
+
+        // unpinned contacts still use their category default
+        let request = pipeline.plan("running late", &contact("Mom"));
+        assert_eq!(request.mode, WritingMode::Casual);
+    }
+
+    #[test]
+    fn test_plan_relaxes_work_contacts_after_hours() {
+        // a window starting an hour from now never contains the current time
+        let now = chrono::Local::now().time();
+        let hours = WorkHours::new(
+            now + chrono::TimeDelta::hours(1),
+            now + chrono::TimeDelta::hours(2),
+        );
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider))
+            .with_classifier(ContactClassifier::new().with_work_hours(hours));
+
+        let request = pipeline.plan("running late", &contact("Dr. Smith"));
+        assert_eq!(request.mode, WritingMode::Casual);
+        assert_eq!(
+            request.contact.unwrap().category,
+            ContactCategory::Professional
+        );
+
+        // a trace explains the relaxed mode
+        let trace = futures::executor::block_on(pipeline.trace(
+            &FixedTranscription("running late"),
+            AudioClip::mono(vec![0; 3200], 16000),
+            contact("Dr. Smith"),
+        ))
+        .unwrap();
+        assert_eq!(trace.mode, WritingMode::Casual);
+        assert!(trace.classification.after_hours);
+        assert!(trace.classification.reason.contains("after hours"));
+    }
+
+    #[test]
+    fn test_adapt_runs_provider() {
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider));
+
+        let output =
+            futures::executor::block_on(pipeline.adapt("running late", contact("Mom"))).unwrap();
+        assert_eq!(output, "Casual|Mom|running late");
+
+        let err = futures::executor::block_on(pipeline.adapt("  ", contact("Mom"))).unwrap_err();
+        assert!(matches!(err, Error::EmptyTranscription));
+
+        let pipeline = pipeline.with_filler_level(FillerLevel::Aggressive);
+        let output =
+            futures::executor::block_on(pipeline.adapt("um, running late", contact("Mom")))
+                .unwrap();
+        assert_eq!(output, "Casual|Mom|Running late");
+        let err = futures::executor::block_on(pipeline.adapt("uh", contact("Mom"))).unwrap_err();
+        assert!(matches!(err, Error::EmptyTranscription));
+    }
+
+    #[test]
+    fn test_adapt_many_keeps_contact_order() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider))
+            .with_max_in_flight(2)
+            .with_storage(storage.clone())
+            .with_classification_logging(true);
+        let contacts = [contact("Mom"), contact("Dr. Smith"), contact("Sam 🔥")];
+
+        let adaptations =
+            futures::executor::block_on(pipeline.adapt_many("running late", &contacts)).unwrap();
+        let grid: Vec<_> = adaptations
+            .iter()
+            .map(|a| (a.category, a.mode, a.text.as_str()))
+            .collect();
+        assert_eq!(
+            grid,
+            vec![
+                (
+                    ContactCategory::CloseFamily,
+                    WritingMode::Casual,
+                    "Casual|Mom|running late"
+                ),
+                (
+                    ContactCategory::Professional,
+                    WritingMode::Formal,
+                    "Formal|Dr. Smith|running late"
+                ),
+                (
+                    ContactCategory::CasualPeer,
+                    WritingMode::VeryCasual,
+                    "VeryCasual|Sam 🔥|running late"
+                ),
+            ]
+        );
+
+        // previews record nothing
+        assert!(storage.get_contact_by_name("Mom").unwrap().is_none());
+        assert!(storage.get_classification_log(10).unwrap().is_empty());
+
+        let err = futures::executor::block_on(pipeline.adapt_many(" ", &contacts)).unwrap_err();
+        assert!(matches!(err, Error::EmptyTranscription));
+    }
+
+    #[test]
+    fn test_trace_records_each_stage() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider))
+            .with_storage(storage.clone())
+            .with_filler_level(FillerLevel::Aggressive)
+            .with_system_affixes(Some("Proxy key sk-abcdefghijklmnop1234".to_string()), None);
+        let clip = AudioClip::mono(vec![0; 3200], 16000);
+
+        let trace = futures::executor::block_on(pipeline.trace(
+            &FixedTranscription("um, running late"),
+            clip.clone(),
+            contact("Mom"),
+        ))
+        .unwrap();
+        assert_eq!(trace.transcript, "um, running late");
+        assert_eq!(trace.cleaned, "Running late");
+        assert_eq!(trace.classification.category, ContactCategory::CloseFamily);
+        assert_eq!(trace.mode, WritingMode::Casual);
+        assert!(
+            trace
+                .prompt
+                .starts_with("Proxy key [REDACTED_API_KEY_1]\n\n")
+        );
+        assert!(trace.prompt.contains(FIRST_PERSON_INSTRUCTION));
+        assert_eq!(trace.output.as_deref(), Some("Casual|Mom|Running late"));
+        assert_eq!(trace.error, None);
+
+        // a replay is not a dictation
+        assert!(storage.get_contact_by_name("Mom").unwrap().is_none());
+
+        let json = trace.to_json().unwrap();
+        assert!(json.contains("\"mode\": \"casual\""));
+        assert!(!json.contains("sk-abcdefghijklmnop1234"));
+
+        // a transcript that's all filler stops before the provider
+        let trace = futures::executor::block_on(pipeline.trace(
+            &FixedTranscription("uh"),
+            clip,
+            contact("Mom"),
+        ))
+        .unwrap();
+        assert_eq!(trace.output, None);
+        assert_eq!(trace.error, Some(Error::EmptyTranscription.to_string()));
+    }
+
+    #[test]
+    fn test_linter_softens_formal_output() {
+        use crate::linter::LintAction;
+
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider))
+            .with_linter(OutputLinter::new(LintAction::Soften));
+        let adaptation = futures::executor::block_on(
+            pipeline.adapt_detailed("report is damn late", contact("Dr. Smith")),
+        )
+        .unwrap();
+        assert_eq!(adaptation.text, "Formal|Dr. Smith|report is darn late");
+        assert_eq!(adaptation.warnings.len(), 1);
+
+        let adaptation = futures::executor::block_on(
+            pipeline.adapt_detailed("report is damn late", contact("Mom")),
+        )
+        .unwrap();
+        assert_eq!(adaptation.text, "Casual|Mom|report is damn late");
+        assert!(adaptation.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_repeat_dictations_count_once() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider)).with_storage(storage.clone());
+        let frequency = |name: &str| {
+            storage
+                .get_contact_by_name(name)
+                .unwrap()
+                .map_or(0, |c| c.frequency)
+        };
+
+        // planning alone never counts
+        pipeline.plan("running late", &contact("Mom"));
+        assert_eq!(frequency("Mom"), 0);
+
+        for _ in 0..3 {
+            futures::executor::block_on(pipeline.adapt("running late", contact("Mom"))).unwrap();
+        }
+        assert_eq!(frequency("Mom"), 1);
+
+        futures::executor::block_on(pipeline.adapt("on my way", contact("Jake"))).unwrap();
+        assert_eq!(frequency("Jake"), 1);
+
+        let pipeline = pipeline.with_frequency_debounce(Duration::ZERO);
+        futures::executor::block_on(pipeline.adapt("running late", contact("Mom"))).unwrap();
+        futures::executor::block_on(pipeline.adapt("running late", contact("Mom"))).unwrap();
+        assert_eq!(frequency("Mom"), 3);
+    }
+
+    #[test]
+    fn test_adapt_uses_prompt_template() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let template =
+            PromptTemplate::parse("Rewrite for {contact_name}, a {category} contact, {length}.")
+                .unwrap();
+        storage
+            .set_prompt_template(WritingMode::Formal, &template)
+            .unwrap();
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider)).with_storage(storage.clone());
+
+        let request = pipeline
+            .apply_prompt_template(pipeline.plan("running late", &contact("Dr. Smith")))
+            .unwrap();
+        assert!(
+            request
+                .style_instruction()
+                .starts_with("Rewrite for Dr. Smith, a professional contact, ")
+        );
+
+        // without a name or length hint the template still renders, with neutral wording
+        let request = pipeline
+            .apply_prompt_template(pipeline.plan("running late", &contact("")))
+            .unwrap();
+        assert!(
+            request
+                .style_instruction()
+                .starts_with("Rewrite for the recipient, a ")
+        );
+        assert!(
+            request
+                .style_instruction()
+                .contains("contact, as long as needed.")
+        );
+        futures::executor::block_on(pipeline.adapt("running late", contact(""))).unwrap();
+    }
+
+    #[test]
+    fn test_classification_logging_is_opt_in() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider)).with_storage(storage.clone());
+        futures::executor::block_on(pipeline.adapt("running late", contact("Mom"))).unwrap();
+        assert!(storage.get_classification_log(10).unwrap().is_empty());
+
+        let pipeline = pipeline.with_classification_logging(true);
+        futures::executor::block_on(pipeline.adapt("running late", contact("Mom"))).unwrap();
+        let log = storage.get_classification_log(10).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].contact_name, "Mom");
+        assert_eq!(log[0].detail.category, ContactCategory::CloseFamily);
+    }
+
+    #[test]
+    fn test_plan_uses_emoji_policy() {
+        let storage = Arc::new(Storage::in_memory().unwrap());
+        let pipeline = AdaptivePipeline::new(Arc::new(EchoProvider)).with_storage(storage.clone());
+        assert_eq!(pipeline.plan("love you", &contact("Sam")).constraints, None);
+
+        for _ in 0..crate::storage::MIN_EMOJI_SAMPLES {
+            futures::executor::block_on(pipeline.adapt("love you ❤️", contact("Sam"))).unwrap();
+        }
+        let constraints = pipeline
+            .plan("love you", &contact("Sam"))
+            .constraints
+            .unwrap();
+        assert!(constraints.preserve_emoji && constraints.add_emoji);
+
+        storage
+            .set_emoji_policy("Sam", crate::types::EmojiPolicy::Avoid)
+            .unwrap();
+        let constraints = pipeline
+            .plan("love you", &contact("Sam"))
+            .constraints
+            .unwrap();
+        assert_eq!(constraints.max_emoji, 0);
+    }
+}
@@ -0,0 +1,189 @@
+//! `{variable}` templates for writing-mode prompts
+//!
+//! A [`PromptTemplate`] stored for a mode (see [`Storage::set_prompt_template`])
+//! replaces that mode's built-in instruction, so prompts can be tuned from
+//! settings. Templates are checked when parsed: only the variables in
+//! [`TEMPLATE_VARIABLES`] are allowed, and `{{` / `}}` stand for literal braces.
+//! Every request has a value for every variable, and storing a template checks
+//! it renders for the least a request in that mode can carry, so a literal
+//! `{contact_name}` is never sent to the model.
+//!
+//! [`Storage::set_prompt_template`]: crate::Storage::set_prompt_template
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+
+/// Variables a template may use
+/// Without a recipient `contact_name` is "the recipient" and `category` "general";
+/// without a target `length` is "as long as needed"; Latin text gets a neutral `language`.
+pub const TEMPLATE_VARIABLES: &[&str] = &["category", "contact_name", "language", "length", "mode"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Variable(String),
+}
+
+/// A prompt with `{variable}` placeholders, parsed and checked up front
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl PromptTemplate {
+    /// Parse `source`, failing on unbalanced braces or unknown variables
+    pub fn parse(source: impl Into<String>) -> Result<Self> {
+        let source = source.into();
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => {
+                                return Err(Error::Config(format!(
+                                    "Prompt template has an unclosed \"{{{}\" (write {{{{ for a literal brace)",
+                                    name
+                                )));
+                            }
+                            Some(c) => name.push(c),
+                        }
+                    }
+                    let name = name.trim().to_string();
+                    if !TEMPLATE_VARIABLES.contains(&name.as_str()) {
+                        return Err(Error::Config(format!(
+                            "Prompt template uses unknown variable {{{}}} (expected one of: {})",
+                            name,
+                            TEMPLATE_VARIABLES.join(", ")
+                        )));
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Variable(name));
+                }
+                '}' => {
+                    return Err(Error::Config(
+                        "Prompt template has a \"}\" with no opening brace (write }} for a literal brace)"
+                            .to_string(),
+                    ));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self { source, parts })
+    }
+
+    /// The template as written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Variables the template uses, in order of first use
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for part in &self.parts {
+            if let Part::Variable(name) = part
+                && !names.contains(&name.as_str())
+            {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// Fill in every variable from `values`
+    /// Fails naming the first variable `values` has no entry for.
+    pub fn render(&self, values: &BTreeMap<&str, String>) -> Result<String> {
+        let mut out = String::with_capacity(self.source.len());
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Variable(name) => {
+                    let value = values.get(name.as_str()).ok_or_else(|| {
+                        Error::Config(format!(
+                            "Prompt template uses {{{}}}, but this message has no {}",
+                            name,
+                            name.replace('_', " ")
+                        ))
+                    })?;
+                    out.push_str(value);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl FromStr for PromptTemplate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for PromptTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let template = PromptTemplate::parse(
+            "Rewrite for {contact_name}, a {category} contact, keeping it {length}. {{literal}}",
+        )
+        .unwrap();
+        assert_eq!(
+            template.variables(),
+            vec!["contact_name", "category", "length"]
+        );
+
+        let mut values = BTreeMap::new();
+        values.insert("contact_name", "Dr. Smith".to_string());
+        values.insert("category", "professional".to_string());
+        values.insert("length", "short".to_string());
+        assert_eq!(
+            template.render(&values).unwrap(),
+            "Rewrite for Dr. Smith, a professional contact, keeping it short. {literal}"
+        );
+
+        values.remove("length");
+        let err = template.render(&values).unwrap_err().to_string();
+        assert!(err.contains("{length}"), "{}", err);
+
+        assert!(PromptTemplate::parse("Hi {contact}").is_err());
+        assert!(PromptTemplate::parse("Hi {contact_name").is_err());
+        assert!(PromptTemplate::parse("Hi }").is_err());
+        assert_eq!(
+            PromptTemplate::parse("").unwrap().render(&values).unwrap(),
+            ""
+        );
+    }
+}
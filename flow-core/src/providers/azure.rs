@@ -0,0 +1,217 @@
+//! Azure OpenAI provider for completion and transcription
+//!
+//! Azure serves the OpenAI API from `{endpoint}/openai/deployments/{deployment}/...`
+//! with an `api-version` query parameter, and takes the key in an `api-key` header
+//! instead of a bearer token. Requests, responses, and errors are otherwise the
+//! same, so they're handled by the OpenAI providers. Each deployment runs one
+//! model: use one provider for the chat deployment and one for the Whisper one.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::error::Result;
+
+use super::http::HttpClient;
+use super::openai::AzureDeployment;
+use super::{
+    CompletionProvider, CompletionRequest, CompletionResponse, OpenAICompletionProvider,
+    OpenAITranscriptionProvider, Pricing, TranscriptionProvider, TranscriptionRequest,
+    TranscriptionResponse,
+};
+
+/// API version sent when none is chosen
+pub const AZURE_OPENAI_API_VERSION: &str = "2024-10-21";
+
+/// Azure OpenAI deployment, usable for completion or transcription
+pub struct AzureOpenAIProvider {
+    azure: AzureDeployment,
+    api_key: Option<String>,
+    completion: OpenAICompletionProvider,
+    transcription: OpenAITranscriptionProvider,
+}
+
+impl AzureOpenAIProvider {
+    /// Create a provider for `deployment` on the resource at `endpoint`
+    /// (API key loaded from `AZURE_OPENAI_API_KEY` if not provided)
+    pub fn new(
+        endpoint: impl Into<String>,
+        deployment: impl Into<String>,
+        api_key: Option<String>,
+    ) -> Self {
+        let key = api_key.or_else(|| std::env::var("AZURE_OPENAI_API_KEY").ok());
+        let azure = AzureDeployment {
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            deployment: deployment.into(),
+            api_version: AZURE_OPENAI_API_VERSION.to_string(),
+        };
+
+        Self {
+            completion: OpenAICompletionProvider::new(None).with_azure(azure.clone(), key.clone()),
+            transcription: OpenAITranscriptionProvider::new(None)
+                .with_azure(azure.clone(), key.clone()),
+            azure,
+            api_key: key,
+        }
+    }
+
+    /// Set the `api-version` query parameter, e.g. "2025-01-01-preview"
+    pub fn with_api_version(mut self, api_version: impl Into<String>) -> Self {
+        self.azure.api_version = api_version.into();
+        let (azure, key) = (&self.azure, &self.api_key);
+        self.completion = self.completion.with_azure(azure.clone(), key.clone());
+        self.transcription = self.transcription.with_azure(azure.clone(), key.clone());
+        self
+    }
+
+    /// Name the model the deployment runs, e.g. "gpt-4o-transcribe"
+    /// Only matters where models differ, like which transcript formats they return.
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        let model = model.into();
+        self.completion = self.completion.with_model(model.clone());
+        self.transcription = self.transcription.with_model(model);
+        self
+    }
+
+    /// Price responses with `pricing` instead of the built-in table
+    pub fn with_pricing(mut self, pricing: Pricing) -> Self {
+        self.completion = self.completion.with_pricing(pricing);
+        self
+    }
+
+    /// Send requests through `http` instead of the network (e.g. a test double)
+    pub fn with_http_client(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.completion = self.completion.with_http_client(http.clone());
+        self.transcription = self.transcription.with_http_client(http);
+        self
+    }
+
+    /// Send `name: value` with every request, e.g. a gateway's subscription key
+    /// The provider's own `api-key` header is kept.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let (name, value) = (name.into(), value.into());
+        self.completion = self.completion.with_header(name.clone(), value.clone());
+        self.transcription = self.transcription.with_header(name, value);
+        self
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for AzureOpenAIProvider {
+    fn name(&self) -> &'static str {
+        "Azure OpenAI"
+    }
+
+    fn provider_name(&self) -> &str {
+        "azure"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.azure.deployment
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        self.completion.complete(request).await
+    }
+
+    fn is_configured(&self) -> bool {
+        self.completion.is_configured()
+    }
+}
+
+#[async_trait]
+impl TranscriptionProvider for AzureOpenAIProvider {
+    fn name(&self) -> &'static str {
+        "Azure OpenAI"
+    }
+
+    fn provider_name(&self) -> &str {
+        "azure"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.azure.deployment
+    }
+
+    async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+        self.transcription.transcribe(request).await
+    }
+
+    fn is_configured(&self) -> bool {
+        self.transcription.is_configured()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::http::testing::canned;
+
+    #[test]
+    fn test_azure_urls_and_auth() {
+        let http = canned(
+            200,
+            r#"{"model": "gpt-4o-mini", "choices": [{"message": {"content": "Hi."}, "finish_reason": "stop"}]}"#,
+        );
+        let provider = AzureOpenAIProvider::new(
+            "https://contoso.openai.azure.com/",
+            "chat",
+            Some("azure-key".to_string()),
+        )
+        .with_http_client(http.clone());
+        let response = futures::executor::block_on(CompletionProvider::complete(
+            &provider,
+            CompletionRequest::new("hi"),
+        ))
+        .unwrap();
+        assert_eq!(response.text, "Hi.");
+
+        let sent = http.sent.lock().unwrap();
+        assert_eq!(
+            sent[0].url().as_str(),
+            "https://contoso.openai.azure.com/openai/deployments/chat/chat/completions?api-version=2024-10-21"
+        );
+        assert_eq!(sent[0].headers()["api-key"], "azure-key");
+        assert!(sent[0].headers().get("Authorization").is_none());
+        drop(sent);
+
+        let http = canned(200, r#"{"text": "Hello there."}"#);
+        let provider = AzureOpenAIProvider::new(
+            "https://contoso.openai.azure.com",
+            "whisper",
+            Some("k".into()),
+        )
+        .with_api_version("2025-01-01-preview")
+        .with_http_client(http.clone());
+        let response = futures::executor::block_on(
+            provider.transcribe(TranscriptionRequest::new(vec![0u8; 3200], 16000)),
+        )
+        .unwrap();
+        assert_eq!(response.text, "Hello there.");
+        assert_eq!(
+            http.sent.lock().unwrap()[0].url().as_str(),
+            "https://contoso.openai.azure.com/openai/deployments/whisper/audio/transcriptions?api-version=2025-01-01-preview"
+        );
+        assert_eq!(TranscriptionProvider::provider_name(&provider), "azure");
+        assert_eq!(TranscriptionProvider::model_name(&provider), "whisper");
+
+        // errors map like OpenAI's
+        let provider = AzureOpenAIProvider::new(
+            "https://contoso.openai.azure.com",
+            "chat",
+            Some("bad".into()),
+        )
+        .with_http_client(canned(401, r#"{"error": {"code": "401"}}"#));
+        let err = futures::executor::block_on(CompletionProvider::complete(
+            &provider,
+            CompletionRequest::new("hi"),
+        ))
+        .unwrap_err();
+        assert_eq!(err.status(), Some(401));
+
+        // Azure always needs a key, even though the endpoint isn't api.openai.com
+        let mut provider =
+            AzureOpenAIProvider::new("https://contoso.openai.azure.com", "chat", None);
+        provider.completion = provider.completion.with_azure(provider.azure.clone(), None);
+        assert!(!CompletionProvider::is_configured(&provider));
+    }
+}
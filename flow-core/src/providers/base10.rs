@@ -6,20 +6,27 @@
 use async_trait::async_trait;
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
-use reqwest::Client;
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, error, instrument};
 
 use crate::error::{Error, Result};
 
-use super::{TranscriptionProvider, TranscriptionRequest, TranscriptionResponse};
+use super::http::{HttpClient, build_request, default_http_client, read_json};
+use super::transcription::trace_transcription;
+use super::{TranscriptFormat, TranscriptionProvider, TranscriptionRequest, TranscriptionResponse};
 
 const BASE10_PROXY_URL: &str = "https://base10-proxy.test-j.workers.dev";
 const BASE10_VALIDATE_URL: &str = "https://base10-proxy.test-j.workers.dev/validate-corrections";
 
+/// The worker runs Whisper, which only reads the last 224 prompt tokens (~800 chars)
+const WHISPER_PROMPT_MAX_CHARS: usize = 800;
+
 /// Base10 transcription provider (with integrated completion)
 pub struct Base10TranscriptionProvider {
-    client: Client,
+    http: Arc<dyn HttpClient>,
 }
 
 /// A correction pair to validate
@@ -50,48 +57,53 @@ struct ValidateCorrectionsResponse {
     results: Vec<CorrectionValidation>,
 }
 
-/// Validate corrections using AI via the Base10 worker
+/// Validate corrections using AI via the Base10 worker, sending through `http`
 pub async fn validate_corrections(
+    http: &dyn HttpClient,
     corrections: Vec<CorrectionPair>,
 ) -> Result<Vec<CorrectionValidation>> {
     if corrections.is_empty() {
         return Ok(vec![]);
     }
 
-    let client = Client::new();
-    let request = ValidateCorrectionsRequest { corrections };
+    let body = ValidateCorrectionsRequest { corrections };
 
     debug!(
         "Validating {} corrections via worker",
-        request.corrections.len()
+        body.corrections.len()
     );
 
-    let response = client
-        .post(BASE10_VALIDATE_URL)
-        .json(&request)
-        .send()
-        .await?;
+    let http_request = build_request(Method::POST, BASE10_VALIDATE_URL)
+        .json(&body)
+        .build()?;
+    let response = http.execute(http_request).await?;
 
     if !response.status().is_success() {
         let status = response.status();
         let error_text = response.text().await.unwrap_or_default();
         error!("Validation worker error: {} - {}", status, error_text);
-        return Err(Error::Transcription(format!(
-            "Validation error: {} - {}",
-            status, error_text
-        )));
+        return Err(Error::provider(
+            status,
+            format!("Validation error: {} - {}", status, error_text),
+        ));
     }
 
-    let validation_response: ValidateCorrectionsResponse = response.json().await?;
+    let validation_response: ValidateCorrectionsResponse = read_json(response, "Base10").await?;
     Ok(validation_response.results)
 }
 
 impl Base10TranscriptionProvider {
     pub fn new(_api_key: Option<String>) -> Self {
         Self {
-            client: Client::new(),
+            http: default_http_client(),
         }
     }
+
+    /// Send requests through `http` instead of the network (e.g. a test double)
+    pub fn with_http_client(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -114,6 +126,9 @@ struct AudioInput {
 #[derive(Debug, Serialize)]
 struct WhisperParams {
     audio_language: String,
+    /// Recognition hints (appended to "Hey Flow," by the worker)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -140,10 +155,22 @@ impl TranscriptionProvider for Base10TranscriptionProvider {
         "Auto (Cloud)"
     }
 
+    fn provider_name(&self) -> &str {
+        "base10"
+    }
+
+    /// The worker picks the completion model; transcription is always Whisper
+    fn model_name(&self) -> &str {
+        "whisper"
+    }
+
+    #[instrument(name = "transcription", skip_all, fields(provider = "base10"))]
     async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
-        let wav_data = pcm_to_wav(&request.audio, request.sample_rate, 1);
+        let started = Instant::now();
+        let wav_data = request.audio.to_wav();
         let audio_base64 = STANDARD.encode(&wav_data);
         let language = request.language.as_deref().unwrap_or("auto").to_string();
+        let prompt = request.whisper_prompt(WHISPER_PROMPT_MAX_CHARS);
 
         // Completion params are required
         let completion = request.completion.ok_or_else(|| {
@@ -157,6 +184,7 @@ impl TranscriptionProvider for Base10TranscriptionProvider {
                 },
                 whisper_params: WhisperParams {
                     audio_language: language,
+                    prompt,
                 },
             },
             completion: WorkerCompletionParams {
@@ -169,36 +197,36 @@ impl TranscriptionProvider for Base10TranscriptionProvider {
 
         debug!("Sending combined transcription+completion request to worker");
 
-        let response = self
-            .client
-            .post(BASE10_PROXY_URL)
+        let http_request = build_request(Method::POST, BASE10_PROXY_URL)
             .json(&worker_request)
-            .send()
-            .await?;
+            .build()?;
+        let response = self.http.execute(http_request).await?;
 
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Worker error: {} - {}", status, error_text);
-            return Err(Error::Transcription(format!(
-                "Worker error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::provider(
+                status,
+                format!("Worker error: {} - {}", status, error_text),
+            ));
         }
 
-        let worker_response: WorkerResponse = response.json().await?;
+        let worker_response: WorkerResponse = read_json(response, "Base10").await?;
 
-        let samples = request.audio.len() / 2;
-        let duration_ms = (samples as u64 * 1000) / request.sample_rate as u64;
+        let duration_ms = request.audio.duration_ms();
 
-        Ok(TranscriptionResponse {
+        let transcription = TranscriptionResponse {
             text: worker_response.transcription,
             confidence: None,
             language: worker_response.language,
             duration_ms,
             segments: None,
             completed_text: Some(worker_response.text),
-        })
+            format: TranscriptFormat::Plain,
+        };
+        trace_transcription(&transcription, started);
+        Ok(transcription)
     }
 
     fn is_configured(&self) -> bool {
@@ -206,47 +234,33 @@ impl TranscriptionProvider for Base10TranscriptionProvider {
     }
 }
 
-fn pcm_to_wav(pcm: &[u8], sample_rate: u32, channels: u16) -> Vec<u8> {
-    let bits_per_sample: u16 = 16;
-    let byte_rate = sample_rate * u32::from(channels) * u32::from(bits_per_sample) / 8;
-    let block_align = channels * bits_per_sample / 8;
-    let data_size = pcm.len() as u32;
-    let file_size = 36 + data_size;
-
-    let mut wav = Vec::with_capacity(44 + pcm.len());
-
-    wav.extend_from_slice(b"RIFF");
-    wav.extend_from_slice(&file_size.to_le_bytes());
-    wav.extend_from_slice(b"WAVE");
-    wav.extend_from_slice(b"fmt ");
-    wav.extend_from_slice(&16u32.to_le_bytes());
-    wav.extend_from_slice(&1u16.to_le_bytes());
-    wav.extend_from_slice(&channels.to_le_bytes());
-    wav.extend_from_slice(&sample_rate.to_le_bytes());
-    wav.extend_from_slice(&byte_rate.to_le_bytes());
-    wav.extend_from_slice(&block_align.to_le_bytes());
-    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
-    wav.extend_from_slice(b"data");
-    wav.extend_from_slice(&data_size.to_le_bytes());
-    wav.extend_from_slice(pcm);
-
-    wav
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::providers::http::testing::canned;
 
     #[test]
-    fn test_pcm_to_wav() {
-        let pcm = vec![0u8; 32000];
-        let wav = pcm_to_wav(&pcm, 16000, 1);
-
-        assert_eq!(&wav[0..4], b"RIFF");
-        assert_eq!(&wav[8..12], b"WAVE");
-        assert_eq!(&wav[12..16], b"fmt ");
-        assert_eq!(&wav[36..40], b"data");
-        assert_eq!(wav.len(), 44 + 32000);
+    fn test_validate_corrections_uses_given_client() {
+        let http = canned(
+            200,
+            r#"{"results": [{"original": "teh", "corrected": "the", "valid": true, "reason": null}]}"#,
+        );
+        let pairs = vec![CorrectionPair {
+            original: "teh".to_string(),
+            corrected: "the".to_string(),
+        }];
+
+        let results =
+            futures::executor::block_on(validate_corrections(http.as_ref(), pairs)).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].valid);
+        let sent = http.sent.lock().unwrap();
+        assert_eq!(sent[0].url().as_str(), BASE10_VALIDATE_URL);
+
+        // nothing to check never reaches the worker
+        let results = futures::executor::block_on(validate_corrections(http.as_ref(), Vec::new()));
+        assert!(results.unwrap().is_empty());
+        assert_eq!(sent.len(), 1);
     }
 
     #[test]
@@ -1,12 +1,69 @@
 //! Completion provider trait and types
 
+use std::collections::BTreeMap;
+use std::time::Instant;
+
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, trace, warn};
 
-use crate::error::Result;
+use crate::contacts::is_emoji_char;
+use crate::error::{Error, Result};
 use crate::modes::WritingMode;
+use crate::prompt_template::PromptTemplate;
+use crate::redaction::{Redacted, Redactor};
+use crate::script::Script;
+use crate::types::ContactCategory;
+
+/// Sampling temperature used when a request doesn't set one (low for consistent formatting)
+pub const DEFAULT_TEMPERATURE: f32 = 0.3;
+
+/// Joins turns for redaction; no redaction pattern can match across it
+const TURN_SEPARATOR: &str = "\u{0}";
+
+/// Appended to the style instruction for messages the user is sending themselves
+pub const FIRST_PERSON_INSTRUCTION: &str = "The text is the user's own message to the recipient, written by them in the first person. Keep every first-person statement (\"I\", \"me\", \"my\", \"we\") in the first person; never rewrite it as \"they\", \"he\", \"she\", or \"the user\".";
+
+/// Follow-up turn asking the model to pick up a reply cut off by the token limit
+const CONTINUE_INSTRUCTION: &str = "Your reply was cut off. Continue exactly where it stopped. Output only the remaining text, without repeating anything already written.";
+
+/// Reply budget assumed when fitting a request without `max_tokens` into a context window
+const REPLY_RESERVE_TOKENS: u32 = 1000;
+
+/// Estimates how many tokens a model's tokenizer produces for some text
+///
+/// Used to keep prompts inside a model's context window; plug in a real
+/// tokenizer where an exact count matters.
+pub trait TokenEstimator: Send + Sync {
+    fn estimate(&self, text: &str) -> usize;
+}
+
+/// About four characters per token, the usual rule of thumb for BPE tokenizers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CharTokenEstimator;
+
+impl TokenEstimator for CharTokenEstimator {
+    fn estimate(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+}
 
 /// Request for text completion/formatting
+///
+/// Built with [`CompletionRequest::new`] and the `with_*` methods. The system
+/// prompt sent to the provider is chosen in this order:
+///
+/// 1. An explicit prompt from [`with_system_prompt`](Self::with_system_prompt), used verbatim
+/// 2. Otherwise, one built from the mode's [`prompt_modifier`](WritingMode::prompt_modifier)
+///    (tailored to the recipient when [`with_contact`](Self::with_contact) is set)
+///
+/// Either way it is framed by the global [`with_system_prefix`](Self::with_system_prefix)
+/// and [`with_system_suffix`](Self::with_system_suffix), giving: prefix, then the mode
+/// (or explicit) prompt, then shortcut preservation, then suffix. See
+/// [`resolve_system_prompt`](Self::resolve_system_prompt).
+///
+/// The mode defaults to [`WritingMode::default`] when `with_mode` is not called.
 #[derive(Debug, Clone)]
 pub struct CompletionRequest {
     /// Raw transcribed text to process
@@ -15,31 +72,370 @@ pub struct CompletionRequest {
     pub mode: WritingMode,
     /// Optional system prompt override
     pub system_prompt: Option<String>,
+    /// Global instruction placed before the system prompt (e.g. "Match the user's language.")
+    pub system_prefix: Option<String>,
+    /// Global instruction placed after the system prompt (e.g. "Never use em-dashes.")
+    pub system_suffix: Option<String>,
     /// Context about the target application
     pub app_context: Option<String>,
-    /// Max tokens to generate
+    /// Max tokens to generate (overrides the cap implied by `target_length`)
     pub max_tokens: Option<u32>,
+    /// How long the output should be
+    pub target_length: Option<LengthHint>,
+    /// Limits on emoji and questions in the output
+    pub constraints: Option<Constraints>,
+    /// The text is the user speaking for themselves; keep it in the first person
+    pub first_person: bool,
+    /// Script the text is written in; anything but Latin asks for a reply in kind
+    pub script: Option<Script>,
+    /// Language the text is in, e.g. "en" from the transcription; a hint for routing
+    pub language: Option<String>,
+    /// Sampling temperature (provider default if unset)
+    pub temperature: Option<f32>,
+    /// Sampling seed for reproducible output; ignored by providers without seed support
+    pub seed: Option<u64>,
+    /// Mode instruction used in place of the built-in one, e.g. a rendered [`PromptTemplate`]
+    pub style_prompt: Option<String>,
     /// Instruction to preserve shortcut text word-for-word
     pub shortcut_preservation: Option<String>,
+    /// Who the text is addressed to, if known
+    pub contact: Option<RecipientContext>,
+    /// Masks sensitive values before the text leaves the machine (opt-in)
+    pub redaction: Option<Redactor>,
+    /// Earlier turns of the conversation, oldest first; `text` is the newest user turn
+    pub messages: Vec<ChatMessage>,
+    /// Set by `truncate_to` when content was dropped to fit the token budget
+    pub truncated: bool,
+}
+
+/// Author of a chat message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+/// One turn of a conversation, serialized in the OpenAI chat format
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::System,
+            content: content.into(),
+        }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+/// Rough output length, enforced with both a token cap and a prompt instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LengthHint {
+    /// A text message: a sentence or two
+    Sms,
+    /// A few sentences
+    Short,
+    /// A paragraph or two
+    Medium,
+    /// As long as the content needs
+    Long,
+}
+
+impl LengthHint {
+    /// Token cap for this length
+    pub fn max_tokens(self) -> u32 {
+        match self {
+            LengthHint::Sms => 80,
+            LengthHint::Short => 160,
+            LengthHint::Medium => 400,
+            LengthHint::Long => 1000,
+        }
+    }
+
+    /// Sentence appended to the system prompt
+    pub fn instruction(self) -> &'static str {
+        match self {
+            LengthHint::Sms => {
+                "Keep it under ~2 sentences, like a text message. Never expand short input."
+            }
+            LengthHint::Short => "Keep it brief: a few sentences at most.",
+            LengthHint::Medium => "Keep it to a paragraph or two.",
+            LengthHint::Long => "Match the length of the input; don't pad it.",
+        }
+    }
+
+    /// Default length for a recipient: texts to close contacts stay SMS-sized
+    pub fn for_recipient(mode: WritingMode, category: Option<ContactCategory>) -> Option<Self> {
+        if mode == WritingMode::VeryCasual || category == Some(ContactCategory::CasualPeer) {
+            Some(LengthHint::Sms)
+        } else {
+            None
+        }
+    }
+}
+
+/// Limits that keep a rewrite from overshooting what the user said
+///
+/// Injected into the system prompt; `max_emoji` is also enforced on the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Constraints {
+    /// Keep every emoji the user dictated
+    pub preserve_emoji: bool,
+    /// Most emoji allowed in the output (`u8::MAX` = no limit)
+    pub max_emoji: u8,
+    /// A question in the input must stay a question
+    pub preserve_questions: bool,
+    /// Add an emoji where it suits the tone (within `max_emoji`)
+    #[serde(default)]
+    pub add_emoji: bool,
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Self {
+            preserve_emoji: false,
+            max_emoji: u8::MAX,
+            preserve_questions: false,
+            add_emoji: false,
+        }
+    }
+}
+
+impl Constraints {
+    /// Sentences appended to the system prompt, or `None` if nothing is constrained
+    pub fn instruction(&self) -> Option<String> {
+        let mut rules = Vec::new();
+        if self.preserve_emoji {
+            rules.push("Keep every emoji from the input.".to_string());
+        }
+        match self.max_emoji {
+            0 if !self.preserve_emoji => rules.push("Do not add emoji.".to_string()),
+            0 => rules.push("Do not add any emoji beyond the input's.".to_string()),
+            u8::MAX => {}
+            n => rules.push(format!("Use at most {} emoji in total.", n)),
+        }
+        if self.add_emoji && self.max_emoji > 0 {
+            rules.push("Add an emoji where it suits the tone.".to_string());
+        }
+        if self.preserve_questions {
+            rules.push(
+                "If the input asks a question, the output must still ask it as a question."
+                    .to_string(),
+            );
+        }
+        (!rules.is_empty()).then(|| rules.join(" "))
+    }
+
+    /// Drop emoji beyond `max_emoji`, newest first, sparing the input's own when `preserve_emoji`
+    pub fn enforce(&self, input: &str, output: String) -> String {
+        let clusters = emoji_clusters(&output);
+        let mut excess = clusters.len().saturating_sub(self.max_emoji as usize);
+        if excess == 0 {
+            return output;
+        }
+
+        let mut allowance: Vec<&str> = if self.preserve_emoji {
+            emoji_clusters(input)
+                .into_iter()
+                .map(|range| &input[range])
+                .collect()
+        } else {
+            Vec::new()
+        };
+        // the input's emoji claim their matches in reading order
+        let mut protected = vec![false; clusters.len()];
+        for (i, range) in clusters.iter().enumerate() {
+            if let Some(pos) = allowance.iter().position(|e| *e == &output[range.clone()]) {
+                allowance.remove(pos);
+                protected[i] = true;
+            }
+        }
+
+        let mut result = output;
+        for (i, range) in clusters.iter().enumerate().rev() {
+            if excess == 0 {
+                break;
+            }
+            if !protected[i] {
+                result.replace_range(range.clone(), "");
+                excess -= 1;
+            }
+        }
+
+        // tidy the gaps removed emoji leave behind
+        let mut tidy = String::with_capacity(result.len());
+        for word in result.split(' ').filter(|w| !w.is_empty()) {
+            if !tidy.is_empty() {
+                tidy.push(' ');
+            }
+            tidy.push_str(word);
+        }
+        tidy
+    }
+}
+
+/// Byte ranges of the emoji in `text`, with their modifiers and ZWJ sequences
+fn emoji_clusters(text: &str) -> Vec<std::ops::Range<usize>> {
+    let is_modifier = |c: char| matches!(c as u32, 0xFE0E..=0xFE0F | 0x1F3FB..=0x1F3FF | 0x200D);
+    let mut clusters: Vec<std::ops::Range<usize>> = Vec::new();
+    let mut joining = false;
+
+    for (i, c) in text.char_indices() {
+        let end = i + c.len_utf8();
+        if !is_emoji_char(c) {
+            joining = false;
+            continue;
+        }
+        match clusters.last_mut() {
+            // modifiers and ZWJ-joined pictographs extend the emoji they follow
+            Some(last) if last.end == i && (is_modifier(c) || joining) => last.end = end,
+            _ if is_modifier(c) => continue,
+            _ => clusters.push(i..end),
+        }
+        joining = c == '\u{200D}';
+    }
+    clusters
+}
+
+/// Recipient of the message being formatted (e.g. the active Messages conversation)
+#[derive(Debug, Clone)]
+pub struct RecipientContext {
+    pub name: String,
+    pub category: ContactCategory,
 }
 
 impl CompletionRequest {
-    pub fn new(text: String, mode: WritingMode) -> Self {
+    pub fn new(text: impl Into<String>) -> Self {
         Self {
-            text,
-            mode,
+            text: text.into(),
+            mode: WritingMode::default(),
             system_prompt: None,
+            system_prefix: None,
+            system_suffix: None,
             app_context: None,
             max_tokens: None,
+            target_length: None,
+            constraints: None,
+            first_person: false,
+            script: None,
+            language: None,
+            temperature: None,
+            seed: None,
+            style_prompt: None,
             shortcut_preservation: None,
+            contact: None,
+            redaction: None,
+            messages: Vec::new(),
+            truncated: false,
         }
     }
 
+    /// Send earlier turns (oldest first) before `text`
+    pub fn with_history(mut self, messages: Vec<ChatMessage>) -> Self {
+        self.messages = messages;
+        self
+    }
+
+    /// Ask for a revision of `previous_output`, e.g. "make it shorter"
+    /// The current text and the output become history and `instruction` the new turn
+    pub fn follow_up(
+        mut self,
+        previous_output: impl Into<String>,
+        instruction: impl Into<String>,
+    ) -> Self {
+        let text = std::mem::replace(&mut self.text, instruction.into());
+        self.messages.push(ChatMessage::user(text));
+        self.messages.push(ChatMessage::assistant(previous_output));
+        self
+    }
+
+    /// Turns to send after the system prompt: the history, then `text`
+    ///
+    /// A single-text request is a one-message conversation. The first user turn is
+    /// the dictation and gets wrapped in `<TRANSCRIPTION>` tags; later user turns are
+    /// follow-up instructions and are sent as-is.
+    pub fn conversation(&self) -> Vec<ChatMessage> {
+        let mut conversation = self.messages.clone();
+        conversation.push(ChatMessage::user(self.text.clone()));
+
+        if let Some(first) = conversation
+            .iter_mut()
+            .find(|message| message.role == ChatRole::User)
+        {
+            first.content = format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", first.content);
+        }
+        conversation
+    }
+
+    pub fn with_mode(mut self, mode: WritingMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Override the mode-derived system prompt entirely
     pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
         self.system_prompt = Some(prompt.into());
         self
     }
 
+    /// Instruction to put before every system prompt, whatever the mode
+    pub fn with_system_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.system_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Instruction to put after every system prompt, whatever the mode
+    pub fn with_system_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.system_suffix = Some(suffix.into());
+        self
+    }
+
+    /// The system prompt to send: the prefix, then the explicit system prompt or
+    /// `build()` (the provider's mode prompt), then shortcut preservation, then the suffix
+    pub fn resolve_system_prompt(&self, build: impl FnOnce() -> String) -> String {
+        let mut prompt = self.system_prompt.clone().unwrap_or_else(build);
+        if let Some(preservation) = &self.shortcut_preservation {
+            prompt.push_str(preservation);
+        }
+        let prefix = self
+            .system_prefix
+            .as_deref()
+            .filter(|p| !p.trim().is_empty());
+        let suffix = self
+            .system_suffix
+            .as_deref()
+            .filter(|s| !s.trim().is_empty());
+        match (prefix, suffix) {
+            (None, None) => prompt,
+            _ => prefix
+                .into_iter()
+                .chain([prompt.as_str()])
+                .chain(suffix)
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        }
+    }
+
     pub fn with_app_context(mut self, context: impl Into<String>) -> Self {
         self.app_context = Some(context.into());
         self
@@ -50,10 +446,327 @@ impl CompletionRequest {
         self
     }
 
+    /// Aim for this output length; sets a token cap unless `with_max_tokens` is used
+    pub fn with_target_length(mut self, length: LengthHint) -> Self {
+        self.target_length = Some(length);
+        self
+    }
+
+    /// Token cap to send: the explicit `max_tokens`, else the one implied by `target_length`
+    pub fn effective_max_tokens(&self) -> Option<u32> {
+        self.max_tokens
+            .or_else(|| self.target_length.map(LengthHint::max_tokens))
+    }
+
+    /// Keep emoji and questions in check (see [`Constraints`])
+    pub fn with_constraints(mut self, constraints: Constraints) -> Self {
+        self.constraints = Some(constraints);
+        self
+    }
+
+    /// Use `prompt` as the mode instruction instead of the built-in one for `mode`
+    /// The first-person, script, length, and constraint instructions still follow it.
+    pub fn with_style_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.style_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Render `template` with this request's variables and use it as the mode instruction
+    pub fn with_prompt_template(self, template: &PromptTemplate) -> Result<Self> {
+        let prompt = template.render(&self.template_variables())?;
+        Ok(self.with_style_prompt(prompt))
+    }
+
+    /// Values for every [`PromptTemplate`] variable, with neutral wording for what
+    /// this request doesn't know (no recipient, target length, or non-Latin script)
+    pub fn template_variables(&self) -> BTreeMap<&'static str, String> {
+        let contact_name = self
+            .contact
+            .as_ref()
+            .map(|contact| contact.name.trim())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("the recipient");
+        let category = match self.contact.as_ref().map(|contact| contact.category) {
+            Some(ContactCategory::Professional) => "professional",
+            Some(ContactCategory::CloseFamily) => "close family",
+            Some(ContactCategory::CasualPeer) => "friend",
+            Some(ContactCategory::Partner) => "partner",
+            Some(ContactCategory::FormalNeutral) => "formal",
+            Some(ContactCategory::Coworker) => "coworker",
+            None => "general",
+        };
+        let length = match self.target_length {
+            Some(LengthHint::Sms) => "as short as a text message",
+            Some(LengthHint::Short) => "short",
+            Some(LengthHint::Medium) => "to a paragraph or two",
+            Some(LengthHint::Long) => "as long as the input",
+            None => "as long as needed",
+        };
+        let language = match self.script.filter(|&script| script != Script::Latin) {
+            Some(script) => script.name(),
+            None => "the language it was written in",
+        };
+        BTreeMap::from([
+            ("mode", self.mode.as_str().replace('_', " ")),
+            ("contact_name", contact_name.to_string()),
+            ("category", category.to_string()),
+            ("length", length.to_string()),
+            ("language", language.to_string()),
+        ])
+    }
+
+    /// Mark the text as the user's own words so "I" is never rewritten as "they"
+    pub fn with_first_person(mut self) -> Self {
+        self.first_person = true;
+        self
+    }
+
+    /// Note the text's script so the reply stays in that language (see [`Script::instruction`])
+    pub fn with_script(mut self, script: Script) -> Self {
+        self.script = Some(script);
+        self
+    }
+
+    /// Tag the text with its language (see [`LanguageRoutingProvider`](super::LanguageRoutingProvider))
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Pin the sampling seed (with temperature 0.0 for reproducible rewrites)
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
     pub fn with_shortcut_preservation(mut self, instruction: impl Into<String>) -> Self {
         self.shortcut_preservation = Some(instruction.into());
         self
     }
+
+    pub fn with_contact(mut self, name: impl Into<String>, category: ContactCategory) -> Self {
+        self.contact = Some(RecipientContext {
+            name: name.into(),
+            category,
+        });
+        self
+    }
+
+    /// Mask sensitive values (cards, SSNs, emails, custom rules) before sending
+    pub fn with_redaction(mut self, redactor: Redactor) -> Self {
+        self.redaction = Some(redactor);
+        self
+    }
+
+    /// Replace `text` and the history with their redacted forms; returns the mapping
+    /// needed to restore them
+    pub fn apply_redaction(&mut self) -> Option<Redacted> {
+        // redact every turn in one pass so placeholder numbers are unique across turns
+        let joined = self
+            .messages
+            .iter()
+            .map(|message| message.content.as_str())
+            .chain([self.text.as_str()])
+            .collect::<Vec<_>>()
+            .join(TURN_SEPARATOR);
+        let redacted = self.redaction.as_ref()?.redact(&joined);
+
+        let mut turns = redacted.text.split(TURN_SEPARATOR);
+        for message in &mut self.messages {
+            message.content = turns.next().unwrap_or_default().to_string();
+        }
+        self.text = turns.next().unwrap_or_default().to_string();
+        Some(redacted)
+    }
+
+    /// Estimated prompt size: the system prompt (or style instruction) plus every turn
+    pub fn estimated_tokens(&self, estimator: &dyn TokenEstimator) -> usize {
+        let system = match &self.system_prompt {
+            Some(prompt) => estimator.estimate(prompt),
+            None => {
+                estimator.estimate(&self.style_instruction())
+                    + self
+                        .app_context
+                        .as_deref()
+                        .map_or(0, |context| estimator.estimate(context))
+            }
+        };
+        let framing = [
+            &self.shortcut_preservation,
+            &self.system_prefix,
+            &self.system_suffix,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|text| estimator.estimate(text))
+        .sum::<usize>();
+        let turns: usize = self
+            .conversation()
+            .iter()
+            .map(|message| estimator.estimate(&message.content))
+            .sum();
+        system + framing + turns
+    }
+
+    /// Trim the prompt to at most `max_tokens` estimated tokens; returns whether anything was cut
+    ///
+    /// The system prompt is never touched. The oldest history turns go first, then
+    /// words from the front of `text`, so the end of the dictation is what survives.
+    pub fn truncate_to(&mut self, max_tokens: u32) -> bool {
+        self.truncate_to_with(max_tokens, &CharTokenEstimator)
+    }
+
+    /// Like `truncate_to`, counting with `estimator`
+    pub fn truncate_to_with(&mut self, max_tokens: u32, estimator: &dyn TokenEstimator) -> bool {
+        let max_tokens = max_tokens as usize;
+        if self.estimated_tokens(estimator) <= max_tokens {
+            return false;
+        }
+        self.truncated = true;
+
+        while !self.messages.is_empty() && self.estimated_tokens(estimator) > max_tokens {
+            self.messages.remove(0);
+        }
+        if self.estimated_tokens(estimator) <= max_tokens {
+            return true;
+        }
+
+        // keep the longest tail of whole words that fits
+        let text = std::mem::take(&mut self.text);
+        let mut starts: Vec<usize> = text
+            .char_indices()
+            .filter(|&(i, c)| {
+                !c.is_whitespace()
+                    && (i == 0
+                        || text[..i]
+                            .chars()
+                            .next_back()
+                            .is_some_and(char::is_whitespace))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        starts.push(text.len());
+
+        let fits = |request: &mut Self, start: usize| {
+            request.text = text[start..].to_string();
+            request.estimated_tokens(estimator) <= max_tokens
+        };
+        let keep = starts.partition_point(|&start| !fits(self, start));
+        let start = starts.get(keep).copied().unwrap_or(text.len());
+        self.text = text[start..].to_string();
+        true
+    }
+
+    /// Formatting style instruction for this request's mode, tailored to the contact if set
+    /// A `style_prompt` replaces the mode's own instruction.
+    /// Ends with the first-person, script, `target_length`, and `constraints` instructions when set
+    pub fn style_instruction(&self) -> String {
+        let mut style = match (&self.style_prompt, &self.contact) {
+            (Some(prompt), _) => prompt.clone(),
+            (None, Some(contact)) => self.mode.contextual_prompt(&contact.name, contact.category),
+            (None, None) => self.mode.prompt_modifier().to_string(),
+        };
+        if self.first_person {
+            style.push(' ');
+            style.push_str(FIRST_PERSON_INSTRUCTION);
+        }
+        if let Some(language) = self.script.and_then(Script::instruction) {
+            style.push(' ');
+            style.push_str(&language);
+        }
+        if let Some(length) = self.target_length {
+            style.push(' ');
+            style.push_str(length.instruction());
+        }
+        if let Some(rules) = self.constraints.as_ref().and_then(Constraints::instruction) {
+            style.push(' ');
+            style.push_str(&rules);
+        }
+        style
+    }
+}
+
+/// Trim `request` so its prompt, the provider's own prompt text (`overhead`), and the
+/// reply fit in `context_tokens`; returns whether anything was cut
+pub(crate) fn fit_context(
+    request: &mut CompletionRequest,
+    context_tokens: u32,
+    overhead: &str,
+) -> bool {
+    let reserved = request
+        .effective_max_tokens()
+        .unwrap_or(REPLY_RESERVE_TOKENS)
+        .saturating_add(CharTokenEstimator.estimate(overhead) as u32);
+    let truncated = request.truncate_to(context_tokens.saturating_sub(reserved));
+    if truncated {
+        warn!(
+            context_tokens,
+            "Prompt exceeds the context window, dropped the oldest content"
+        );
+    }
+    truncated
+}
+
+/// Make a failed health check say what to fix: bad keys become `ProviderNotConfigured`,
+/// unknown models become `Config`, and anything else is returned unchanged
+pub(crate) fn health_check_error(provider: &str, model: &str, error: Error) -> Error {
+    if error.is_auth_error() {
+        return Error::ProviderNotConfigured(format!(
+            "{} rejected the API key: {}",
+            provider, error
+        ));
+    }
+    let unknown_model = error.status() == Some(404)
+        || (error.status() == Some(400) && error.to_string().to_lowercase().contains("model"));
+    if unknown_model {
+        return Error::Config(format!(
+            "{} model '{}' is unavailable: {}",
+            provider, model, error
+        ));
+    }
+    error
+}
+
+/// Restore redacted values in provider output, if the request was redacted
+pub(crate) fn restore_redacted(redacted: Option<&Redacted>, output: String) -> String {
+    match redacted {
+        Some(redacted) => redacted.restore(&output),
+        None => output,
+    }
+}
+
+/// Enforce the request's constraints on provider output, if it has any
+pub(crate) fn apply_constraints(
+    constraints: Option<&Constraints>,
+    input: &str,
+    output: String,
+) -> String {
+    match constraints {
+        Some(constraints) => constraints.enforce(input, output),
+        None => output,
+    }
+}
+
+/// Log latency and token usage for a finished completion, inside the provider's span
+/// Message content is only emitted at trace level so normal logs never carry it
+pub(crate) fn trace_completion(response: &CompletionResponse, started: Instant) {
+    let latency_ms = started.elapsed().as_millis() as u64;
+    match &response.usage {
+        Some(usage) => info!(
+            latency_ms,
+            prompt_tokens = usage.prompt_tokens,
+            completion_tokens = usage.completion_tokens,
+            total_tokens = usage.total_tokens,
+            "Completion finished"
+        ),
+        None => info!(latency_ms, "Completion finished"),
+    }
+    trace!(output = %response.text, "Completion output");
 }
 
 /// Response from completion
@@ -65,6 +778,52 @@ pub struct CompletionResponse {
     pub usage: Option<TokenUsage>,
     /// Model used for completion
     pub model: Option<String>,
+    /// Why generation stopped
+    #[serde(default)]
+    pub finish_reason: FinishReason,
+    /// The prompt was trimmed to fit the model's context window
+    #[serde(default)]
+    pub prompt_truncated: bool,
+    /// What the call cost, from `usage` and the provider's [`Pricing`](super::Pricing)
+    /// `None` when usage wasn't reported or the model has no known price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+}
+
+impl CompletionResponse {
+    /// Whether the output was cut off by the token limit
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason == FinishReason::Length
+    }
+}
+
+/// Why the model stopped generating, normalized across providers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// Natural end of the message
+    Stop,
+    /// Hit `max_tokens`; the text is truncated
+    Length,
+    /// Blocked or cut short by a content filter
+    ContentFilter,
+    /// Unknown or not reported
+    #[default]
+    Other,
+}
+
+impl FinishReason {
+    /// Map a provider's finish field ("stop", "length", "MAX_TOKENS", "SAFETY", ...)
+    pub fn from_provider(reason: Option<&str>) -> Self {
+        match reason.map(|r| r.to_ascii_lowercase()).as_deref() {
+            Some("stop" | "end_turn" | "stop_sequence") => FinishReason::Stop,
+            Some("length" | "max_tokens") => FinishReason::Length,
+            Some("content_filter" | "safety" | "recitation" | "blocklist") => {
+                FinishReason::ContentFilter
+            }
+            _ => FinishReason::Other,
+        }
+    }
 }
 
 /// Token usage statistics
@@ -75,15 +834,234 @@ pub struct TokenUsage {
     pub total_tokens: u32,
 }
 
+impl TokenUsage {
+    /// Usage of two calls together; `None` only when neither reported any
+    pub fn sum(a: Option<&TokenUsage>, b: Option<&TokenUsage>) -> Option<TokenUsage> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(TokenUsage {
+                prompt_tokens: a.prompt_tokens + b.prompt_tokens,
+                completion_tokens: a.completion_tokens + b.completion_tokens,
+                total_tokens: a.total_tokens + b.total_tokens,
+            }),
+            (Some(usage), None) | (None, Some(usage)) => Some(usage.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Append a continuation to the text it continues
+/// Models usually drop the leading space, so one is added between words.
+fn join_continuation(previous: &str, continuation: &str) -> String {
+    let needs_space = !previous.is_empty()
+        && !previous.ends_with(char::is_whitespace)
+        && continuation.chars().next().is_some_and(|c| {
+            !c.is_whitespace() && !matches!(c, '.' | ',' | '!' | '?' | ';' | ':' | ')' | '\'' | '’')
+        });
+    if needs_space {
+        format!("{} {}", previous, continuation)
+    } else {
+        format!("{}{}", previous, continuation)
+    }
+}
+
 /// Trait for completion/formatting providers
 #[async_trait]
 pub trait CompletionProvider: Send + Sync {
     /// Get the provider name
     fn name(&self) -> &'static str;
 
+    /// Stable identifier for logs and usage accounting, e.g. "openai" (defaults to `name()`)
+    fn provider_name(&self) -> &str {
+        self.name()
+    }
+
+    /// Model that requests are sent to, e.g. "gpt-4o-mini" (empty if the provider doesn't say)
+    fn model_name(&self) -> &str {
+        ""
+    }
+
     /// Process text with the given mode
     async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse>;
 
+    /// Send a one-token request to confirm the key and model work
+    async fn health_check(&self) -> Result<()> {
+        if !self.is_configured() {
+            return Err(Error::ProviderNotConfigured(format!(
+                "{} is not configured",
+                self.name()
+            )));
+        }
+        let request = CompletionRequest::new("ping")
+            .with_system_prompt("Reply with OK.")
+            .with_max_tokens(1);
+        self.complete(request)
+            .await
+            .map(|_| ())
+            .map_err(|e| health_check_error(self.name(), self.model_name(), e))
+    }
+
+    /// Pick up a reply that stopped at the token limit
+    ///
+    /// Sends `request` again with `previous` as the assistant's turn and asks for the
+    /// rest. The returned response holds the joined text, the usage of both calls,
+    /// and the continuation's finish reason, so it can be fed back in until `Stop`.
+    async fn continue_completion(
+        &self,
+        previous: &CompletionResponse,
+        request: &CompletionRequest,
+    ) -> Result<CompletionResponse> {
+        let follow_up = request
+            .clone()
+            .follow_up(previous.text.clone(), CONTINUE_INSTRUCTION);
+        let continuation = self.complete(follow_up).await?;
+
+        Ok(CompletionResponse {
+            text: join_continuation(&previous.text, &continuation.text),
+            usage: TokenUsage::sum(previous.usage.as_ref(), continuation.usage.as_ref()),
+            model: continuation.model.or_else(|| previous.model.clone()),
+            finish_reason: continuation.finish_reason,
+            prompt_truncated: previous.prompt_truncated || continuation.prompt_truncated,
+            estimated_cost_usd: previous
+                .estimated_cost_usd
+                .into_iter()
+                .chain(continuation.estimated_cost_usd)
+                .reduce(|a, b| a + b),
+        })
+    }
+
+    /// Like `complete`, but returns `Error::Cancelled` as soon as `cancel` fires
+    /// The in-flight request is dropped, which aborts the HTTP call
+    async fn complete_cancellable(
+        &self,
+        request: CompletionRequest,
+        cancel: &CancellationToken,
+    ) -> Result<CompletionResponse> {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(Error::Cancelled),
+            result = self.complete(request) => result,
+        }
+    }
+
     /// Check if the provider is configured and ready
     fn is_configured(&self) -> bool;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::FallbackCompletionProvider;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers every request with `result` and counts the calls; names no model
+    struct Scripted {
+        result: fn() -> Result<CompletionResponse>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl CompletionProvider for Scripted {
+        fn name(&self) -> &'static str {
+            "Scripted"
+        }
+
+        async fn complete(&self, _request: CompletionRequest) -> Result<CompletionResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            (self.result)()
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    fn scripted(result: fn() -> Result<CompletionResponse>) -> (Scripted, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let provider = Scripted {
+            result,
+            calls: Arc::clone(&calls),
+        };
+        (provider, calls)
+    }
+
+    fn ok() -> Result<CompletionResponse> {
+        Ok(CompletionResponse {
+            text: "done".to_string(),
+            usage: None,
+            model: None,
+            finish_reason: FinishReason::Stop,
+            prompt_truncated: false,
+            estimated_cost_usd: None,
+        })
+    }
+
+    #[test]
+    fn test_health_check_explains_failures() {
+        let (provider, calls) = scripted(ok);
+        futures::executor::block_on(provider.health_check()).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        fn auth_error() -> Result<CompletionResponse> {
+            Err(Error::ProviderError {
+                status: Some(401),
+                message: "OpenRouter API error (401 Unauthorized): bad key".to_string(),
+            })
+        }
+        let (provider, _) = scripted(auth_error);
+        let err = futures::executor::block_on(provider.health_check()).unwrap_err();
+        assert!(matches!(err, Error::ProviderNotConfigured(_)));
+
+        fn missing_model() -> Result<CompletionResponse> {
+            Err(Error::ProviderError {
+                status: Some(404),
+                message: "The model `gpt-9` does not exist".to_string(),
+            })
+        }
+        let (provider, _) = scripted(missing_model);
+        let err = futures::executor::block_on(provider.health_check()).unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+        assert!(err.to_string().contains("Scripted model"));
+
+        // transient failures pass through untouched
+        fn server_error() -> Result<CompletionResponse> {
+            Err(Error::ProviderError {
+                status: Some(503),
+                message: "OpenAI API error: 503 Service Unavailable - overloaded".to_string(),
+            })
+        }
+        let (provider, _) = scripted(server_error);
+        let err = futures::executor::block_on(provider.health_check()).unwrap_err();
+        assert_eq!(err.status(), Some(503));
+    }
+
+    #[test]
+    fn test_cancelled_request_never_runs() {
+        let (provider, calls) = scripted(ok);
+        let request = || CompletionRequest::new("hello").with_mode(WritingMode::Casual);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let err = futures::executor::block_on(provider.complete_cancellable(request(), &cancel))
+            .unwrap_err();
+        assert!(matches!(err, Error::Cancelled));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        let live = CancellationToken::new();
+        let response =
+            futures::executor::block_on(provider.complete_cancellable(request(), &live)).unwrap();
+        assert_eq!(response.text, "done");
+    }
+
+    #[test]
+    fn test_reports_first_configured_model() {
+        let (provider, _) = scripted(ok);
+        assert_eq!(provider.provider_name(), "Scripted");
+        assert_eq!(provider.model_name(), "");
+
+        let chain = FallbackCompletionProvider::new(vec![Box::new(provider)]);
+        assert_eq!(chain.provider_name(), "fallback");
+        assert_eq!(chain.model_name(), "");
+        assert_eq!(FallbackCompletionProvider::new(Vec::new()).model_name(), "");
+    }
+}
@@ -0,0 +1,153 @@
+//! Construct providers from persisted settings
+
+use crate::error::{Error, Result};
+
+use crate::storage::{
+    SETTING_COMPLETION_PROVIDER, Storage, completion_model_setting, rate_limit_setting,
+};
+
+use super::{
+    CompletionProvider, ProviderRegistry, ProviderSettings, RateLimitedCompletionProvider,
+    RateLimiters,
+};
+
+/// Build the completion provider selected in storage
+///
+/// Reads `completion_provider` ("openai" when unset), the matching API key, and
+/// that provider's optional model override. Returns `ProviderNotConfigured` when
+/// the selected provider has no key (stored or from its environment variable).
+/// `"none"` selects [`PassthroughCompletionProvider`] and `"rules"` the offline
+/// [`RuleBasedAdapter`]; neither needs a key. A positive `rate_limit_rpm_<provider>`
+/// wraps the provider in a [`RateLimitedCompletionProvider`] with a fresh limiter;
+/// rebuild through [`build_from_storage_with`] to keep the budget already used.
+///
+/// [`PassthroughCompletionProvider`]: super::PassthroughCompletionProvider
+/// [`RuleBasedAdapter`]: super::RuleBasedAdapter
+pub fn build_from_storage(storage: &Storage) -> Result<Box<dyn CompletionProvider>> {
+    build_from_storage_with(storage, &ProviderRegistry::new(), &RateLimiters::new())
+}
+
+/// Like [`build_from_storage`], but selecting from `registry` instead of the built-ins
+/// and rate limiting with the provider's limiter from `limiters`
+pub fn build_from_storage_with(
+    storage: &Storage,
+    registry: &ProviderRegistry,
+    limiters: &RateLimiters,
+) -> Result<Box<dyn CompletionProvider>> {
+    let provider_name = storage
+        .get_setting(SETTING_COMPLETION_PROVIDER)?
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "openai".to_string());
+    if !registry.is_registered(&provider_name) {
+        return Err(Error::Config(format!(
+            "Unknown completion provider: {}",
+            provider_name
+        )));
+    }
+
+    let model = storage
+        .get_setting(&completion_model_setting(&provider_name))?
+        .filter(|model| !model.is_empty());
+    let api_key = match registry.key_setting(&provider_name) {
+        Some(key_setting) => storage
+            .get_setting(key_setting)?
+            .filter(|key| !key.is_empty()),
+        None => None,
+    };
+
+    let provider = registry.build(&provider_name, ProviderSettings { api_key, model })?;
+    if !provider.is_configured() {
+        return Err(Error::ProviderNotConfigured(format!(
+            "No API key configured for {}",
+            provider_name
+        )));
+    }
+
+    let rate_limit = storage
+        .get_setting(&rate_limit_setting(&provider_name))?
+        .and_then(|rpm| rpm.trim().parse::<u32>().ok())
+        .filter(|rpm| *rpm > 0);
+    match rate_limit {
+        Some(rpm) => Ok(Box::new(RateLimitedCompletionProvider::new(
+            provider,
+            limiters.limiter(&provider_name, rpm),
+        ))),
+        None => Ok(provider),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{OpenRouterCompletionProvider, PassthroughCompletionProvider};
+    use crate::storage::SETTING_OPENROUTER_API_KEY;
+
+    #[test]
+    fn test_build_from_storage_selects_provider() {
+        let storage = Storage::in_memory().unwrap();
+        storage
+            .set_setting(SETTING_COMPLETION_PROVIDER, "openrouter")
+            .unwrap();
+        storage
+            .set_setting(SETTING_OPENROUTER_API_KEY, "sk-or-test")
+            .unwrap();
+        storage
+            .set_setting(
+                &completion_model_setting("openrouter"),
+                "anthropic/claude-3.5-haiku",
+            )
+            .unwrap();
+
+        let provider = build_from_storage(&storage).unwrap();
+        assert_eq!(
+            provider.name(),
+            OpenRouterCompletionProvider::new(None).name()
+        );
+        assert!(provider.is_configured());
+
+        // a rate limit keeps the provider's identity
+        storage
+            .set_setting(&rate_limit_setting("openrouter"), "30")
+            .unwrap();
+        let provider = build_from_storage(&storage).unwrap();
+        assert_eq!(provider.provider_name(), "openrouter");
+        assert_eq!(provider.model_name(), "anthropic/claude-3.5-haiku");
+    }
+
+    #[test]
+    fn test_build_from_storage_passthrough() {
+        let storage = Storage::in_memory().unwrap();
+        storage
+            .set_setting(SETTING_COMPLETION_PROVIDER, "none")
+            .unwrap();
+
+        let provider = build_from_storage(&storage).unwrap();
+        assert_eq!(provider.provider_name(), "none");
+        assert!(provider.is_configured());
+
+        storage
+            .set_setting(SETTING_COMPLETION_PROVIDER, "rules")
+            .unwrap();
+        let provider = build_from_storage(&storage).unwrap();
+        assert_eq!(provider.provider_name(), "rules");
+    }
+
+    #[test]
+    fn test_build_from_storage_rejects_unknown_provider() {
+        let storage = Storage::in_memory().unwrap();
+        storage
+            .set_setting(SETTING_COMPLETION_PROVIDER, "carrier-pigeon")
+            .unwrap();
+
+        let err = build_from_storage(&storage).err().unwrap();
+        assert!(matches!(err, Error::Config(_)));
+
+        // until the app registers it
+        let mut registry = ProviderRegistry::new();
+        registry.register("carrier-pigeon", None, |_| {
+            Box::new(PassthroughCompletionProvider::new())
+        });
+        let provider = build_from_storage_with(&storage, &registry, &RateLimiters::new()).unwrap();
+        assert_eq!(provider.provider_name(), "none");
+    }
+}
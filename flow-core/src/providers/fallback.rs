@@ -0,0 +1,392 @@
+//! Fallback chains that try providers in order until one succeeds
+//!
+//! A provider that fails with a transient error (network, 429, 5xx) hands the
+//! request to the next one. Errors that would fail the same way everywhere
+//! (bad request, auth, missing configuration) are returned immediately.
+
+use async_trait::async_trait;
+use tracing::{info, warn};
+
+use crate::error::{Error, Result};
+
+use super::{
+    CompletionProvider, CompletionRequest, CompletionResponse, TranscriptionProvider,
+    TranscriptionRequest, TranscriptionResponse,
+};
+
+/// Completion provider that wraps an ordered list of providers
+pub struct FallbackCompletionProvider {
+    providers: Vec<Box<dyn CompletionProvider>>,
+}
+
+impl FallbackCompletionProvider {
+    pub fn new(providers: Vec<Box<dyn CompletionProvider>>) -> Self {
+        Self { providers }
+    }
+
+    /// Append a provider to the end of the chain
+    pub fn with_provider(mut self, provider: Box<dyn CompletionProvider>) -> Self {
+        self.providers.push(provider);
+        self
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for FallbackCompletionProvider {
+    fn name(&self) -> &'static str {
+        "Fallback"
+    }
+
+    fn provider_name(&self) -> &str {
+        "fallback"
+    }
+
+    /// Model of the first provider that would be tried
+    fn model_name(&self) -> &str {
+        self.providers
+            .iter()
+            .find(|p| p.is_configured())
+            .map(|p| p.model_name())
+            .unwrap_or_default()
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let mut last_error = None;
+
+        for provider in self.providers.iter().filter(|p| p.is_configured()) {
+            match provider.complete(request.clone()).await {
+                Ok(response) => {
+                    info!(
+                        "Completion served by {} ({})",
+                        provider.provider_name(),
+                        provider.model_name()
+                    );
+                    return Ok(response);
+                }
+                Err(e) if should_fall_back(&e) => {
+                    warn!(
+                        "{} completion failed, trying next provider: {}",
+                        provider.name(),
+                        e
+                    );
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            Error::ProviderNotConfigured("No configured completion provider in chain".to_string())
+        }))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.providers.iter().any(|p| p.is_configured())
+    }
+}
+
+/// Transcription provider that wraps an ordered list of providers
+pub struct FallbackTranscriptionProvider {
+    providers: Vec<Box<dyn TranscriptionProvider>>,
+}
+
+impl FallbackTranscriptionProvider {
+    pub fn new(providers: Vec<Box<dyn TranscriptionProvider>>) -> Self {
+        Self { providers }
+    }
+
+    /// Append a provider to the end of the chain
+    pub fn with_provider(mut self, provider: Box<dyn TranscriptionProvider>) -> Self {
+        self.providers.push(provider);
+        self
+    }
+}
+
+#[async_trait]
+impl TranscriptionProvider for FallbackTranscriptionProvider {
+    fn name(&self) -> &'static str {
+        "Fallback"
+    }
+
+    fn provider_name(&self) -> &str {
+        "fallback"
+    }
+
+    /// Model of the first provider that would be tried
+    fn model_name(&self) -> &str {
+        self.providers
+            .iter()
+            .find(|p| p.is_configured())
+            .map(|p| p.model_name())
+            .unwrap_or_default()
+    }
+
+    async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+        let mut last_error = None;
+
+        for provider in self.providers.iter().filter(|p| p.is_configured()) {
+            match provider.transcribe(request.clone()).await {
+                Ok(response) => {
+                    info!(
+                        "Transcription served by {} ({})",
+                        provider.provider_name(),
+                        provider.model_name()
+                    );
+                    return Ok(response);
+                }
+                Err(e) if should_fall_back(&e) => {
+                    warn!(
+                        "{} transcription failed, trying next provider: {}",
+                        provider.name(),
+                        e
+                    );
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            Error::ProviderNotConfigured(
+                "No configured transcription provider in chain".to_string(),
+            )
+        }))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.providers.iter().any(|p| p.is_configured())
+    }
+}
+
+/// Whether another provider could plausibly succeed where this one failed
+/// Transient failures, plus responses this provider couldn't make sense of
+fn should_fall_back(error: &Error) -> bool {
+    error.is_retryable() || matches!(error, Error::Completion(_) | Error::Transcription(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{FinishReason, TranscriptFormat};
+    use crate::types::{AudioClip, WritingMode};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct StubProvider {
+        name: &'static str,
+        result: fn() -> Result<CompletionResponse>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl CompletionProvider for StubProvider {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn model_name(&self) -> &str {
+            "stub"
+        }
+
+        async fn complete(&self, _request: CompletionRequest) -> Result<CompletionResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            (self.result)()
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    fn stub(
+        name: &'static str,
+        result: fn() -> Result<CompletionResponse>,
+    ) -> (Box<dyn CompletionProvider>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let provider = StubProvider {
+            name,
+            result,
+            calls: Arc::clone(&calls),
+        };
+        (Box::new(provider), calls)
+    }
+
+    fn ok() -> Result<CompletionResponse> {
+        Ok(CompletionResponse {
+            text: "done".to_string(),
+            usage: None,
+            model: None,
+            finish_reason: FinishReason::Stop,
+            prompt_truncated: false,
+            estimated_cost_usd: None,
+        })
+    }
+
+    fn server_error() -> Result<CompletionResponse> {
+        Err(Error::ProviderError {
+            status: Some(503),
+            message: "OpenAI API error: 503 Service Unavailable - overloaded".to_string(),
+        })
+    }
+
+    fn auth_error() -> Result<CompletionResponse> {
+        Err(Error::ProviderError {
+            status: Some(401),
+            message: "OpenRouter API error (401 Unauthorized): bad key".to_string(),
+        })
+    }
+
+    fn request() -> CompletionRequest {
+        CompletionRequest::new("hello").with_mode(WritingMode::Casual)
+    }
+
+    #[test]
+    fn test_falls_back_on_server_error() {
+        let (first, first_calls) = stub("first", server_error);
+        let (second, second_calls) = stub("second", ok);
+        let chain = FallbackCompletionProvider::new(vec![first, second]);
+
+        let response = futures::executor::block_on(chain.complete(request())).unwrap();
+        assert_eq!(response.text, "done");
+        assert_eq!(first_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(second_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_short_circuits_on_auth_error() {
+        let (first, _) = stub("first", auth_error);
+        let (second, second_calls) = stub("second", ok);
+        let chain = FallbackCompletionProvider::new(vec![first, second]);
+
+        assert!(futures::executor::block_on(chain.complete(request())).is_err());
+        assert_eq!(second_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_returns_last_error_when_all_fail() {
+        let (first, _) = stub("first", server_error);
+        let (second, _) = stub("second", server_error);
+        let chain = FallbackCompletionProvider::new(vec![first, second]);
+
+        let err = futures::executor::block_on(chain.complete(request())).unwrap_err();
+        assert!(err.to_string().contains("503"));
+    }
+
+    struct StubTranscriber {
+        result: fn() -> Result<TranscriptionResponse>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl TranscriptionProvider for StubTranscriber {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        async fn transcribe(
+            &self,
+            _request: TranscriptionRequest,
+        ) -> Result<TranscriptionResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            (self.result)()
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    fn transcriber(
+        result: fn() -> Result<TranscriptionResponse>,
+    ) -> (Box<dyn TranscriptionProvider>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let provider = StubTranscriber {
+            result,
+            calls: Arc::clone(&calls),
+        };
+        (Box::new(provider), calls)
+    }
+
+    fn heard() -> Result<TranscriptionResponse> {
+        Ok(TranscriptionResponse {
+            text: "hello".to_string(),
+            confidence: None,
+            language: None,
+            duration_ms: 200,
+            segments: None,
+            completed_text: None,
+            format: TranscriptFormat::Plain,
+        })
+    }
+
+    fn transcription_unavailable() -> Result<TranscriptionResponse> {
+        Err(Error::ProviderError {
+            status: Some(503),
+            message: "Whisper API error: 503 Service Unavailable".to_string(),
+        })
+    }
+
+    fn transcription_gateway_error() -> Result<TranscriptionResponse> {
+        Err(Error::ProviderError {
+            status: Some(502),
+            message: "Whisper API error: 502 Bad Gateway".to_string(),
+        })
+    }
+
+    fn transcription_auth_error() -> Result<TranscriptionResponse> {
+        Err(Error::ProviderError {
+            status: Some(401),
+            message: "Whisper API error: 401 Unauthorized".to_string(),
+        })
+    }
+
+    fn audio() -> TranscriptionRequest {
+        TranscriptionRequest::from_clip(AudioClip::mono(vec![0; 3200], 16000))
+    }
+
+    #[test]
+    fn test_transcription_falls_back_on_server_error() {
+        let (first, first_calls) = transcriber(transcription_unavailable);
+        let (second, second_calls) = transcriber(heard);
+        let chain = FallbackTranscriptionProvider::new(vec![first, second]);
+
+        let response = futures::executor::block_on(chain.transcribe(audio())).unwrap();
+        assert_eq!(response.text, "hello");
+        assert_eq!(first_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(second_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_transcription_short_circuits_on_auth_error() {
+        let (first, _) = transcriber(transcription_auth_error);
+        let (second, second_calls) = transcriber(heard);
+        let chain = FallbackTranscriptionProvider::new(vec![first, second]);
+
+        let err = futures::executor::block_on(chain.transcribe(audio())).unwrap_err();
+        assert_eq!(err.status(), Some(401));
+        assert_eq!(second_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_transcription_returns_last_error_when_all_fail() {
+        let (first, _) = transcriber(transcription_unavailable);
+        let (second, _) = transcriber(transcription_gateway_error);
+        let chain = FallbackTranscriptionProvider::new(vec![first, second]);
+
+        let err = futures::executor::block_on(chain.transcribe(audio())).unwrap_err();
+        assert_eq!(err.status(), Some(502));
+    }
+
+    #[test]
+    fn test_should_fall_back() {
+        let status = |status| Error::ProviderError {
+            status: Some(status),
+            message: String::new(),
+        };
+        // malformed responses aren't worth retrying on the same provider, but another may do better
+        let malformed = Error::Completion("no choices".to_string());
+        assert!(should_fall_back(&malformed));
+        assert!(should_fall_back(&status(503)));
+        assert!(!should_fall_back(&status(400)));
+    }
+}
@@ -3,25 +3,54 @@
 use async_trait::async_trait;
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
-use reqwest::Client;
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, instrument, trace, warn};
 
 use crate::error::{Error, Result};
-use crate::types::WritingMode;
 
-use super::completion::TokenUsage;
+use super::completion::{
+    ChatMessage, DEFAULT_TEMPERATURE, FinishReason, TokenUsage, apply_constraints, fit_context,
+    restore_redacted, trace_completion,
+};
+use super::http::{CustomHeaders, HttpClient, build_request, default_http_client, read_json};
+use super::transcription::trace_transcription;
 use super::{
-    CompletionProvider, CompletionRequest, CompletionResponse, TranscriptionProvider,
-    TranscriptionRequest, TranscriptionResponse,
+    CompletionProvider, CompletionRequest, CompletionResponse, Pricing, TranscriptFormat,
+    TranscriptionProvider, TranscriptionRequest, TranscriptionResponse,
 };
 
 const GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
+const GEMINI_UPLOAD_BASE: &str = "https://generativelanguage.googleapis.com/upload/v1beta";
 const GEMINI_OPENAI_COMPAT_BASE: &str = "https://generativelanguage.googleapis.com/v1beta/openai";
 
+/// Context window of the Gemini Flash models
+const GEMINI_CONTEXT_TOKENS: u32 = 1_048_576;
+
+/// Cap on vocabulary hint length; Gemini's context is large but requests should stay small
+const VOCABULARY_MAX_CHARS: usize = 2000;
+
+/// Gemini rejects generateContent requests over 20MB, base64 audio included
+const INLINE_REQUEST_MAX_BYTES: usize = 20 * 1024 * 1024;
+
+/// Room left in an inline request for the prompt and JSON framing
+const INLINE_REQUEST_HEADROOM: usize = 64 * 1024;
+
+/// How long to wait for an uploaded file to finish processing
+const FILE_ACTIVE_TIMEOUT: Duration = Duration::from_secs(60);
+const FILE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether a WAV of this size can be sent inline rather than through the File API
+fn fits_inline(wav_len: usize) -> bool {
+    wav_len.div_ceil(3) * 4 + INLINE_REQUEST_HEADROOM <= INLINE_REQUEST_MAX_BYTES
+}
+
 /// Gemini transcription provider (using native API with audio input)
 pub struct GeminiTranscriptionProvider {
-    client: Client,
+    http: Arc<dyn HttpClient>,
+    headers: CustomHeaders,
     api_key: Option<String>,
     model: String,
 }
@@ -32,7 +61,8 @@ impl GeminiTranscriptionProvider {
         let key = api_key.or_else(|| std::env::var("GEMINI_API_KEY").ok());
 
         Self {
-            client: Client::new(),
+            http: default_http_client(),
+            headers: CustomHeaders::new(),
             api_key: key,
             model: "gemini-3-flash-preview".to_string(),
         }
@@ -44,11 +74,273 @@ impl GeminiTranscriptionProvider {
         self
     }
 
+    /// Send requests through `http` instead of the network (e.g. a test double)
+    pub fn with_http_client(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Send `name: value` with every request, e.g. a proxy's own auth header
+    /// A header the provider sets itself, like its `Authorization`, is kept.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// [`with_header`](Self::with_header) for each of `headers`
+    pub fn with_headers<I, K, V>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (name, value) in headers {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    /// Send `name: value` in place of the provider's own header of that name
+    pub fn with_header_override(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.headers.insert_override(name, value);
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
             .ok_or_else(|| Error::ProviderNotConfigured("Gemini API key not set".to_string()))
     }
+
+    /// Upload audio through the File API's resumable protocol and wait until it's usable
+    async fn upload_file(&self, api_key: &str, wav_data: Vec<u8>) -> Result<GeminiFile> {
+        let upload_error = |status: Option<u16>, message: String| {
+            error!("Gemini upload error: {}", message);
+            Error::Upload { status, message }
+        };
+
+        let start = build_request(
+            Method::POST,
+            format!("{}/files?key={}", GEMINI_UPLOAD_BASE, api_key),
+        )
+        .header("X-Goog-Upload-Protocol", "resumable")
+        .header("X-Goog-Upload-Command", "start")
+        .header("X-Goog-Upload-Header-Content-Length", wav_data.len())
+        .header("X-Goog-Upload-Header-Content-Type", "audio/wav")
+        .json(&serde_json::json!({ "file": { "display_name": "flow-dictation" } }))
+        .build()
+        .map_err(|e| upload_error(None, e.without_url().to_string()))?;
+        let start = self
+            .headers
+            .send(self.http.as_ref(), start)
+            .await
+            .map_err(|e| upload_error(None, e.to_string()))?;
+        if !start.status().is_success() {
+            let status = start.status();
+            let error_text = start.text().await.unwrap_or_default();
+            return Err(upload_error(
+                Some(status.as_u16()),
+                format!("Gemini File API error: {} - {}", status, error_text),
+            ));
+        }
+        let upload_url = start
+            .headers()
+            .get("x-goog-upload-url")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| upload_error(None, "No upload URL returned".to_string()))?;
+
+        debug!("Uploading {} bytes to the Gemini File API", wav_data.len());
+        let finalize = build_request(Method::POST, &upload_url)
+            .header("X-Goog-Upload-Offset", 0)
+            .header("X-Goog-Upload-Command", "upload, finalize")
+            .body(wav_data)
+            .build()
+            .map_err(|e| upload_error(None, e.without_url().to_string()))?;
+        let finalize = self
+            .headers
+            .send(self.http.as_ref(), finalize)
+            .await
+            .map_err(|e| upload_error(None, e.to_string()))?;
+        if !finalize.status().is_success() {
+            let status = finalize.status();
+            let error_text = finalize.text().await.unwrap_or_default();
+            return Err(upload_error(
+                Some(status.as_u16()),
+                format!("Gemini File API error: {} - {}", status, error_text),
+            ));
+        }
+        let mut file = finalize
+            .json::<GeminiFileResponse>()
+            .await
+            .map_err(|e| upload_error(None, e.without_url().to_string()))?
+            .file;
+
+        let deadline = Instant::now() + FILE_ACTIVE_TIMEOUT;
+        while file.state.as_deref() == Some("PROCESSING") {
+            if Instant::now() >= deadline {
+                self.delete_file(api_key, &file.name).await;
+                return Err(upload_error(
+                    None,
+                    "Uploaded audio is still processing".to_string(),
+                ));
+            }
+            tokio::time::sleep(FILE_POLL_INTERVAL).await;
+            let poll = build_request(
+                Method::GET,
+                format!("{}/{}?key={}", GEMINI_API_BASE, file.name, api_key),
+            )
+            .build()
+            .map_err(|e| upload_error(None, e.without_url().to_string()))?;
+            file = self
+                .headers
+                .send(self.http.as_ref(), poll)
+                .await
+                .map_err(|e| upload_error(None, e.to_string()))?
+                .json()
+                .await
+                .map_err(|e| upload_error(None, e.without_url().to_string()))?;
+        }
+        if file.state.as_deref() == Some("FAILED") {
+            self.delete_file(api_key, &file.name).await;
+            return Err(upload_error(
+                None,
+                "Gemini could not process the uploaded audio".to_string(),
+            ));
+        }
+
+        Ok(file)
+    }
+
+    /// Delete an uploaded file; failures are only logged since files expire on their own
+    async fn delete_file(&self, api_key: &str, name: &str) {
+        let result = match build_request(
+            Method::DELETE,
+            format!("{}/{}?key={}", GEMINI_API_BASE, name, api_key),
+        )
+        .build()
+        {
+            Ok(request) => self.headers.send(self.http.as_ref(), request).await,
+            Err(e) => Err(Error::Network(e.without_url())),
+        };
+        match result {
+            Ok(response) if response.status().is_success() => {
+                debug!("Deleted uploaded file {}", name)
+            }
+            Ok(response) => warn!(
+                "Failed to delete uploaded file {}: {}",
+                name,
+                response.status()
+            ),
+            Err(e) => warn!("Failed to delete uploaded file {}: {}", name, e),
+        }
+    }
+
+    /// Run generateContent and return the first text part
+    async fn generate_text(
+        &self,
+        api_key: &str,
+        generate_request: &GeminiGenerateContentRequest,
+    ) -> Result<String> {
+        let url = format!(
+            "{}/models/{}:generateContent?key={}",
+            GEMINI_API_BASE, self.model, api_key
+        );
+        let http_request = build_request(Method::POST, &url)
+            .header("Content-Type", "application/json")
+            .json(generate_request)
+            .build()
+            // the key is in the query string; keep it out of the error message
+            .map_err(|e| e.without_url())?;
+        let response = self.headers.send(self.http.as_ref(), http_request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            error!("Gemini API error: {} - {}", status, error_text);
+            return Err(Error::provider(
+                status,
+                format!("Gemini API error: {} - {}", status, error_text),
+            ));
+        }
+
+        let gemini_response: GeminiGenerateContentResponse = read_json(response, "Gemini").await?;
+        response_text(gemini_response)
+    }
+}
+
+/// Text of the first candidate, or `ContentBlocked` when a safety filter withheld it
+fn response_text(response: GeminiGenerateContentResponse) -> Result<String> {
+    if let Some(feedback) = response.prompt_feedback
+        && let Some(reason) = feedback.block_reason
+    {
+        return Err(Error::ContentBlocked {
+            reason,
+            category: blocking_category(&feedback.safety_ratings),
+        });
+    }
+
+    let Some(candidate) = response.candidates.into_iter().next() else {
+        return Err(Error::Transcription(
+            "No transcription returned".to_string(),
+        ));
+    };
+    let text = candidate.content.and_then(|c| {
+        c.parts.into_iter().find_map(|p| match p {
+            GeminiPartResponse::Text { text } => Some(text),
+            _ => None,
+        })
+    });
+    match (text, candidate.finish_reason) {
+        (Some(text), _) => Ok(text),
+        (None, Some(reason))
+            if FinishReason::from_provider(Some(reason.as_str()))
+                == FinishReason::ContentFilter =>
+        {
+            Err(Error::ContentBlocked {
+                reason,
+                category: blocking_category(&candidate.safety_ratings),
+            })
+        }
+        (None, _) => Err(Error::Transcription(
+            "No transcription returned".to_string(),
+        )),
+    }
+}
+
+/// Harm category behind a block: the one marked blocked, else the most likely one
+fn blocking_category(ratings: &[GeminiSafetyRating]) -> Option<String> {
+    let likelihood = |rating: &GeminiSafetyRating| match rating.probability.as_deref() {
+        Some("HIGH") => 3,
+        Some("MEDIUM") => 2,
+        Some("LOW") => 1,
+        _ => 0,
+    };
+    ratings
+        .iter()
+        .find(|rating| rating.blocked)
+        .or_else(|| ratings.iter().max_by_key(|rating| likelihood(rating)))
+        .map(|rating| rating.category.clone())
+}
+
+/// File API metadata for uploaded audio
+#[derive(Debug, Deserialize)]
+struct GeminiFile {
+    /// Resource name, e.g. "files/abc-123"
+    name: String,
+    uri: String,
+    /// PROCESSING, ACTIVE, or FAILED
+    #[serde(default)]
+    state: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiFileResponse {
+    file: GeminiFile,
 }
 
 #[derive(Debug, Serialize)]
@@ -73,6 +365,10 @@ enum GeminiPart {
         #[serde(rename = "inlineData")]
         inline_data: GeminiInlineData,
     },
+    FileData {
+        #[serde(rename = "fileData")]
+        file_data: GeminiFileData,
+    },
 }
 
 #[derive(Debug, Serialize)]
@@ -82,6 +378,14 @@ struct GeminiInlineData {
     data: String,
 }
 
+#[derive(Debug, Serialize)]
+struct GeminiFileData {
+    #[serde(rename = "mimeType")]
+    mime_type: String,
+    #[serde(rename = "fileUri")]
+    file_uri: String,
+}
+
 #[derive(Debug, Serialize)]
 struct GeminiGenerationConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -89,17 +393,51 @@ struct GeminiGenerationConfig {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiGenerateContentResponse {
+    /// Empty when the prompt itself was blocked
+    #[serde(default)]
     candidates: Vec<GeminiCandidate>,
+    #[serde(default)]
+    prompt_feedback: Option<GeminiPromptFeedback>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiCandidate {
-    content: GeminiContentResponse,
+    /// Missing when the output was withheld
+    #[serde(default)]
+    content: Option<GeminiContentResponse>,
+    /// STOP, MAX_TOKENS, SAFETY, ...
+    #[serde(default)]
+    finish_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<GeminiSafetyRating>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiPromptFeedback {
+    #[serde(default)]
+    block_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<GeminiSafetyRating>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiSafetyRating {
+    /// e.g. HARM_CATEGORY_HARASSMENT
+    category: String,
+    /// NEGLIGIBLE, LOW, MEDIUM, or HIGH
+    #[serde(default)]
+    probability: Option<String>,
+    #[serde(default)]
+    blocked: bool,
 }
 
 #[derive(Debug, Deserialize)]
 struct GeminiContentResponse {
+    #[serde(default)]
     parts: Vec<GeminiPartResponse>,
 }
 
@@ -128,28 +466,61 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
         "Gemini"
     }
 
+    fn provider_name(&self) -> &str {
+        "gemini"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    #[instrument(
+        name = "transcription",
+        skip_all,
+        fields(provider = "gemini", model = %self.model)
+    )]
     async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+        let started = Instant::now();
         let api_key = self.api_key()?;
 
         // Convert PCM to WAV format for the API
-        let wav_data = pcm_to_wav(&request.audio, request.sample_rate, 1);
-        let audio_base64 = STANDARD.encode(&wav_data);
-
-        // Build the request with audio input
-        let mut parts = vec![GeminiPart::InlineData {
-            inline_data: GeminiInlineData {
-                mime_type: "audio/wav".to_string(),
-                data: audio_base64,
-            },
-        }];
+        let wav_data = request.audio.to_wav();
+
+        // Small clips go inline; larger ones through the File API, deleted once we're done
+        let mut uploaded = None;
+        let audio_part = if fits_inline(wav_data.len()) {
+            GeminiPart::InlineData {
+                inline_data: GeminiInlineData {
+                    mime_type: "audio/wav".to_string(),
+                    data: STANDARD.encode(&wav_data),
+                },
+            }
+        } else {
+            let file = self.upload_file(api_key, wav_data).await?;
+            let part = GeminiPart::FileData {
+                file_data: GeminiFileData {
+                    mime_type: "audio/wav".to_string(),
+                    file_uri: file.uri.clone(),
+                },
+            };
+            uploaded = Some(file);
+            part
+        };
+        let mut parts = vec![audio_part];
 
         // Add prompt if provided
-        let prompt_text = if let Some(prompt) = &request.prompt {
+        let mut prompt_text = if let Some(prompt) = &request.prompt {
             prompt.clone()
         } else {
             "Transcribe this audio accurately. Output only the transcribed text, nothing else."
                 .to_string()
         };
+        if let Some(hint) = request.vocabulary_hint(VOCABULARY_MAX_CHARS) {
+            prompt_text.push_str(&format!(
+                "\n\nThese names and terms may appear; spell them exactly as written: {}",
+                hint
+            ));
+        }
         parts.insert(0, GeminiPart::Text { text: prompt_text });
 
         let generate_request = GeminiGenerateContentRequest {
@@ -161,54 +532,26 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
 
         debug!("Sending transcription request to Gemini");
 
-        let url = format!(
-            "{}/models/{}:generateContent?key={}",
-            GEMINI_API_BASE, self.model, api_key
-        );
-        let response = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&generate_request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            error!("Gemini API error: {} - {}", status, error_text);
-            return Err(Error::Transcription(format!(
-                "Gemini API error: {} - {}",
-                status, error_text
-            )));
+        let result = self.generate_text(api_key, &generate_request).await;
+        if let Some(file) = uploaded {
+            self.delete_file(api_key, &file.name).await;
         }
-
-        let gemini_response: GeminiGenerateContentResponse = response.json().await?;
-
-        let text = gemini_response
-            .candidates
-            .into_iter()
-            .next()
-            .and_then(|c| {
-                c.content.parts.into_iter().find_map(|p| match p {
-                    GeminiPartResponse::Text { text } => Some(text),
-                    _ => None,
-                })
-            })
-            .ok_or_else(|| Error::Transcription("No transcription returned".to_string()))?;
+        let text = result?;
 
         // Estimate duration from audio size
-        let samples = request.audio.len() / 2;
-        let duration_ms = (samples as u64 * 1000) / request.sample_rate as u64;
+        let duration_ms = request.audio.duration_ms();
 
-        Ok(TranscriptionResponse {
+        let transcription = TranscriptionResponse {
             text: text.trim().to_string(),
             confidence: None, // Gemini doesn't provide confidence scores
             language: request.language,
             duration_ms,
             segments: None,
             completed_text: None,
-        })
+            format: TranscriptFormat::Plain,
+        };
+        trace_transcription(&transcription, started);
+        Ok(transcription)
     }
 
     fn is_configured(&self) -> bool {
@@ -218,9 +561,11 @@ impl TranscriptionProvider for GeminiTranscriptionProvider {
 
 /// Gemini completion provider (using OpenAI-compatible endpoint)
 pub struct GeminiCompletionProvider {
-    client: Client,
+    http: Arc<dyn HttpClient>,
+    headers: CustomHeaders,
     api_key: Option<String>,
     model: String,
+    pricing: Pricing,
 }
 
 impl GeminiCompletionProvider {
@@ -229,9 +574,11 @@ impl GeminiCompletionProvider {
         let key = api_key.or_else(|| std::env::var("GEMINI_API_KEY").ok());
 
         Self {
-            client: Client::new(),
+            http: default_http_client(),
+            headers: CustomHeaders::new(),
             api_key: key,
             model: "gemini-3-flash-preview".to_string(),
+            pricing: Pricing::new(),
         }
     }
 
@@ -241,13 +588,55 @@ impl GeminiCompletionProvider {
         self
     }
 
+    /// Price responses with `pricing` instead of the built-in table
+    pub fn with_pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    /// Send requests through `http` instead of the network (e.g. a test double)
+    pub fn with_http_client(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Send `name: value` with every request, e.g. a proxy's own auth header
+    /// A header the provider sets itself, like its `Authorization`, is kept.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// [`with_header`](Self::with_header) for each of `headers`
+    pub fn with_headers<I, K, V>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (name, value) in headers {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    /// Send `name: value` in place of the provider's own header of that name
+    pub fn with_header_override(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.headers.insert_override(name, value);
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
             .ok_or_else(|| Error::ProviderNotConfigured("Gemini API key not set".to_string()))
     }
 
-    fn build_system_prompt(&self, mode: WritingMode, app_context: Option<&str>) -> String {
+    fn build_system_prompt(&self, style: &str, app_context: Option<&str>) -> String {
         let mut prompt = String::from(
             "You are a text formatter. The user will provide raw transcribed text wrapped in <TRANSCRIPTION> tags. \
              Reformat ONLY the text inside according to the style below. Output the reformatted text exactly as it would \
@@ -255,7 +644,7 @@ impl GeminiCompletionProvider {
         );
 
         prompt.push_str("Formatting style: ");
-        prompt.push_str(mode.prompt_modifier());
+        prompt.push_str(style);
 
         if let Some(context) = app_context {
             prompt.push_str("\n\nContext: User is typing in ");
@@ -276,12 +665,6 @@ struct ChatRequest {
     temperature: f32,
 }
 
-#[derive(Debug, Serialize)]
-struct ChatMessage {
-    role: String,
-    content: String,
-}
-
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     choices: Vec<ChatChoice>,
@@ -292,11 +675,14 @@ struct ChatResponse {
 #[derive(Debug, Deserialize)]
 struct ChatChoice {
     message: ChatMessageResponse,
+    finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct ChatMessageResponse {
-    content: String,
+    /// Null when a safety filter withheld the output
+    #[serde(default)]
+    content: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -312,73 +698,106 @@ impl CompletionProvider for GeminiCompletionProvider {
         "Gemini"
     }
 
-    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+    fn provider_name(&self) -> &str {
+        "gemini"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    #[instrument(name = "completion", skip_all, fields(provider = "gemini", model = %self.model))]
+    async fn complete(&self, mut request: CompletionRequest) -> Result<CompletionResponse> {
+        let started = Instant::now();
         let api_key = self.api_key()?;
+        let redacted = request.apply_redaction();
+        let prompt_truncated = fit_context(
+            &mut request,
+            GEMINI_CONTEXT_TOKENS,
+            &self.build_system_prompt("", None),
+        );
+        trace!(input = %request.text, "Completion input");
 
-        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
-            self.build_system_prompt(request.mode, request.app_context.as_deref())
+        let conversation = request.conversation();
+        let style = request.style_instruction();
+        let max_tokens = request.effective_max_tokens();
+        let system_prompt = request.resolve_system_prompt(|| {
+            self.build_system_prompt(&style, request.app_context.as_deref())
         });
 
-        // Add shortcut preservation instruction if present
-        if let Some(preservation) = request.shortcut_preservation {
-            system_prompt.push_str(&preservation);
-        }
+        let mut messages = vec![ChatMessage::system(system_prompt)];
+        messages.extend(conversation);
 
         let chat_request = ChatRequest {
             model: self.model.clone(),
-            messages: vec![
-                ChatMessage {
-                    role: "system".to_string(),
-                    content: system_prompt,
-                },
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
-                },
-            ],
-            max_tokens: request.max_tokens,
-            temperature: 0.3, // low temperature for consistent formatting
+            messages,
+            max_tokens,
+            temperature: request.temperature.unwrap_or(DEFAULT_TEMPERATURE),
         };
 
         debug!("Sending completion request to Gemini");
 
-        let response = self
-            .client
-            .post(format!("{}/chat/completions", GEMINI_OPENAI_COMPAT_BASE))
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&chat_request)
-            .send()
-            .await?;
+        let http_request = build_request(
+            Method::POST,
+            format!("{}/chat/completions", GEMINI_OPENAI_COMPAT_BASE),
+        )
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("Content-Type", "application/json")
+        .json(&chat_request)
+        .build()?;
+        let response = self.headers.send(self.http.as_ref(), http_request).await?;
 
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Gemini API error: {} - {}", status, error_text);
-            return Err(Error::Completion(format!(
-                "Gemini API error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::provider(
+                status,
+                format!("Gemini API error: {} - {}", status, error_text),
+            ));
         }
 
-        let chat_response: ChatResponse = response.json().await?;
+        let chat_response: ChatResponse = read_json(response, "Gemini").await?;
 
-        let text = chat_response
+        let choice = chat_response
             .choices
             .into_iter()
             .next()
-            .map(|c| c.message.content)
             .ok_or_else(|| Error::Completion("No completion returned".to_string()))?;
+        let finish_reason = FinishReason::from_provider(choice.finish_reason.as_deref());
+        let content = choice.message.content.unwrap_or_default();
+        if finish_reason == FinishReason::ContentFilter && content.trim().is_empty() {
+            return Err(Error::ContentBlocked {
+                reason: choice.finish_reason.unwrap_or_default(),
+                category: None,
+            });
+        }
 
-        Ok(CompletionResponse {
-            text,
-            usage: chat_response.usage.map(|u| TokenUsage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
-            }),
+        let usage = chat_response.usage.map(|u| TokenUsage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+            total_tokens: u.total_tokens,
+        });
+        // the compat endpoint may answer with the "models/" resource prefix
+        let priced_model = chat_response.model.trim_start_matches("models/");
+        let estimated_cost_usd = usage
+            .as_ref()
+            .and_then(|usage| self.pricing.estimate("gemini", priced_model, usage));
+
+        let completion = CompletionResponse {
+            text: apply_constraints(
+                request.constraints.as_ref(),
+                &request.text,
+                restore_redacted(redacted.as_ref(), content),
+            ),
+            usage,
             model: Some(chat_response.model),
-        })
+            finish_reason,
+            prompt_truncated,
+            estimated_cost_usd,
+        };
+        trace_completion(&completion, started);
+        Ok(completion)
     }
 
     fn is_configured(&self) -> bool {
@@ -386,75 +805,93 @@ impl CompletionProvider for GeminiCompletionProvider {
     }
 }
 
-/// Convert raw PCM data to WAV format
-fn pcm_to_wav(pcm: &[u8], sample_rate: u32, channels: u16) -> Vec<u8> {
-    let bits_per_sample: u16 = 16;
-    let byte_rate = sample_rate * u32::from(channels) * u32::from(bits_per_sample) / 8;
-    let block_align = channels * bits_per_sample / 8;
-    let data_size = pcm.len() as u32;
-    let file_size = 36 + data_size;
-
-    let mut wav = Vec::with_capacity(44 + pcm.len());
-
-    // RIFF header
-    wav.extend_from_slice(b"RIFF");
-    wav.extend_from_slice(&file_size.to_le_bytes());
-    wav.extend_from_slice(b"WAVE");
-
-    // fmt chunk
-    wav.extend_from_slice(b"fmt ");
-    wav.extend_from_slice(&16u32.to_le_bytes()); // chunk size
-    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM format
-    wav.extend_from_slice(&channels.to_le_bytes());
-    wav.extend_from_slice(&sample_rate.to_le_bytes());
-    wav.extend_from_slice(&byte_rate.to_le_bytes());
-    wav.extend_from_slice(&block_align.to_le_bytes());
-    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
-
-    // data chunk
-    wav.extend_from_slice(b"data");
-    wav.extend_from_slice(&data_size.to_le_bytes());
-    wav.extend_from_slice(pcm);
-
-    wav
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::WritingMode;
 
     #[test]
-    fn test_pcm_to_wav() {
-        // 1 second of silence at 16kHz mono
-        let pcm = vec![0u8; 32000]; // 16000 samples * 2 bytes
-        let wav = pcm_to_wav(&pcm, 16000, 1);
+    fn test_safety_block_is_typed() {
+        let parse = |json: &str| {
+            response_text(serde_json::from_str::<GeminiGenerateContentResponse>(json).unwrap())
+        };
 
-        // check RIFF header
-        assert_eq!(&wav[0..4], b"RIFF");
-        assert_eq!(&wav[8..12], b"WAVE");
-        assert_eq!(&wav[12..16], b"fmt ");
-        assert_eq!(&wav[36..40], b"data");
+        let err = parse(
+            r#"{"promptFeedback": {"blockReason": "SAFETY", "safetyRatings": [
+                {"category": "HARM_CATEGORY_HATE_SPEECH", "probability": "NEGLIGIBLE"},
+                {"category": "HARM_CATEGORY_HARASSMENT", "probability": "HIGH", "blocked": true}
+            ]}}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::ContentBlocked { reason, category }
+                if reason == "SAFETY" && category.as_deref() == Some("HARM_CATEGORY_HARASSMENT")
+        ));
+
+        let err = parse(
+            r#"{"candidates": [{"finishReason": "SAFETY", "safetyRatings": [
+                {"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "MEDIUM"}
+            ]}]}"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The model refused to respond: SAFETY (HARM_CATEGORY_DANGEROUS_CONTENT)"
+        );
 
-        // total size should be 44 header + 32000 data
-        assert_eq!(wav.len(), 44 + 32000);
+        let text = parse(
+            r#"{"candidates": [{"content": {"parts": [{"text": "hello"}]}, "finishReason": "STOP"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(text, "hello");
+        assert!(matches!(
+            parse(r#"{"candidates": []}"#),
+            Err(Error::Transcription(_))
+        ));
     }
 
     #[test]
     fn test_system_prompt_building() {
         let provider = GeminiCompletionProvider::new(None);
 
-        let prompt = provider.build_system_prompt(WritingMode::Formal, None);
+        let prompt = provider.build_system_prompt(WritingMode::Formal.prompt_modifier(), None);
         assert!(prompt.contains("professional"));
         assert!(prompt.contains("Transform slang into professional alternatives"));
         assert!(prompt.contains("<TRANSCRIPTION>"));
         assert!(prompt.contains("Do NOT generate new content"));
 
-        let prompt = provider.build_system_prompt(WritingMode::VeryCasual, Some("Slack"));
+        let prompt =
+            provider.build_system_prompt(WritingMode::VeryCasual.prompt_modifier(), Some("Slack"));
         assert!(prompt.contains("texting style"));
         assert!(prompt.contains("Slack"));
         assert!(prompt.contains("exactly as it would be typed"));
     }
 
+    #[test]
+    fn test_large_audio_uses_file_api() {
+        // ~5 minutes of 16kHz 16-bit mono fits inline; ~15 minutes does not
+        assert!(fits_inline(16_000 * 2 * 60 * 5));
+        assert!(!fits_inline(16_000 * 2 * 60 * 15));
+
+        let part = GeminiPart::FileData {
+            file_data: GeminiFileData {
+                mime_type: "audio/wav".to_string(),
+                file_uri: "https://example.com/files/abc".to_string(),
+            },
+        };
+        let json = serde_json::to_value(&part).unwrap();
+        assert_eq!(json["fileData"]["fileUri"], "https://example.com/files/abc");
+        assert_eq!(json["fileData"]["mimeType"], "audio/wav");
+
+        let upload = Error::Upload {
+            status: Some(503),
+            message: "busy".to_string(),
+        };
+        assert!(upload.is_retryable());
+        assert!(upload.to_string().starts_with("Upload failed"));
+    }
+
     #[test]
     fn test_provider_not_configured() {
         let provider = GeminiTranscriptionProvider::new(None);
@@ -0,0 +1,272 @@
+//! HTTP transport and response body decoding shared by the HTTP providers
+//!
+//! Providers build a `reqwest::Request` and send it through an [`HttpClient`],
+//! so tests can swap in canned responses instead of the network. The default
+//! client shares one connection pool across every provider.
+//!
+//! [`CustomHeaders`] ride along on every request a provider sends, for proxies
+//! that authenticate with headers of their own.
+//!
+//! Proxies in front of a provider occasionally mangle a byte or two. Bodies are
+//! decoded lossily (with a warning) so a stray invalid sequence costs a `U+FFFD`
+//! in the text instead of the whole dictation.
+
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{Client, IntoUrl, Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use tracing::warn;
+
+use crate::error::{Error, Result};
+
+/// Sends a built request and returns the raw response, whatever its status
+#[async_trait]
+pub trait HttpClient: Send + Sync {
+    async fn execute(&self, request: reqwest::Request) -> Result<reqwest::Response>;
+}
+
+/// [`HttpClient`] backed by a process-wide `reqwest::Client`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReqwestHttpClient;
+
+#[async_trait]
+impl HttpClient for ReqwestHttpClient {
+    async fn execute(&self, request: reqwest::Request) -> Result<reqwest::Response> {
+        shared_client()
+            .execute(request)
+            .await
+            // some providers put the key in the query string; keep it out of errors
+            .map_err(|e| Error::Network(e.without_url()))
+    }
+}
+
+/// The client providers use unless given another
+pub fn default_http_client() -> Arc<dyn HttpClient> {
+    Arc::new(ReqwestHttpClient)
+}
+
+fn shared_client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(Client::new)
+}
+
+/// Extra headers added to every request, e.g. for a corporate proxy
+///
+/// A header the provider sets itself (its `Authorization`, `Content-Type`) wins
+/// over one added with [`insert`](Self::insert); use
+/// [`insert_override`](Self::insert_override) to replace it on purpose. Names and
+/// values are checked when a request is sent, failing it with `Error::Config`.
+#[derive(Debug, Clone, Default)]
+pub struct CustomHeaders {
+    entries: Vec<CustomHeader>,
+}
+
+#[derive(Debug, Clone)]
+struct CustomHeader {
+    name: String,
+    value: String,
+    replace: bool,
+}
+
+impl CustomHeaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `name: value`, unless the provider already sets `name`
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.push(name.into(), value.into(), false);
+    }
+
+    /// Send `name: value` in place of any value the provider sets
+    pub fn insert_override(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.push(name.into(), value.into(), true);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn push(&mut self, name: String, value: String, replace: bool) {
+        self.entries.push(CustomHeader {
+            name,
+            value,
+            replace,
+        });
+    }
+
+    /// Add the headers to `request`
+    pub(crate) fn apply(&self, request: &mut reqwest::Request) -> Result<()> {
+        for entry in &self.entries {
+            let name = HeaderName::from_bytes(entry.name.trim().as_bytes())
+                .map_err(|_| Error::Config(format!("Invalid header name: {}", entry.name)))?;
+            let mut value = HeaderValue::from_str(&entry.value)
+                .map_err(|_| Error::Config(format!("Invalid value for header {}", name)))?;
+            // proxy credentials stay out of debug output
+            value.set_sensitive(true);
+
+            let headers = request.headers_mut();
+            if entry.replace || !headers.contains_key(&name) {
+                headers.insert(name, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Send `request` through `http` with the headers added
+    pub(crate) async fn send(
+        &self,
+        http: &dyn HttpClient,
+        mut request: reqwest::Request,
+    ) -> Result<reqwest::Response> {
+        self.apply(&mut request)?;
+        http.execute(request).await
+    }
+}
+
+/// [`HttpClient`] adding [`CustomHeaders`] to every request before passing it on
+///
+/// Gives any provider that accepts an `HttpClient` the same headers the OpenAI
+/// and Gemini providers take through `with_header`.
+pub struct HeaderHttpClient {
+    inner: Arc<dyn HttpClient>,
+    headers: CustomHeaders,
+}
+
+impl HeaderHttpClient {
+    pub fn new(inner: Arc<dyn HttpClient>, headers: CustomHeaders) -> Self {
+        Self { inner, headers }
+    }
+}
+
+#[async_trait]
+impl HttpClient for HeaderHttpClient {
+    async fn execute(&self, request: reqwest::Request) -> Result<reqwest::Response> {
+        self.headers.send(self.inner.as_ref(), request).await
+    }
+}
+
+/// Start building a request to send through an [`HttpClient`]
+pub(crate) fn build_request(method: Method, url: impl IntoUrl) -> RequestBuilder {
+    shared_client().request(method, url)
+}
+
+/// Decode a body as UTF-8, replacing invalid sequences
+pub(crate) fn decode_body(bytes: &[u8], source: &str) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(e) => {
+            warn!(
+                source,
+                valid_up_to = e.valid_up_to(),
+                "Response is not valid UTF-8, decoding lossily"
+            );
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+}
+
+/// Parse a JSON body after lossy UTF-8 decoding
+pub(crate) fn parse_json<T: DeserializeOwned>(bytes: &[u8], source: &str) -> Result<T> {
+    Ok(serde_json::from_str(&decode_body(bytes, source))?)
+}
+
+/// Read and parse a JSON response body; `source` names the provider in logs
+pub(crate) async fn read_json<T: DeserializeOwned>(
+    response: reqwest::Response,
+    source: &str,
+) -> Result<T> {
+    let bytes = response.bytes().await?;
+    parse_json(&bytes, source)
+}
+
+/// Canned-response client for provider tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+
+    use super::HttpClient;
+    use crate::error::Result;
+
+    /// Answers every request with a canned status and body, recording what was sent
+    pub(crate) struct CannedHttpClient {
+        status: u16,
+        body: &'static str,
+        pub(crate) sent: Mutex<Vec<reqwest::Request>>,
+    }
+
+    #[async_trait]
+    impl HttpClient for CannedHttpClient {
+        async fn execute(&self, request: reqwest::Request) -> Result<reqwest::Response> {
+            self.sent.lock().unwrap().push(request);
+            let response = ::http::Response::builder()
+                .status(self.status)
+                .body(self.body.to_string())
+                .unwrap();
+            Ok(reqwest::Response::from(response))
+        }
+    }
+
+    pub(crate) fn canned(status: u16, body: &'static str) -> Arc<CannedHttpClient> {
+        Arc::new(CannedHttpClient {
+            status,
+            body,
+            sent: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Deserialize)]
+    struct Body {
+        text: String,
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_decoded_lossily() {
+        assert_eq!(decode_body("héllo".as_bytes(), "test"), "héllo");
+
+        let mut bytes = br#"{"text": "caf"#.to_vec();
+        bytes.push(0xE9); // Latin-1 é from a misbehaving proxy
+        bytes.extend_from_slice(br#" ok"}"#);
+        let body: Body = parse_json(&bytes, "test").unwrap();
+        assert_eq!(body.text, "caf\u{FFFD} ok");
+
+        assert!(parse_json::<Body>(b"not json", "test").is_err());
+    }
+
+    #[test]
+    fn test_custom_headers_keep_provider_auth() {
+        let request = || {
+            build_request(Method::POST, "https://api.openai.com/v1/chat/completions")
+                .header("Authorization", "Bearer sk-test")
+                .build()
+                .unwrap()
+        };
+
+        let mut headers = CustomHeaders::new();
+        headers.insert("X-Proxy-Token", "corp-123");
+        headers.insert("Authorization", "Bearer proxy");
+        let mut sent = request();
+        headers.apply(&mut sent).unwrap();
+        assert_eq!(sent.headers()["X-Proxy-Token"], "corp-123");
+        assert_eq!(sent.headers()["Authorization"], "Bearer sk-test");
+
+        headers.insert_override("Authorization", "Bearer proxy");
+        let mut sent = request();
+        headers.apply(&mut sent).unwrap();
+        assert_eq!(sent.headers()["Authorization"], "Bearer proxy");
+
+        let mut invalid = CustomHeaders::new();
+        invalid.insert("bad header", "x");
+        let err = invalid.apply(&mut request()).unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+    }
+}
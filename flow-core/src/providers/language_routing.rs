@@ -0,0 +1,230 @@
+//! Completion provider that picks a provider per language
+//!
+//! Routes are keyed by language code ("en", "es", "ja"). The language comes from
+//! the request's [`language`](CompletionRequest::language) hint when set (e.g. what
+//! the transcription detected), otherwise from the script of the text. Script
+//! detection only tells apart languages with their own writing system, so
+//! Latin-script text without a hint goes to the default provider. Routes and the
+//! default are ordinary providers, so a route can be a fallback chain or a
+//! rate-limited provider, and this provider can sit inside either.
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt;
+use tracing::info;
+
+use crate::error::Result;
+use crate::script::{Script, detect_script};
+
+use super::{CompletionProvider, CompletionRequest, CompletionResponse};
+
+/// How the language for a request was decided
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LanguageSource {
+    /// The request's language hint
+    Hint,
+    /// Detected from the script of the text
+    Detected(Script),
+    /// Neither a hint nor a script that identifies the language
+    Unknown,
+}
+
+/// Which provider a request goes to and why, for logging
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutingDecision {
+    /// Normalized language code, if one was found
+    pub language: Option<String>,
+    pub source: LanguageSource,
+    /// A route for `language` was found and is configured; otherwise the default is used
+    pub routed: bool,
+    /// `provider_name` of the chosen provider
+    pub provider: String,
+}
+
+impl fmt::Display for RoutingDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let language = self.language.as_deref().unwrap_or("unknown");
+        let source = match &self.source {
+            LanguageSource::Hint => "hint".to_string(),
+            LanguageSource::Detected(script) => format!("{} script", script.name()),
+            LanguageSource::Unknown => "undetected".to_string(),
+        };
+        let target = if self.routed { "route" } else { "default" };
+        write!(
+            f,
+            "language {} ({}) -> {} {}",
+            language, source, target, self.provider
+        )
+    }
+}
+
+/// Completion provider that sends each request to the provider for its language
+pub struct LanguageRoutingProvider {
+    routes: HashMap<String, Box<dyn CompletionProvider>>,
+    default: Box<dyn CompletionProvider>,
+}
+
+impl LanguageRoutingProvider {
+    /// Send requests without a matching route to `default`
+    pub fn new(default: Box<dyn CompletionProvider>) -> Self {
+        Self {
+            routes: HashMap::new(),
+            default,
+        }
+    }
+
+    /// Send requests in `language` (e.g. "es" or "pt-BR"; only the primary code counts) to `provider`
+    pub fn with_route(
+        mut self,
+        language: impl AsRef<str>,
+        provider: Box<dyn CompletionProvider>,
+    ) -> Self {
+        self.routes
+            .insert(normalize_language(language.as_ref()), provider);
+        self
+    }
+
+    /// Decide where `request` would go, without sending it
+    pub fn route(&self, request: &CompletionRequest) -> RoutingDecision {
+        let hint = request
+            .language
+            .as_deref()
+            .map(normalize_language)
+            .filter(|language| !language.is_empty());
+        let (language, source) = match hint {
+            Some(language) => (Some(language), LanguageSource::Hint),
+            None => match request.script.or_else(|| detect_script(&request.text)) {
+                Some(script) => (
+                    script_language(script).map(str::to_string),
+                    LanguageSource::Detected(script),
+                ),
+                None => (None, LanguageSource::Unknown),
+            },
+        };
+
+        let provider = self.provider_for(language.as_deref());
+        RoutingDecision {
+            routed: language
+                .as_deref()
+                .and_then(|language| self.routes.get(language))
+                .is_some_and(|route| route.is_configured()),
+            provider: provider.provider_name().to_string(),
+            language,
+            source,
+        }
+    }
+
+    /// The configured route for `language`, or the default
+    fn provider_for(&self, language: Option<&str>) -> &dyn CompletionProvider {
+        language
+            .and_then(|language| self.routes.get(language))
+            .filter(|route| route.is_configured())
+            .map_or(self.default.as_ref(), |route| route.as_ref())
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for LanguageRoutingProvider {
+    fn name(&self) -> &'static str {
+        "Language Routing"
+    }
+
+    fn provider_name(&self) -> &str {
+        "language_routing"
+    }
+
+    /// Model of the default provider
+    fn model_name(&self) -> &str {
+        self.default.model_name()
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let decision = self.route(&request);
+        info!("Completion routed by {}", decision);
+        self.provider_for(decision.language.as_deref())
+            .complete(request)
+            .await
+    }
+
+    fn is_configured(&self) -> bool {
+        self.default.is_configured()
+    }
+}
+
+/// Lowercased primary language code: "pt-BR" and "pt_br" both become "pt"
+fn normalize_language(language: &str) -> String {
+    language
+        .trim()
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Language a script implies, when it's (nearly) only used for one
+fn script_language(script: Script) -> Option<&'static str> {
+    match script {
+        Script::Latin => None,
+        Script::Cyrillic => Some("ru"),
+        Script::Greek => Some("el"),
+        Script::Arabic => Some("ar"),
+        Script::Hebrew => Some("he"),
+        Script::Devanagari => Some("hi"),
+        Script::Thai => Some("th"),
+        Script::Han => Some("zh"),
+        Script::Kana => Some("ja"),
+        Script::Hangul => Some("ko"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockCompletionProvider;
+    use crate::types::WritingMode;
+
+    fn canned(text: &str) -> Box<dyn CompletionProvider> {
+        Box::new(MockCompletionProvider::new().with_response(WritingMode::Casual, text))
+    }
+
+    #[test]
+    fn test_routes_by_hint_then_script() {
+        let provider = LanguageRoutingProvider::new(canned("default"))
+            .with_route("EN", canned("english"))
+            .with_route("ja", canned("japanese"));
+        let complete = |request: CompletionRequest| {
+            futures::executor::block_on(provider.complete(request))
+                .unwrap()
+                .text
+        };
+
+        let hinted = CompletionRequest::new("hola").with_language("en-US");
+        assert_eq!(complete(hinted.clone()), "english");
+        let decision = provider.route(&hinted);
+        assert_eq!(decision.language.as_deref(), Some("en"));
+        assert_eq!(decision.source, LanguageSource::Hint);
+        assert!(decision.routed);
+
+        let decision = provider.route(&CompletionRequest::new("今日は遅れます"));
+        assert_eq!(decision.source, LanguageSource::Detected(Script::Kana));
+        assert_eq!(
+            decision.to_string(),
+            "language ja (Japanese script) -> route mock"
+        );
+        assert_eq!(
+            complete(CompletionRequest::new("今日は遅れます")),
+            "japanese"
+        );
+
+        // Latin script alone doesn't say which language; neither does an unrouted one
+        let decision = provider.route(&CompletionRequest::new("running late"));
+        assert_eq!(decision.language, None);
+        assert!(!decision.routed);
+        assert_eq!(complete(CompletionRequest::new("running late")), "default");
+        assert_eq!(complete(CompletionRequest::new("Привет")), "default");
+        assert_eq!(
+            provider.route(&CompletionRequest::new("🔥")).source,
+            LanguageSource::Unknown
+        );
+    }
+}
@@ -7,6 +7,7 @@
 //! - Quality: Distilled medium (~400MB) - great accuracy, still fast (recommended)
 //! - Best: Distilled large-v3 (~750MB) - best quality available
 
+use crate::audio::{clip_to_f32_mono, resample};
 use crate::error::{Error, Result};
 use async_trait::async_trait;
 use candle_core::{Device, IndexOp, Tensor};
@@ -17,10 +18,12 @@ use hf_hub::{Repo, RepoType, api::sync::Api};
 use parking_lot::Mutex;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 use tokenizers::Tokenizer;
-use tracing::{debug, info};
+use tracing::{debug, info, instrument};
 
-use super::{TranscriptionProvider, TranscriptionRequest, TranscriptionResponse};
+use super::transcription::trace_transcription;
+use super::{TranscriptFormat, TranscriptionProvider, TranscriptionRequest, TranscriptionResponse};
 
 // Include the mel filter bytes (80 mel bins for Whisper)
 const MEL_FILTER_BYTES: &[u8] = include_bytes!("../../melfilters.bytes");
@@ -634,42 +637,6 @@ impl LocalWhisperTranscriptionProvider {
     pub fn is_model_loaded(&self) -> bool {
         self.engine.lock().is_some()
     }
-
-    /// Resample audio using linear interpolation
-    fn resample_audio(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
-        if from_rate == to_rate {
-            return samples.to_vec();
-        }
-
-        let ratio = to_rate as f32 / from_rate as f32;
-        let output_len = (samples.len() as f32 * ratio) as usize;
-        let mut output = Vec::with_capacity(output_len);
-
-        for i in 0..output_len {
-            let src_pos = i as f32 / ratio;
-            let src_idx = src_pos as usize;
-            let frac = src_pos - src_idx as f32;
-
-            if src_idx + 1 < samples.len() {
-                let sample = samples[src_idx] * (1.0 - frac) + samples[src_idx + 1] * frac;
-                output.push(sample);
-            } else if src_idx < samples.len() {
-                output.push(samples[src_idx]);
-            }
-        }
-
-        output
-    }
-
-    /// Convert PCM bytes (16-bit little-endian) to f32 normalized audio
-    fn pcm_bytes_to_f32(audio_bytes: &[u8]) -> Vec<f32> {
-        let mut samples = Vec::with_capacity(audio_bytes.len() / 2);
-        for chunk in audio_bytes.chunks_exact(2) {
-            let sample = i16::from_le_bytes([chunk[0], chunk[1]]);
-            samples.push(sample as f32 / 32768.0);
-        }
-        samples
-    }
 }
 
 #[async_trait]
@@ -678,18 +645,32 @@ impl TranscriptionProvider for LocalWhisperTranscriptionProvider {
         "Local Whisper (Metal)"
     }
 
+    fn provider_name(&self) -> &str {
+        "local_whisper"
+    }
+
+    fn model_name(&self) -> &str {
+        self.model_size.model_id().0
+    }
+
+    #[instrument(
+        name = "transcription",
+        skip_all,
+        fields(provider = "local_whisper", model = ?self.model_size)
+    )]
     async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+        let started = Instant::now();
         // Ensure model is loaded
         if !self.is_model_loaded() {
             self.load_model().await?;
         }
 
         // Convert audio bytes to f32 format expected by whisper (mono at 16kHz)
-        let mut audio_data = Self::pcm_bytes_to_f32(&request.audio);
+        let mut audio_data = clip_to_f32_mono(&request.audio);
 
         // Resample to 16kHz if needed
-        if request.sample_rate != 16000 {
-            audio_data = Self::resample_audio(&audio_data, request.sample_rate, 16000);
+        if request.audio.sample_rate != 16000 {
+            audio_data = resample(&audio_data, request.audio.sample_rate, 16000);
         }
 
         // Transcribe
@@ -700,16 +681,17 @@ impl TranscriptionProvider for LocalWhisperTranscriptionProvider {
 
         let text = engine.transcribe_pcm(&audio_data)?;
 
-        debug!("Local Whisper transcription: {}", text);
-
-        Ok(TranscriptionResponse {
+        let transcription = TranscriptionResponse {
             text,
             confidence: None,
             language: Some("en".to_string()),
-            duration_ms: request.audio.len() as u64 * 1000 / request.sample_rate as u64,
+            duration_ms: request.audio.duration_ms(),
             segments: None,
             completed_text: None,
-        })
+            format: TranscriptFormat::Plain,
+        };
+        trace_transcription(&transcription, started);
+        Ok(transcription)
     }
 
     fn is_configured(&self) -> bool {
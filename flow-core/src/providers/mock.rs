@@ -0,0 +1,127 @@
+//! Deterministic completion provider for demos and tests
+//!
+//! Built with the `demo` feature. Instead of calling a model it answers with
+//! canned text for the request's [`WritingMode`], so examples and downstream
+//! tests can drive the real [`AdaptivePipeline`](crate::AdaptivePipeline) and
+//! get the same output on every run, offline.
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::types::WritingMode;
+
+use super::completion::{FinishReason, TokenUsage};
+use super::{CompletionProvider, CompletionRequest, CompletionResponse};
+
+/// Canned reply per mode for the sample dictation "running late, be there in 10"
+const DEFAULT_RESPONSES: &[(WritingMode, &str)] = &[
+    (
+        WritingMode::Formal,
+        "I am running a few minutes late and will be there in 10 minutes.",
+    ),
+    (WritingMode::Casual, "Running late, be there in 10."),
+    (WritingMode::VeryCasual, "running late, there in 10"),
+    (WritingMode::Excited, "Running late, be there in 10!"),
+];
+
+/// Answers every request with fixed text for its writing mode
+#[derive(Debug, Clone)]
+pub struct MockCompletionProvider {
+    responses: HashMap<WritingMode, String>,
+}
+
+impl MockCompletionProvider {
+    /// Reply with the built-in sample for each mode
+    pub fn new() -> Self {
+        Self {
+            responses: DEFAULT_RESPONSES
+                .iter()
+                .map(|(mode, text)| (*mode, text.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Reply with `text` to requests in `mode`
+    pub fn with_response(mut self, mode: WritingMode, text: impl Into<String>) -> Self {
+        self.responses.insert(mode, text.into());
+        self
+    }
+
+    /// Text returned for `mode`
+    pub fn response(&self, mode: WritingMode) -> &str {
+        self.responses.get(&mode).map_or("", String::as_str)
+    }
+}
+
+impl Default for MockCompletionProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for MockCompletionProvider {
+    fn name(&self) -> &'static str {
+        "Mock"
+    }
+
+    fn provider_name(&self) -> &str {
+        "mock"
+    }
+
+    fn model_name(&self) -> &str {
+        "mock"
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        Ok(CompletionResponse {
+            text: self.response(request.mode).to_string(),
+            usage: Some(TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }),
+            model: None,
+            finish_reason: FinishReason::Stop,
+            prompt_truncated: false,
+            estimated_cost_usd: Some(0.0),
+        })
+    }
+
+    fn is_configured(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contacts::ContactInput;
+    use crate::pipeline::AdaptivePipeline;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_mock_through_pipeline() {
+        let pipeline = AdaptivePipeline::new(Arc::new(MockCompletionProvider::new()));
+        let adapt = |name: &str| {
+            let contact = ContactInput {
+                name: name.to_string(),
+                organization: String::new(),
+                relationship: None,
+            };
+            futures::executor::block_on(pipeline.adapt("running late be there in 10", contact))
+                .unwrap()
+        };
+
+        assert_eq!(
+            adapt("Dr. Smith"),
+            "I am running a few minutes late and will be there in 10 minutes."
+        );
+        assert_eq!(adapt("Mom"), "Running late, be there in 10.");
+
+        let provider =
+            MockCompletionProvider::new().with_response(WritingMode::Casual, "omw, 10 min");
+        assert_eq!(provider.response(WritingMode::Casual), "omw, 10 min");
+    }
+}
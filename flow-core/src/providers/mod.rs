@@ -1,27 +1,68 @@
 //! Provider abstraction layer for transcription and completion services
 //!
 //! Supports pluggable providers for cloud (OpenAI, ElevenLabs, Anthropic, Base10) and local services.
+mod azure;
 mod base10;
 mod completion;
+mod factory;
+mod fallback;
 mod gemini;
+mod http;
+mod language_routing;
 mod local_whisper;
+#[cfg(any(test, feature = "demo"))]
+mod mock;
 mod openai;
 mod openrouter;
+mod passthrough;
+mod pricing;
+mod rate_limit;
+mod registry;
+mod rule_based;
 mod streaming;
+mod streaming_transcription;
 mod transcription;
 
+pub use azure::{AZURE_OPENAI_API_VERSION, AzureOpenAIProvider};
 pub use base10::{
     Base10TranscriptionProvider, CorrectionPair, CorrectionValidation, validate_corrections,
 };
-pub use completion::{CompletionProvider, CompletionRequest, CompletionResponse, TokenUsage};
+pub use completion::{
+    CharTokenEstimator, ChatMessage, ChatRole, CompletionProvider, CompletionRequest,
+    CompletionResponse, Constraints, FIRST_PERSON_INSTRUCTION, FinishReason, LengthHint,
+    RecipientContext, TokenEstimator, TokenUsage,
+};
+pub use factory::{build_from_storage, build_from_storage_with};
+pub use fallback::{FallbackCompletionProvider, FallbackTranscriptionProvider};
 pub use gemini::{GeminiCompletionProvider, GeminiTranscriptionProvider};
+pub use http::{
+    CustomHeaders, HeaderHttpClient, HttpClient, ReqwestHttpClient, default_http_client,
+};
+pub use language_routing::{LanguageRoutingProvider, LanguageSource, RoutingDecision};
 pub use local_whisper::{LocalWhisperTranscriptionProvider, WhisperModel};
+#[cfg(any(test, feature = "demo"))]
+pub use mock::MockCompletionProvider;
 pub use openai::{OpenAICompletionProvider, OpenAITranscriptionProvider};
 pub use openrouter::OpenRouterCompletionProvider;
+pub use passthrough::PassthroughCompletionProvider;
+pub use pricing::{ModelPrice, Pricing};
+pub use rate_limit::{
+    RateLimitedCompletionProvider, RateLimitedTranscriptionProvider, RateLimiter, RateLimiters,
+};
+pub use registry::{CompletionFactory, ProviderRegistry, ProviderSettings};
+pub use rule_based::RuleBasedAdapter;
 pub use streaming::{
-    CompletionChunk, CompletionStream, StreamingCompletionProvider, collect_stream,
+    CompletionChunk, CompletionStream, PartialCompletion, SseDecoder, StreamError,
+    StreamingCompletionProvider, collect_stream, collect_stream_partial,
+    collect_stream_partial_with, collect_stream_with, sse_completion_stream, with_reconnect,
+};
+pub use streaming_transcription::{
+    AudioChunkStream, ChunkedTranscriptionProvider, StreamingTranscriptionProvider,
+    TranscriptionStream,
 };
 pub use transcription::{
-    CompletionParams as TranscriptionCompletionParams, TranscriptionProvider, TranscriptionRequest,
-    TranscriptionResponse,
+    CompletionParams as TranscriptionCompletionParams, CueOptions, DEFAULT_WINDOW_CONCURRENCY,
+    FILE_SAMPLE_RATE, ResponseFormat, TranscriptFormat, TranscriptionProvider,
+    TranscriptionRequest, TranscriptionResponse, TranscriptionSegment, WordTiming, to_srt,
+    to_srt_with, to_vtt, to_vtt_with, transcribe_long, transcribe_long_with,
 };
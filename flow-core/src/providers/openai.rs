@@ -1,26 +1,90 @@
 //! OpenAI provider implementations for Whisper transcription and GPT completion
 
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::{Method, RequestBuilder};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, error, instrument, trace};
 
 use crate::error::{Error, Result};
-use crate::types::WritingMode;
 
-use super::completion::TokenUsage;
+use super::completion::{
+    ChatMessage, DEFAULT_TEMPERATURE, FinishReason, TokenUsage, apply_constraints, fit_context,
+    restore_redacted, trace_completion,
+};
+use super::http::{
+    CustomHeaders, HttpClient, build_request, decode_body, default_http_client, read_json,
+};
+use super::transcription::trace_transcription;
 use super::{
-    CompletionProvider, CompletionRequest, CompletionResponse, TranscriptionProvider,
-    TranscriptionRequest, TranscriptionResponse,
+    CompletionProvider, CompletionRequest, CompletionResponse, Pricing, ResponseFormat,
+    TranscriptFormat, TranscriptionProvider, TranscriptionRequest, TranscriptionResponse,
+    TranscriptionSegment,
 };
 
 const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
 
+/// Context window of the GPT-4o family
+const OPENAI_CONTEXT_TOKENS: u32 = 128_000;
+
+/// Whisper only reads the last 224 prompt tokens, roughly 800 characters of English
+const WHISPER_PROMPT_MAX_CHARS: usize = 800;
+
+/// An Azure OpenAI deployment that stands in for api.openai.com
+#[derive(Debug, Clone)]
+pub(super) struct AzureDeployment {
+    pub(super) endpoint: String,
+    pub(super) deployment: String,
+    pub(super) api_version: String,
+}
+
+impl AzureDeployment {
+    /// `{endpoint}/openai/deployments/{deployment}/{path}?api-version={api_version}`
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/openai/deployments/{}/{}?api-version={}",
+            self.endpoint, self.deployment, path, self.api_version
+        )
+    }
+}
+
+/// POST to `path`, authenticated the way OpenAI or Azure expects
+fn post(
+    base_url: &str,
+    azure: Option<&AzureDeployment>,
+    path: &str,
+    api_key: Option<&str>,
+) -> RequestBuilder {
+    let (url, auth) = match azure {
+        Some(azure) => (
+            azure.url(path),
+            api_key.map(|key| ("api-key", key.to_string())),
+        ),
+        None => (
+            format!("{}/{}", base_url, path),
+            api_key.map(|key| ("Authorization", format!("Bearer {}", key))),
+        ),
+    };
+    let request = build_request(Method::POST, url);
+    match auth {
+        Some((name, value)) => request.header(name, value),
+        None => request,
+    }
+}
+
 /// OpenAI Whisper transcription provider
+///
+/// Uses `whisper-1` unless the provider or the request picks another model such
+/// as `gpt-4o-transcribe` or `gpt-4o-mini-transcribe`. Those newer models only
+/// answer in `json` or `text`; asking them for segments or subtitles is an error.
 pub struct OpenAITranscriptionProvider {
-    client: Client,
+    http: Arc<dyn HttpClient>,
+    headers: CustomHeaders,
     api_key: Option<String>,
     model: String,
+    base_url: String,
+    azure: Option<AzureDeployment>,
 }
 
 impl OpenAITranscriptionProvider {
@@ -29,9 +93,12 @@ impl OpenAITranscriptionProvider {
         let key = api_key.or_else(|| std::env::var("OPENAI_API_KEY").ok());
 
         Self {
-            client: Client::new(),
+            http: default_http_client(),
+            headers: CustomHeaders::new(),
             api_key: key,
             model: "whisper-1".to_string(),
+            base_url: OPENAI_API_BASE.to_string(),
+            azure: None,
         }
     }
 
@@ -41,10 +108,65 @@ impl OpenAITranscriptionProvider {
         self
     }
 
-    fn api_key(&self) -> Result<&str> {
-        self.api_key
-            .as_deref()
-            .ok_or_else(|| Error::ProviderNotConfigured("OpenAI API key not set".to_string()))
+    /// Point at an OpenAI-compatible server (Ollama, LM Studio, the base10 worker, ...)
+    /// Custom endpoints work without an API key, since local servers usually don't check one
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Send requests to an Azure OpenAI deployment, authenticated with `api_key`
+    pub(super) fn with_azure(mut self, azure: AzureDeployment, api_key: Option<String>) -> Self {
+        self.azure = Some(azure);
+        self.api_key = api_key;
+        self
+    }
+
+    /// Send requests through `http` instead of the network (e.g. a test double)
+    pub fn with_http_client(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Send `name: value` with every request, e.g. a proxy's own auth header
+    /// A header the provider sets itself, like its `Authorization`, is kept.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// [`with_header`](Self::with_header) for each of `headers`
+    pub fn with_headers<I, K, V>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (name, value) in headers {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    /// Send `name: value` in place of the provider's own header of that name
+    pub fn with_header_override(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.headers.insert_override(name, value);
+        self
+    }
+
+    /// API key to send, if any; only the official endpoint requires one
+    fn api_key(&self) -> Result<Option<&str>> {
+        match self.api_key.as_deref() {
+            Some(key) => Ok(Some(key)),
+            None if self.azure.is_none() && self.base_url != OPENAI_API_BASE => Ok(None),
+            None => Err(Error::ProviderNotConfigured(
+                "OpenAI API key not set".to_string(),
+            )),
+        }
     }
 }
 
@@ -55,6 +177,33 @@ struct WhisperResponse {
     language: Option<String>,
     #[serde(default)]
     duration: Option<f64>,
+    /// Only present for `verbose_json`
+    #[serde(default)]
+    segments: Option<Vec<WhisperSegment>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WhisperSegment {
+    text: String,
+    /// Seconds from the start of the audio
+    start: f64,
+    end: f64,
+}
+
+impl From<WhisperSegment> for TranscriptionSegment {
+    fn from(segment: WhisperSegment) -> Self {
+        Self {
+            text: segment.text.trim().to_string(),
+            start_ms: (segment.start * 1000.0) as u64,
+            end_ms: (segment.end * 1000.0) as u64,
+            confidence: None,
+        }
+    }
+}
+
+/// Whether `model` is one of the GPT-4o transcription models, which only return json or text
+fn is_gpt4o_transcribe(model: &str) -> bool {
+    model.starts_with("gpt-4o") && model.contains("transcribe")
 }
 
 #[async_trait]
@@ -63,11 +212,36 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
         "OpenAI Whisper"
     }
 
+    fn provider_name(&self) -> &str {
+        "openai"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    #[instrument(
+        name = "transcription",
+        skip_all,
+        fields(provider = "openai", model = %request.model.as_deref().unwrap_or(&self.model))
+    )]
     async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+        let started = Instant::now();
         let api_key = self.api_key()?;
+        let model = request.model.as_deref().unwrap_or(&self.model);
+        let response_format = request.response_format.unwrap_or_default();
+        if is_gpt4o_transcribe(model)
+            && !matches!(response_format, ResponseFormat::Json | ResponseFormat::Text)
+        {
+            return Err(Error::Config(format!(
+                "{} only supports json and text responses, not {}",
+                model,
+                response_format.as_str()
+            )));
+        }
 
         // convert PCM to WAV format for the API
-        let wav_data = pcm_to_wav(&request.audio, request.sample_rate, 1);
+        let wav_data = request.audio.to_wav();
 
         // build multipart form
         let file_part = reqwest::multipart::Part::bytes(wav_data)
@@ -77,69 +251,98 @@ impl TranscriptionProvider for OpenAITranscriptionProvider {
 
         let mut form = reqwest::multipart::Form::new()
             .part("file", file_part)
-            .text("model", self.model.clone())
-            .text("response_format", "json");
+            .text("model", model.to_string())
+            .text("response_format", response_format.as_str());
 
         if let Some(lang) = &request.language {
             form = form.text("language", lang.clone());
         }
 
-        if let Some(prompt) = &request.prompt {
-            form = form.text("prompt", prompt.clone());
+        if let Some(prompt) = request.whisper_prompt(WHISPER_PROMPT_MAX_CHARS) {
+            form = form.text("prompt", prompt);
         }
 
         debug!("Sending transcription request to OpenAI Whisper");
 
+        let http_request = post(
+            &self.base_url,
+            self.azure.as_ref(),
+            "audio/transcriptions",
+            api_key,
+        );
         let response = self
-            .client
-            .post(format!("{}/audio/transcriptions", OPENAI_API_BASE))
-            .header("Authorization", format!("Bearer {}", api_key))
-            .multipart(form)
-            .send()
+            .headers
+            .send(self.http.as_ref(), http_request.multipart(form).build()?)
             .await?;
 
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("Whisper API error: {} - {}", status, error_text);
-            return Err(Error::Transcription(format!(
-                "Whisper API error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::provider(
+                status,
+                format!("Whisper API error: {} - {}", status, error_text),
+            ));
         }
 
-        let whisper_response: WhisperResponse = response.json().await?;
-
-        // estimate duration from audio size if not provided
-        let duration_ms = whisper_response
-            .duration
-            .map(|d| (d * 1000.0) as u64)
-            .unwrap_or_else(|| {
-                // PCM 16-bit mono at sample_rate
-                let samples = request.audio.len() / 2;
-                (samples as u64 * 1000) / request.sample_rate as u64
-            });
-
-        Ok(TranscriptionResponse {
-            text: whisper_response.text,
-            confidence: None, // Whisper doesn't provide confidence
-            language: whisper_response.language,
-            duration_ms,
-            segments: None,
-            completed_text: None,
-        })
+        let transcription = match response_format {
+            ResponseFormat::Json | ResponseFormat::VerboseJson => {
+                let whisper_response: WhisperResponse = read_json(response, "OpenAI").await?;
+
+                // estimate duration from audio size if not provided
+                let duration_ms = whisper_response
+                    .duration
+                    .map(|d| (d * 1000.0) as u64)
+                    .unwrap_or_else(|| request.audio.duration_ms());
+
+                TranscriptionResponse {
+                    text: whisper_response.text,
+                    confidence: None, // Whisper doesn't provide confidence
+                    language: whisper_response.language,
+                    duration_ms,
+                    segments: whisper_response
+                        .segments
+                        .map(|segments| segments.into_iter().map(Into::into).collect()),
+                    completed_text: None,
+                    format: TranscriptFormat::Plain,
+                }
+            }
+            ResponseFormat::Text | ResponseFormat::Srt | ResponseFormat::Vtt => {
+                let body = decode_body(&response.bytes().await?, "OpenAI");
+                let format = response_format.transcript_format();
+                TranscriptionResponse {
+                    // subtitles are passed through untouched
+                    text: match format {
+                        TranscriptFormat::Plain => body.trim().to_string(),
+                        TranscriptFormat::Srt | TranscriptFormat::Vtt => body,
+                    },
+                    confidence: None,
+                    language: request.language.clone(),
+                    duration_ms: request.audio.duration_ms(),
+                    segments: None,
+                    completed_text: None,
+                    format,
+                }
+            }
+        };
+        trace_transcription(&transcription, started);
+        Ok(transcription)
     }
 
     fn is_configured(&self) -> bool {
-        self.api_key.is_some()
+        self.api_key.is_some() || (self.azure.is_none() && self.base_url != OPENAI_API_BASE)
     }
 }
 
 /// OpenAI GPT completion provider
 pub struct OpenAICompletionProvider {
-    client: Client,
+    http: Arc<dyn HttpClient>,
+    headers: CustomHeaders,
     api_key: Option<String>,
     model: String,
+    base_url: String,
+    azure: Option<AzureDeployment>,
+    pricing: Pricing,
 }
 
 impl OpenAICompletionProvider {
@@ -148,9 +351,13 @@ impl OpenAICompletionProvider {
         let key = api_key.or_else(|| std::env::var("OPENAI_API_KEY").ok());
 
         Self {
-            client: Client::new(),
+            http: default_http_client(),
+            headers: CustomHeaders::new(),
             api_key: key,
             model: "gpt-4o-mini".to_string(),
+            base_url: OPENAI_API_BASE.to_string(),
+            azure: None,
+            pricing: Pricing::new(),
         }
     }
 
@@ -160,13 +367,74 @@ impl OpenAICompletionProvider {
         self
     }
 
-    fn api_key(&self) -> Result<&str> {
-        self.api_key
-            .as_deref()
-            .ok_or_else(|| Error::ProviderNotConfigured("OpenAI API key not set".to_string()))
+    /// Price responses with `pricing` instead of the built-in table
+    pub fn with_pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    /// Point at an OpenAI-compatible server (Ollama, LM Studio, the base10 worker, ...)
+    /// Custom endpoints work without an API key, since local servers usually don't check one
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
     }
 
-    fn build_system_prompt(&self, mode: WritingMode, app_context: Option<&str>) -> String {
+    /// Send requests to an Azure OpenAI deployment, authenticated with `api_key`
+    pub(super) fn with_azure(mut self, azure: AzureDeployment, api_key: Option<String>) -> Self {
+        self.azure = Some(azure);
+        self.api_key = api_key;
+        self
+    }
+
+    /// Send requests through `http` instead of the network (e.g. a test double)
+    pub fn with_http_client(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Send `name: value` with every request, e.g. a proxy's own auth header
+    /// A header the provider sets itself, like its `Authorization`, is kept.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// [`with_header`](Self::with_header) for each of `headers`
+    pub fn with_headers<I, K, V>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (name, value) in headers {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    /// Send `name: value` in place of the provider's own header of that name
+    pub fn with_header_override(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.headers.insert_override(name, value);
+        self
+    }
+
+    /// API key to send, if any; only the official endpoint requires one
+    fn api_key(&self) -> Result<Option<&str>> {
+        match self.api_key.as_deref() {
+            Some(key) => Ok(Some(key)),
+            None if self.azure.is_none() && self.base_url != OPENAI_API_BASE => Ok(None),
+            None => Err(Error::ProviderNotConfigured(
+                "OpenAI API key not set".to_string(),
+            )),
+        }
+    }
+
+    fn build_system_prompt(&self, style: &str, app_context: Option<&str>) -> String {
         let mut prompt = String::from(
             "You are a text formatter. The user will provide raw transcribed text wrapped in <TRANSCRIPTION> tags. \
              Reformat ONLY the text inside according to the style below. Output the reformatted text exactly as it would \
@@ -174,7 +442,7 @@ impl OpenAICompletionProvider {
         );
 
         prompt.push_str("Formatting style: ");
-        prompt.push_str(mode.prompt_modifier());
+        prompt.push_str(style);
 
         if let Some(context) = app_context {
             prompt.push_str("\n\nContext: User is typing in ");
@@ -193,12 +461,8 @@ struct ChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
     temperature: f32,
-}
-
-#[derive(Debug, Serialize)]
-struct ChatMessage {
-    role: String,
-    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -211,6 +475,7 @@ struct ChatResponse {
 #[derive(Debug, Deserialize)]
 struct ChatChoice {
     message: ChatMessageResponse,
+    finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -231,149 +496,373 @@ impl CompletionProvider for OpenAICompletionProvider {
         "OpenAI GPT"
     }
 
-    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+    fn provider_name(&self) -> &str {
+        "openai"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    #[instrument(name = "completion", skip_all, fields(provider = "openai", model = %self.model))]
+    async fn complete(&self, mut request: CompletionRequest) -> Result<CompletionResponse> {
+        let started = Instant::now();
         let api_key = self.api_key()?;
+        let redacted = request.apply_redaction();
+        let prompt_truncated = fit_context(
+            &mut request,
+            OPENAI_CONTEXT_TOKENS,
+            &self.build_system_prompt("", None),
+        );
+        trace!(input = %request.text, "Completion input");
 
-        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
-            self.build_system_prompt(request.mode, request.app_context.as_deref())
+        let conversation = request.conversation();
+        let style = request.style_instruction();
+        let max_tokens = request.effective_max_tokens();
+        let system_prompt = request.resolve_system_prompt(|| {
+            self.build_system_prompt(&style, request.app_context.as_deref())
         });
 
-        // Add shortcut preservation instruction if present
-        if let Some(preservation) = request.shortcut_preservation {
-            system_prompt.push_str(&preservation);
-        }
+        let mut messages = vec![ChatMessage::system(system_prompt)];
+        messages.extend(conversation);
 
         let chat_request = ChatRequest {
             model: self.model.clone(),
-            messages: vec![
-                ChatMessage {
-                    role: "system".to_string(),
-                    content: system_prompt,
-                },
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
-                },
-            ],
-            max_tokens: request.max_tokens,
-            temperature: 0.3, // low temperature for consistent formatting
+            messages,
+            max_tokens,
+            temperature: request.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            seed: request.seed,
         };
 
         debug!("Sending completion request to OpenAI");
 
-        let response = self
-            .client
-            .post(format!("{}/chat/completions", OPENAI_API_BASE))
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&chat_request)
-            .send()
-            .await?;
+        let http_request = post(
+            &self.base_url,
+            self.azure.as_ref(),
+            "chat/completions",
+            api_key,
+        )
+        .header("Content-Type", "application/json")
+        .json(&chat_request)
+        .build()?;
+        let response = self.headers.send(self.http.as_ref(), http_request).await?;
 
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             error!("OpenAI API error: {} - {}", status, error_text);
-            return Err(Error::Completion(format!(
-                "OpenAI API error: {} - {}",
-                status, error_text
-            )));
+            return Err(Error::provider(
+                status,
+                format!("OpenAI API error: {} - {}", status, error_text),
+            ));
         }
 
-        let chat_response: ChatResponse = response.json().await?;
+        let chat_response: ChatResponse = read_json(response, "OpenAI").await?;
 
-        let text = chat_response
+        let choice = chat_response
             .choices
             .into_iter()
             .next()
-            .map(|c| c.message.content)
             .ok_or_else(|| Error::Completion("No completion returned".to_string()))?;
 
-        Ok(CompletionResponse {
-            text,
-            usage: chat_response.usage.map(|u| TokenUsage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
-            }),
+        let usage = chat_response.usage.map(|u| TokenUsage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+            total_tokens: u.total_tokens,
+        });
+        let estimated_cost_usd = usage
+            .as_ref()
+            .and_then(|usage| self.pricing.estimate("openai", &chat_response.model, usage));
+
+        let completion = CompletionResponse {
+            text: apply_constraints(
+                request.constraints.as_ref(),
+                &request.text,
+                restore_redacted(redacted.as_ref(), choice.message.content),
+            ),
+            usage,
             model: Some(chat_response.model),
-        })
+            finish_reason: FinishReason::from_provider(choice.finish_reason.as_deref()),
+            prompt_truncated,
+            estimated_cost_usd,
+        };
+        trace_completion(&completion, started);
+        Ok(completion)
     }
 
     fn is_configured(&self) -> bool {
-        self.api_key.is_some()
+        self.api_key.is_some() || (self.azure.is_none() && self.base_url != OPENAI_API_BASE)
     }
 }
 
-/// Convert raw PCM data to WAV format
-fn pcm_to_wav(pcm: &[u8], sample_rate: u32, channels: u16) -> Vec<u8> {
-    let bits_per_sample: u16 = 16;
-    let byte_rate = sample_rate * u32::from(channels) * u32::from(bits_per_sample) / 8;
-    let block_align = channels * bits_per_sample / 8;
-    let data_size = pcm.len() as u32;
-    let file_size = 36 + data_size;
-
-    let mut wav = Vec::with_capacity(44 + pcm.len());
-
-    // RIFF header
-    wav.extend_from_slice(b"RIFF");
-    wav.extend_from_slice(&file_size.to_le_bytes());
-    wav.extend_from_slice(b"WAVE");
-
-    // fmt chunk
-    wav.extend_from_slice(b"fmt ");
-    wav.extend_from_slice(&16u32.to_le_bytes()); // chunk size
-    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM format
-    wav.extend_from_slice(&channels.to_le_bytes());
-    wav.extend_from_slice(&sample_rate.to_le_bytes());
-    wav.extend_from_slice(&byte_rate.to_le_bytes());
-    wav.extend_from_slice(&block_align.to_le_bytes());
-    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
-
-    // data chunk
-    wav.extend_from_slice(b"data");
-    wav.extend_from_slice(&data_size.to_le_bytes());
-    wav.extend_from_slice(pcm);
-
-    wav
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_pcm_to_wav() {
-        // 1 second of silence at 16kHz mono
-        let pcm = vec![0u8; 32000]; // 16000 samples * 2 bytes
-        let wav = pcm_to_wav(&pcm, 16000, 1);
-
-        // check RIFF header
-        assert_eq!(&wav[0..4], b"RIFF");
-        assert_eq!(&wav[8..12], b"WAVE");
-        assert_eq!(&wav[12..16], b"fmt ");
-        assert_eq!(&wav[36..40], b"data");
-
-        // total size should be 44 header + 32000 data
-        assert_eq!(wav.len(), 44 + 32000);
-    }
+    use crate::providers::http::testing::canned;
+    use crate::providers::{CharTokenEstimator, ChatRole, Constraints};
+    use crate::types::WritingMode;
 
     #[test]
     fn test_system_prompt_building() {
         let provider = OpenAICompletionProvider::new(None);
 
-        let prompt = provider.build_system_prompt(WritingMode::Formal, None);
+        let prompt = provider.build_system_prompt(WritingMode::Formal.prompt_modifier(), None);
         assert!(prompt.contains("professional"));
         assert!(prompt.contains("Transform slang into professional alternatives"));
         assert!(prompt.contains("<TRANSCRIPTION>"));
         assert!(prompt.contains("Do NOT generate new content"));
 
-        let prompt = provider.build_system_prompt(WritingMode::VeryCasual, Some("Slack"));
+        let prompt =
+            provider.build_system_prompt(WritingMode::VeryCasual.prompt_modifier(), Some("Slack"));
         assert!(prompt.contains("texting style"));
         assert!(prompt.contains("Slack"));
         assert!(prompt.contains("exactly as it would be typed"));
     }
 
+    #[test]
+    fn test_request_style_uses_contact() {
+        use crate::types::ContactCategory;
+
+        let request = CompletionRequest::new("hey").with_mode(WritingMode::Excited);
+        assert_eq!(
+            request.style_instruction(),
+            WritingMode::Excited.prompt_modifier()
+        );
+
+        let request = request.with_contact("Alex", ContactCategory::Partner);
+        let provider = OpenAICompletionProvider::new(None);
+        let prompt = provider.build_system_prompt(&request.style_instruction(), None);
+        assert!(prompt.contains("Alex"));
+        assert!(prompt.contains("romantic partner"));
+    }
+
+    #[test]
+    fn test_completion_request_builder() {
+        let request = CompletionRequest::new("um hi there");
+        assert_eq!(request.mode, WritingMode::default());
+        assert!(request.system_prompt.is_none());
+        assert!(request.temperature.is_none());
+
+        let request = request
+            .with_mode(WritingMode::Formal)
+            .with_system_prompt("Fix typos only.")
+            .with_temperature(0.0)
+            .with_max_tokens(64);
+        assert_eq!(request.mode, WritingMode::Formal);
+        assert_eq!(request.system_prompt.as_deref(), Some("Fix typos only."));
+        assert_eq!(request.temperature, Some(0.0));
+        assert_eq!(request.max_tokens, Some(64));
+    }
+
+    #[test]
+    fn test_system_prompt_framing() {
+        let provider = OpenAICompletionProvider::new(None);
+        let request = CompletionRequest::new("hi")
+            .with_mode(WritingMode::Formal)
+            .with_system_prefix("Match the user's original language.")
+            .with_system_suffix("Never use em-dashes.");
+        let built = provider.build_system_prompt(&request.style_instruction(), None);
+        let prompt = request.resolve_system_prompt(|| built.clone());
+        assert_eq!(
+            prompt,
+            format!(
+                "Match the user's original language.\n\n{}\n\nNever use em-dashes.",
+                built
+            )
+        );
+
+        // an explicit system prompt replaces the mode prompt but is still framed
+        let request = request
+            .with_system_prompt("Fix typos only.")
+            .with_shortcut_preservation("Keep \"brb\".");
+        assert_eq!(
+            request.resolve_system_prompt(|| built.clone()),
+            "Match the user's original language.\n\nFix typos only.Keep \"brb\".\n\nNever use em-dashes."
+        );
+
+        // no prefix or suffix leaves the prompt untouched
+        let request = CompletionRequest::new("hi");
+        assert_eq!(request.resolve_system_prompt(|| "base".to_string()), "base");
+    }
+
+    #[test]
+    fn test_conversation_history() {
+        let request = CompletionRequest::new("hey can we move the meeting to 3");
+        let conversation = request.conversation();
+        assert_eq!(conversation.len(), 1);
+        assert!(conversation[0].content.starts_with("<TRANSCRIPTION>"));
+
+        let request = request.follow_up("Hi, could we move the meeting to 3pm?", "make it shorter");
+        let conversation = request.conversation();
+        assert_eq!(conversation.len(), 3);
+        assert_eq!(conversation[1].role, ChatRole::Assistant);
+        assert_eq!(conversation[2], ChatMessage::user("make it shorter"));
+
+        let json = serde_json::to_value(&conversation[1]).unwrap();
+        assert_eq!(json["role"], "assistant");
+
+        // placeholders stay unique across turns
+        let mut request = CompletionRequest::new("send it to b@c.io instead")
+            .with_history(vec![ChatMessage::user("email a@b.io the notes")])
+            .with_redaction(crate::redaction::Redactor::new());
+        let redacted = request.apply_redaction().unwrap();
+        assert_eq!(
+            request.messages[0].content,
+            "email [REDACTED_EMAIL_1] the notes"
+        );
+        assert_eq!(request.text, "send it to [REDACTED_EMAIL_2] instead");
+        assert_eq!(
+            redacted.restore("Send it to [REDACTED_EMAIL_2]."),
+            "Send it to b@c.io."
+        );
+    }
+
+    #[test]
+    fn test_truncate_to_keeps_system_prompt_and_tail() {
+        let dictation = (0..400)
+            .map(|n| format!("word{}", n))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut request = CompletionRequest::new(dictation)
+            .with_system_prompt("Format this.")
+            .with_history(vec![ChatMessage::user("earlier turn")]);
+        let estimator = CharTokenEstimator;
+        assert!(request.estimated_tokens(&estimator) > 200);
+
+        assert!(request.truncate_to(200));
+        assert!(request.truncated);
+        assert!(request.estimated_tokens(&estimator) <= 200);
+        assert!(request.messages.is_empty());
+        assert_eq!(request.system_prompt.as_deref(), Some("Format this."));
+        assert!(request.text.ends_with("word399"));
+        assert!(request.text.starts_with("word"));
+
+        // already within budget: untouched
+        let mut request = CompletionRequest::new("running late");
+        assert!(!request.truncate_to(1000));
+        assert!(!request.truncated);
+        assert_eq!(request.text, "running late");
+
+        // a tiny budget reserves the reply and provider prompt first
+        let mut request = CompletionRequest::new("a ".repeat(2000))
+            .with_system_prompt("Format this.")
+            .with_max_tokens(100);
+        assert!(fit_context(&mut request, 400, &"x".repeat(400)));
+        assert!(request.estimated_tokens(&estimator) <= 200);
+    }
+
+    #[test]
+    fn test_constraints() {
+        let constraints = Constraints {
+            preserve_emoji: true,
+            max_emoji: 1,
+            preserve_questions: true,
+            add_emoji: false,
+        };
+        let request = CompletionRequest::new("miss you ❤️ are you home?")
+            .with_mode(WritingMode::Excited)
+            .with_constraints(constraints);
+        let style = request.style_instruction();
+        assert!(style.contains("Keep every emoji"));
+        assert!(style.contains("at most 1 emoji"));
+        assert!(style.contains("question"));
+
+        // added emoji go first; the dictated heart survives
+        assert_eq!(
+            constraints.enforce(&request.text, "Miss you ❤️! Are you home? 😍🥰".to_string()),
+            "Miss you ❤️! Are you home?"
+        );
+        // ZWJ sequences and skin tones count as one emoji
+        let capped = Constraints {
+            max_emoji: 1,
+            ..Constraints::default()
+        };
+        assert_eq!(capped.enforce("hi", "hi 👨‍👩‍👧 👍🏽 🎉".to_string()), "hi 👨‍👩‍👧");
+        assert_eq!(Constraints::default().instruction(), None);
+        assert_eq!(
+            Constraints::default().enforce("hi", "hi 🎉🎉".to_string()),
+            "hi 🎉🎉"
+        );
+    }
+
+    #[test]
+    fn test_chat_request_seed_serialization() {
+        let mut chat_request = ChatRequest {
+            model: "gpt-4o-mini".to_string(),
+            messages: vec![],
+            max_tokens: None,
+            temperature: 0.0,
+            seed: Some(42),
+        };
+        let json = serde_json::to_value(&chat_request).unwrap();
+        assert_eq!(json["seed"], 42);
+        assert_eq!(json["temperature"], 0.0);
+
+        chat_request.seed = None;
+        let json = serde_json::to_value(&chat_request).unwrap();
+        assert!(json.get("seed").is_none());
+    }
+
+    #[test]
+    fn test_finish_reason_parsing() {
+        let json = r#"{
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "message": {"content": "Hello, how are"},
+                "finish_reason": "length"
+            }]
+        }"#;
+        let chat_response: ChatResponse = serde_json::from_str(json).unwrap();
+        let reason = FinishReason::from_provider(chat_response.choices[0].finish_reason.as_deref());
+        assert_eq!(reason, FinishReason::Length);
+
+        assert_eq!(
+            FinishReason::from_provider(Some("stop")),
+            FinishReason::Stop
+        );
+        assert_eq!(
+            FinishReason::from_provider(Some("MAX_TOKENS")),
+            FinishReason::Length
+        );
+        assert_eq!(
+            FinishReason::from_provider(Some("SAFETY")),
+            FinishReason::ContentFilter
+        );
+        assert_eq!(
+            FinishReason::from_provider(Some("tool_calls")),
+            FinishReason::Other
+        );
+        assert_eq!(FinishReason::from_provider(None), FinishReason::Other);
+    }
+
+    #[test]
+    fn test_whisper_prompt_with_vocabulary() {
+        let request = TranscriptionRequest::new(vec![], 16000)
+            .with_prompt("Meeting notes.")
+            .with_vocabulary([
+                "Flowwhispr",
+                "  ",
+                "Priya Raman",
+                "flowwhispr",
+                "Kubernetes",
+            ]);
+
+        assert_eq!(
+            request.whisper_prompt(WHISPER_PROMPT_MAX_CHARS).as_deref(),
+            Some("Meeting notes. Flowwhispr, Priya Raman, Kubernetes")
+        );
+
+        // terms that don't fit are dropped whole
+        assert_eq!(
+            request.vocabulary_hint(24).as_deref(),
+            Some("Flowwhispr, Priya Raman")
+        );
+        assert_eq!(request.vocabulary_hint(5), None);
+
+        let bare = TranscriptionRequest::new(vec![], 16000);
+        assert_eq!(bare.whisper_prompt(WHISPER_PROMPT_MAX_CHARS), None);
+    }
+
     #[test]
     fn test_provider_not_configured() {
         let provider = OpenAITranscriptionProvider::new(None);
@@ -381,4 +870,122 @@ mod tests {
         // but in tests the env might be set, so we just verify the method works
         let _ = provider.is_configured();
     }
+
+    #[test]
+    fn test_custom_base_url() {
+        let provider = OpenAICompletionProvider::new(Some("sk-test".to_string()))
+            .with_base_url("http://localhost:11434/v1/");
+        assert_eq!(provider.base_url, "http://localhost:11434/v1");
+        assert_eq!(provider.api_key().unwrap(), Some("sk-test"));
+
+        // local servers don't need a key
+        let mut provider =
+            OpenAITranscriptionProvider::new(None).with_base_url("http://localhost:1234/v1");
+        provider.api_key = None;
+        assert!(provider.is_configured());
+        assert_eq!(provider.api_key().unwrap(), None);
+
+        let mut official = OpenAICompletionProvider::new(None);
+        official.api_key = None;
+        assert!(!official.is_configured());
+        assert!(official.api_key().is_err());
+    }
+
+    #[test]
+    fn test_completion_through_http_client() {
+        let http = canned(
+            200,
+            r#"{"model": "gpt-4o-mini", "choices": [{"message": {"content": "Hi there."}, "finish_reason": "stop"}], "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}}"#,
+        );
+        let provider = OpenAICompletionProvider::new(Some("sk-test".to_string()))
+            .with_http_client(http.clone());
+        let response =
+            futures::executor::block_on(provider.complete(CompletionRequest::new("hi there")))
+                .unwrap();
+        assert_eq!(response.text, "Hi there.");
+        let cost = response.estimated_cost_usd.unwrap();
+        assert!((cost - (12.0 * 0.00015 + 3.0 * 0.0006) / 1000.0).abs() < 1e-12);
+
+        let sent = http.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            sent[0].url().as_str(),
+            "https://api.openai.com/v1/chat/completions"
+        );
+        assert_eq!(sent[0].headers()["Authorization"], "Bearer sk-test");
+        drop(sent);
+
+        // an Azure-style key header rides along without replacing the bearer token
+        let http = canned(
+            200,
+            r#"{"model": "gpt-4o-mini", "choices": [{"message": {"content": "Hi."}, "finish_reason": "stop"}]}"#,
+        );
+        let provider = OpenAICompletionProvider::new(Some("sk-test".to_string()))
+            .with_http_client(http.clone())
+            .with_headers([("api-key", "azure-key"), ("Authorization", "Bearer other")]);
+        futures::executor::block_on(provider.complete(CompletionRequest::new("hi"))).unwrap();
+        let sent = http.sent.lock().unwrap();
+        assert_eq!(sent[0].headers()["api-key"], "azure-key");
+        assert_eq!(sent[0].headers()["Authorization"], "Bearer sk-test");
+
+        let provider = OpenAICompletionProvider::new(Some("sk-bad".to_string()))
+            .with_http_client(canned(401, r#"{"error": "invalid api key"}"#));
+        let err = futures::executor::block_on(provider.complete(CompletionRequest::new("hi")))
+            .unwrap_err();
+        assert_eq!(err.status(), Some(401));
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_transcription_response_formats() {
+        let request = || TranscriptionRequest::new(vec![0u8; 3200], 16000);
+
+        let http = canned(
+            200,
+            r#"{"text": " Hello there.", "language": "english", "duration": 1.5, "segments": [{"text": " Hello there.", "start": 0.0, "end": 1.25}]}"#,
+        );
+        let provider =
+            OpenAITranscriptionProvider::new(Some("sk-test".to_string())).with_http_client(http);
+        let response = futures::executor::block_on(
+            provider.transcribe(request().with_response_format(ResponseFormat::VerboseJson)),
+        )
+        .unwrap();
+        assert_eq!(response.duration_ms, 1500);
+        assert_eq!(response.format, TranscriptFormat::Plain);
+        let segments = response.segments.unwrap();
+        assert_eq!(segments[0].text, "Hello there.");
+        assert_eq!(segments[0].end_ms, 1250);
+
+        let srt = "1\n00:00:00,000 --> 00:00:01,250\nHello there.\n\n";
+        let provider = OpenAITranscriptionProvider::new(Some("sk-test".to_string()))
+            .with_http_client(canned(200, srt));
+        let response = futures::executor::block_on(
+            provider.transcribe(request().with_response_format(ResponseFormat::Srt)),
+        )
+        .unwrap();
+        assert_eq!(response.text, srt);
+        assert_eq!(response.format, TranscriptFormat::Srt);
+
+        // gpt-4o transcription models can't produce subtitles
+        let err = futures::executor::block_on(
+            provider.transcribe(
+                request()
+                    .with_model("gpt-4o-transcribe")
+                    .with_response_format(ResponseFormat::Vtt),
+            ),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+    }
+
+    #[test]
+    fn test_provider_and_model_names() {
+        let provider = OpenAICompletionProvider::new(None).with_model("gpt-4o");
+        assert_eq!(provider.provider_name(), "openai");
+        assert_eq!(provider.model_name(), "gpt-4o");
+
+        let provider = OpenAITranscriptionProvider::new(None);
+        assert_eq!(provider.provider_name(), "openai");
+        assert!(!provider.model_name().is_empty());
+    }
 }
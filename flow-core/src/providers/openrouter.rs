@@ -1,23 +1,32 @@
 //! OpenRouter provider implementation for LLM completion
 
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, error, instrument, trace};
 
 use crate::error::{Error, Result};
-use crate::types::WritingMode;
 
-use super::completion::TokenUsage;
-use super::{CompletionProvider, CompletionRequest, CompletionResponse};
+use super::completion::{
+    ChatMessage, DEFAULT_TEMPERATURE, FinishReason, TokenUsage, apply_constraints, fit_context,
+    restore_redacted, trace_completion,
+};
+use super::http::{HttpClient, build_request, default_http_client, read_json};
+use super::{CompletionProvider, CompletionRequest, CompletionResponse, Pricing};
 
 const OPENROUTER_API_BASE: &str = "https://openrouter.ai/api/v1";
 
+/// Context window assumed for routed models; conservative since they vary
+const OPENROUTER_CONTEXT_TOKENS: u32 = 32_000;
+
 /// OpenRouter completion provider
 pub struct OpenRouterCompletionProvider {
-    client: Client,
+    http: Arc<dyn HttpClient>,
     api_key: Option<String>,
     models: Vec<String>,
+    pricing: Pricing,
 }
 
 impl OpenRouterCompletionProvider {
@@ -26,12 +35,13 @@ impl OpenRouterCompletionProvider {
         let key = api_key.or_else(|| std::env::var("OPENROUTER_API_KEY").ok());
 
         Self {
-            client: Client::new(),
+            http: default_http_client(),
             api_key: key,
             models: vec![
                 "meta-llama/llama-4-maverick:nitro".to_string(),
                 "openai/gpt-oss-120b:nitro".to_string(),
             ],
+            pricing: Pricing::new(),
         }
     }
 
@@ -47,13 +57,25 @@ impl OpenRouterCompletionProvider {
         self
     }
 
+    /// Price responses with `pricing` instead of the built-in table
+    pub fn with_pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    /// Send requests through `http` instead of the network (e.g. a test double)
+    pub fn with_http_client(mut self, http: Arc<dyn HttpClient>) -> Self {
+        self.http = http;
+        self
+    }
+
     fn api_key(&self) -> Result<&str> {
         self.api_key
             .as_deref()
             .ok_or_else(|| Error::ProviderNotConfigured("OpenRouter API key not set".to_string()))
     }
 
-    fn build_system_prompt(&self, mode: WritingMode, app_context: Option<&str>) -> String {
+    fn build_system_prompt(&self, style: &str, app_context: Option<&str>) -> String {
         let mut prompt = String::from(
             "You are a text formatter. The user will provide raw transcribed text wrapped in <TRANSCRIPTION> tags. \
              Reformat ONLY the text inside according to the style below. Output the reformatted text exactly as it would \
@@ -61,7 +83,7 @@ impl OpenRouterCompletionProvider {
         );
 
         prompt.push_str("Formatting style: ");
-        prompt.push_str(mode.prompt_modifier());
+        prompt.push_str(style);
 
         if let Some(context) = app_context {
             prompt.push_str("\n\nContext: User is typing in ");
@@ -81,6 +103,8 @@ struct ChatRequest {
     max_tokens: Option<u32>,
     temperature: f32,
     #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     provider: Option<ProviderConfig>,
 }
 
@@ -98,12 +122,6 @@ struct SortConfig {
     partition: String,
 }
 
-#[derive(Debug, Serialize)]
-struct ChatMessage {
-    role: String,
-    content: String,
-}
-
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     choices: Vec<ChatChoice>,
@@ -114,6 +132,7 @@ struct ChatResponse {
 #[derive(Debug, Deserialize)]
 struct ChatChoice {
     message: ChatMessageResponse,
+    finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -134,32 +153,47 @@ impl CompletionProvider for OpenRouterCompletionProvider {
         "OpenRouter"
     }
 
-    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+    fn provider_name(&self) -> &str {
+        "openrouter"
+    }
+
+    /// Primary model; OpenRouter may route to the fallbacks after it
+    fn model_name(&self) -> &str {
+        self.models.first().map(String::as_str).unwrap_or_default()
+    }
+
+    #[instrument(
+        name = "completion",
+        skip_all,
+        fields(provider = "openrouter", model = %self.models.join(","))
+    )]
+    async fn complete(&self, mut request: CompletionRequest) -> Result<CompletionResponse> {
+        let started = Instant::now();
         let api_key = self.api_key()?;
+        let redacted = request.apply_redaction();
+        let prompt_truncated = fit_context(
+            &mut request,
+            OPENROUTER_CONTEXT_TOKENS,
+            &self.build_system_prompt("", None),
+        );
+        trace!(input = %request.text, "Completion input");
 
-        let mut system_prompt = request.system_prompt.unwrap_or_else(|| {
-            self.build_system_prompt(request.mode, request.app_context.as_deref())
+        let conversation = request.conversation();
+        let style = request.style_instruction();
+        let max_tokens = request.effective_max_tokens().unwrap_or(1000);
+        let system_prompt = request.resolve_system_prompt(|| {
+            self.build_system_prompt(&style, request.app_context.as_deref())
         });
 
-        // Add shortcut preservation instruction if present
-        if let Some(preservation) = request.shortcut_preservation {
-            system_prompt.push_str(&preservation);
-        }
+        let mut messages = vec![ChatMessage::system(system_prompt)];
+        messages.extend(conversation);
 
         let chat_request = ChatRequest {
             models: self.models.clone(),
-            messages: vec![
-                ChatMessage {
-                    role: "system".to_string(),
-                    content: system_prompt,
-                },
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: format!("<TRANSCRIPTION>\n{}\n</TRANSCRIPTION>", request.text),
-                },
-            ],
-            max_tokens: Some(1000),
-            temperature: 0.3,
+            messages,
+            max_tokens: Some(max_tokens),
+            temperature: request.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            seed: request.seed,
             provider: Some(ProviderConfig {
                 allow_fallbacks: Some(true),
                 sort: Some(SortConfig {
@@ -174,14 +208,15 @@ impl CompletionProvider for OpenRouterCompletionProvider {
             self.models
         );
 
-        let response = self
-            .client
-            .post(format!("{}/chat/completions", OPENROUTER_API_BASE))
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&chat_request)
-            .send()
-            .await?;
+        let http_request = build_request(
+            Method::POST,
+            format!("{}/chat/completions", OPENROUTER_API_BASE),
+        )
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("Content-Type", "application/json")
+        .json(&chat_request)
+        .build()?;
+        let response = self.http.execute(http_request).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -190,19 +225,24 @@ impl CompletionProvider for OpenRouterCompletionProvider {
                 .await
                 .unwrap_or_else(|_| String::from("Unknown error"));
             error!("OpenRouter API error ({}): {}", status, error_text);
-            return Err(Error::Completion(format!(
-                "OpenRouter API error ({}): {}",
-                status, error_text
-            )));
+            return Err(Error::provider(
+                status,
+                format!("OpenRouter API error ({}): {}", status, error_text),
+            ));
         }
 
-        let chat_response: ChatResponse = response.json().await?;
+        let chat_response: ChatResponse = read_json(response, "OpenRouter").await?;
 
-        let text = chat_response
+        let choice = chat_response
             .choices
             .first()
-            .map(|choice| choice.message.content.clone())
             .ok_or_else(|| Error::Completion("No completion returned".to_string()))?;
+        let text = apply_constraints(
+            request.constraints.as_ref(),
+            &request.text,
+            restore_redacted(redacted.as_ref(), choice.message.content.clone()),
+        );
+        let finish_reason = FinishReason::from_provider(choice.finish_reason.as_deref());
 
         let usage = chat_response.usage.map(|u| TokenUsage {
             prompt_tokens: u.prompt_tokens,
@@ -210,13 +250,24 @@ impl CompletionProvider for OpenRouterCompletionProvider {
             total_tokens: u.total_tokens,
         });
 
+        // priced by the model that answered, which may be one of the fallbacks
+        let estimated_cost_usd = usage.as_ref().and_then(|usage| {
+            self.pricing
+                .estimate("openrouter", &chat_response.model, usage)
+        });
+
         debug!("Received completion from OpenRouter");
 
-        Ok(CompletionResponse {
+        let completion = CompletionResponse {
             text,
             usage,
             model: Some(chat_response.model),
-        })
+            finish_reason,
+            prompt_truncated,
+            estimated_cost_usd,
+        };
+        trace_completion(&completion, started);
+        Ok(completion)
     }
 
     fn is_configured(&self) -> bool {
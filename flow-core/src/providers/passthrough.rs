@@ -0,0 +1,79 @@
+//! Completion provider that returns the transcription unchanged
+//!
+//! Selected with `completion_provider = "none"`. Nothing leaves the machine and no
+//! tokens are spent, which also makes it a baseline for comparing against adapted output.
+
+use async_trait::async_trait;
+
+use crate::error::Result;
+
+use super::completion::{FinishReason, TokenUsage};
+use super::{CompletionProvider, CompletionRequest, CompletionResponse};
+
+/// No-op completion: the output is the input text
+pub struct PassthroughCompletionProvider;
+
+impl PassthroughCompletionProvider {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for PassthroughCompletionProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for PassthroughCompletionProvider {
+    fn name(&self) -> &'static str {
+        "Passthrough"
+    }
+
+    fn provider_name(&self) -> &str {
+        "none"
+    }
+
+    fn model_name(&self) -> &str {
+        "none"
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        Ok(CompletionResponse {
+            text: request.text,
+            usage: Some(TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }),
+            model: None,
+            finish_reason: FinishReason::Stop,
+            prompt_truncated: false,
+            // no request is made, so it's free
+            estimated_cost_usd: Some(0.0),
+        })
+    }
+
+    fn is_configured(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::WritingMode;
+
+    #[test]
+    fn test_passthrough_returns_input() {
+        let provider = PassthroughCompletionProvider::new();
+        let request =
+            CompletionRequest::new("um so yeah running late").with_mode(WritingMode::Formal);
+
+        let response = futures::executor::block_on(provider.complete(request)).unwrap();
+        assert_eq!(response.text, "um so yeah running late");
+        assert_eq!(response.usage.unwrap().total_tokens, 0);
+        assert!(provider.is_configured());
+    }
+}
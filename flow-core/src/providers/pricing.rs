@@ -0,0 +1,178 @@
+//! Per-model token prices for estimating what a completion cost
+//!
+//! Providers report token counts, not money. A [`Pricing`] table turns a
+//! response's [`TokenUsage`] into dollars so the UI can show "$0.0003" per
+//! dictation. The built-in prices are list prices at the time of writing and
+//! drift; apps can override any entry or start from [`Pricing::empty`].
+
+use std::collections::BTreeMap;
+
+use super::TokenUsage;
+
+/// Price of one model, in US dollars per 1,000 tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input_per_1k: f64,
+    pub output_per_1k: f64,
+}
+
+impl ModelPrice {
+    pub const fn new(input_per_1k: f64, output_per_1k: f64) -> Self {
+        Self {
+            input_per_1k,
+            output_per_1k,
+        }
+    }
+
+    /// Dollars spent on `usage`
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (f64::from(usage.prompt_tokens) * self.input_per_1k
+            + f64::from(usage.completion_tokens) * self.output_per_1k)
+            / 1000.0
+    }
+}
+
+/// Built-in (provider, model, price) entries
+const DEFAULT_PRICES: &[(&str, &str, ModelPrice)] = &[
+    ("openai", "gpt-4o-mini", ModelPrice::new(0.00015, 0.0006)),
+    ("openai", "gpt-4o", ModelPrice::new(0.0025, 0.01)),
+    ("openai", "gpt-4.1-nano", ModelPrice::new(0.0001, 0.0004)),
+    ("openai", "gpt-4.1-mini", ModelPrice::new(0.0004, 0.0016)),
+    ("openai", "gpt-4.1", ModelPrice::new(0.002, 0.008)),
+    (
+        "gemini",
+        "gemini-3-flash-preview",
+        ModelPrice::new(0.0005, 0.003),
+    ),
+    (
+        "gemini",
+        "gemini-2.5-flash",
+        ModelPrice::new(0.0003, 0.0025),
+    ),
+    (
+        "gemini",
+        "gemini-2.0-flash",
+        ModelPrice::new(0.0001, 0.0004),
+    ),
+    (
+        "openrouter",
+        "meta-llama/llama-4-maverick",
+        ModelPrice::new(0.00015, 0.0006),
+    ),
+    (
+        "openrouter",
+        "openai/gpt-oss-120b",
+        ModelPrice::new(0.0001, 0.0005),
+    ),
+    (
+        "openrouter",
+        "openai/gpt-4o-mini",
+        ModelPrice::new(0.00015, 0.0006),
+    ),
+];
+
+/// Token prices keyed by provider and model
+#[derive(Debug, Clone)]
+pub struct Pricing {
+    prices: BTreeMap<(String, String), ModelPrice>,
+}
+
+impl Pricing {
+    /// A table with no prices; every estimate is `None`
+    pub fn empty() -> Self {
+        Self {
+            prices: BTreeMap::new(),
+        }
+    }
+
+    /// The built-in prices for the default models of each provider
+    pub fn new() -> Self {
+        let mut pricing = Self::empty();
+        for (provider, model, price) in DEFAULT_PRICES {
+            pricing.set_price(*provider, *model, *price);
+        }
+        pricing
+    }
+
+    /// Add or replace the price of `model` on `provider`
+    pub fn set_price(
+        &mut self,
+        provider: impl Into<String>,
+        model: impl Into<String>,
+        price: ModelPrice,
+    ) {
+        self.prices.insert((provider.into(), model.into()), price);
+    }
+
+    /// Builder form of [`set_price`](Self::set_price)
+    pub fn with_price(
+        mut self,
+        provider: impl Into<String>,
+        model: impl Into<String>,
+        price: ModelPrice,
+    ) -> Self {
+        self.set_price(provider, model, price);
+        self
+    }
+
+    /// Price of `model` on `provider`
+    ///
+    /// APIs answer with dated snapshots ("gpt-4o-mini-2024-07-18") and routing
+    /// suffixes (":nitro"), so when there's no exact entry the longest model name
+    /// that `model` starts with is used.
+    pub fn price(&self, provider: &str, model: &str) -> Option<ModelPrice> {
+        self.prices
+            .range((provider.to_string(), String::new())..)
+            .take_while(|((p, _), _)| p == provider)
+            .filter(|((_, m), _)| model.starts_with(m.as_str()))
+            .max_by_key(|((_, m), _)| m.len())
+            .map(|(_, price)| *price)
+    }
+
+    /// Estimated dollars for `usage` on `model`, `None` when the model has no price
+    pub fn estimate(&self, provider: &str, model: &str, usage: &TokenUsage) -> Option<f64> {
+        self.price(provider, model).map(|price| price.cost(usage))
+    }
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(prompt_tokens: u32, completion_tokens: u32) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+
+    #[test]
+    fn test_pricing_estimates_cost() {
+        let pricing = Pricing::new();
+        let cost = pricing
+            .estimate("openai", "gpt-4o-mini", &usage(1000, 500))
+            .unwrap();
+        assert!((cost - 0.00045).abs() < 1e-12);
+
+        // a dated snapshot uses its base model's price, not the shorter "gpt-4o"
+        assert_eq!(
+            pricing.price("openai", "gpt-4o-mini-2024-07-18"),
+            pricing.price("openai", "gpt-4o-mini")
+        );
+        assert_eq!(pricing.price("gemini", "gpt-4o-mini"), None);
+        assert_eq!(pricing.estimate("openai", "llama3", &usage(10, 10)), None);
+
+        let pricing = pricing.with_price("openai", "gpt-4o-mini", ModelPrice::new(1.0, 2.0));
+        let cost = pricing
+            .estimate("openai", "gpt-4o-mini", &usage(1000, 1000))
+            .unwrap();
+        assert!((cost - 3.0).abs() < 1e-12);
+    }
+}
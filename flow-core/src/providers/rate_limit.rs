@@ -0,0 +1,268 @@
+//! Client-side rate limiting for provider requests
+//!
+//! A [`RateLimiter`] is a token bucket: it holds up to a minute's worth of
+//! requests and refills continuously, so short bursts go out immediately and
+//! sustained load is spread to the configured rate. Callers wait for a token
+//! instead of failing. Wrap providers in [`RateLimitedCompletionProvider`] or
+//! [`RateLimitedTranscriptionProvider`]; providers sharing one `Arc<RateLimiter>`
+//! share its budget (e.g. every client of one API key). [`RateLimiters`] keeps
+//! one per provider, so a provider rebuilt after a settings change keeps the
+//! budget its predecessor already used.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tracing::debug;
+
+use crate::error::Result;
+
+use super::{
+    CompletionProvider, CompletionRequest, CompletionResponse, TranscriptionProvider,
+    TranscriptionRequest, TranscriptionResponse,
+};
+
+/// Token bucket allowing a fixed number of requests per minute
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Most tokens the bucket holds (one minute's worth)
+    capacity: f64,
+    /// Tokens added per second
+    refill_per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Limiter allowing `requests` per minute, starting full (0 is treated as 1)
+    pub fn per_minute(requests: u32) -> Self {
+        let capacity = f64::from(requests.max(1));
+        Self {
+            capacity,
+            refill_per_sec: capacity / 60.0,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Configured rate in requests per minute
+    pub fn requests_per_minute(&self) -> u32 {
+        self.capacity as u32
+    }
+
+    /// Wait until a request may be sent, then use up its token
+    pub async fn acquire(&self) {
+        loop {
+            match self.take_at(Instant::now()) {
+                Ok(()) => return,
+                Err(wait) => {
+                    debug!(wait_ms = wait.as_millis() as u64, "Rate limited, waiting");
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+
+    /// Use up a token if one is available right now
+    pub fn try_acquire(&self) -> bool {
+        self.take_at(Instant::now()).is_ok()
+    }
+
+    /// Take a token at `now`, or return how long until one is available
+    fn take_at(&self, now: Instant) -> std::result::Result<(), Duration> {
+        let mut bucket = self.bucket.lock();
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * self.refill_per_sec).min(self.capacity);
+        bucket.refilled_at = bucket.refilled_at.max(now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.refill_per_sec,
+            ))
+        }
+    }
+}
+
+/// One [`RateLimiter`] per provider name, outliving the providers that use them
+#[derive(Debug, Default)]
+pub struct RateLimiters {
+    limiters: Mutex<HashMap<String, Arc<RateLimiter>>>,
+}
+
+impl RateLimiters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The limiter for `provider` at `requests` per minute; a changed rate replaces it
+    pub fn limiter(&self, provider: &str, requests: u32) -> Arc<RateLimiter> {
+        let mut limiters = self.limiters.lock();
+        match limiters.get(provider) {
+            Some(limiter) if limiter.requests_per_minute() == requests.max(1) => {
+                Arc::clone(limiter)
+            }
+            _ => {
+                let limiter = Arc::new(RateLimiter::per_minute(requests));
+                limiters.insert(provider.to_string(), Arc::clone(&limiter));
+                limiter
+            }
+        }
+    }
+}
+
+/// Completion provider that waits on a [`RateLimiter`] before each request
+pub struct RateLimitedCompletionProvider {
+    inner: Box<dyn CompletionProvider>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitedCompletionProvider {
+    pub fn new(inner: Box<dyn CompletionProvider>, limiter: Arc<RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+
+    /// The limiter requests wait on, to share with other providers
+    pub fn limiter(&self) -> &Arc<RateLimiter> {
+        &self.limiter
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for RateLimitedCompletionProvider {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        self.limiter.acquire().await;
+        self.inner.complete(request).await
+    }
+
+    fn is_configured(&self) -> bool {
+        self.inner.is_configured()
+    }
+}
+
+/// Transcription provider that waits on a [`RateLimiter`] before each request
+/// Windows sent by `transcribe_long` each take a token.
+pub struct RateLimitedTranscriptionProvider {
+    inner: Box<dyn TranscriptionProvider>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitedTranscriptionProvider {
+    pub fn new(inner: Box<dyn TranscriptionProvider>, limiter: Arc<RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+
+    /// The limiter requests wait on, to share with other providers
+    pub fn limiter(&self) -> &Arc<RateLimiter> {
+        &self.limiter
+    }
+}
+
+#[async_trait]
+impl TranscriptionProvider for RateLimitedTranscriptionProvider {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+
+    async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+        self.limiter.acquire().await;
+        self.inner.transcribe(request).await
+    }
+
+    fn is_configured(&self) -> bool {
+        self.inner.is_configured()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::PassthroughCompletionProvider;
+
+    #[test]
+    fn test_bucket_refills_at_rate() {
+        let limiter = RateLimiter::per_minute(2);
+        let start = limiter.bucket.lock().refilled_at;
+
+        // a full minute's worth goes out at once
+        assert!(limiter.take_at(start).is_ok());
+        assert!(limiter.take_at(start).is_ok());
+        let wait = limiter.take_at(start).unwrap_err();
+        assert!((wait.as_secs_f64() - 30.0).abs() < 1e-6);
+
+        // one token back every 30s, never more than the capacity
+        assert!(limiter.take_at(start + Duration::from_secs(31)).is_ok());
+        assert!(limiter.take_at(start + Duration::from_secs(32)).is_err());
+        assert!(limiter.take_at(start + Duration::from_secs(600)).is_ok());
+        assert!(limiter.take_at(start + Duration::from_secs(600)).is_ok());
+        assert!(limiter.take_at(start + Duration::from_secs(600)).is_err());
+    }
+
+    #[test]
+    fn test_providers_share_a_limiter() {
+        let limiter = Arc::new(RateLimiter::per_minute(1));
+        let first = RateLimitedCompletionProvider::new(
+            Box::new(PassthroughCompletionProvider::new()),
+            Arc::clone(&limiter),
+        );
+        assert_eq!(first.provider_name(), "none");
+
+        let response =
+            futures::executor::block_on(first.complete(CompletionRequest::new("hi"))).unwrap();
+        assert_eq!(response.text, "hi");
+
+        // the budget is spent for every provider holding the limiter
+        let second = RateLimitedCompletionProvider::new(
+            Box::new(PassthroughCompletionProvider::new()),
+            Arc::clone(first.limiter()),
+        );
+        assert!(!second.limiter().try_acquire());
+    }
+
+    #[test]
+    fn test_limiters_outlive_providers() {
+        let limiters = RateLimiters::new();
+        assert!(limiters.limiter("openai", 1).try_acquire());
+
+        // the same provider at the same rate gets the spent bucket back
+        assert!(!limiters.limiter("openai", 1).try_acquire());
+        assert!(limiters.limiter("gemini", 1).try_acquire());
+
+        // a new rate starts over
+        let limiter = limiters.limiter("openai", 2);
+        assert_eq!(limiter.requests_per_minute(), 2);
+        assert!(limiter.try_acquire());
+    }
+}
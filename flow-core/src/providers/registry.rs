@@ -0,0 +1,181 @@
+//! Completion providers selectable by name
+//!
+//! Each provider registers a factory under the name stored in `completion_provider`,
+//! along with the setting that holds its API key. [`build_from_storage`] looks the
+//! stored name up here, so an app can add its own providers next to the built-ins.
+//!
+//! [`build_from_storage`]: super::build_from_storage
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::storage::api_key_setting;
+
+use super::{
+    CompletionProvider, GeminiCompletionProvider, OpenAICompletionProvider,
+    OpenRouterCompletionProvider, PassthroughCompletionProvider, RuleBasedAdapter,
+};
+
+/// What a factory gets from storage to build its provider
+#[derive(Debug, Clone, Default)]
+pub struct ProviderSettings {
+    /// Value of the provider's key setting, if set and non-empty
+    pub api_key: Option<String>,
+    /// Model override from `completion_model_<name>`, if set
+    pub model: Option<String>,
+}
+
+/// Builds a completion provider from its stored settings
+pub type CompletionFactory =
+    Arc<dyn Fn(ProviderSettings) -> Box<dyn CompletionProvider> + Send + Sync>;
+
+#[derive(Clone)]
+struct Registration {
+    key_setting: Option<String>,
+    factory: CompletionFactory,
+}
+
+/// Completion provider factories keyed by name
+#[derive(Clone)]
+pub struct ProviderRegistry {
+    providers: BTreeMap<String, Registration>,
+}
+
+impl ProviderRegistry {
+    /// Create a registry with no providers
+    pub fn empty() -> Self {
+        Self {
+            providers: BTreeMap::new(),
+        }
+    }
+
+    /// Create a registry with the built-in providers: "openai", "gemini",
+    /// "openrouter", and the keyless "none" (passthrough) and "rules" (offline)
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register("openai", api_key_setting("openai"), |settings| {
+            let provider = OpenAICompletionProvider::new(settings.api_key);
+            match settings.model {
+                Some(model) => Box::new(provider.with_model(model)),
+                None => Box::new(provider),
+            }
+        });
+        registry.register("gemini", api_key_setting("gemini"), |settings| {
+            let provider = GeminiCompletionProvider::new(settings.api_key);
+            match settings.model {
+                Some(model) => Box::new(provider.with_model(model)),
+                None => Box::new(provider),
+            }
+        });
+        registry.register("openrouter", api_key_setting("openrouter"), |settings| {
+            let provider = OpenRouterCompletionProvider::new(settings.api_key);
+            match settings.model {
+                Some(model) => Box::new(provider.with_model(model)),
+                None => Box::new(provider),
+            }
+        });
+        registry.register("none", None, |_| {
+            Box::new(PassthroughCompletionProvider::new())
+        });
+        registry.register("rules", None, |_| Box::new(RuleBasedAdapter::new()));
+        registry
+    }
+
+    /// Add a provider under `name`, replacing any registered before
+    /// `key_setting` is the setting its API key is read from (`None` if it needs none).
+    pub fn register<F>(&mut self, name: impl Into<String>, key_setting: Option<&str>, factory: F)
+    where
+        F: Fn(ProviderSettings) -> Box<dyn CompletionProvider> + Send + Sync + 'static,
+    {
+        self.providers.insert(
+            name.into(),
+            Registration {
+                key_setting: key_setting.map(str::to_string),
+                factory: Arc::new(factory),
+            },
+        );
+    }
+
+    /// Names of every registered provider, sorted, e.g. to populate a settings picker
+    pub fn list_registered(&self) -> Vec<&str> {
+        self.providers.keys().map(String::as_str).collect()
+    }
+
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.providers.contains_key(name)
+    }
+
+    /// Setting that holds `name`'s API key, if it is registered and needs one
+    pub fn key_setting(&self, name: &str) -> Option<&str> {
+        self.providers.get(name)?.key_setting.as_deref()
+    }
+
+    /// Build the provider registered as `name`
+    pub fn build(
+        &self,
+        name: &str,
+        settings: ProviderSettings,
+    ) -> Result<Box<dyn CompletionProvider>> {
+        let registration = self
+            .providers
+            .get(name)
+            .ok_or_else(|| Error::Config(format!("Unknown completion provider: {}", name)))?;
+        Ok((registration.factory)(settings))
+    }
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for ProviderRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProviderRegistry")
+            .field("providers", &self.list_registered())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_builds_by_name() {
+        let mut registry = ProviderRegistry::new();
+        assert_eq!(
+            registry.list_registered(),
+            vec!["gemini", "none", "openai", "openrouter", "rules"]
+        );
+        assert_eq!(registry.key_setting("gemini"), Some("gemini_api_key"));
+        assert_eq!(registry.key_setting("none"), None);
+
+        let provider = registry
+            .build(
+                "openai",
+                ProviderSettings {
+                    api_key: Some("sk-test".to_string()),
+                    model: Some("gpt-4o".to_string()),
+                },
+            )
+            .unwrap();
+        assert_eq!(provider.provider_name(), "openai");
+        assert_eq!(provider.model_name(), "gpt-4o");
+
+        // an app's own provider sits next to the built-ins
+        registry.register("echo", None, |_| {
+            Box::new(PassthroughCompletionProvider::new())
+        });
+        assert!(registry.is_registered("echo"));
+        assert!(registry.list_registered().contains(&"echo"));
+
+        let err = registry
+            .build("carrier-pigeon", ProviderSettings::default())
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::Config(_)));
+    }
+}
@@ -0,0 +1,382 @@
+//! Offline completion provider built from simple per-mode rewrite rules
+//!
+//! Selected with `completion_provider = "rules"`. No network and no key: slang is
+//! expanded or contracted to suit the mode, sentences are capitalized and
+//! punctuated, and formal or excited messages to a known contact get a greeting.
+//! Far cruder than a model, but it gives offline users more than raw text.
+
+use async_trait::async_trait;
+
+use crate::error::Result;
+use crate::types::WritingMode;
+
+use super::completion::{FinishReason, TokenUsage};
+use super::{CompletionProvider, CompletionRequest, CompletionResponse};
+
+/// Slang and texting shorthand spelled out for formal messages
+const FORMAL_EXPANSIONS: &[(&str, &str)] = &[
+    ("gonna", "going to"),
+    ("wanna", "want to"),
+    ("gotta", "have to"),
+    ("kinda", "kind of"),
+    ("sorta", "sort of"),
+    ("lemme", "let me"),
+    ("gimme", "give me"),
+    ("dunno", "don't know"),
+    ("idk", "I don't know"),
+    ("btw", "by the way"),
+    ("rn", "right now"),
+    ("tmrw", "tomorrow"),
+    ("thx", "thanks"),
+    ("pls", "please"),
+    ("plz", "please"),
+    ("cuz", "because"),
+    ("u", "you"),
+    ("ur", "your"),
+    ("r", "are"),
+    ("ya", "you"),
+    ("yeah", "yes"),
+    ("yep", "yes"),
+    ("nope", "no"),
+    ("ok", "okay"),
+];
+
+/// Texting shorthand that reads oddly even in a casual message
+const CASUAL_EXPANSIONS: &[(&str, &str)] = &[
+    ("u", "you"),
+    ("ur", "your"),
+    ("r", "are"),
+    ("thx", "thanks"),
+    ("pls", "please"),
+    ("plz", "please"),
+];
+
+/// Spelled-out phrases shortened for texts to close friends
+const VERY_CASUAL_CONTRACTIONS: &[(&str, &str)] = &[
+    ("i don't know", "idk"),
+    ("by the way", "btw"),
+    ("going to", "gonna"),
+    ("want to", "wanna"),
+    ("got to", "gotta"),
+    ("right now", "rn"),
+    ("sorry", "sry"),
+    ("thanks", "thx"),
+    ("please", "pls"),
+    ("because", "cuz"),
+    ("tomorrow", "tmrw"),
+    ("okay", "ok"),
+];
+
+/// Openings that already greet the recipient
+const GREETINGS: &[&str] = &[
+    "hi",
+    "hello",
+    "hey",
+    "dear",
+    "good morning",
+    "good afternoon",
+];
+
+/// Rule-based formatting that runs entirely on the machine
+pub struct RuleBasedAdapter;
+
+impl RuleBasedAdapter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Apply the rules for `mode`, greeting `contact` when one is given
+    pub fn adapt(&self, text: &str, mode: WritingMode, contact: Option<&str>) -> String {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            return text;
+        }
+
+        match mode {
+            WritingMode::Formal => {
+                let text =
+                    finish_sentence(&capitalize_sentences(&substitute(&text, FORMAL_EXPANSIONS)));
+                match contact.filter(|_| !has_greeting(&text)) {
+                    Some(name) => format!("Hello {}, {}", name, lowercase_first(&text)),
+                    None => text,
+                }
+            }
+            WritingMode::Casual => {
+                finish_sentence(&capitalize_sentences(&substitute(&text, CASUAL_EXPANSIONS)))
+            }
+            WritingMode::VeryCasual => {
+                let text = substitute(&text.to_lowercase(), VERY_CASUAL_CONTRACTIONS);
+                text.trim_end_matches('.').to_string()
+            }
+            WritingMode::Excited => {
+                let text = capitalize_sentences(&substitute(&text, CASUAL_EXPANSIONS));
+                let text = match text.strip_suffix('.') {
+                    Some(body) => format!("{}!", body),
+                    None if text.ends_with(['!', '?']) => text,
+                    None => format!("{}!", text),
+                };
+                match contact.filter(|_| !has_greeting(&text)) {
+                    Some(name) => format!("Hey {}! {}", first_name(name), text),
+                    None => text,
+                }
+            }
+        }
+    }
+}
+
+impl Default for RuleBasedAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for RuleBasedAdapter {
+    fn name(&self) -> &'static str {
+        "Rule-based"
+    }
+
+    fn provider_name(&self) -> &str {
+        "rules"
+    }
+
+    fn model_name(&self) -> &str {
+        "rules"
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let contact = request
+            .contact
+            .as_ref()
+            .map(|contact| contact.name.as_str())
+            .filter(|name| !name.trim().is_empty());
+        Ok(CompletionResponse {
+            text: self.adapt(&request.text, request.mode, contact),
+            usage: Some(TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }),
+            model: None,
+            finish_reason: FinishReason::Stop,
+            prompt_truncated: false,
+            // runs locally, so it's free
+            estimated_cost_usd: Some(0.0),
+        })
+    }
+
+    fn is_configured(&self) -> bool {
+        true
+    }
+}
+
+/// Split a word into leading punctuation, the word, and trailing punctuation
+fn split_word(word: &str) -> (&str, &str, &str) {
+    let is_word = |c: char| c.is_alphanumeric();
+    let start = word.find(is_word).unwrap_or(word.len());
+    let end = word.rfind(is_word).map_or(start, |i| {
+        i + word[i..].chars().next().map_or(0, char::len_utf8)
+    });
+    (&word[..start], &word[start..end], &word[end..])
+}
+
+/// Replace whole-word phrases from `table`, first match wins
+///
+/// Punctuation around the phrase is kept, and a capitalized phrase gets a
+/// capitalized replacement. Phrases never match across punctuation.
+fn substitute(text: &str, table: &[(&str, &str)]) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut out: Vec<String> = Vec::with_capacity(words.len());
+    let mut i = 0;
+
+    'words: while i < words.len() {
+        for (from, to) in table {
+            let phrase: Vec<&str> = from.split(' ').collect();
+            let Some(candidate) = words.get(i..i + phrase.len()) else {
+                continue;
+            };
+            let last = phrase.len() - 1;
+            let matches = candidate
+                .iter()
+                .zip(&phrase)
+                .enumerate()
+                .all(|(j, (word, expected))| {
+                    let (lead, core, trail) = split_word(word);
+                    core.eq_ignore_ascii_case(expected)
+                        && (j == 0 || lead.is_empty())
+                        && (j == last || trail.is_empty())
+                });
+            if matches {
+                let (lead, core, _) = split_word(candidate[0]);
+                let (_, _, trail) = split_word(candidate[last]);
+                let replacement = if core.starts_with(char::is_uppercase) {
+                    capitalize(to)
+                } else {
+                    to.to_string()
+                };
+                out.push(format!("{}{}{}", lead, replacement, trail));
+                i += phrase.len();
+                continue 'words;
+            }
+        }
+        out.push(words[i].to_string());
+        i += 1;
+    }
+    out.join(" ")
+}
+
+/// Capitalize the first word of every sentence and the pronoun "i"
+fn capitalize_sentences(text: &str) -> String {
+    let mut at_start = true;
+    text.split(' ')
+        .map(|word| {
+            let (lead, core, trail) = split_word(word);
+            let is_pronoun = core == "i" || core.starts_with("i'") || core.starts_with("i’");
+            let core = if at_start || is_pronoun {
+                capitalize(core)
+            } else {
+                core.to_string()
+            };
+            at_start = trail.ends_with(['.', '?', '!']);
+            format!("{}{}{}", lead, core, trail)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// End with a period unless the text already ends a sentence
+fn finish_sentence(text: &str) -> String {
+    if text.ends_with(['.', '?', '!']) {
+        text.to_string()
+    } else {
+        format!("{}.", text)
+    }
+}
+
+fn has_greeting(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    GREETINGS.iter().any(|greeting| {
+        lower
+            .strip_prefix(greeting)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(|c: char| !c.is_alphanumeric()))
+    })
+}
+
+fn first_name(name: &str) -> &str {
+    name.split_whitespace().next().unwrap_or(name)
+}
+
+/// Lowercase the first letter unless it starts "I" or "I'm"
+fn lowercase_first(text: &str) -> String {
+    let first = text.split(' ').next().unwrap_or_default();
+    let (_, core, _) = split_word(first);
+    if core == "I" || core.starts_with("I'") || core.starts_with("I’") {
+        return text.to_string();
+    }
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) => c.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ContactCategory;
+
+    #[test]
+    fn test_formal_expands_slang() {
+        let adapter = RuleBasedAdapter::new();
+        assert_eq!(
+            adapter.adapt(
+                "gonna be late, idk when i'll get there",
+                WritingMode::Formal,
+                None
+            ),
+            "Going to be late, I don't know when I'll get there."
+        );
+        assert_eq!(
+            adapter.adapt(
+                "i wanna move the meeting",
+                WritingMode::Formal,
+                Some("Dr. Smith")
+            ),
+            "Hello Dr. Smith, I want to move the meeting."
+        );
+        // an existing greeting isn't doubled
+        assert_eq!(
+            adapter.adapt(
+                "hi, thx for the notes",
+                WritingMode::Formal,
+                Some("Dr. Smith")
+            ),
+            "Hi, thanks for the notes."
+        );
+    }
+
+    #[test]
+    fn test_casual_modes() {
+        let adapter = RuleBasedAdapter::new();
+        assert_eq!(
+            adapter.adapt("see u soon. i'm gonna grab food", WritingMode::Casual, None),
+            "See you soon. I'm gonna grab food."
+        );
+        assert_eq!(
+            adapter.adapt(
+                "Sorry, I am going to be late.",
+                WritingMode::VeryCasual,
+                None
+            ),
+            "sry, i am gonna be late"
+        );
+        assert_eq!(
+            adapter.adapt(
+                "we won the game.",
+                WritingMode::Excited,
+                Some("Jake Miller")
+            ),
+            "Hey Jake! We won the game!"
+        );
+        assert_eq!(adapter.adapt("   ", WritingMode::Formal, Some("Jake")), "");
+    }
+
+    #[test]
+    fn test_example_transform() {
+        let sample = "gonna be 5 min late, sorry";
+        let previews: Vec<String> = WritingMode::all()
+            .iter()
+            .map(|mode| mode.example_transform(sample))
+            .collect();
+        assert_eq!(
+            previews,
+            vec![
+                "Going to be 5 min late, sorry.",
+                "Gonna be 5 min late, sorry.",
+                "gonna be 5 min late, sry",
+                "Gonna be 5 min late, sorry!",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rule_based_provider() {
+        let provider = RuleBasedAdapter::new();
+        let request = CompletionRequest::new("thx, see u tmrw")
+            .with_mode(WritingMode::Formal)
+            .with_contact("Alex", ContactCategory::Professional);
+
+        let response = futures::executor::block_on(provider.complete(request)).unwrap();
+        assert_eq!(response.text, "Hello Alex, thanks, see you tomorrow.");
+        assert_eq!(provider.provider_name(), "rules");
+        assert!(provider.is_configured());
+    }
+}
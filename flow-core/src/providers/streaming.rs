@@ -1,16 +1,22 @@
 //! Streaming support for completion providers
 //!
 //! Provides Server-Sent Events (SSE) parsing and streaming completion traits.
+//! Keep-alive comments (`: ping`) are dropped, and a connection that closes before
+//! the provider signals the end surfaces as `StreamError::Disconnected`.
 
+use std::collections::VecDeque;
+use std::future::Future;
 use std::pin::Pin;
 
 use async_trait::async_trait;
-use futures::Stream;
+use futures::{Stream, StreamExt};
 use serde::Deserialize;
+use tracing::warn;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 
-use super::{CompletionRequest, CompletionResponse, TokenUsage};
+use super::http::decode_body;
+use super::{CompletionRequest, CompletionResponse, FinishReason, TokenUsage};
 
 /// A chunk of streamed completion text
 #[derive(Debug, Clone)]
@@ -26,6 +32,14 @@ pub struct CompletionChunk {
 /// Type alias for the boxed stream of completion chunks
 pub type CompletionStream = Pin<Box<dyn Stream<Item = Result<CompletionChunk>> + Send>>;
 
+/// Failures specific to streamed responses
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum StreamError {
+    /// The connection closed before the provider finished; `partial` is the text received so far
+    #[error("connection dropped after {} chars", partial.chars().count())]
+    Disconnected { partial: String },
+}
+
 /// Trait for completion providers that support streaming
 #[async_trait]
 pub trait StreamingCompletionProvider: Send + Sync {
@@ -50,25 +64,237 @@ pub struct SseEvent {
 /// Parse SSE data from a line
 #[allow(dead_code)]
 pub fn parse_sse_line(line: &str) -> Option<SseEvent> {
-    let line = line.trim();
+    // only strip the line ending; tokens can end in meaningful spaces
+    let line = line.trim_end_matches(['\r', '\n']);
 
     if line.is_empty() || line.starts_with(':') {
         return None;
     }
 
-    if let Some(data) = line.strip_prefix("data: ") {
+    if let Some(data) = line.strip_prefix("data:") {
         Some(SseEvent {
             event: None,
-            data: data.to_string(),
+            data: data.strip_prefix(' ').unwrap_or(data).to_string(),
         })
     } else {
-        line.strip_prefix("event: ").map(|event| SseEvent {
-            event: Some(event.to_string()),
+        line.strip_prefix("event:").map(|event| SseEvent {
+            event: Some(event.trim_start().to_string()),
             data: String::new(),
         })
     }
 }
 
+/// Splits raw SSE bytes into `data:` payloads
+/// Buffers partial lines (and split UTF-8 sequences) across network reads
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed bytes and return the non-empty data payloads of every completed line
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut payloads = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            if let Some(event) = parse_sse_line(&decode_body(&line, "SSE stream"))
+                && event.event.is_none()
+                && !event.data.is_empty()
+            {
+                payloads.push(event.data);
+            }
+        }
+        payloads
+    }
+}
+
+struct SseState<S, F> {
+    bytes: Pin<Box<S>>,
+    decoder: SseDecoder,
+    parse: F,
+    pending: VecDeque<Result<CompletionChunk>>,
+    partial: String,
+    saw_final: bool,
+    done: bool,
+}
+
+/// Turn an SSE response body into a completion stream
+///
+/// `parse` maps one `data:` payload to a chunk, or `None` for events without
+/// text. `[DONE]` ends the stream. If the body ends (or errors) before `[DONE]`
+/// or a final chunk, the stream yields `StreamError::Disconnected` last.
+pub fn sse_completion_stream<S, B, F>(bytes: S, parse: F) -> CompletionStream
+where
+    S: Stream<Item = std::result::Result<B, reqwest::Error>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    F: FnMut(&str) -> Result<Option<CompletionChunk>> + Send + 'static,
+{
+    let state = SseState {
+        bytes: Box::pin(bytes),
+        decoder: SseDecoder::new(),
+        parse,
+        pending: VecDeque::new(),
+        partial: String::new(),
+        saw_final: false,
+        done: false,
+    };
+
+    Box::pin(futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(item) = state.pending.pop_front() {
+                return Some((item, state));
+            }
+            if state.done {
+                return None;
+            }
+
+            let bytes = match state.bytes.next().await {
+                Some(Ok(bytes)) => bytes,
+                Some(Err(e)) => {
+                    warn!("Completion stream read failed: {}", e);
+                    state.done = true;
+                    state.pending.push_back(Err(disconnected(&state.partial)));
+                    continue;
+                }
+                None => {
+                    state.done = true;
+                    if !state.saw_final {
+                        state.pending.push_back(Err(disconnected(&state.partial)));
+                    }
+                    continue;
+                }
+            };
+
+            for data in state.decoder.push(bytes.as_ref()) {
+                if data == "[DONE]" {
+                    state.done = true;
+                    break;
+                }
+                match (state.parse)(&data) {
+                    Ok(Some(chunk)) => {
+                        state.saw_final |= chunk.is_final;
+                        // role-only and empty deltas carry nothing worth emitting
+                        if !chunk.text.is_empty() || chunk.is_final {
+                            state.partial.push_str(&chunk.text);
+                            state.pending.push_back(Ok(chunk));
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        state.done = true;
+                        state.pending.push_back(Err(e));
+                        break;
+                    }
+                }
+            }
+        }
+    }))
+}
+
+fn disconnected(partial: &str) -> Error {
+    Error::Stream(StreamError::Disconnected {
+        partial: partial.to_string(),
+    })
+}
+
+struct ReconnectState<F> {
+    stream: Option<CompletionStream>,
+    reopen: F,
+    delivered: String,
+    /// Bytes of `delivered` a reopened stream has replayed so far
+    replayed: usize,
+    reconnects_left: u32,
+}
+
+/// Reopen a dropped stream up to `max_reconnects` times and resume where it stopped
+///
+/// `reopen` re-sends the original request. The replayed prefix is checked
+/// against the text already delivered and skipped, so callers only see new
+/// text. This relies on deterministic output (temperature 0); if the replay
+/// diverges the stream ends with `StreamError::Disconnected`.
+pub fn with_reconnect<F, Fut>(
+    stream: CompletionStream,
+    max_reconnects: u32,
+    reopen: F,
+) -> CompletionStream
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<CompletionStream>> + Send + 'static,
+{
+    let state = ReconnectState {
+        stream: Some(stream),
+        reopen,
+        delivered: String::new(),
+        replayed: 0,
+        reconnects_left: max_reconnects,
+    };
+
+    Box::pin(futures::stream::unfold(state, |mut state| async move {
+        loop {
+            let stream = state.stream.as_mut()?;
+            match stream.next().await {
+                None => return None,
+                Some(Ok(mut chunk)) => {
+                    let remaining = state.delivered.len() - state.replayed;
+                    if remaining > 0 {
+                        let overlap = remaining.min(chunk.text.len());
+                        let expected =
+                            &state.delivered.as_bytes()[state.replayed..state.replayed + overlap];
+                        if &chunk.text.as_bytes()[..overlap] != expected {
+                            warn!("Reconnected stream diverged from the text already received");
+                            state.stream = None;
+                            return Some((Err(disconnected(&state.delivered)), state));
+                        }
+                        state.replayed += overlap;
+                        chunk.text = chunk.text.get(overlap..).unwrap_or_default().to_string();
+                    }
+
+                    if chunk.text.is_empty() && !chunk.is_final {
+                        continue;
+                    }
+                    state.delivered.push_str(&chunk.text);
+                    state.replayed = state.delivered.len();
+                    return Some((Ok(chunk), state));
+                }
+                Some(Err(Error::Stream(StreamError::Disconnected { .. })))
+                    if state.reconnects_left > 0 =>
+                {
+                    state.reconnects_left -= 1;
+                    warn!(
+                        "Completion stream dropped after {} chars, reconnecting",
+                        state.delivered.chars().count()
+                    );
+                    match (state.reopen)().await {
+                        Ok(stream) => {
+                            state.stream = Some(stream);
+                            state.replayed = 0;
+                        }
+                        Err(e) => {
+                            warn!("Reconnect failed: {}", e);
+                            state.stream = None;
+                            return Some((Err(disconnected(&state.delivered)), state));
+                        }
+                    }
+                }
+                Some(Err(Error::Stream(StreamError::Disconnected { .. }))) => {
+                    state.stream = None;
+                    return Some((Err(disconnected(&state.delivered)), state));
+                }
+                Some(Err(e)) => {
+                    state.stream = None;
+                    return Some((Err(e), state));
+                }
+            }
+        }
+    }))
+}
+
 /// OpenAI streaming response chunk
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
@@ -184,24 +410,94 @@ pub struct AnthropicError {
     pub message: String,
 }
 
+/// Whatever a stream delivered, plus the error that ended it early, if any
+#[derive(Debug)]
+pub struct PartialCompletion {
+    /// Text received before the stream ended or failed
+    pub response: CompletionResponse,
+    pub error: Option<Error>,
+}
+
+impl PartialCompletion {
+    /// All-or-nothing view: the response only if the stream finished cleanly
+    pub fn into_result(self) -> Result<CompletionResponse> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.response),
+        }
+    }
+
+    /// Whether the stream failed partway
+    pub fn is_partial(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
 /// Collect a stream into a complete response
+/// A dropped connection returns `StreamError::Disconnected`, which carries the partial text
 pub async fn collect_stream(stream: CompletionStream) -> Result<CompletionResponse> {
-    use futures::StreamExt;
+    collect_stream_with(stream, |_| {}).await
+}
+
+/// Collect a stream, calling `on_chunk` for every chunk as it arrives (for live display)
+/// Usage is taken from whichever chunk carries it, normally the final one
+pub async fn collect_stream_with<F>(
+    stream: CompletionStream,
+    on_chunk: F,
+) -> Result<CompletionResponse>
+where
+    F: FnMut(&CompletionChunk),
+{
+    collect_stream_partial_with(stream, on_chunk)
+        .await
+        .into_result()
+}
 
+/// Collect a stream, keeping the text received before any error
+/// Use on flaky connections where a partial adaptation beats none.
+pub async fn collect_stream_partial(stream: CompletionStream) -> PartialCompletion {
+    collect_stream_partial_with(stream, |_| {}).await
+}
+
+/// `collect_stream_partial`, calling `on_chunk` for every chunk as it arrives
+pub async fn collect_stream_partial_with<F>(
+    stream: CompletionStream,
+    mut on_chunk: F,
+) -> PartialCompletion
+where
+    F: FnMut(&CompletionChunk),
+{
     let mut text = String::new();
     let mut usage = None;
-    let model = None;
+    let mut error = None;
 
     let mut stream = stream;
     while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result?;
+        let chunk = match chunk_result {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        };
+        on_chunk(&chunk);
         text.push_str(&chunk.text);
-        if chunk.is_final {
+        if chunk.usage.is_some() {
             usage = chunk.usage;
         }
     }
 
-    Ok(CompletionResponse { text, usage, model })
+    PartialCompletion {
+        response: CompletionResponse {
+            text,
+            usage,
+            model: None,
+            finish_reason: FinishReason::Other,
+            prompt_truncated: false,
+            estimated_cost_usd: None,
+        },
+        error,
+    }
 }
 
 #[cfg(test)]
@@ -221,6 +517,143 @@ mod tests {
         assert_eq!(event.event, Some("message".to_string()));
     }
 
+    #[test]
+    fn test_sse_decoder_skips_keep_alive() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(b": keep-alive\n\ndata: hel").is_empty());
+        assert_eq!(decoder.push(b"lo\n\n: ping\ndata:\n"), vec!["hello"]);
+
+        // a multi-byte character split across reads
+        let bytes = "data: café\n".as_bytes();
+        assert!(decoder.push(&bytes[..10]).is_empty());
+        assert_eq!(decoder.push(&bytes[10..]), vec!["café"]);
+    }
+
+    fn text_chunk(data: &str) -> Result<Option<CompletionChunk>> {
+        Ok(Some(CompletionChunk {
+            text: data.to_string(),
+            is_final: false,
+            usage: None,
+        }))
+    }
+
+    fn body(parts: &[&str]) -> impl Stream<Item = std::result::Result<Vec<u8>, reqwest::Error>> {
+        let parts: Vec<_> = parts.iter().map(|p| Ok(p.as_bytes().to_vec())).collect();
+        futures::stream::iter(parts)
+    }
+
+    #[test]
+    fn test_sse_stream_done_and_disconnect() {
+        let stream = sse_completion_stream(
+            body(&[
+                "data: Hi\n\n: keep-alive\n\n",
+                "data:  there\n\ndata: [DONE]\n\n",
+            ]),
+            text_chunk,
+        );
+        let response = futures::executor::block_on(collect_stream(stream)).unwrap();
+        assert_eq!(response.text, "Hi there");
+
+        let dropped = sse_completion_stream(body(&["data: Hi\n\n"]), text_chunk);
+        let err = futures::executor::block_on(collect_stream(dropped)).unwrap_err();
+        match err {
+            Error::Stream(StreamError::Disconnected { partial }) => assert_eq!(partial, "Hi"),
+            other => panic!("expected disconnect, got {other}"),
+        }
+    }
+
+    #[test]
+    fn test_collect_partial_keeps_text_on_error() {
+        let stream: CompletionStream = Box::pin(futures::stream::iter(vec![
+            Ok(CompletionChunk {
+                text: "Running ".to_string(),
+                is_final: false,
+                usage: None,
+            }),
+            Ok(CompletionChunk {
+                text: "late, be".to_string(),
+                is_final: false,
+                usage: None,
+            }),
+            Err(Error::Completion("connection reset".to_string())),
+        ]));
+        let partial = futures::executor::block_on(collect_stream_partial(stream));
+        assert!(partial.is_partial());
+        assert_eq!(partial.response.text, "Running late, be");
+        assert!(matches!(
+            partial.into_result(),
+            Err(Error::Completion(message)) if message == "connection reset"
+        ));
+
+        let stream = sse_completion_stream(body(&["data: Hi\n\ndata: [DONE]\n\n"]), text_chunk);
+        let partial = futures::executor::block_on(collect_stream_partial(stream));
+        assert!(!partial.is_partial());
+        assert_eq!(partial.into_result().unwrap().text, "Hi");
+    }
+
+    #[test]
+    fn test_reconnect_resumes_after_received_text() {
+        let first = sse_completion_stream(body(&["data: Running \n\ndata: la"]), text_chunk);
+        let stream = with_reconnect(first, 1, || async {
+            Ok(sse_completion_stream(
+                body(&["data: Running \n\ndata: late\n\ndata: [DONE]\n\n"]),
+                text_chunk,
+            ))
+        });
+        let response = futures::executor::block_on(collect_stream(stream)).unwrap();
+        assert_eq!(response.text, "Running late");
+
+        let first = sse_completion_stream(body(&["data: Running \n\n"]), text_chunk);
+        let diverged = with_reconnect(first, 1, || async {
+            Ok(sse_completion_stream(
+                body(&["data: Walking\n\ndata: [DONE]\n\n"]),
+                text_chunk,
+            ))
+        });
+        let err = futures::executor::block_on(collect_stream(diverged)).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Stream(StreamError::Disconnected { ref partial }) if partial == "Running "
+        ));
+    }
+
+    #[test]
+    fn test_collect_stream_with_callback() {
+        let chunk = |text: &str, is_final: bool| {
+            Ok(CompletionChunk {
+                text: text.to_string(),
+                is_final,
+                usage: is_final.then_some(TokenUsage {
+                    prompt_tokens: 12,
+                    completion_tokens: 3,
+                    total_tokens: 15,
+                }),
+            })
+        };
+        let stream: CompletionStream = Box::pin(futures::stream::iter(vec![
+            chunk("Running", false),
+            chunk(" late", false),
+            chunk("", true),
+        ]));
+
+        let mut seen = Vec::new();
+        let response = futures::executor::block_on(collect_stream_with(stream, |chunk| {
+            seen.push((chunk.text.clone(), chunk.is_final));
+        }))
+        .unwrap();
+
+        assert_eq!(
+            seen,
+            vec![
+                ("Running".to_string(), false),
+                (" late".to_string(), false),
+                (String::new(), true),
+            ]
+        );
+        assert_eq!(response.text, "Running late");
+        assert_eq!(response.usage.unwrap().total_tokens, 15);
+    }
+
     #[test]
     fn test_openai_chunk_deserialize() {
         let json = r#"{
@@ -0,0 +1,287 @@
+//! Streaming support for transcription providers
+//!
+//! Audio arrives as a stream of PCM chunks while the user is still talking and
+//! partial transcriptions are yielded as they become available. Providers
+//! without a native streaming API can be wrapped in [`ChunkedTranscriptionProvider`],
+//! which transcribes rolling windows with the batch API.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+
+use crate::error::Result;
+use crate::types::{AudioClip, AudioData, PcmFormat};
+
+use super::{TranscriptFormat, TranscriptionProvider, TranscriptionRequest, TranscriptionResponse};
+
+/// Stream of captured mono PCM chunks, in the provider's [`PcmFormat`] (16-bit by default)
+pub type AudioChunkStream = Pin<Box<dyn Stream<Item = AudioData> + Send>>;
+
+/// Stream of partial transcriptions
+/// Each item holds the full text so far; the last item before the stream ends is final
+pub type TranscriptionStream = Pin<Box<dyn Stream<Item = Result<TranscriptionResponse>> + Send>>;
+
+/// Trait for transcription providers that accept audio incrementally
+#[async_trait]
+pub trait StreamingTranscriptionProvider: Send + Sync {
+    /// Get the provider name
+    fn name(&self) -> &'static str;
+
+    /// Transcribe audio as it arrives, yielding partial results
+    async fn transcribe_stream(
+        &self,
+        audio: AudioChunkStream,
+        sample_rate: u32,
+    ) -> Result<TranscriptionStream>;
+
+    /// Check if the provider is configured and ready
+    fn is_configured(&self) -> bool;
+}
+
+/// Default window length for chunked transcription
+const DEFAULT_WINDOW_MS: u32 = 3000;
+
+/// Characters of earlier text passed as the prompt for the next window
+const PROMPT_TAIL_CHARS: usize = 200;
+
+/// Streaming adapter over any batch provider
+///
+/// Buffers incoming audio into fixed windows, transcribes each window as soon
+/// as it fills, and appends the result. The tail of the text so far is passed
+/// as the prompt so words carry across window boundaries.
+pub struct ChunkedTranscriptionProvider {
+    inner: Arc<dyn TranscriptionProvider>,
+    window_ms: u32,
+    language: Option<String>,
+    format: PcmFormat,
+}
+
+impl ChunkedTranscriptionProvider {
+    pub fn new(inner: Arc<dyn TranscriptionProvider>) -> Self {
+        Self {
+            inner,
+            window_ms: DEFAULT_WINDOW_MS,
+            language: None,
+            format: PcmFormat::default(),
+        }
+    }
+
+    pub fn with_window_ms(mut self, window_ms: u32) -> Self {
+        self.window_ms = window_ms.max(1);
+        self
+    }
+
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Encoding of the incoming chunks
+    pub fn with_format(mut self, format: PcmFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+struct ChunkedState {
+    audio: AudioChunkStream,
+    provider: Arc<dyn TranscriptionProvider>,
+    sample_rate: u32,
+    format: PcmFormat,
+    window_bytes: usize,
+    language: Option<String>,
+    pending: AudioData,
+    text: String,
+    duration_ms: u64,
+    finished: bool,
+}
+
+impl ChunkedState {
+    /// Pull audio until a full window is buffered or the input ends
+    async fn next_window(&mut self) -> Option<AudioData> {
+        while !self.finished && self.pending.len() < self.window_bytes {
+            match self.audio.next().await {
+                Some(chunk) => self.pending.extend(chunk),
+                None => self.finished = true,
+            }
+        }
+
+        if self.pending.is_empty() {
+            return None;
+        }
+
+        let split = self.window_bytes.min(self.pending.len());
+        let rest = self.pending.split_off(split);
+        Some(std::mem::replace(&mut self.pending, rest))
+    }
+
+    fn request_for(&self, window: AudioData) -> TranscriptionRequest {
+        let clip = AudioClip::mono(window, self.sample_rate).with_format(self.format);
+        let mut request = TranscriptionRequest::from_clip(clip);
+        if let Some(language) = &self.language {
+            request = request.with_language(language.clone());
+        }
+        if !self.text.is_empty() {
+            let skip = self.text.chars().count().saturating_sub(PROMPT_TAIL_CHARS);
+            request = request.with_prompt(self.text.chars().skip(skip).collect::<String>());
+        }
+        request
+    }
+}
+
+#[async_trait]
+impl StreamingTranscriptionProvider for ChunkedTranscriptionProvider {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn transcribe_stream(
+        &self,
+        audio: AudioChunkStream,
+        sample_rate: u32,
+    ) -> Result<TranscriptionStream> {
+        let window_bytes = (sample_rate as usize * self.window_ms as usize / 1000).max(1)
+            * self.format.bytes_per_sample();
+
+        let state = ChunkedState {
+            audio,
+            provider: Arc::clone(&self.inner),
+            sample_rate,
+            format: self.format,
+            window_bytes,
+            language: self.language.clone(),
+            pending: Vec::new(),
+            text: String::new(),
+            duration_ms: 0,
+            finished: false,
+        };
+
+        let stream = futures::stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            let window = state.next_window().await?;
+            let request = state.request_for(window);
+
+            match state.provider.transcribe(request).await {
+                Ok(response) => {
+                    let piece = response.text.trim();
+                    if !piece.is_empty() {
+                        if !state.text.is_empty() {
+                            state.text.push(' ');
+                        }
+                        state.text.push_str(piece);
+                    }
+                    state.duration_ms += response.duration_ms;
+
+                    let partial = TranscriptionResponse {
+                        text: state.text.clone(),
+                        confidence: None,
+                        language: response.language,
+                        duration_ms: state.duration_ms,
+                        segments: None,
+                        completed_text: None,
+                        format: TranscriptFormat::Plain,
+                    };
+                    Some((Ok(partial), Some(state)))
+                }
+                // stop after surfacing the error
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+
+        Ok(Box::pin(stream))
+    }
+
+    fn is_configured(&self) -> bool {
+        self.inner.is_configured()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reports how many samples it was given, and the prompt it saw
+    struct CountingProvider;
+
+    #[async_trait]
+    impl TranscriptionProvider for CountingProvider {
+        fn name(&self) -> &'static str {
+            "Counting"
+        }
+
+        fn model_name(&self) -> &str {
+            "counting"
+        }
+
+        async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+            let samples = request.audio.data.len() / request.audio.format.bytes_per_sample();
+            let text = match request.prompt {
+                Some(prompt) => format!("{}<{}>", samples, prompt.len()),
+                None => samples.to_string(),
+            };
+            Ok(TranscriptionResponse {
+                text,
+                confidence: None,
+                language: None,
+                duration_ms: request.audio.duration_ms(),
+                segments: None,
+                completed_text: None,
+                format: TranscriptFormat::Plain,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_chunked_transcription_yields_rolling_windows() {
+        // 1000 Hz sample rate and a 100ms window = 100 samples per window
+        let provider =
+            ChunkedTranscriptionProvider::new(Arc::new(CountingProvider)).with_window_ms(100);
+
+        // 250 samples arriving in uneven chunks
+        let chunks: Vec<AudioData> = vec![vec![0; 120], vec![0; 200], vec![0; 180]];
+        let audio: AudioChunkStream = Box::pin(futures::stream::iter(chunks));
+
+        let partials: Vec<TranscriptionResponse> = futures::executor::block_on(async {
+            let stream = provider.transcribe_stream(audio, 1000).await.unwrap();
+            stream.map(|r| r.unwrap()).collect().await
+        });
+
+        let texts: Vec<&str> = partials.iter().map(|p| p.text.as_str()).collect();
+        assert_eq!(texts, vec!["100", "100 100<3>", "100 100<3> 50<10>"]);
+        assert_eq!(partials.last().unwrap().duration_ms, 250);
+    }
+
+    #[test]
+    fn test_chunked_transcription_windows_follow_format() {
+        // 100 float samples per window, not 200
+        let provider = ChunkedTranscriptionProvider::new(Arc::new(CountingProvider))
+            .with_window_ms(100)
+            .with_format(PcmFormat::Float32);
+        let audio: AudioChunkStream = Box::pin(futures::stream::iter(vec![vec![0; 4 * 150]]));
+
+        let partials: Vec<TranscriptionResponse> = futures::executor::block_on(async {
+            let stream = provider.transcribe_stream(audio, 1000).await.unwrap();
+            stream.map(|r| r.unwrap()).collect().await
+        });
+        assert_eq!(partials.last().unwrap().text, "100 50<3>");
+        assert_eq!(partials.last().unwrap().duration_ms, 150);
+    }
+
+    #[test]
+    fn test_chunked_transcription_empty_audio() {
+        let provider = ChunkedTranscriptionProvider::new(Arc::new(CountingProvider));
+        let audio: AudioChunkStream = Box::pin(futures::stream::empty());
+
+        let count = futures::executor::block_on(async {
+            let stream = provider.transcribe_stream(audio, 16000).await.unwrap();
+            stream.count().await
+        });
+        assert_eq!(count, 0);
+    }
+}
@@ -1,24 +1,45 @@
 //! Transcription provider trait and types
 
+use std::path::Path;
+use std::time::Instant;
+
 use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, trace};
+
+use crate::error::{Error, Result};
+use crate::{AudioClip, AudioData};
+
+use super::completion::health_check_error;
 
-use crate::AudioData;
-use crate::error::Result;
+/// Rate recordings loaded by [`TranscriptionRequest::from_file`] are converted to
+pub const FILE_SAMPLE_RATE: u32 = 16000;
 
 /// Request for transcription
 #[derive(Debug, Clone)]
 pub struct TranscriptionRequest {
-    /// Raw audio data (16-bit PCM)
-    pub audio: AudioData,
-    /// Sample rate of the audio
-    pub sample_rate: u32,
+    /// Audio to transcribe, with its sample format, rate, and channel count
+    pub audio: AudioClip,
     /// Optional language hint (ISO 639-1 code, e.g., "en")
     pub language: Option<String>,
     /// Optional prompt to guide transcription
     pub prompt: Option<String>,
+    /// Names and terms to bias recognition toward (product names, contacts)
+    ///
+    /// Providers fold these into their prompt under their own length limits:
+    /// OpenAI Whisper and Base10 only read the last 224 prompt tokens (~800 chars),
+    /// Gemini is capped at 2000 chars to keep requests small, and local Whisper
+    /// ignores them. Terms that don't fit are dropped whole, never cut mid-word.
+    pub vocabulary: Vec<String>,
     /// Optional completion parameters for combined transcription+completion
     pub completion: Option<CompletionParams>,
+    /// Model to use instead of the provider's own (e.g. `gpt-4o-transcribe`)
+    /// Only OpenAI honours this; other providers always use their configured model.
+    pub model: Option<String>,
+    /// Output format to ask for; OpenAI only, `Json` when unset
+    pub response_format: Option<ResponseFormat>,
 }
 
 /// Parameters for completion (used in combined transcription+completion flow)
@@ -35,14 +56,97 @@ pub struct CompletionParams {
     pub voice_instruction: Option<String>,
 }
 
+/// Output format for Whisper-style APIs (`response_format`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Text only
+    #[default]
+    Json,
+    /// Text with language, duration, and timestamped segments (whisper-1 only)
+    VerboseJson,
+    /// Bare text body
+    Text,
+    /// SubRip subtitles (whisper-1 only)
+    Srt,
+    /// WebVTT subtitles (whisper-1 only)
+    Vtt,
+}
+
+impl ResponseFormat {
+    /// Value sent as `response_format`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "json",
+            ResponseFormat::VerboseJson => "verbose_json",
+            ResponseFormat::Text => "text",
+            ResponseFormat::Srt => "srt",
+            ResponseFormat::Vtt => "vtt",
+        }
+    }
+
+    /// What the transcript text holds when this format is returned
+    pub fn transcript_format(&self) -> TranscriptFormat {
+        match self {
+            ResponseFormat::Srt => TranscriptFormat::Srt,
+            ResponseFormat::Vtt => TranscriptFormat::Vtt,
+            _ => TranscriptFormat::Plain,
+        }
+    }
+}
+
+/// What [`TranscriptionResponse::text`] holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptFormat {
+    /// The spoken text
+    #[default]
+    Plain,
+    /// A SubRip document, as returned by the provider
+    Srt,
+    /// A WebVTT document, as returned by the provider
+    Vtt,
+}
+
 impl TranscriptionRequest {
+    /// Request for mono PCM bytes captured at `sample_rate`
     pub fn new(audio: AudioData, sample_rate: u32) -> Self {
+        Self::from_clip(AudioClip::mono(audio, sample_rate))
+    }
+
+    /// Request for a recording on disk, converted to 16 kHz mono 16-bit PCM
+    ///
+    /// WAV (16/24-bit integer or 32-bit float) loads everywhere. m4a, aac, mp3, caf,
+    /// and aiff need the `audio-decode` feature, which decodes them with macOS's
+    /// `afconvert`. Every provider then receives the same clip a live capture makes.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let clip = crate::audio::read_audio_file(path.as_ref())?;
+        let samples = crate::audio::resample(
+            &crate::audio::clip_to_f32_mono(&clip),
+            clip.sample_rate,
+            FILE_SAMPLE_RATE,
+        );
+        if samples.is_empty() {
+            return Err(Error::Audio(format!(
+                "{} contains no audio",
+                path.as_ref().display()
+            )));
+        }
+        Ok(Self::new(
+            crate::audio::samples_to_pcm(&samples),
+            FILE_SAMPLE_RATE,
+        ))
+    }
+
+    pub fn from_clip(audio: AudioClip) -> Self {
         Self {
             audio,
-            sample_rate,
             language: None,
             prompt: None,
+            vocabulary: Vec::new(),
             completion: None,
+            model: None,
+            response_format: None,
         }
     }
 
@@ -56,10 +160,81 @@ impl TranscriptionRequest {
         self
     }
 
+    pub fn with_vocabulary<I, S>(mut self, terms: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.vocabulary.extend(terms.into_iter().map(Into::into));
+        self
+    }
+
+    /// Vocabulary as a comma-separated list of at most `max_chars` characters
+    /// Blank and duplicate terms are skipped; terms that would overflow are dropped
+    pub fn vocabulary_hint(&self, max_chars: usize) -> Option<String> {
+        let mut hint = String::new();
+        let mut seen = Vec::new();
+
+        for term in &self.vocabulary {
+            let term = term.trim();
+            let key = term.to_lowercase();
+            if term.is_empty() || seen.contains(&key) {
+                continue;
+            }
+
+            let separator = if hint.is_empty() { 0 } else { 2 };
+            if hint.chars().count() + separator + term.chars().count() > max_chars {
+                continue;
+            }
+
+            if separator > 0 {
+                hint.push_str(", ");
+            }
+            hint.push_str(term);
+            seen.push(key);
+        }
+
+        (!hint.is_empty()).then_some(hint)
+    }
+
+    /// Prompt for Whisper-style APIs: the free-form prompt followed by the vocabulary,
+    /// at most `max_chars` characters in total
+    pub fn whisper_prompt(&self, max_chars: usize) -> Option<String> {
+        let prompt: String = self
+            .prompt
+            .as_deref()
+            .map(str::trim)
+            .unwrap_or_default()
+            .chars()
+            .take(max_chars)
+            .collect();
+
+        let used = prompt.chars().count();
+        let hint = self.vocabulary_hint(max_chars.saturating_sub(used + 1));
+
+        match (prompt.is_empty(), hint) {
+            (true, hint) => hint,
+            (false, Some(hint)) => Some(format!("{} {}", prompt, hint)),
+            (false, None) => Some(prompt),
+        }
+    }
+
     pub fn with_completion(mut self, params: CompletionParams) -> Self {
         self.completion = Some(params);
         self
     }
+
+    /// Use `model` for this request instead of the provider's configured model
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Ask for `format` output (subtitles come back in `text` unchanged)
+    pub fn with_response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = Some(format);
+        self
+    }
 }
 
 /// Response from transcription
@@ -78,6 +253,184 @@ pub struct TranscriptionResponse {
     /// Completed/formatted text if worker performed completion
     #[serde(default)]
     pub completed_text: Option<String>,
+    /// Whether `text` is the transcript itself or a subtitle document
+    #[serde(default)]
+    pub format: TranscriptFormat,
+}
+
+impl TranscriptionResponse {
+    /// Whether nothing but whitespace was transcribed (e.g. a silent recording)
+    pub fn is_empty(&self) -> bool {
+        self.text.trim().is_empty()
+    }
+}
+
+/// Log latency and audio length for a finished transcription, inside the provider's span
+/// Transcribed text is only emitted at trace level so normal logs never carry it
+pub(crate) fn trace_transcription(response: &TranscriptionResponse, started: Instant) {
+    info!(
+        latency_ms = started.elapsed().as_millis() as u64,
+        audio_ms = response.duration_ms,
+        chars = response.text.chars().count(),
+        "Transcription finished"
+    );
+    trace!(text = %response.text, "Transcription output");
+}
+
+/// Windows `transcribe_long` sends to the provider at once
+pub const DEFAULT_WINDOW_CONCURRENCY: usize = 4;
+
+/// How many words at each edge of a window are searched for the overlap
+const STITCH_SEARCH_WORDS: usize = 48;
+
+/// Transcribe a clip longer than the provider accepts by splitting it into windows
+///
+/// Consecutive windows share `overlap_ms` of audio; the words transcribed twice
+/// in that region are matched up and kept once. Clips no longer than `window_ms`
+/// are sent as-is.
+pub async fn transcribe_long(
+    provider: &dyn TranscriptionProvider,
+    clip: AudioClip,
+    window_ms: u64,
+    overlap_ms: u64,
+) -> Result<TranscriptionResponse> {
+    transcribe_long_with(
+        provider,
+        TranscriptionRequest::from_clip(clip),
+        window_ms,
+        overlap_ms,
+        DEFAULT_WINDOW_CONCURRENCY,
+    )
+    .await
+}
+
+/// Like `transcribe_long`, but with the language/prompt/vocabulary of `request`
+/// applied to every window and up to `concurrency` windows in flight at once
+pub async fn transcribe_long_with(
+    provider: &dyn TranscriptionProvider,
+    request: TranscriptionRequest,
+    window_ms: u64,
+    overlap_ms: u64,
+    concurrency: usize,
+) -> Result<TranscriptionResponse> {
+    if window_ms == 0 || overlap_ms >= window_ms {
+        return Err(Error::Config(format!(
+            "Overlap ({}ms) must be shorter than the window ({}ms)",
+            overlap_ms, window_ms
+        )));
+    }
+
+    let total_ms = request.audio.duration_ms();
+    if total_ms <= window_ms {
+        return provider.transcribe(request).await;
+    }
+
+    let step_ms = window_ms - overlap_ms;
+    let mut starts = Vec::new();
+    let mut start = 0;
+    loop {
+        starts.push(start);
+        if start + window_ms >= total_ms {
+            break;
+        }
+        start += step_ms;
+    }
+    debug!(
+        windows = starts.len(),
+        window_ms, overlap_ms, "Splitting long transcription"
+    );
+
+    let started = Instant::now();
+    let responses: Vec<TranscriptionResponse> = futures::stream::iter(starts)
+        .map(|start| {
+            let mut window = request.clone();
+            window.audio = request.audio.slice_ms(start, start + window_ms);
+            provider.transcribe(window)
+        })
+        .buffered(concurrency.max(1))
+        .try_collect()
+        .await?;
+
+    let text = responses
+        .iter()
+        .map(|response| response.text.as_str())
+        .fold(String::new(), |text, next| stitch(&text, next));
+    let confidences: Vec<f32> = responses.iter().filter_map(|r| r.confidence).collect();
+    let response = TranscriptionResponse {
+        text,
+        confidence: (!confidences.is_empty())
+            .then(|| confidences.iter().sum::<f32>() / confidences.len() as f32),
+        language: responses.iter().find_map(|r| r.language.clone()),
+        duration_ms: total_ms,
+        segments: None,
+        completed_text: None,
+        format: TranscriptFormat::Plain,
+    };
+    trace_transcription(&response, started);
+    Ok(response)
+}
+
+/// Join two window transcripts, keeping the words they share only once
+///
+/// Looks for the longest run of words (ignoring case and punctuation) that
+/// appears both near the end of `before` and near the start of `after`. Words
+/// cut off by a window edge come back as fragments ("rememb") that match
+/// nothing, so they fall outside that run and are dropped in favour of the
+/// other window's full copy.
+fn stitch(before: &str, after: &str) -> String {
+    let left: Vec<&str> = before.split_whitespace().collect();
+    let right: Vec<&str> = after.split_whitespace().collect();
+    if left.is_empty() || right.is_empty() {
+        return left.into_iter().chain(right).collect::<Vec<_>>().join(" ");
+    }
+
+    let tail = left.len().saturating_sub(STITCH_SEARCH_WORDS);
+    let head = right.len().min(STITCH_SEARCH_WORDS);
+    let left_keys: Vec<String> = left[tail..].iter().map(|w| stitch_key(w)).collect();
+    let right_keys: Vec<String> = right[..head].iter().map(|w| stitch_key(w)).collect();
+
+    // longest common run of words; on ties prefer the one latest in `before`
+    let mut best: Option<(usize, usize, usize)> = None;
+    for i in 0..left_keys.len() {
+        for j in 0..right_keys.len() {
+            let len = left_keys[i..]
+                .iter()
+                .zip(&right_keys[j..])
+                .take_while(|(a, b)| !a.is_empty() && a == b)
+                .count();
+            if len > 0 && best.is_none_or(|(_, _, best_len)| len >= best_len) {
+                best = Some((tail + i, j, len));
+            }
+        }
+    }
+
+    // a single shared word is only trusted when both edges are that short, or when it
+    // sits right at the seam with at most a cut-off word on either side of it
+    let trusted = best.filter(|&(i, j, len)| {
+        len >= 2
+            || left_keys.len().min(right_keys.len()) == 1
+            || (left.len() - (i + len) <= 1 && j <= 1)
+    });
+    match trusted {
+        Some((i, j, _)) => left[..i]
+            .iter()
+            .chain(&right[j..])
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" "),
+        None => {
+            debug!("No overlap found between transcription windows");
+            left.into_iter().chain(right).collect::<Vec<_>>().join(" ")
+        }
+    }
+}
+
+/// Comparison key for a word: lowercase, letters and digits only (empty never matches)
+fn stitch_key(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
 }
 
 /// A segment of transcribed text with timing
@@ -89,15 +442,434 @@ pub struct TranscriptionSegment {
     pub confidence: Option<f32>,
 }
 
+/// One spoken word and when it was said
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WordTiming {
+    pub word: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+impl WordTiming {
+    pub fn new(word: impl Into<String>, start_ms: u64, end_ms: u64) -> Self {
+        Self {
+            word: word.into(),
+            start_ms,
+            end_ms,
+        }
+    }
+}
+
+/// How `to_srt_with` and `to_vtt_with` group words into cues
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CueOptions {
+    /// A pause longer than this between words starts a new cue
+    pub max_gap_ms: u64,
+    /// Longest line, in characters, before the cue wraps
+    pub max_line_chars: usize,
+    /// Lines per cue before a new cue starts
+    pub max_lines: usize,
+}
+
+impl Default for CueOptions {
+    /// The usual broadcast limits: two lines of 42 characters, split on pauses over 0.7s
+    fn default() -> Self {
+        Self {
+            max_gap_ms: 700,
+            max_line_chars: 42,
+            max_lines: 2,
+        }
+    }
+}
+
+/// Words shown on screen together
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SubtitleCue {
+    start_ms: u64,
+    end_ms: u64,
+    lines: Vec<String>,
+}
+
+/// Group `words` into cues, starting a new one at long pauses or when the lines fill up
+/// A single word longer than a line gets a line of its own rather than being split.
+fn subtitle_cues(words: &[WordTiming], options: &CueOptions) -> Vec<SubtitleCue> {
+    let mut cues: Vec<SubtitleCue> = Vec::new();
+    let mut last_end = None;
+    for timing in words {
+        let word = timing.word.trim();
+        if word.is_empty() {
+            continue;
+        }
+
+        let paused =
+            last_end.is_some_and(|end| timing.start_ms.saturating_sub(end) > options.max_gap_ms);
+        last_end = Some(timing.end_ms);
+        if let Some(cue) = cues.last_mut().filter(|_| !paused) {
+            let line = cue.lines.last_mut().expect("cues are never empty");
+            if line.chars().count() + 1 + word.chars().count() <= options.max_line_chars {
+                line.push(' ');
+                line.push_str(word);
+                cue.end_ms = timing.end_ms;
+                continue;
+            }
+            if cue.lines.len() < options.max_lines.max(1) {
+                cue.lines.push(word.to_string());
+                cue.end_ms = timing.end_ms;
+                continue;
+            }
+        }
+        cues.push(SubtitleCue {
+            start_ms: timing.start_ms,
+            end_ms: timing.end_ms,
+            lines: vec![word.to_string()],
+        });
+    }
+    cues
+}
+
+/// `HH:MM:SS` plus milliseconds after `separator` ("," for SRT, "." for VTT)
+fn timecode(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+/// SubRip subtitles for `words`, grouped with the default [`CueOptions`]
+pub fn to_srt(words: &[WordTiming]) -> String {
+    to_srt_with(words, &CueOptions::default())
+}
+
+/// SubRip subtitles for `words`: numbered cues with `00:00:01,250` timecodes
+pub fn to_srt_with(words: &[WordTiming], options: &CueOptions) -> String {
+    subtitle_cues(words, options)
+        .iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                timecode(cue.start_ms, ','),
+                timecode(cue.end_ms, ','),
+                cue.lines.join("\n")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// WebVTT subtitles for `words`, grouped with the default [`CueOptions`]
+pub fn to_vtt(words: &[WordTiming]) -> String {
+    to_vtt_with(words, &CueOptions::default())
+}
+
+/// WebVTT subtitles for `words`: a `WEBVTT` header, then cues with `00:00:01.250` timecodes
+pub fn to_vtt_with(words: &[WordTiming], options: &CueOptions) -> String {
+    subtitle_cues(words, options)
+        .iter()
+        .fold("WEBVTT\n".to_string(), |mut vtt, cue| {
+            vtt.push_str(&format!(
+                "\n{} --> {}\n{}\n",
+                timecode(cue.start_ms, '.'),
+                timecode(cue.end_ms, '.'),
+                cue.lines.join("\n")
+            ));
+            vtt
+        })
+}
+
 /// Trait for transcription providers
 #[async_trait]
 pub trait TranscriptionProvider: Send + Sync {
     /// Get the provider name
     fn name(&self) -> &'static str;
 
+    /// Stable identifier for logs and usage accounting, e.g. "openai" (defaults to `name()`)
+    fn provider_name(&self) -> &str {
+        self.name()
+    }
+
+    /// Model that requests are sent to, e.g. "gpt-4o-mini" (empty if the provider doesn't say)
+    fn model_name(&self) -> &str {
+        ""
+    }
+
     /// Transcribe audio to text
     async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse>;
 
+    /// Transcribe half a second of silence to confirm the key and model work
+    /// Reporting no speech counts as healthy; the provider answered.
+    async fn health_check(&self) -> Result<()> {
+        if !self.is_configured() {
+            return Err(Error::ProviderNotConfigured(format!(
+                "{} is not configured",
+                self.name()
+            )));
+        }
+        let silence = AudioClip::mono(vec![0; 16000], 16000);
+        match self
+            .transcribe(TranscriptionRequest::from_clip(silence))
+            .await
+        {
+            Ok(_) | Err(Error::EmptyTranscription) | Err(Error::NoSignal(_)) => Ok(()),
+            Err(e) => Err(health_check_error(self.name(), self.model_name(), e)),
+        }
+    }
+
+    /// Like `transcribe`, but returns `Error::Cancelled` as soon as `cancel` fires
+    /// The in-flight request is dropped, which aborts the HTTP upload; local inference
+    /// that is already running synchronously can only be abandoned once it yields
+    async fn transcribe_cancellable(
+        &self,
+        request: TranscriptionRequest,
+        cancel: &CancellationToken,
+    ) -> Result<TranscriptionResponse> {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(Error::Cancelled),
+            result = self.transcribe(request) => result,
+        }
+    }
+
     /// Check if the provider is configured and ready
     fn is_configured(&self) -> bool;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Milliseconds of audio each synthetic word occupies
+    const WORD_MS: u64 = 120;
+    const SAMPLE_RATE: u32 = 16000;
+
+    /// "Hears" word `n` wherever the samples hold the value `n + 1`; a word cut
+    /// by the window edge comes back as the fragment that was heard, like a real
+    /// clipped word would: its start at the end of a window, its end at the start
+    struct SampleWords;
+
+    #[async_trait]
+    impl TranscriptionProvider for SampleWords {
+        fn name(&self) -> &'static str {
+            "SampleWords"
+        }
+
+        async fn transcribe(&self, request: TranscriptionRequest) -> Result<TranscriptionResponse> {
+            let samples: Vec<i16> = request
+                .audio
+                .data
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                .collect();
+            let full = (WORD_MS * u64::from(SAMPLE_RATE) / 1000) as usize;
+
+            let words: Vec<String> = samples
+                .chunk_by(|a, b| a == b)
+                .enumerate()
+                .map(|(i, run)| {
+                    let word = format!("word{}", run[0] - 1);
+                    let heard = (word.len() * run.len() / full).max(1);
+                    if run.len() == full {
+                        word
+                    } else if i == 0 {
+                        word[word.len() - heard..].to_string()
+                    } else {
+                        word[..heard].to_string()
+                    }
+                })
+                .collect();
+            Ok(TranscriptionResponse {
+                text: words.join(" "),
+                confidence: Some(0.9),
+                language: Some("en".to_string()),
+                duration_ms: request.audio.duration_ms(),
+                segments: None,
+                completed_text: None,
+                format: TranscriptFormat::Plain,
+            })
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_model_name_defaults_to_empty() {
+        assert_eq!(SampleWords.provider_name(), "SampleWords");
+        assert_eq!(SampleWords.model_name(), "");
+    }
+
+    fn spoken(words: usize) -> AudioClip {
+        let per_word = (WORD_MS * u64::from(SAMPLE_RATE) / 1000) as usize;
+        let data = (0..words)
+            .flat_map(|n| std::iter::repeat_n(n as i16 + 1, per_word))
+            .flat_map(i16::to_le_bytes)
+            .collect();
+        AudioClip::mono(data, SAMPLE_RATE)
+    }
+
+    #[test]
+    fn test_transcribe_long_stitches_windows() {
+        let words = 60;
+        let expected = (0..words)
+            .map(|n| format!("word{}", n))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        // 7.2s of audio in 1s windows whose edges land mid-word
+        let response =
+            futures::executor::block_on(transcribe_long(&SampleWords, spoken(words), 1000, 300))
+                .unwrap();
+        assert_eq!(response.text, expected);
+        assert_eq!(response.duration_ms, words as u64 * WORD_MS);
+        assert_eq!(response.language.as_deref(), Some("en"));
+
+        // edges on word boundaries, run one window at a time
+        let response = futures::executor::block_on(transcribe_long_with(
+            &SampleWords,
+            TranscriptionRequest::from_clip(spoken(words)),
+            1200,
+            480,
+            1,
+        ))
+        .unwrap();
+        assert_eq!(response.text, expected);
+
+        // short clips go through in one request
+        let response =
+            futures::executor::block_on(transcribe_long(&SampleWords, spoken(3), 1000, 300))
+                .unwrap();
+        assert_eq!(response.text, "word0 word1 word2");
+
+        let err = futures::executor::block_on(transcribe_long(&SampleWords, spoken(3), 300, 300))
+            .unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+    }
+
+    fn words(timings: &[(&str, u64, u64)]) -> Vec<WordTiming> {
+        timings
+            .iter()
+            .map(|&(word, start, end)| WordTiming::new(word, start, end))
+            .collect()
+    }
+
+    #[test]
+    fn test_subtitle_timecodes() {
+        assert_eq!(timecode(1250, ','), "00:00:01,250");
+        assert_eq!(timecode(3_723_004, '.'), "01:02:03.004");
+
+        let timings = words(&[("Hello", 1250, 1600), ("there.", 1650, 2000)]);
+        assert_eq!(
+            to_srt(&timings),
+            "1\n00:00:01,250 --> 00:00:02,000\nHello there.\n"
+        );
+        assert_eq!(
+            to_vtt(&timings),
+            "WEBVTT\n\n00:00:01.250 --> 00:00:02.000\nHello there.\n"
+        );
+        assert_eq!(to_srt(&[]), "");
+        assert_eq!(to_vtt(&[]), "WEBVTT\n");
+    }
+
+    #[test]
+    fn test_subtitle_cues_split_on_pauses_and_length() {
+        // a 1.5s pause after "late" starts a new cue
+        let timings = words(&[
+            ("running", 0, 400),
+            ("late", 450, 800),
+            ("see", 2300, 2500),
+            ("you", 2550, 2700),
+            ("soon", 2750, 3000),
+        ]);
+        assert_eq!(
+            to_srt(&timings),
+            "1\n00:00:00,000 --> 00:00:00,800\nrunning late\n\n\
+             2\n00:00:02,300 --> 00:00:03,000\nsee you soon\n"
+        );
+
+        // 10-character lines, two per cue
+        let options = CueOptions {
+            max_line_chars: 10,
+            ..CueOptions::default()
+        };
+        let timings = words(&[
+            ("one", 0, 100),
+            ("two", 100, 200),
+            ("three", 200, 300),
+            ("four", 300, 400),
+            ("five", 400, 500),
+            ("six", 500, 600),
+        ]);
+        assert_eq!(
+            to_vtt_with(&timings, &options),
+            "WEBVTT\n\n\
+             00:00:00.000 --> 00:00:00.400\none two\nthree four\n\n\
+             00:00:00.400 --> 00:00:00.600\nfive six\n"
+        );
+    }
+
+    #[test]
+    fn test_stitch_keeps_repeated_phrases() {
+        assert_eq!(
+            stitch("we said no, no", "No, no more waiting"),
+            "we said No, no more waiting"
+        );
+        assert_eq!(
+            stitch("hello there", "general kenobi"),
+            "hello there general kenobi"
+        );
+        assert_eq!(stitch("", "hi"), "hi");
+    }
+
+    #[test]
+    fn test_stitch_drops_clipped_words() {
+        // neighbouring windows repeat the overlap; the word cut at each edge is a
+        // fragment, and case and punctuation can differ between the two copies
+        assert_eq!(
+            stitch(
+                "I'll be there in about ten minutes so don't wa",
+                "ten minutes so don't wait up for me"
+            ),
+            "I'll be there in about ten minutes so don't wait up for me"
+        );
+        assert_eq!(
+            stitch(
+                "we should grab lunch. Maybe Thurs",
+                "ch grab lunch, maybe Thursday works?"
+            ),
+            "we should grab lunch, maybe Thursday works?"
+        );
+    }
+
+    #[test]
+    fn test_from_file_converts_wav() {
+        // one second of 44.1 kHz stereo float
+        let frames: Vec<f32> = crate::audio::generate_tone(440.0, 1000, 44100)
+            .into_iter()
+            .flat_map(|s| [s, s])
+            .collect();
+        let clip = AudioClip::new(
+            crate::audio::encode_samples(&frames, crate::PcmFormat::Float32),
+            44100,
+            2,
+        )
+        .with_format(crate::PcmFormat::Float32);
+        let path = std::env::temp_dir().join(format!("flow-memo-{}.wav", uuid::Uuid::new_v4()));
+        std::fs::write(&path, clip.to_wav()).unwrap();
+
+        let request = TranscriptionRequest::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(request.audio.sample_rate, FILE_SAMPLE_RATE);
+        assert_eq!(request.audio.channels, 1);
+        assert_eq!(request.audio.format, crate::PcmFormat::Pcm16);
+        assert_eq!(request.audio.duration_ms(), 1000);
+
+        let err = TranscriptionRequest::from_file("memo.ogg").unwrap_err();
+        assert!(matches!(err, Error::Audio(_)));
+    }
+}
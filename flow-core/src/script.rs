@@ -0,0 +1,157 @@
+//! Offline writing-script detection for dictated text
+//!
+//! The writing modes are tuned for English: "very casual" means lowercase and
+//! texting abbreviations like "rn", which misfire in Russian or Japanese. Knowing
+//! the dominant script lets the pipeline keep the reply in the user's language and
+//! skip modes that don't carry over. Detection counts letters per Unicode block,
+//! so it needs no models or dependencies.
+//! Example: "Привет, я опоздаю" -> Cyrillic
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::WritingMode;
+
+/// Writing system a piece of text is (mostly) written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    /// Chinese characters, also the kanji in Japanese
+    Han,
+    /// Hiragana and katakana; any kana marks the text as Japanese
+    Kana,
+    Hangul,
+}
+
+impl Script {
+    /// Script a single character belongs to, if it's a letter we recognize
+    pub fn of(c: char) -> Option<Script> {
+        let script = match c as u32 {
+            0x0041..=0x005A | 0x0061..=0x007A | 0x00C0..=0x024F | 0x1E00..=0x1EFF => Script::Latin,
+            0x0370..=0x03FF | 0x1F00..=0x1FFF => Script::Greek,
+            0x0400..=0x052F => Script::Cyrillic,
+            0x0590..=0x05FF => Script::Hebrew,
+            0x0600..=0x06FF | 0x0750..=0x077F => Script::Arabic,
+            0x0900..=0x097F => Script::Devanagari,
+            0x0E00..=0x0E7F => Script::Thai,
+            0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Script::Kana,
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Script::Hangul,
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF => Script::Han,
+            _ => return None,
+        };
+        // µ, ×, ÷ and friends sit in the Latin-1 range but aren't letters
+        (script != Script::Latin || c.is_alphabetic()).then_some(script)
+    }
+
+    /// Human-readable name used in prompts
+    pub fn name(self) -> &'static str {
+        match self {
+            Script::Latin => "Latin",
+            Script::Cyrillic => "Cyrillic",
+            Script::Greek => "Greek",
+            Script::Arabic => "Arabic",
+            Script::Hebrew => "Hebrew",
+            Script::Devanagari => "Devanagari",
+            Script::Thai => "Thai",
+            Script::Han => "Chinese",
+            Script::Kana => "Japanese",
+            Script::Hangul => "Korean",
+        }
+    }
+
+    /// Prompt instruction keeping the reply in this script, `None` for Latin
+    /// Latin text is the case the modes were written for, so it gets no extra prompt.
+    pub fn instruction(self) -> Option<String> {
+        (self != Script::Latin).then(|| {
+            format!(
+                "The text is written in {} script. Reply in the same language and script; never translate or transliterate it.",
+                self.name()
+            )
+        })
+    }
+
+    /// `mode` adjusted for text in this script
+    /// Very casual relies on lowercase and English texting shorthand, so outside Latin
+    /// it falls back to Casual; the other modes carry over.
+    pub fn adjust_mode(self, mode: WritingMode) -> WritingMode {
+        match (self, mode) {
+            (Script::Latin, mode) => mode,
+            (_, WritingMode::VeryCasual) => WritingMode::Casual,
+            (_, mode) => mode,
+        }
+    }
+}
+
+/// Dominant script of `text`, or `None` when it has no letters we recognize
+///
+/// Counts letters per script and picks the most common. Any kana makes the text
+/// Japanese even when kanji outnumber it, since Chinese never uses kana.
+pub fn detect_script(text: &str) -> Option<Script> {
+    let mut counts: Vec<(Script, usize)> = Vec::new();
+    for script in text.chars().filter_map(Script::of) {
+        match counts.iter_mut().find(|(s, _)| *s == script) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((script, 1)),
+        }
+    }
+
+    if counts.iter().any(|(s, _)| *s == Script::Kana) {
+        return Some(Script::Kana);
+    }
+    // on a tie the script seen first wins
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(script, _)| script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_script() {
+        assert_eq!(
+            detect_script("running late, be there in 5"),
+            Some(Script::Latin)
+        );
+        assert_eq!(detect_script("Привет, я опоздаю"), Some(Script::Cyrillic));
+        assert_eq!(detect_script("我马上到"), Some(Script::Han));
+        assert_eq!(detect_script("今日は遅れます"), Some(Script::Kana));
+        assert_eq!(detect_script("곧 도착해"), Some(Script::Hangul));
+        assert_eq!(detect_script("مرحبا"), Some(Script::Arabic));
+        assert_eq!(detect_script("Café à 5h"), Some(Script::Latin));
+
+        // a brand name doesn't outvote the rest of the message
+        assert_eq!(detect_script("Встреча в Zoom в 5"), Some(Script::Cyrillic));
+
+        assert_eq!(detect_script("123 🔥 !!"), None);
+        assert_eq!(detect_script(""), None);
+    }
+
+    #[test]
+    fn test_script_adjusts_mode_and_prompt() {
+        assert_eq!(
+            Script::Latin.adjust_mode(WritingMode::VeryCasual),
+            WritingMode::VeryCasual
+        );
+        assert_eq!(
+            Script::Cyrillic.adjust_mode(WritingMode::VeryCasual),
+            WritingMode::Casual
+        );
+        assert_eq!(
+            Script::Han.adjust_mode(WritingMode::Formal),
+            WritingMode::Formal
+        );
+
+        assert_eq!(Script::Latin.instruction(), None);
+        assert!(Script::Hangul.instruction().unwrap().contains("Korean"));
+    }
+}
@@ -3,32 +3,169 @@
 use chrono::{DateTime, Utc};
 use parking_lot::Mutex;
 use rusqlite::{Connection, OptionalExtension, params};
-use std::path::Path;
-use tracing::{debug, info};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use crate::error::Result;
+use crate::contacts::{
+    ClassificationDecision, ClassificationDetail, is_emoji_char, normalize_name,
+};
+use crate::error::{Error, Result};
+use crate::prompt_template::PromptTemplate;
+use crate::providers::{CompletionRequest, TokenUsage};
 use crate::types::{
-    AnalyticsEvent, AppCategory, AppContext, Contact, ContactCategory, Correction,
-    CorrectionSource, EventType, Shortcut, Transcription, TranscriptionHistoryEntry,
-    TranscriptionStatus, WritingMode,
+    AnalyticsEvent, AppCategory, AppContext, AudioClip, Contact, ContactCategory, Correction,
+    CorrectionSource, EmojiPolicy, EventType, Shortcut, Transcription, TranscriptionHistoryEntry,
+    TranscriptionId, TranscriptionStatus, UsageSummary, WritingMode,
 };
 
 /// Storage backend using SQLite
 pub struct Storage {
     conn: Mutex<Connection>,
+    audio_cache: Mutex<Option<AudioCacheConfig>>,
+}
+
+/// A contact with its recency-weighted score, see [`Storage::get_contacts_ranked`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RankedContact {
+    pub contact: Contact,
+    /// Dictations to this contact, each counting half as much per half-life of age
+    pub score: f64,
+}
+
+/// Classification decisions kept by [`Storage::log_classification`]; older ones are dropped
+pub const CLASSIFICATION_LOG_MAX_ROWS: usize = 1000;
+
+/// Dictations to a contact before an emoji policy is inferred from them
+pub const MIN_EMOJI_SAMPLES: u32 = 5;
+
+/// How often the user puts emoji in dictations to a contact, see [`Storage::get_emoji_usage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct EmojiUsage {
+    /// Dictations to the contact counted so far
+    pub dictations: u32,
+    /// How many of them contained an emoji
+    pub with_emoji: u32,
+    /// Policy set by the user, which wins over the learned one
+    pub pinned: Option<EmojiPolicy>,
 }
 
+impl EmojiUsage {
+    /// Policy inferred from the counts, once there are `MIN_EMOJI_SAMPLES` of them
+    /// Never using emoji means avoid, using them in at least half means add.
+    pub fn learned(&self) -> Option<EmojiPolicy> {
+        if self.dictations < MIN_EMOJI_SAMPLES {
+            None
+        } else if self.with_emoji == 0 {
+            Some(EmojiPolicy::Avoid)
+        } else if self.with_emoji * 2 >= self.dictations {
+            Some(EmojiPolicy::Add)
+        } else {
+            Some(EmojiPolicy::Preserve)
+        }
+    }
+
+    /// The pinned policy, or else the learned one
+    pub fn policy(&self) -> Option<EmojiPolicy> {
+        self.pinned.or_else(|| self.learned())
+    }
+}
+
+/// Where dictation audio is kept and how much of it, see [`Storage::set_audio_cache`]
+#[derive(Debug, Clone)]
+pub struct AudioCacheConfig {
+    pub dir: PathBuf,
+    /// Least recently used clips are deleted once the cache grows past this
+    pub max_bytes: u64,
+}
+
+// API keys live under "<provider>_api_key"; look them up by provider name with
+// [`api_key_setting`] or [`Storage::get_api_key`] rather than naming the key directly
+/// OpenAI key, used for both transcription and completion
 pub const SETTING_OPENAI_API_KEY: &str = "openai_api_key";
+/// Google AI Studio key for Gemini transcription and completion
 pub const SETTING_GEMINI_API_KEY: &str = "gemini_api_key";
 pub const SETTING_ANTHROPIC_API_KEY: &str = "anthropic_api_key";
+/// OpenRouter key, completion only
 pub const SETTING_OPENROUTER_API_KEY: &str = "openrouter_api_key";
+/// Base10 worker key, transcription only
 pub const SETTING_BASE10_API_KEY: &str = "base10_api_key";
+/// Completion provider name: "openai" (default), "gemini", "openrouter", "none", or "rules"
 pub const SETTING_COMPLETION_PROVIDER: &str = "completion_provider";
+/// Filler word removal before formatting: "off" (default), "light", or "aggressive"
+pub const SETTING_FILLER_LEVEL: &str = "filler_level";
+/// Profanity check for formal contacts: "off" (default), "warn", or "soften"
+pub const SETTING_PROFANITY_ACTION: &str = "profanity_action";
+/// Comma-separated words replacing the default profanity list, each optionally `word=replacement`
+pub const SETTING_PROFANITY_WORDS: &str = "profanity_words";
+/// Instruction put before every completion system prompt, e.g. "Match the user's original language."
+pub const SETTING_SYSTEM_PROMPT_PREFIX: &str = "system_prompt_prefix";
+/// Instruction put after every completion system prompt, e.g. "Never use em-dashes."
+pub const SETTING_SYSTEM_PROMPT_SUFFIX: &str = "system_prompt_suffix";
+/// Comma-separated organizations or email domains the user works for; contacts there are Coworkers
+pub const SETTING_KNOWN_EMPLOYERS: &str = "known_employers";
+/// "true" keeps a log of contact classifications (see [`Storage::log_classification`])
+pub const SETTING_LOG_CLASSIFICATIONS: &str = "log_classifications";
+/// The user's work hours as "HH:MM-HH:MM" local time; unset means no time-of-day adjustment
+pub const SETTING_WORK_HOURS: &str = "work_hours";
+/// Prefix for per-provider completion model overrides (see [`completion_model_setting`])
+pub const SETTING_COMPLETION_MODEL: &str = "completion_model";
+/// Prefix for per-provider request limits in requests per minute (see [`rate_limit_setting`])
+pub const SETTING_RATE_LIMIT_RPM: &str = "rate_limit_rpm";
 pub const SETTING_USE_LOCAL_TRANSCRIPTION: &str = "use_local_transcription";
 pub const SETTING_LOCAL_WHISPER_MODEL: &str = "local_whisper_model";
 /// Cloud transcription provider: "auto" (default) | "openai"
 pub const SETTING_CLOUD_TRANSCRIPTION_PROVIDER: &str = "cloud_transcription_provider";
+/// "true" keeps each dictation's audio so it can be re-run with another provider
+pub const SETTING_SAVE_DICTATION_AUDIO: &str = "save_dictation_audio";
+/// "true" posts a notification with the formatted text when a dictation finishes
+pub const SETTING_NOTIFY_ON_DICTATION: &str = "notify_on_dictation";
+/// Seconds a detected Messages contact is reused when detection comes back empty
+pub const SETTING_LAST_CONTACT_TTL_SECS: &str = "last_contact_ttl_secs";
+/// Default for [`SETTING_LAST_CONTACT_TTL_SECS`]
+pub const DEFAULT_LAST_CONTACT_TTL_SECS: i64 = 120;
+/// Days for a contact's recency score to halve without new dictations
+/// (see [`Storage::get_contacts_ranked`]); applies to dictations from then on
+pub const SETTING_CONTACT_HALF_LIFE_DAYS: &str = "contact_half_life_days";
+/// Default for [`SETTING_CONTACT_HALF_LIFE_DAYS`]
+pub const DEFAULT_CONTACT_HALF_LIFE_DAYS: f64 = 14.0;
+/// The most recently detected contact; its `updated_at` is when it was seen
+const LAST_CONTACT_KEY: &str = "last_contact";
+/// Input gain multiplier from `flow_calibrate_gain` (1.0 when unset)
+pub const SETTING_INPUT_GAIN: &str = "input_gain";
+/// Shortest recording kept, in milliseconds (see [`AudioCaptureConfig::min_duration_ms`])
+///
+/// [`AudioCaptureConfig::min_duration_ms`]: crate::audio::AudioCaptureConfig::min_duration_ms
+pub const SETTING_MIN_RECORDING_MS: &str = "min_recording_ms";
+/// Size cap for saved dictation audio in megabytes
+pub const SETTING_AUDIO_CACHE_MAX_MB: &str = "audio_cache_max_mb";
+pub const DEFAULT_AUDIO_CACHE_MAX_MB: u64 = 512;
+
+/// Per-call usage rows older than this are rolled up into daily totals
+pub const USAGE_RAW_RETENTION_DAYS: i64 = 30;
+
+/// Setting key holding `provider`'s API key, e.g. "gemini" -> "gemini_api_key"
+/// `None` for providers that take no key ("none", "rules") or aren't known.
+pub fn api_key_setting(provider: &str) -> Option<&'static str> {
+    match provider {
+        "openai" => Some(SETTING_OPENAI_API_KEY),
+        "gemini" => Some(SETTING_GEMINI_API_KEY),
+        "anthropic" => Some(SETTING_ANTHROPIC_API_KEY),
+        "openrouter" => Some(SETTING_OPENROUTER_API_KEY),
+        "base10" => Some(SETTING_BASE10_API_KEY),
+        _ => None,
+    }
+}
+
+/// Setting key for a completion provider's model override, e.g. "completion_model_gemini"
+pub fn completion_model_setting(provider: &str) -> String {
+    format!("{}_{}", SETTING_COMPLETION_MODEL, provider)
+}
+
+/// Setting key for a provider's request limit, e.g. "rate_limit_rpm_openai"
+pub fn rate_limit_setting(provider: &str) -> String {
+    format!("{}_{}", SETTING_RATE_LIMIT_RPM, provider)
+}
 
 impl Storage {
     /// Open or create a database at the given path
@@ -36,6 +173,7 @@ impl Storage {
         let conn = Connection::open(path)?;
         let storage = Self {
             conn: Mutex::new(conn),
+            audio_cache: Mutex::new(None),
         };
         storage.init_schema()?;
         Ok(storage)
@@ -46,6 +184,7 @@ impl Storage {
         let conn = Connection::open_in_memory()?;
         let storage = Self {
             conn: Mutex::new(conn),
+            audio_cache: Mutex::new(None),
         };
         storage.init_schema()?;
         Ok(storage)
@@ -123,6 +262,71 @@ impl Storage {
                 updated_at TEXT NOT NULL
             );
 
+            CREATE TABLE IF NOT EXISTS category_modes (
+                app_category TEXT PRIMARY KEY,
+                writing_mode TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS contact_modes (
+                contact_name TEXT PRIMARY KEY,
+                writing_mode TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS mode_prompts (
+                writing_mode TEXT PRIMARY KEY,
+                template TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS contact_emoji (
+                contact_name TEXT PRIMARY KEY,
+                dictations INTEGER NOT NULL DEFAULT 0,
+                with_emoji INTEGER NOT NULL DEFAULT 0,
+                pinned_policy TEXT,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS classification_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                contact_name TEXT NOT NULL,
+                category TEXT NOT NULL,
+                confidence REAL NOT NULL,
+                reason TEXT NOT NULL,
+                fallback_applied INTEGER NOT NULL DEFAULT 0,
+                after_hours INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS usage_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                prompt_tokens INTEGER NOT NULL,
+                completion_tokens INTEGER NOT NULL,
+                total_tokens INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS usage_daily (
+                day TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                prompt_tokens INTEGER NOT NULL,
+                completion_tokens INTEGER NOT NULL,
+                total_tokens INTEGER NOT NULL,
+                calls INTEGER NOT NULL,
+                PRIMARY KEY (day, provider, model)
+            );
+
+            CREATE TABLE IF NOT EXISTS dictation_audio (
+                history_id TEXT PRIMARY KEY,
+                path TEXT NOT NULL,
+                size_bytes INTEGER NOT NULL,
+                last_used_at TEXT NOT NULL
+            );
+
             CREATE TABLE IF NOT EXISTS style_samples (
                 id TEXT PRIMARY KEY,
                 app_name TEXT NOT NULL,
@@ -154,6 +358,7 @@ impl Storage {
             CREATE INDEX IF NOT EXISTS idx_events_type ON events(event_type);
             CREATE INDEX IF NOT EXISTS idx_events_created ON events(created_at);
             CREATE INDEX IF NOT EXISTS idx_style_samples_app ON style_samples(app_name);
+            CREATE INDEX IF NOT EXISTS idx_usage_log_created ON usage_log(created_at);
             CREATE INDEX IF NOT EXISTS idx_contacts_name ON contacts(name);
             CREATE INDEX IF NOT EXISTS idx_contacts_frequency ON contacts(frequency DESC);
             "#,
@@ -165,6 +370,17 @@ impl Storage {
             [],
         );
 
+        // Migration: Add recency_score to contacts, starting from the raw frequency
+        if conn
+            .execute(
+                "ALTER TABLE contacts ADD COLUMN recency_score REAL NOT NULL DEFAULT 0",
+                [],
+            )
+            .is_ok()
+        {
+            conn.execute("UPDATE contacts SET recency_score = frequency", [])?;
+        }
+
         // Seed default corrections (only if table is empty)
         let count: i64 = conn.query_row(
             "SELECT COUNT(*) FROM corrections",
@@ -270,6 +486,52 @@ impl Storage {
         .map_err(Into::into)
     }
 
+    /// Stored API key for `provider` ("openai", "gemini", ...); an empty key counts as unset
+    /// Errors with `Config` for providers that have no key setting (see [`api_key_setting`]).
+    pub fn get_api_key(&self, provider: &str) -> Result<Option<String>> {
+        let key = self.get_setting(Self::api_key_key(provider)?)?;
+        Ok(key.filter(|key| !key.is_empty()))
+    }
+
+    /// Save the API key for `provider`
+    pub fn set_api_key(&self, provider: &str, key: &str) -> Result<()> {
+        self.set_setting(Self::api_key_key(provider)?, key)
+    }
+
+    fn api_key_key(provider: &str) -> Result<&'static str> {
+        api_key_setting(provider)
+            .ok_or_else(|| Error::Config(format!("{} has no API key setting", provider)))
+    }
+
+    /// Remember `name` as the contact the user is talking to right now
+    pub fn set_last_contact(&self, name: &str) -> Result<()> {
+        self.set_setting(LAST_CONTACT_KEY, name)
+    }
+
+    /// The last contact from `set_last_contact`, if it was seen within the TTL
+    /// (`last_contact_ttl_secs`, two minutes by default)
+    pub fn get_last_contact(&self) -> Result<Option<String>> {
+        let ttl_secs = self
+            .get_setting(SETTING_LAST_CONTACT_TTL_SECS)?
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .unwrap_or(DEFAULT_LAST_CONTACT_TTL_SECS);
+
+        let conn = self.conn.lock();
+        let row: Option<(String, String)> = conn
+            .query_row(
+                "SELECT value, updated_at FROM settings WHERE key = ?1",
+                params![LAST_CONTACT_KEY],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        Ok(row.and_then(|(name, seen_at)| {
+            let seen_at = DateTime::parse_from_rfc3339(&seen_at).ok()?;
+            let age = Utc::now().signed_duration_since(seen_at);
+            (!name.is_empty() && age < chrono::Duration::seconds(ttl_secs)).then_some(name)
+        }))
+    }
+
     /// Get recent transcriptions
     pub fn get_recent_transcriptions(&self, limit: usize) -> Result<Vec<Transcription>> {
         let conn = self.conn.lock();
@@ -805,6 +1067,273 @@ impl Storage {
         Ok(result.and_then(|s| parse_writing_mode(&s)))
     }
 
+    /// Override the default writing mode for every app in a category
+    pub fn set_category_mode(&self, category: AppCategory, mode: WritingMode) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            INSERT OR REPLACE INTO category_modes (app_category, writing_mode, updated_at)
+            VALUES (?1, ?2, ?3)
+            "#,
+            params![
+                format!("{:?}", category),
+                format!("{:?}", mode),
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get the writing mode override for an app category
+    pub fn get_category_mode(&self, category: AppCategory) -> Result<Option<WritingMode>> {
+        let conn = self.conn.lock();
+        let result: Option<String> = conn
+            .query_row(
+                "SELECT writing_mode FROM category_modes WHERE app_category = ?1",
+                params![format!("{:?}", category)],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(result.and_then(|s| parse_writing_mode(&s)))
+    }
+
+    // ========== Contact mode methods ==========
+
+    /// Pin a writing mode to a contact, overriding their category's default
+    /// Keyed by normalized name, so "Mom ❤️" and "mom" share a pin
+    pub fn set_contact_mode(&self, name: &str, mode: WritingMode) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            INSERT OR REPLACE INTO contact_modes (contact_name, writing_mode, updated_at)
+            VALUES (?1, ?2, ?3)
+            "#,
+            params![
+                normalize_name(name),
+                format!("{:?}", mode),
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get the writing mode pinned to a contact, if any
+    pub fn get_contact_mode(&self, name: &str) -> Result<Option<WritingMode>> {
+        let conn = self.conn.lock();
+        let result: Option<String> = conn
+            .query_row(
+                "SELECT writing_mode FROM contact_modes WHERE contact_name = ?1",
+                params![normalize_name(name)],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(result.and_then(|s| parse_writing_mode(&s)))
+    }
+
+    /// Remove a contact's pinned mode so the category default applies again
+    /// Returns true if a pin was removed
+    pub fn clear_contact_mode(&self, name: &str) -> Result<bool> {
+        let conn = self.conn.lock();
+        let removed = conn.execute(
+            "DELETE FROM contact_modes WHERE contact_name = ?1",
+            params![normalize_name(name)],
+        )?;
+        Ok(removed > 0)
+    }
+
+    // ========== Prompt template methods ==========
+
+    /// Use `template` instead of `mode`'s built-in instruction
+    /// Fails if the template can't render for a bare request in `mode` (no recipient,
+    /// script, or length), so a bad template is caught here rather than at send time.
+    pub fn set_prompt_template(&self, mode: WritingMode, template: &PromptTemplate) -> Result<()> {
+        let bare = CompletionRequest::new("").with_mode(mode);
+        template.render(&bare.template_variables())?;
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            INSERT OR REPLACE INTO mode_prompts (writing_mode, template, updated_at)
+            VALUES (?1, ?2, ?3)
+            "#,
+            params![
+                format!("{:?}", mode),
+                template.source(),
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get the template stored for `mode`, if any
+    pub fn get_prompt_template(&self, mode: WritingMode) -> Result<Option<PromptTemplate>> {
+        let conn = self.conn.lock();
+        let result: Option<String> = conn
+            .query_row(
+                "SELECT template FROM mode_prompts WHERE writing_mode = ?1",
+                params![format!("{:?}", mode)],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        result.map(PromptTemplate::parse).transpose()
+    }
+
+    /// Remove `mode`'s template so its built-in instruction applies again
+    /// Returns true if a template was removed
+    pub fn clear_prompt_template(&self, mode: WritingMode) -> Result<bool> {
+        let conn = self.conn.lock();
+        let removed = conn.execute(
+            "DELETE FROM mode_prompts WHERE writing_mode = ?1",
+            params![format!("{:?}", mode)],
+        )?;
+        Ok(removed > 0)
+    }
+
+    // ========== Contact emoji methods ==========
+
+    /// Count a dictation to a contact towards their emoji usage
+    /// Pass the user's own words, not the formatted output, so added emoji don't feed back.
+    pub fn record_emoji_usage(&self, name: &str, text: &str) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            INSERT INTO contact_emoji (contact_name, dictations, with_emoji, updated_at)
+            VALUES (?1, 1, ?2, ?3)
+            ON CONFLICT(contact_name) DO UPDATE SET
+                dictations = dictations + 1,
+                with_emoji = with_emoji + excluded.with_emoji,
+                updated_at = excluded.updated_at
+            "#,
+            params![
+                normalize_name(name),
+                text.chars().any(is_emoji_char) as i64,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Emoji counts and pinned policy for a contact (all zero if never recorded)
+    pub fn get_emoji_usage(&self, name: &str) -> Result<EmojiUsage> {
+        let conn = self.conn.lock();
+        let usage = conn
+            .query_row(
+                "SELECT dictations, with_emoji, pinned_policy FROM contact_emoji WHERE contact_name = ?1",
+                params![normalize_name(name)],
+                |row| {
+                    let pinned: Option<String> = row.get(2)?;
+                    Ok(EmojiUsage {
+                        dictations: row.get::<_, i64>(0)? as u32,
+                        with_emoji: row.get::<_, i64>(1)? as u32,
+                        pinned: pinned.and_then(|s| s.parse().ok()),
+                    })
+                },
+            )
+            .optional()?;
+        Ok(usage.unwrap_or_default())
+    }
+
+    /// Pin an emoji policy to a contact, overriding what was learned
+    pub fn set_emoji_policy(&self, name: &str, policy: EmojiPolicy) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            INSERT INTO contact_emoji (contact_name, pinned_policy, updated_at)
+            VALUES (?1, ?2, ?3)
+            ON CONFLICT(contact_name) DO UPDATE SET
+                pinned_policy = excluded.pinned_policy,
+                updated_at = excluded.updated_at
+            "#,
+            params![
+                normalize_name(name),
+                policy.as_str(),
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Remove a contact's pinned emoji policy so the learned one applies again
+    /// Returns true if a pin was removed
+    pub fn clear_emoji_policy(&self, name: &str) -> Result<bool> {
+        let conn = self.conn.lock();
+        let removed = conn.execute(
+            "UPDATE contact_emoji SET pinned_policy = NULL WHERE contact_name = ?1 AND pinned_policy IS NOT NULL",
+            params![normalize_name(name)],
+        )?;
+        Ok(removed > 0)
+    }
+
+    // ========== Classification log methods ==========
+
+    /// Record a classification decision, keeping only the newest [`CLASSIFICATION_LOG_MAX_ROWS`]
+    pub fn log_classification(&self, decision: &ClassificationDecision) -> Result<()> {
+        let conn = self.conn.lock();
+        let detail = &decision.detail;
+        conn.execute(
+            r#"
+            INSERT INTO classification_log (contact_name, category, confidence, reason,
+                                            fallback_applied, after_hours, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+            params![
+                decision.contact_name,
+                serialize_contact_category(detail.category),
+                detail.confidence as f64,
+                detail.reason,
+                detail.fallback_applied as i32,
+                detail.after_hours as i32,
+                decision.timestamp.to_rfc3339(),
+            ],
+        )?;
+        conn.execute(
+            r#"
+            DELETE FROM classification_log WHERE id NOT IN (
+                SELECT id FROM classification_log ORDER BY id DESC LIMIT ?1
+            )
+            "#,
+            params![CLASSIFICATION_LOG_MAX_ROWS as i64],
+        )?;
+        Ok(())
+    }
+
+    /// The most recent classification decisions, newest first
+    pub fn get_classification_log(&self, limit: usize) -> Result<Vec<ClassificationDecision>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT contact_name, category, confidence, reason, fallback_applied, after_hours, created_at
+            FROM classification_log
+            ORDER BY id DESC
+            LIMIT ?1
+            "#,
+        )?;
+
+        let decisions = stmt
+            .query_map(params![limit as i64], |row| {
+                let category: String = row.get(1)?;
+                let created_at: String = row.get(6)?;
+                Ok(ClassificationDecision {
+                    contact_name: row.get(0)?,
+                    detail: ClassificationDetail {
+                        category: parse_contact_category(&category),
+                        confidence: row.get::<_, f64>(2)? as f32,
+                        reason: row.get(3)?,
+                        fallback_applied: row.get::<_, i32>(4)? != 0,
+                        after_hours: row.get::<_, i32>(5)? != 0,
+                    },
+                    timestamp: DateTime::parse_from_rfc3339(&created_at)
+                        .map(|dt| dt.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now()),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(decisions)
+    }
+
     // ========== Style sample methods ==========
 
     /// Save a style sample for learning user's writing style in an app
@@ -844,6 +1373,233 @@ impl Storage {
         Ok(samples)
     }
 
+    // ========== Usage methods ==========
+
+    /// Record the tokens one completion call spent
+    /// Rows older than `USAGE_RAW_RETENTION_DAYS` are folded into daily totals to keep the log bounded
+    pub fn record_usage(&self, provider: &str, model: &str, usage: &TokenUsage) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            r#"
+            INSERT INTO usage_log (provider, model, prompt_tokens, completion_tokens, total_tokens, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            params![
+                provider,
+                model,
+                usage.prompt_tokens,
+                usage.completion_tokens,
+                usage.total_tokens,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+
+        let cutoff = (Utc::now() - chrono::Duration::days(USAGE_RAW_RETENTION_DAYS)).to_rfc3339();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            r#"
+            INSERT INTO usage_daily (day, provider, model, prompt_tokens, completion_tokens, total_tokens, calls)
+            SELECT substr(created_at, 1, 10), provider, model,
+                   SUM(prompt_tokens), SUM(completion_tokens), SUM(total_tokens), COUNT(*)
+            FROM usage_log
+            WHERE created_at < ?1
+            GROUP BY substr(created_at, 1, 10), provider, model
+            ON CONFLICT(day, provider, model) DO UPDATE SET
+                prompt_tokens = prompt_tokens + excluded.prompt_tokens,
+                completion_tokens = completion_tokens + excluded.completion_tokens,
+                total_tokens = total_tokens + excluded.total_tokens,
+                calls = calls + excluded.calls
+            "#,
+            params![cutoff],
+        )?;
+        let rolled_up = tx.execute(
+            "DELETE FROM usage_log WHERE created_at < ?1",
+            params![cutoff],
+        )?;
+        tx.commit()?;
+
+        if rolled_up > 0 {
+            debug!("Rolled up {} usage rows into daily totals", rolled_up);
+        }
+        Ok(())
+    }
+
+    /// Token totals per provider/model since `since`, sorted by provider then model
+    /// Rolled-up days count whole, so a window starting mid-day includes all of that day
+    pub fn get_usage_summary(&self, since: DateTime<Utc>) -> Result<Vec<UsageSummary>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT provider, model, SUM(prompt_tokens), SUM(completion_tokens), SUM(total_tokens), SUM(calls)
+            FROM (
+                SELECT provider, model, prompt_tokens, completion_tokens, total_tokens, 1 AS calls
+                FROM usage_log
+                WHERE created_at >= ?1
+                UNION ALL
+                SELECT provider, model, prompt_tokens, completion_tokens, total_tokens, calls
+                FROM usage_daily
+                WHERE day >= ?2
+            )
+            GROUP BY provider, model
+            ORDER BY provider, model
+            "#,
+        )?;
+
+        let summaries = stmt
+            .query_map(
+                params![since.to_rfc3339(), since.format("%Y-%m-%d").to_string()],
+                |row| {
+                    Ok(UsageSummary {
+                        provider: row.get(0)?,
+                        model: row.get(1)?,
+                        prompt_tokens: row.get::<_, i64>(2)? as u64,
+                        completion_tokens: row.get::<_, i64>(3)? as u64,
+                        total_tokens: row.get::<_, i64>(4)? as u64,
+                        calls: row.get::<_, i64>(5)? as u64,
+                    })
+                },
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(summaries)
+    }
+
+    // ========== Dictation audio methods ==========
+
+    /// Start (or with `None`, stop) saving dictation audio
+    /// Audio already saved stays readable until evicted or deleted
+    pub fn set_audio_cache(&self, config: Option<AudioCacheConfig>) {
+        *self.audio_cache.lock() = config;
+    }
+
+    pub fn audio_cache_enabled(&self) -> bool {
+        self.audio_cache.lock().is_some()
+    }
+
+    /// Write `clip` to the cache as a WAV file linked to a history entry
+    /// Returns the file path, or `None` when the cache is off or the clip alone exceeds the cap.
+    /// Evicts least recently used clips to stay under the cap.
+    pub fn save_dictation_audio(
+        &self,
+        history_id: &TranscriptionId,
+        clip: &AudioClip,
+    ) -> Result<Option<PathBuf>> {
+        let Some(config) = self.audio_cache.lock().clone() else {
+            return Ok(None);
+        };
+
+        let wav = clip.to_wav();
+        if wav.len() as u64 > config.max_bytes {
+            warn!(
+                "Dictation audio ({} bytes) exceeds the cache cap, not saving",
+                wav.len()
+            );
+            return Ok(None);
+        }
+
+        std::fs::create_dir_all(&config.dir)?;
+        let path = config.dir.join(format!("{}.wav", history_id));
+        std::fs::write(&path, &wav)?;
+
+        {
+            let conn = self.conn.lock();
+            conn.execute(
+                r#"
+                INSERT INTO dictation_audio (history_id, path, size_bytes, last_used_at)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT(history_id) DO UPDATE SET
+                    path = excluded.path,
+                    size_bytes = excluded.size_bytes,
+                    last_used_at = excluded.last_used_at
+                "#,
+                params![
+                    history_id.to_string(),
+                    path.to_string_lossy(),
+                    wav.len() as i64,
+                    Utc::now().to_rfc3339()
+                ],
+            )?;
+        }
+        debug!("Saved dictation audio for {}", history_id);
+
+        self.evict_dictation_audio(config.max_bytes)?;
+        Ok(Some(path))
+    }
+
+    /// Reload the audio saved for a history entry
+    /// Returns `None` if none was saved or the file has since disappeared
+    pub fn get_audio_for_dictation(
+        &self,
+        history_id: &TranscriptionId,
+    ) -> Result<Option<AudioClip>> {
+        let conn = self.conn.lock();
+        let path: Option<String> = conn
+            .query_row(
+                "SELECT path FROM dictation_audio WHERE history_id = ?1",
+                params![history_id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(path) = path else {
+            return Ok(None);
+        };
+
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                conn.execute(
+                    "UPDATE dictation_audio SET last_used_at = ?1 WHERE history_id = ?2",
+                    params![Utc::now().to_rfc3339(), history_id.to_string()],
+                )?;
+                Ok(Some(AudioClip::from_wav(&bytes)?))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                conn.execute(
+                    "DELETE FROM dictation_audio WHERE history_id = ?1",
+                    params![history_id.to_string()],
+                )?;
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Delete least recently used clips until the cache fits in `max_bytes`
+    fn evict_dictation_audio(&self, max_bytes: u64) -> Result<()> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT history_id, path, size_bytes FROM dictation_audio ORDER BY last_used_at DESC",
+        )?;
+        let entries = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)? as u64,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut kept_bytes = 0u64;
+        for (history_id, path, size_bytes) in entries {
+            if kept_bytes + size_bytes <= max_bytes {
+                kept_bytes += size_bytes;
+                continue;
+            }
+            if let Err(e) = std::fs::remove_file(&path)
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                warn!("Failed to delete cached audio {}: {}", path, e);
+                continue;
+            }
+            conn.execute(
+                "DELETE FROM dictation_audio WHERE history_id = ?1",
+                params![history_id],
+            )?;
+            debug!("Evicted cached audio for {}", history_id);
+        }
+        Ok(())
+    }
+
     // ========== Stats methods ==========
 
     /// Get total transcription time in milliseconds
@@ -914,13 +1670,7 @@ fn parse_correction_source(s: &str) -> CorrectionSource {
 }
 
 fn parse_writing_mode(s: &str) -> Option<WritingMode> {
-    match s {
-        "Formal" => Some(WritingMode::Formal),
-        "Casual" => Some(WritingMode::Casual),
-        "VeryCasual" => Some(WritingMode::VeryCasual),
-        "Excited" => Some(WritingMode::Excited),
-        _ => None,
-    }
+    s.parse().ok()
 }
 
 fn parse_contact_category(s: &str) -> ContactCategory {
@@ -930,6 +1680,7 @@ fn parse_contact_category(s: &str) -> ContactCategory {
         "CasualPeer" => ContactCategory::CasualPeer,
         "Partner" => ContactCategory::Partner,
         "FormalNeutral" => ContactCategory::FormalNeutral,
+        "Coworker" => ContactCategory::Coworker,
         _ => ContactCategory::FormalNeutral,
     }
 }
@@ -941,9 +1692,24 @@ fn serialize_contact_category(category: ContactCategory) -> &'static str {
         ContactCategory::CasualPeer => "CasualPeer",
         ContactCategory::Partner => "Partner",
         ContactCategory::FormalNeutral => "FormalNeutral",
+        ContactCategory::Coworker => "Coworker",
     }
 }
 
+/// `score` as of `now`, halved for every `half_life_days` since `last_contacted`
+fn decayed_score(
+    score: f64,
+    last_contacted: Option<&str>,
+    now: DateTime<Utc>,
+    half_life_days: f64,
+) -> f64 {
+    let Some(last) = last_contacted.and_then(|s| DateTime::parse_from_rfc3339(s).ok()) else {
+        return score;
+    };
+    let elapsed_days = (now - last.with_timezone(&Utc)).num_seconds().max(0) as f64 / 86_400.0;
+    score * 0.5f64.powf(elapsed_days / half_life_days)
+}
+
 impl Storage {
     // ============ Contact Management ============
 
@@ -978,6 +1744,56 @@ impl Storage {
         Ok(())
     }
 
+    /// Count one completed dictation to `name`, creating the contact if needed
+    ///
+    /// `frequency` is the number of dictations sent to a contact. Classification and
+    /// detection never touch it; callers invoke this once a dictation has finished.
+    /// `category` is only used when the contact is new. Returns the updated count.
+    /// The contact's recency score is decayed to now and gains one as well.
+    pub fn increment_frequency(&self, name: &str, category: ContactCategory) -> Result<u32> {
+        let half_life_days = self.contact_half_life_days()?;
+        let conn = self.conn.lock();
+        let now = Utc::now();
+
+        let previous: Option<(f64, Option<String>)> = conn
+            .query_row(
+                "SELECT recency_score, last_contacted FROM contacts WHERE name = ?1",
+                params![name],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let score = match previous {
+            Some((score, last_contacted)) => {
+                decayed_score(score, last_contacted.as_deref(), now, half_life_days) + 1.0
+            }
+            None => 1.0,
+        };
+
+        let frequency: i64 = conn.query_row(
+            r#"
+            INSERT INTO contacts (id, name, organization, category, frequency, last_contacted, created_at, updated_at, recency_score)
+            VALUES (?1, ?2, NULL, ?3, 1, ?4, ?4, ?4, ?5)
+            ON CONFLICT(name) DO UPDATE SET
+                frequency = frequency + 1,
+                last_contacted = excluded.last_contacted,
+                updated_at = excluded.updated_at,
+                recency_score = excluded.recency_score
+            RETURNING frequency
+            "#,
+            params![
+                Uuid::new_v4().to_string(),
+                name,
+                serialize_contact_category(category),
+                now.to_rfc3339(),
+                score,
+            ],
+            |row| row.get(0),
+        )?;
+
+        debug!("Contact {} frequency is now {}", name, frequency);
+        Ok(frequency as u32)
+    }
+
     /// Get a contact by name
     pub fn get_contact_by_name(&self, name: &str) -> Result<Option<Contact>> {
         let conn = self.conn.lock();
@@ -1054,6 +1870,71 @@ impl Storage {
         Ok(contacts)
     }
 
+    /// Contacts ordered by recency-weighted use, most relevant first
+    ///
+    /// Each dictation adds one to a contact's score, and the score halves every
+    /// `contact_half_life_days` (two weeks by default) without one, so a daily
+    /// contact outranks someone messaged 200 times a year ago. Ties fall back to
+    /// the raw `frequency`, which [`get_all_contacts`](Self::get_all_contacts) still sorts by.
+    pub fn get_contacts_ranked(&self) -> Result<Vec<RankedContact>> {
+        let half_life_days = self.contact_half_life_days()?;
+        let conn = self.conn.lock();
+        let now = Utc::now();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, name, organization, category, frequency, last_contacted, created_at, updated_at, recency_score
+             FROM contacts",
+        )?;
+
+        let mut ranked = stmt
+            .query_map([], |row| {
+                let id: String = row.get(0)?;
+                let last_contacted: Option<String> = row.get(5)?;
+                let created_at: String = row.get(6)?;
+                let updated_at: String = row.get(7)?;
+                let score: f64 = row.get(8)?;
+
+                Ok(RankedContact {
+                    score: decayed_score(score, last_contacted.as_deref(), now, half_life_days),
+                    contact: Contact {
+                        id: Uuid::parse_str(&id).unwrap(),
+                        name: row.get(1)?,
+                        organization: row.get(2)?,
+                        category: parse_contact_category(&row.get::<_, String>(3)?),
+                        frequency: row.get::<_, i64>(4)? as u32,
+                        last_contacted: last_contacted.and_then(|s| {
+                            DateTime::parse_from_rfc3339(&s)
+                                .ok()
+                                .map(|dt| dt.with_timezone(&Utc))
+                        }),
+                        created_at: DateTime::parse_from_rfc3339(&created_at)
+                            .unwrap()
+                            .with_timezone(&Utc),
+                        updated_at: DateTime::parse_from_rfc3339(&updated_at)
+                            .unwrap()
+                            .with_timezone(&Utc),
+                    },
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        ranked.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(b.contact.frequency.cmp(&a.contact.frequency))
+        });
+        Ok(ranked)
+    }
+
+    /// Half-life for contact recency scores, from settings or the default
+    fn contact_half_life_days(&self) -> Result<f64> {
+        Ok(self
+            .get_setting(SETTING_CONTACT_HALF_LIFE_DAYS)?
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|days| *days > 0.0)
+            .unwrap_or(DEFAULT_CONTACT_HALF_LIFE_DAYS))
+    }
+
     /// Get top N frequent contacts
     pub fn get_frequent_contacts(&self, limit: usize) -> Result<Vec<Contact>> {
         let conn = self.conn.lock();
@@ -1160,6 +2041,287 @@ mod tests {
         assert_eq!(mode, None);
     }
 
+    #[test]
+    fn test_dictation_audio_cache() {
+        let storage = Storage::in_memory().unwrap();
+        let dir = std::env::temp_dir().join(format!("flow-audio-{}", Uuid::new_v4()));
+        let clip = AudioClip::mono(vec![1u8; 3200], 16000);
+        let first = Uuid::new_v4();
+
+        // off by default
+        assert!(
+            storage
+                .save_dictation_audio(&first, &clip)
+                .unwrap()
+                .is_none()
+        );
+
+        // room for two clips
+        let wav_len = clip.to_wav().len() as u64;
+        storage.set_audio_cache(Some(AudioCacheConfig {
+            dir: dir.clone(),
+            max_bytes: wav_len * 2,
+        }));
+        storage
+            .save_dictation_audio(&first, &clip)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            storage.get_audio_for_dictation(&first).unwrap(),
+            Some(clip.clone())
+        );
+
+        let second = Uuid::new_v4();
+        storage.save_dictation_audio(&second, &clip).unwrap();
+        // touching the first makes the second the least recently used
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        storage.get_audio_for_dictation(&first).unwrap();
+        let third = Uuid::new_v4();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        storage.save_dictation_audio(&third, &clip).unwrap();
+
+        assert!(storage.get_audio_for_dictation(&first).unwrap().is_some());
+        assert!(storage.get_audio_for_dictation(&second).unwrap().is_none());
+        assert!(storage.get_audio_for_dictation(&third).unwrap().is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_usage_summary() {
+        let storage = Storage::in_memory().unwrap();
+        let usage = |prompt, completion| TokenUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            total_tokens: prompt + completion,
+        };
+
+        // an old row that the next write should roll up
+        let old = (Utc::now() - chrono::Duration::days(USAGE_RAW_RETENTION_DAYS + 5)).to_rfc3339();
+        storage
+            .conn
+            .lock()
+            .execute(
+                "INSERT INTO usage_log (provider, model, prompt_tokens, completion_tokens, total_tokens, created_at) VALUES ('openai', 'gpt-4o-mini', 100, 50, 150, ?1)",
+                params![old],
+            )
+            .unwrap();
+
+        storage
+            .record_usage("openai", "gpt-4o-mini", &usage(10, 5))
+            .unwrap();
+        storage
+            .record_usage("openai", "gpt-4o-mini", &usage(20, 10))
+            .unwrap();
+        storage
+            .record_usage("gemini", "gemini-2.0-flash", &usage(7, 3))
+            .unwrap();
+
+        let raw_rows: i64 = storage
+            .conn
+            .lock()
+            .query_row("SELECT COUNT(*) FROM usage_log", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(raw_rows, 3);
+
+        let recent = storage
+            .get_usage_summary(Utc::now() - chrono::Duration::days(1))
+            .unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].provider, "gemini");
+        assert_eq!(recent[1].prompt_tokens, 30);
+        assert_eq!(recent[1].total_tokens, 45);
+        assert_eq!(recent[1].calls, 2);
+
+        // the rolled-up day still counts toward longer windows
+        let all = storage
+            .get_usage_summary(Utc::now() - chrono::Duration::days(90))
+            .unwrap();
+        assert_eq!(all[1].prompt_tokens, 130);
+        assert_eq!(all[1].calls, 3);
+    }
+
+    #[test]
+    fn test_last_contact_expires() {
+        let storage = Storage::in_memory().unwrap();
+        assert_eq!(storage.get_last_contact().unwrap(), None);
+
+        storage.set_last_contact("Mom").unwrap();
+        assert_eq!(storage.get_last_contact().unwrap().as_deref(), Some("Mom"));
+
+        storage.set_last_contact("Jake").unwrap();
+        assert_eq!(storage.get_last_contact().unwrap().as_deref(), Some("Jake"));
+
+        storage
+            .set_setting(SETTING_LAST_CONTACT_TTL_SECS, "0")
+            .unwrap();
+        assert_eq!(storage.get_last_contact().unwrap(), None);
+    }
+
+    #[test]
+    fn test_increment_frequency() {
+        let storage = Storage::in_memory().unwrap();
+        assert_eq!(
+            storage
+                .increment_frequency("Mom", ContactCategory::CloseFamily)
+                .unwrap(),
+            1
+        );
+        // an existing contact keeps its category
+        assert_eq!(
+            storage
+                .increment_frequency("Mom", ContactCategory::Professional)
+                .unwrap(),
+            2
+        );
+        let contact = storage.get_contact_by_name("Mom").unwrap().unwrap();
+        assert_eq!(contact.frequency, 2);
+        assert_eq!(contact.category, ContactCategory::CloseFamily);
+        assert!(contact.last_contacted.is_some());
+    }
+
+    #[test]
+    fn test_contacts_ranked_by_recency() {
+        let storage = Storage::in_memory().unwrap();
+        let now = Utc::now();
+
+        // messaged 200 times, but a year ago
+        let mut old = Contact::new("Jake".to_string(), None, ContactCategory::CasualPeer);
+        old.frequency = 200;
+        old.last_contacted = Some(now - chrono::Duration::days(365));
+        storage.save_contact(&old).unwrap();
+        storage
+            .conn
+            .lock()
+            .execute(
+                "UPDATE contacts SET recency_score = 200 WHERE name = 'Jake'",
+                [],
+            )
+            .unwrap();
+        for _ in 0..3 {
+            storage
+                .increment_frequency("Mom", ContactCategory::CloseFamily)
+                .unwrap();
+        }
+
+        let ranked = storage.get_contacts_ranked().unwrap();
+        let names: Vec<&str> = ranked.iter().map(|r| r.contact.name.as_str()).collect();
+        assert_eq!(names, vec!["Mom", "Jake"]);
+        assert!((ranked[0].score - 3.0).abs() < 0.01);
+        // raw frequency still puts the old contact first
+        assert_eq!(storage.get_all_contacts().unwrap()[0].name, "Jake");
+
+        let score = decayed_score(
+            8.0,
+            Some(&(now - chrono::Duration::days(14)).to_rfc3339()),
+            now,
+            14.0,
+        );
+        assert!((score - 4.0).abs() < 1e-6);
+        assert_eq!(decayed_score(8.0, None, now, 14.0), 8.0);
+    }
+
+    #[test]
+    fn test_contact_modes() {
+        let storage = Storage::in_memory().unwrap();
+
+        storage
+            .set_contact_mode("Mike 🍺", WritingMode::Formal)
+            .unwrap();
+        storage
+            .set_contact_mode("mike", WritingMode::VeryCasual)
+            .unwrap();
+        assert_eq!(
+            storage.get_contact_mode("  MIKE ").unwrap(),
+            Some(WritingMode::VeryCasual)
+        );
+        assert_eq!(storage.get_contact_mode("Sarah").unwrap(), None);
+
+        assert!(storage.clear_contact_mode("Mike 🍺").unwrap());
+        assert!(!storage.clear_contact_mode("Mike 🍺").unwrap());
+        assert_eq!(storage.get_contact_mode("mike").unwrap(), None);
+    }
+
+    #[test]
+    fn test_classification_log_is_bounded() {
+        let storage = Storage::in_memory().unwrap();
+        let classifier = crate::contacts::ContactClassifier::new();
+        let decision = |name: &str| {
+            let input = crate::contacts::ContactInput {
+                name: name.to_string(),
+                organization: String::new(),
+                relationship: None,
+            };
+            ClassificationDecision::new(name, classifier.classify_with_reason(&input))
+        };
+
+        storage.log_classification(&decision("Dr. Smith")).unwrap();
+        storage.log_classification(&decision("Mom")).unwrap();
+        let log = storage.get_classification_log(10).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].contact_name, "Mom");
+        assert_eq!(log[0].detail.category, ContactCategory::CloseFamily);
+        assert_eq!(log[1].detail.category, ContactCategory::Professional);
+        assert!(
+            log[1].detail.reason.contains("dr."),
+            "{}",
+            log[1].detail.reason
+        );
+        assert!(log[1].detail.confidence > 0.5);
+
+        for i in 0..CLASSIFICATION_LOG_MAX_ROWS {
+            storage
+                .log_classification(&decision(&format!("contact {}", i)))
+                .unwrap();
+        }
+        let log = storage
+            .get_classification_log(CLASSIFICATION_LOG_MAX_ROWS * 2)
+            .unwrap();
+        assert_eq!(log.len(), CLASSIFICATION_LOG_MAX_ROWS);
+        assert!(log.iter().all(|d| d.contact_name.starts_with("contact")));
+    }
+
+    #[test]
+    fn test_emoji_usage_learns_policy() {
+        let storage = Storage::in_memory().unwrap();
+
+        for _ in 0..MIN_EMOJI_SAMPLES {
+            storage
+                .record_emoji_usage("Boss", "sending the deck now")
+                .unwrap();
+            storage.record_emoji_usage("Sam ❤️", "miss you ❤️").unwrap();
+        }
+        storage.record_emoji_usage("Alex", "see you 👋").unwrap();
+
+        let boss = storage.get_emoji_usage("boss").unwrap();
+        assert_eq!((boss.dictations, boss.with_emoji), (5, 0));
+        assert_eq!(boss.policy(), Some(EmojiPolicy::Avoid));
+        assert_eq!(
+            storage.get_emoji_usage("sam").unwrap().policy(),
+            Some(EmojiPolicy::Add)
+        );
+        // too few dictations to tell
+        assert_eq!(storage.get_emoji_usage("Alex").unwrap().policy(), None);
+        assert_eq!(
+            storage.get_emoji_usage("Nobody").unwrap(),
+            EmojiUsage::default()
+        );
+
+        storage
+            .set_emoji_policy("Boss", EmojiPolicy::Preserve)
+            .unwrap();
+        let boss = storage.get_emoji_usage("Boss").unwrap();
+        assert_eq!(boss.learned(), Some(EmojiPolicy::Avoid));
+        assert_eq!(boss.policy(), Some(EmojiPolicy::Preserve));
+
+        assert!(storage.clear_emoji_policy("Boss").unwrap());
+        assert!(!storage.clear_emoji_policy("Boss").unwrap());
+        assert_eq!(
+            storage.get_emoji_usage("Boss").unwrap().policy(),
+            Some(EmojiPolicy::Avoid)
+        );
+    }
+
     #[test]
     fn test_settings_roundtrip() {
         let storage = Storage::in_memory().unwrap();
@@ -1172,6 +2334,35 @@ mod tests {
         assert_eq!(value, Some("test-key".to_string()));
     }
 
+    #[test]
+    fn test_api_keys_by_provider() {
+        let storage = Storage::in_memory().unwrap();
+
+        storage.set_api_key("gemini", "AIza-test").unwrap();
+        assert_eq!(
+            storage.get_setting(SETTING_GEMINI_API_KEY).unwrap(),
+            Some("AIza-test".to_string())
+        );
+        assert_eq!(
+            storage.get_api_key("gemini").unwrap(),
+            Some("AIza-test".to_string())
+        );
+
+        // cleared keys read as unset
+        storage.set_api_key("openai", "").unwrap();
+        assert_eq!(storage.get_api_key("openai").unwrap(), None);
+
+        assert!(matches!(
+            storage.set_api_key("rules", "x"),
+            Err(Error::Config(_))
+        ));
+        assert_eq!(
+            api_key_setting("openrouter"),
+            Some(SETTING_OPENROUTER_API_KEY)
+        );
+        assert_eq!(api_key_setting("none"), None);
+    }
+
     #[test]
     fn test_correction_deletion() {
         let storage = Storage::in_memory().unwrap();
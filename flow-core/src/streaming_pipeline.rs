@@ -0,0 +1,340 @@
+//! Audio chunks -> partial transcripts -> incrementally adapted text, as one stream
+//!
+//! [`StreamingPipeline`] feeds captured chunks to a streaming transcription
+//! provider and watches the partial transcripts for sentences that have settled:
+//! a sentence counts once it ends in `.`, `?`, or `!` and a following partial
+//! repeats it unchanged. Each settled run of sentences is sent to a streaming
+//! completion provider and its output is yielded chunk by chunk, so adapted text
+//! starts appearing while the user is still talking.
+//!
+//! The tradeoff: every sentence is adapted before the rest of the message exists.
+//! Latency drops from "whole dictation + whole completion" to roughly one
+//! transcription window plus the first sentence's completion, but the model never
+//! sees later sentences, so it can't merge them, reorder them, or fix a pronoun
+//! that only makes sense in context, and a transcript revised after its sentence
+//! was sent keeps the early version. Sentences are adapted one batch at a time,
+//! so a slow completion holds back the next batch (the audio keeps buffering).
+//! For the most faithful result, adapt the finished transcript with
+//! [`AdaptivePipeline`](crate::AdaptivePipeline) instead.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::providers::{
+    AudioChunkStream, CompletionRequest, CompletionStream, StreamingCompletionProvider,
+    StreamingTranscriptionProvider, TranscriptionStream,
+};
+
+/// A piece of adapted text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdaptedChunk {
+    /// Which batch of sentences this belongs to, counting from 0
+    pub segment: usize,
+    /// New text; concatenating every chunk's text gives the whole message
+    pub text: String,
+    /// The provider marked this as the last chunk of its segment
+    pub segment_done: bool,
+}
+
+/// Stream of adapted text, ending once the audio does and the last segment is adapted
+pub type AdaptedStream = Pin<Box<dyn Stream<Item = Result<AdaptedChunk>> + Send>>;
+
+/// Streaming transcription and streaming completion chained sentence by sentence
+pub struct StreamingPipeline {
+    transcription: Arc<dyn StreamingTranscriptionProvider>,
+    completion: Arc<dyn StreamingCompletionProvider>,
+}
+
+impl StreamingPipeline {
+    pub fn new(
+        transcription: Arc<dyn StreamingTranscriptionProvider>,
+        completion: Arc<dyn StreamingCompletionProvider>,
+    ) -> Self {
+        Self {
+            transcription,
+            completion,
+        }
+    }
+
+    /// Transcribe `audio` and adapt it as it settles
+    ///
+    /// `request` is the template for every segment's completion: its text is
+    /// replaced by the segment, everything else (mode, contact, constraints) is
+    /// kept. [`AdaptivePipeline::plan`](crate::AdaptivePipeline::plan) builds one
+    /// for a contact. `audio` can be any chunk stream, such as the receiving end of
+    /// a `futures::channel::mpsc` channel fed from the capture callback.
+    pub async fn run(
+        &self,
+        audio: AudioChunkStream,
+        sample_rate: u32,
+        request: CompletionRequest,
+    ) -> Result<AdaptedStream> {
+        let transcripts = self
+            .transcription
+            .transcribe_stream(audio, sample_rate)
+            .await?;
+
+        let state = RunState {
+            transcripts,
+            transcription_done: false,
+            completion: Arc::clone(&self.completion),
+            request,
+            stabilizer: SentenceStabilizer::default(),
+            queue: VecDeque::new(),
+            current: None,
+            segment: 0,
+            started: false,
+        };
+
+        let stream = futures::stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            loop {
+                if let Some(current) = state.current.as_mut() {
+                    match current.next().await {
+                        Some(Ok(chunk)) => {
+                            let mut text = chunk.text;
+                            // segments are separate sentences; keep a space between them
+                            if !state.started && !text.is_empty() {
+                                state.started = true;
+                                if state.segment > 0 && !text.starts_with(char::is_whitespace) {
+                                    text.insert(0, ' ');
+                                }
+                            }
+                            let adapted = AdaptedChunk {
+                                segment: state.segment,
+                                text,
+                                segment_done: chunk.is_final,
+                            };
+                            return Some((Ok(adapted), Some(state)));
+                        }
+                        Some(Err(e)) => return Some((Err(e), None)),
+                        None => {
+                            state.current = None;
+                            state.segment += 1;
+                            state.started = false;
+                        }
+                    }
+                } else if let Some(text) = state.queue.pop_front() {
+                    debug!(segment = state.segment, "Adapting settled transcript");
+                    let mut request = state.request.clone();
+                    request.text = text;
+                    match state.completion.complete_stream(request).await {
+                        Ok(stream) => state.current = Some(stream),
+                        Err(e) => return Some((Err(e), None)),
+                    }
+                } else if state.transcription_done {
+                    return None;
+                } else {
+                    match state.transcripts.next().await {
+                        Some(Ok(partial)) => {
+                            state.queue.extend(state.stabilizer.push(&partial.text));
+                        }
+                        Some(Err(e)) => return Some((Err(e), None)),
+                        None => {
+                            state.transcription_done = true;
+                            state.queue.extend(state.stabilizer.finish());
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Box::pin(stream))
+    }
+}
+
+struct RunState {
+    transcripts: TranscriptionStream,
+    transcription_done: bool,
+    completion: Arc<dyn StreamingCompletionProvider>,
+    request: CompletionRequest,
+    stabilizer: SentenceStabilizer,
+    /// Settled text waiting for its completion
+    queue: VecDeque<String>,
+    current: Option<CompletionStream>,
+    segment: usize,
+    /// Whether the current segment has yielded any text yet
+    started: bool,
+}
+
+/// Tracks partial transcripts and releases sentences once they stop changing
+#[derive(Debug, Default)]
+struct SentenceStabilizer {
+    previous: String,
+    /// Bytes of transcript already released
+    committed: usize,
+}
+
+impl SentenceStabilizer {
+    /// Feed the next partial transcript; returns newly settled sentences, if any
+    fn push(&mut self, text: &str) -> Option<String> {
+        // only the part both partials agree on can settle
+        let agreed: usize = self
+            .previous
+            .chars()
+            .zip(text.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(c, _)| c.len_utf8())
+            .sum();
+        let boundary = last_sentence_end(text, agreed);
+        let released = if boundary > self.committed {
+            let segment = text.get(self.committed..boundary).map(str::trim);
+            self.committed = boundary;
+            segment.filter(|s| !s.is_empty()).map(str::to_string)
+        } else {
+            None
+        };
+        self.previous = text.to_string();
+        released
+    }
+
+    /// The transcript is final: release whatever hasn't been yet
+    fn finish(&mut self) -> Option<String> {
+        let rest = self.previous.get(self.committed..).map(str::trim);
+        self.committed = self.previous.len();
+        rest.filter(|s| !s.is_empty()).map(str::to_string)
+    }
+}
+
+/// Byte offset just past the last sentence-ending punctuation (followed by a space
+/// or the end of `text`) that lies within the first `limit` bytes
+fn last_sentence_end(text: &str, limit: usize) -> usize {
+    let mut end = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if i + c.len_utf8() > limit {
+            break;
+        }
+        if matches!(c, '.' | '?' | '!') && chars.peek().is_none_or(|(_, next)| next.is_whitespace())
+        {
+            end = i + c.len_utf8();
+        }
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AudioData;
+    use crate::providers::{CompletionChunk, TranscriptFormat, TranscriptionResponse};
+    use async_trait::async_trait;
+    use parking_lot::Mutex;
+
+    /// Yields canned partial transcripts, ignoring the audio
+    struct ScriptedTranscription(Vec<&'static str>);
+
+    #[async_trait]
+    impl StreamingTranscriptionProvider for ScriptedTranscription {
+        fn name(&self) -> &'static str {
+            "Scripted"
+        }
+
+        async fn transcribe_stream(
+            &self,
+            _audio: AudioChunkStream,
+            _sample_rate: u32,
+        ) -> Result<TranscriptionStream> {
+            let partials = self.0.iter().map(|text| {
+                Ok(TranscriptionResponse {
+                    text: text.to_string(),
+                    confidence: None,
+                    language: None,
+                    duration_ms: 0,
+                    segments: None,
+                    completed_text: None,
+                    format: TranscriptFormat::Plain,
+                })
+            });
+            Ok(Box::pin(futures::stream::iter(
+                partials.collect::<Vec<_>>(),
+            )))
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    /// Uppercases each request in two chunks, remembering what it was asked
+    #[derive(Default)]
+    struct ShoutingCompletion {
+        seen: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl StreamingCompletionProvider for ShoutingCompletion {
+        fn name(&self) -> &'static str {
+            "Shouting"
+        }
+
+        async fn complete_stream(&self, request: CompletionRequest) -> Result<CompletionStream> {
+            self.seen.lock().push(request.text.clone());
+            let text = request.text.to_uppercase();
+            let (head, tail) = text.split_at(text.len() / 2);
+            let chunks = vec![
+                Ok(CompletionChunk {
+                    text: head.to_string(),
+                    is_final: false,
+                    usage: None,
+                }),
+                Ok(CompletionChunk {
+                    text: tail.to_string(),
+                    is_final: true,
+                    usage: None,
+                }),
+            ];
+            Ok(Box::pin(futures::stream::iter(chunks)))
+        }
+
+        fn is_configured(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_streaming_pipeline_adapts_settled_sentences() {
+        let transcription = ScriptedTranscription(vec![
+            "Running late.",
+            "Running late. Be there",
+            "Running late. Be there in ten. Sorry",
+            "Running late. Be there in ten. Sorry!",
+        ]);
+        let completion = Arc::new(ShoutingCompletion::default());
+        let pipeline = StreamingPipeline::new(Arc::new(transcription), completion.clone());
+
+        let audio: AudioChunkStream = Box::pin(futures::stream::iter(Vec::<AudioData>::new()));
+        let chunks: Vec<AdaptedChunk> = futures::executor::block_on(async {
+            let stream = pipeline
+                .run(audio, 16000, CompletionRequest::new(""))
+                .await
+                .unwrap();
+            stream.map(|chunk| chunk.unwrap()).collect().await
+        });
+
+        // "Running late." settles on the second partial, "Be there in ten." on the
+        // fourth, and the rest is flushed when the transcript ends
+        assert_eq!(
+            *completion.seen.lock(),
+            vec!["Running late.", "Be there in ten.", "Sorry!"]
+        );
+        let text: String = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(text, "RUNNING LATE. BE THERE IN TEN. SORRY!");
+        assert_eq!(chunks.last().unwrap().segment, 2);
+        assert!(chunks.last().unwrap().segment_done);
+    }
+
+    #[test]
+    fn test_last_sentence_end() {
+        assert_eq!(last_sentence_end("no boundary yet", 15), 0);
+        assert_eq!(last_sentence_end("Done. Next", 10), 5);
+        assert_eq!(last_sentence_end("Costs 3.50 today", 16), 0);
+        assert_eq!(last_sentence_end("Really?! Yes", 12), 8);
+        assert_eq!(last_sentence_end("One. Two. Three", 8), 4);
+    }
+}
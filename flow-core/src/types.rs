@@ -1,9 +1,14 @@
 //! Core types used throughout FlowWhispr
 
+use std::fmt;
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::error::{Error, Result};
+
 /// Unique identifier for transcriptions
 pub type TranscriptionId = Uuid;
 
@@ -19,9 +24,251 @@ pub type EventId = Uuid;
 /// Unique identifier for contacts
 pub type ContactId = Uuid;
 
-/// Audio data as raw bytes (16-bit PCM)
+/// Audio data as raw bytes (16-bit PCM unless a clip says otherwise)
 pub type AudioData = Vec<u8>;
 
+/// Sample encoding of PCM audio bytes (always little-endian)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PcmFormat {
+    /// 16-bit signed integer, what Whisper expects
+    #[default]
+    Pcm16,
+    /// 24-bit signed integer packed into 3 bytes
+    Pcm24,
+    /// 32-bit IEEE float
+    Float32,
+}
+
+impl PcmFormat {
+    pub fn bytes_per_sample(&self) -> usize {
+        match self {
+            Self::Pcm16 => 2,
+            Self::Pcm24 => 3,
+            Self::Float32 => 4,
+        }
+    }
+
+    pub fn bits_per_sample(&self) -> u16 {
+        self.bytes_per_sample() as u16 * 8
+    }
+
+    /// Format tag for a WAV fmt chunk (1 = integer PCM, 3 = IEEE float)
+    pub fn wav_format_tag(&self) -> u16 {
+        match self {
+            Self::Pcm16 | Self::Pcm24 => 1,
+            Self::Float32 => 3,
+        }
+    }
+}
+
+/// Fixed tail of a WAVE_FORMAT_EXTENSIBLE subformat GUID, after its two-byte format tag
+const WAV_GUID_SUFFIX: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// PCM audio together with the format needed to interpret it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioClip {
+    /// Interleaved little-endian PCM in `format`
+    pub data: AudioData,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub format: PcmFormat,
+    /// Capture hit its maximum duration and stopped before the user did
+    pub truncated: bool,
+}
+
+impl AudioClip {
+    pub fn new(data: AudioData, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            data,
+            sample_rate,
+            channels,
+            format: PcmFormat::Pcm16,
+            truncated: false,
+        }
+    }
+
+    /// Mark the data as encoded in `format` (default: 16-bit)
+    pub fn with_format(mut self, format: PcmFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Single-channel clip, the format audio capture produces
+    pub fn mono(data: AudioData, sample_rate: u32) -> Self {
+        Self::new(data, sample_rate, 1)
+    }
+
+    /// Duration of the clip in milliseconds
+    pub fn duration_ms(&self) -> u64 {
+        let frame_bytes = self.format.bytes_per_sample() as u64 * self.channels.max(1) as u64;
+        let frames = self.data.len() as u64 / frame_bytes;
+        frames * 1000 / self.sample_rate.max(1) as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Bytes in one frame (one sample for every channel)
+    fn frame_bytes(&self) -> usize {
+        self.format.bytes_per_sample() * self.channels.max(1) as usize
+    }
+
+    /// Append another clip, e.g. the segment recorded after a pause
+    /// Errors if the sample rate, channel count, or sample format differ. Partial
+    /// trailing frames are dropped so the join never splits a sample.
+    pub fn append(&mut self, other: &AudioClip) -> Result<()> {
+        if self.sample_rate != other.sample_rate
+            || self.channels != other.channels
+            || self.format != other.format
+        {
+            return Err(Error::Audio(format!(
+                "Cannot join {}Hz/{}ch/{:?} audio onto {}Hz/{}ch/{:?}",
+                other.sample_rate,
+                other.channels,
+                other.format,
+                self.sample_rate,
+                self.channels,
+                self.format
+            )));
+        }
+
+        let frame_bytes = self.frame_bytes();
+        self.data
+            .truncate(self.data.len() - self.data.len() % frame_bytes);
+        let whole = other.data.len() - other.data.len() % frame_bytes;
+        self.data.extend_from_slice(&other.data[..whole]);
+        self.truncated |= other.truncated;
+        Ok(())
+    }
+
+    /// The audio between `start_ms` and `end_ms`, cut on frame boundaries
+    /// Bounds past the end of the clip are clamped.
+    pub fn slice_ms(&self, start_ms: u64, end_ms: u64) -> AudioClip {
+        let frame_bytes = self.frame_bytes() as u64;
+        let frames = self.data.len() as u64 / frame_bytes;
+        let to_frame = |ms: u64| (ms * u64::from(self.sample_rate) / 1000).min(frames);
+        let start = to_frame(start_ms);
+        let end = to_frame(end_ms).max(start);
+
+        AudioClip {
+            data: self.data[(start * frame_bytes) as usize..(end * frame_bytes) as usize].to_vec(),
+            ..self.clone()
+        }
+    }
+
+    /// Join clips in order into one; `None` if `clips` is empty
+    pub fn concat(clips: &[AudioClip]) -> Result<Option<AudioClip>> {
+        let Some((first, rest)) = clips.split_first() else {
+            return Ok(None);
+        };
+
+        let mut joined = first.clone();
+        for clip in rest {
+            joined.append(clip)?;
+        }
+        Ok(Some(joined))
+    }
+
+    /// Encode as a WAV file
+    pub fn to_wav(&self) -> Vec<u8> {
+        let bits_per_sample = self.format.bits_per_sample();
+        let block_align = self.frame_bytes() as u16;
+        let byte_rate = self.sample_rate * u32::from(block_align);
+        let data_size = self.data.len() as u32;
+
+        let mut wav = Vec::with_capacity(44 + self.data.len());
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+        wav.extend_from_slice(b"WAVE");
+        wav.extend_from_slice(b"fmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&self.format.wav_format_tag().to_le_bytes());
+        wav.extend_from_slice(&self.channels.to_le_bytes());
+        wav.extend_from_slice(&self.sample_rate.to_le_bytes());
+        wav.extend_from_slice(&byte_rate.to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&bits_per_sample.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_size.to_le_bytes());
+        wav.extend_from_slice(&self.data);
+        wav
+    }
+
+    /// Decode a WAV file in one of the [`PcmFormat`] encodings
+    /// Reads both plain and WAVE_FORMAT_EXTENSIBLE fmt chunks.
+    pub fn from_wav(bytes: &[u8]) -> Result<Self> {
+        let invalid = |what: &str| Error::Audio(format!("Invalid WAV: {}", what));
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(invalid("missing RIFF/WAVE header"));
+        }
+
+        let mut fmt: Option<(PcmFormat, u16, u32)> = None;
+        let mut offset = 12;
+        while offset + 8 <= bytes.len() {
+            let id = &bytes[offset..offset + 4];
+            let size = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap());
+            let body = offset + 8;
+            let end = body
+                .checked_add(size as usize)
+                .filter(|&end| end <= bytes.len())
+                .ok_or_else(|| invalid("chunk runs past end of file"))?;
+
+            match id {
+                b"fmt " if size >= 16 => {
+                    let field =
+                        |at: usize| u16::from_le_bytes([bytes[body + at], bytes[body + at + 1]]);
+                    let channels = field(2);
+                    let sample_rate =
+                        u32::from_le_bytes(bytes[body + 4..body + 8].try_into().unwrap());
+                    // WAVE_FORMAT_EXTENSIBLE keeps the real tag at the front of its subformat GUID
+                    let tag = match field(0) {
+                        0xFFFE if size >= 40 && bytes[body + 26..body + 40] == WAV_GUID_SUFFIX => {
+                            field(24)
+                        }
+                        0xFFFE => return Err(invalid("unrecognized extensible subformat")),
+                        tag => tag,
+                    };
+                    let format = match (tag, field(14)) {
+                        (1, 16) => PcmFormat::Pcm16,
+                        (1, 24) => PcmFormat::Pcm24,
+                        (3, 32) => PcmFormat::Float32,
+                        (tag, bits) => {
+                            return Err(Error::Audio(format!(
+                                "Unsupported WAV encoding: format {} at {} bits",
+                                tag, bits
+                            )));
+                        }
+                    };
+                    fmt = Some((format, channels, sample_rate));
+                }
+                b"data" => {
+                    let (format, channels, sample_rate) =
+                        fmt.ok_or_else(|| invalid("data chunk before fmt chunk"))?;
+                    return Ok(
+                        AudioClip::new(bytes[body..end].to_vec(), sample_rate, channels)
+                            .with_format(format),
+                    );
+                }
+                _ => {}
+            }
+            // chunks are padded to an even length
+            offset = end + (size as usize & 1);
+        }
+
+        Err(invalid("no data chunk"))
+    }
+}
+
+impl From<AudioClip> for AudioData {
+    fn from(clip: AudioClip) -> Self {
+        clip.data
+    }
+}
+
 /// Writing mode that affects transcription style
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -56,6 +303,46 @@ impl WritingMode {
         }
     }
 
+    /// Get the system prompt modifier tailored to the recipient of the message
+    ///
+    /// Falls back to the plain `prompt_modifier()` instruction, prefixed with who the
+    /// user is writing to so the model can pick an appropriate register.
+    pub fn contextual_prompt(&self, contact_name: &str, category: ContactCategory) -> String {
+        let relationship = match category {
+            ContactCategory::Professional => "a professional contact",
+            ContactCategory::CloseFamily => "a close family member",
+            ContactCategory::CasualPeer => "a friend",
+            ContactCategory::Partner => "their romantic partner",
+            ContactCategory::FormalNeutral => "someone they don't know well",
+            ContactCategory::Coworker => "a coworker",
+        };
+
+        let name = contact_name.trim();
+        let mut prompt = if name.is_empty() {
+            format!("The user is messaging {relationship}. ")
+        } else {
+            format!("The user is messaging {name} ({relationship}). ")
+        };
+        prompt.push_str(self.prompt_modifier());
+
+        if !name.is_empty() && matches!(self, Self::Excited | Self::Casual) {
+            prompt.push_str(&format!(
+                " If it reads naturally, the message may address them as \"{name}\"."
+            ));
+        }
+
+        prompt
+    }
+
+    /// Rough, offline preview of what this mode does to `sample`
+    ///
+    /// Runs the rule-based adapter, so it's instant and free but only an
+    /// approximation: a model rewrites far more than these rules do. Meant for
+    /// showing modes side by side in settings, not for formatting real messages.
+    pub fn example_transform(&self, sample: &str) -> String {
+        crate::providers::RuleBasedAdapter::new().adapt(sample, *self, None)
+    }
+
     /// Get all available modes
     pub fn all() -> &'static [WritingMode] {
         &[
@@ -66,6 +353,16 @@ impl WritingMode {
         ]
     }
 
+    /// Sampling temperature that suits the tone: conservative for formal, looser for excited
+    pub fn suggested_temperature(&self) -> f32 {
+        match self {
+            Self::Formal => 0.2,
+            Self::Casual => 0.4,
+            Self::VeryCasual => 0.5,
+            Self::Excited => 0.8,
+        }
+    }
+
     /// Suggest default mode for an app category
     pub fn suggested_for_category(category: AppCategory) -> Self {
         match category {
@@ -79,6 +376,98 @@ impl WritingMode {
             AppCategory::Unknown => WritingMode::Casual,
         }
     }
+
+    /// Snake-case name, the same one serde uses
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Formal => "formal",
+            Self::Casual => "casual",
+            Self::VeryCasual => "very_casual",
+            Self::Excited => "excited",
+        }
+    }
+}
+
+impl fmt::Display for WritingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WritingMode {
+    type Err = Error;
+
+    /// Case-insensitive; accepts "very_casual", "very-casual", "Very Casual", and "VeryCasual"
+    fn from_str(s: &str) -> Result<Self> {
+        let key: String = s
+            .chars()
+            .filter(|c| !matches!(c, '_' | '-' | ' '))
+            .flat_map(char::to_lowercase)
+            .collect();
+        WritingMode::all()
+            .iter()
+            .copied()
+            .find(|mode| mode.as_str().replace('_', "") == key)
+            .ok_or_else(|| {
+                Error::Config(format!(
+                    "Unknown writing mode \"{}\" (expected one of: formal, casual, very_casual, excited)",
+                    s
+                ))
+            })
+    }
+}
+
+/// How freely messages to a contact use emoji
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmojiPolicy {
+    /// Keep the emoji the user dictated, add none
+    Avoid,
+    /// Keep the emoji the user dictated, leave the rest to the mode
+    Preserve,
+    /// Keep the user's emoji and add one where it fits
+    Add,
+}
+
+impl EmojiPolicy {
+    /// Output constraints that carry this policy into a completion
+    pub fn constraints(&self) -> crate::providers::Constraints {
+        let mut constraints = crate::providers::Constraints {
+            preserve_emoji: true,
+            ..Default::default()
+        };
+        match self {
+            Self::Avoid => constraints.max_emoji = 0,
+            Self::Preserve => {}
+            Self::Add => constraints.add_emoji = true,
+        }
+        constraints
+    }
+
+    /// Snake-case name, the same one serde uses
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Avoid => "avoid",
+            Self::Preserve => "preserve",
+            Self::Add => "add",
+        }
+    }
+}
+
+impl FromStr for EmojiPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "avoid" => Ok(Self::Avoid),
+            "preserve" => Ok(Self::Preserve),
+            "add" => Ok(Self::Add),
+            _ => Err(Error::Config(format!(
+                "Unknown emoji policy \"{}\" (expected one of: avoid, preserve, add)",
+                s
+            ))),
+        }
+    }
 }
 
 /// A single transcription result
@@ -411,6 +800,8 @@ pub enum ContactCategory {
     Partner,
     /// Default for unknown or neutral contacts
     FormalNeutral,
+    /// Works at one of the user's own employers
+    Coworker,
 }
 
 impl ContactCategory {
@@ -422,9 +813,15 @@ impl ContactCategory {
             Self::CasualPeer => WritingMode::VeryCasual,
             Self::Partner => WritingMode::Excited,
             Self::FormalNeutral => WritingMode::Formal,
+            Self::Coworker => WritingMode::Casual,
         }
     }
 
+    /// Temperature of the category's suggested writing mode
+    pub fn suggested_temperature(&self) -> f32 {
+        self.suggested_writing_mode().suggested_temperature()
+    }
+
     /// Get all available categories
     pub fn all() -> &'static [ContactCategory] {
         &[
@@ -433,10 +830,23 @@ impl ContactCategory {
             ContactCategory::CasualPeer,
             ContactCategory::Partner,
             ContactCategory::FormalNeutral,
+            ContactCategory::Coworker,
         ]
     }
 }
 
+/// Tokens spent with one provider/model over a time window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageSummary {
+    pub provider: String,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    /// Number of completion calls
+    pub calls: u64,
+}
+
 /// A contact entry with metadata and categorization
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contact {